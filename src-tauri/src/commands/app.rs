@@ -0,0 +1,1252 @@
+use crate::services::*;
+use crate::utils::calculate_file_hash;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+// Global service instances
+pub struct AppState {
+    pub translation: Mutex<TranslationService>,
+    pub ocr: Mutex<OCRService>,
+    pub keychain: Mutex<KeychainService>,
+    pub transcription_streams: Mutex<HashMap<String, TranscriptionStream>>,
+    pub reading_aligners: Mutex<HashMap<String, ReadingAligner>>,
+    pub pronunciation: Mutex<Option<PronunciationService>>,
+    pub tts_streams: Mutex<HashMap<String, TtsStream>>,
+    pub audiobook_jobs: Mutex<HashMap<String, AudiobookJob>>,
+    pub model_downloads: Mutex<HashMap<String, ModelDownload>>,
+    /// Opened during setup, once the app data directory is known
+    pub database: Mutex<Option<Database>>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            translation: Mutex::new(TranslationService::new()),
+            ocr: Mutex::new(OCRService::new()),
+            keychain: Mutex::new(KeychainService::new()),
+            transcription_streams: Mutex::new(HashMap::new()),
+            reading_aligners: Mutex::new(HashMap::new()),
+            pronunciation: Mutex::new(None),
+            tts_streams: Mutex::new(HashMap::new()),
+            audiobook_jobs: Mutex::new(HashMap::new()),
+            model_downloads: Mutex::new(HashMap::new()),
+            database: Mutex::new(None),
+        }
+    }
+}
+
+impl AppState {
+    /// Open the application database in the app data directory, migrating it
+    /// to the current schema
+    pub fn open_database(&self, app: &AppHandle) -> Result<(), String> {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+        let db = Database::open(dir.join(DATABASE_FILE_NAME))?;
+        // The frontend used to keep practice history in its own database
+        let config_dir = app
+            .path()
+            .app_config_dir()
+            .map_err(|e| format!("Failed to get app config directory: {}", e))?;
+        db.import_legacy_practice(&config_dir.join(LEGACY_PRACTICE_DATABASE_FILE_NAME))?;
+        *self.database.lock().map_err(|e| e.to_string())? = Some(db);
+        Ok(())
+    }
+
+    /// Run `f` against the application database
+    pub fn with_database<T>(
+        &self,
+        f: impl FnOnce(&Database) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let database = self.database.lock().map_err(|e| e.to_string())?;
+        let db = database
+            .as_ref()
+            .ok_or_else(|| "Database is not open".to_string())?;
+        f(db)
+    }
+}
+
+// Translation Commands
+
+/// The installed translation model for `options`
+fn translation_model(
+    app: AppHandle,
+    options: &TranslationOptions,
+) -> Result<DownloadedModel, String> {
+    ModelManager::new(app).model_for(
+        ModelKind::Translation,
+        "translation",
+        Some(&options.target_language),
+    )
+}
+
+#[tauri::command]
+pub fn translate_text(
+    app: AppHandle,
+    text: String,
+    options: TranslationOptions,
+    state: State<AppState>,
+) -> Result<TranslationResult, String> {
+    let model = translation_model(app, &options)?;
+    let mut service = state.translation.lock().map_err(|e| e.to_string())?;
+    Ok(service.translate_text(&text, &options, &model))
+}
+
+#[tauri::command]
+pub fn translate_page(
+    app: AppHandle,
+    page_text: String,
+    page_number: usize,
+    options: TranslationOptions,
+    state: State<AppState>,
+) -> Result<PageTranslationResult, String> {
+    let model = translation_model(app, &options)?;
+    let mut service = state.translation.lock().map_err(|e| e.to_string())?;
+    Ok(service.translate_page(&page_text, page_number, &options, &model))
+}
+
+#[tauri::command]
+pub fn translate_document(
+    app: AppHandle,
+    pages: Vec<(usize, String)>,
+    options: TranslationOptions,
+    state: State<AppState>,
+) -> Result<DocumentTranslationResult, String> {
+    let model = translation_model(app, &options)?;
+    let mut service = state.translation.lock().map_err(|e| e.to_string())?;
+    Ok(service.translate_document(pages, &options, &model))
+}
+
+#[tauri::command]
+pub fn detect_language(
+    text: String,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let service = state.translation.lock().map_err(|e| e.to_string())?;
+    Ok(service.detect_language(&text))
+}
+
+// OCR Commands
+
+#[tauri::command]
+pub fn is_page_scanned(
+    page_data: Vec<u8>,
+    state: State<AppState>,
+) -> Result<bool, String> {
+    let service = state.ocr.lock().map_err(|e| e.to_string())?;
+    Ok(service.is_page_scanned(&page_data))
+}
+
+/// The installed OCR language data for `options`, English unless set
+fn ocr_model(app: AppHandle, options: &OCROptions) -> Result<DownloadedModel, String> {
+    let language = options.language.as_deref().unwrap_or("en");
+    ModelManager::new(app).model_for(ModelKind::Ocr, "ocr", Some(language))
+}
+
+#[tauri::command]
+pub fn ocr_page(
+    app: AppHandle,
+    image_data: Vec<u8>,
+    page_number: usize,
+    options: OCROptions,
+    state: State<AppState>,
+) -> Result<OCRResult, String> {
+    let model = ocr_model(app, &options)?;
+    let service = state.ocr.lock().map_err(|e| e.to_string())?;
+    Ok(service.ocr_page(&image_data, page_number, &options, &model))
+}
+
+#[tauri::command]
+pub fn ocr_document(
+    app: AppHandle,
+    pages: Vec<(usize, Vec<u8>)>,
+    options: OCROptions,
+    state: State<AppState>,
+) -> Result<DocumentOCRResult, String> {
+    let model = ocr_model(app, &options)?;
+    let service = state.ocr.lock().map_err(|e| e.to_string())?;
+    Ok(service.ocr_document(pages, &options, &model))
+}
+
+#[tauri::command]
+pub fn extract_text_from_image(
+    app: AppHandle,
+    image_data: Vec<u8>,
+    options: OCROptions,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let model = ocr_model(app, &options)?;
+    let service = state.ocr.lock().map_err(|e| e.to_string())?;
+    Ok(service.extract_text(&image_data, &options, &model))
+}
+
+// Keychain Commands
+
+#[tauri::command]
+pub fn store_api_key(
+    service: String,
+    key: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let keychain = state.keychain.lock().map_err(|e| e.to_string())?;
+    keychain.store_key(&service, &key).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_api_key(
+    service: String,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let keychain = state.keychain.lock().map_err(|e| e.to_string())?;
+    keychain.get_key(&service).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_api_key(
+    service: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let keychain = state.keychain.lock().map_err(|e| e.to_string())?;
+    keychain.delete_key(&service).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn has_api_key(
+    service: String,
+    state: State<AppState>,
+) -> Result<bool, String> {
+    let keychain = state.keychain.lock().map_err(|e| e.to_string())?;
+    Ok(keychain.has_key(&service))
+}
+
+#[tauri::command]
+pub fn list_api_services(
+    state: State<AppState>,
+) -> Result<Vec<String>, String> {
+    let keychain = state.keychain.lock().map_err(|e| e.to_string())?;
+    keychain.list_services().map_err(|e| e.to_string())
+}
+
+// Streaming Transcription Commands
+
+#[tauri::command]
+pub fn start_transcription_stream(
+    app: AppHandle,
+    options: StreamingOptions,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let stream = TranscriptionStream::start(app, session_id.clone(), options)?;
+
+    let mut streams = state.transcription_streams.lock().map_err(|e| e.to_string())?;
+    streams.insert(session_id.clone(), stream);
+    Ok(session_id)
+}
+
+#[tauri::command]
+pub fn push_transcription_audio(
+    session_id: String,
+    samples: Vec<f32>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let streams = state.transcription_streams.lock().map_err(|e| e.to_string())?;
+    let stream = streams
+        .get(&session_id)
+        .ok_or_else(|| format!("Unknown transcription stream: {}", session_id))?;
+    stream.push_audio(samples)
+}
+
+#[tauri::command]
+pub async fn stop_transcription_stream(
+    session_id: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let stream = state
+        .transcription_streams
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&session_id)
+        .ok_or_else(|| format!("Unknown transcription stream: {}", session_id))?;
+
+    // Waiting for the final hypothesis can take a moment; keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || stream.stop())
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Text-to-Speech Commands
+
+/// Start speaking `text`; audio and word timings arrive as `tts-chunk` events.
+/// With `structure`, paragraphs, quotes and foreign-language sentences are
+/// read as if marked up with SSML.
+#[tauri::command]
+pub fn start_speaking(
+    app: AppHandle,
+    text: String,
+    options: Option<TtsOptions>,
+    structure: Option<SpeechStructureOptions>,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let segments = match structure {
+        Some(structure) => {
+            let translation = state.translation.lock().map_err(|e| e.to_string())?;
+            structure_segments(&text, &structure, |sentence| {
+                translation.detect_language(sentence)
+            })
+        }
+        None => vec![SpeechSegment::plain(&text, 0)],
+    };
+    start_speech_session(app, segments, options, &state)
+}
+
+/// Speak SSML; chunk offsets point into the markup
+#[tauri::command]
+pub fn start_speaking_ssml(
+    app: AppHandle,
+    ssml: String,
+    options: Option<TtsOptions>,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let segments = parse_ssml(&ssml)?;
+    start_speech_session(app, segments, options, &state)
+}
+
+/// SSML for `text` as `start_speaking` would read it with `structure`
+#[tauri::command]
+pub fn generate_ssml(
+    text: String,
+    structure: Option<SpeechStructureOptions>,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let structure = structure.unwrap_or_default();
+    let translation = state.translation.lock().map_err(|e| e.to_string())?;
+    let segments = structure_segments(&text, &structure, |sentence| {
+        translation.detect_language(sentence)
+    });
+    Ok(segments_to_ssml(&segments, structure.language.as_deref()))
+}
+
+fn start_speech_session(
+    app: AppHandle,
+    segments: Vec<SpeechSegment>,
+    options: Option<TtsOptions>,
+    state: &State<AppState>,
+) -> Result<String, String> {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let stream = TtsStream::start_segments(
+        app,
+        session_id.clone(),
+        segments,
+        options.unwrap_or_default(),
+    )?;
+
+    let mut streams = state.tts_streams.lock().map_err(|e| e.to_string())?;
+    // Sessions that ran to completion are never stopped explicitly
+    streams.retain(|_, stream| !stream.is_finished());
+    streams.insert(session_id.clone(), stream);
+    Ok(session_id)
+}
+
+#[tauri::command]
+pub async fn stop_speaking(session_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let stream = state
+        .tts_streams
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&session_id);
+
+    match stream {
+        Some(stream) => tauri::async_runtime::spawn_blocking(move || stream.stop())
+            .await
+            .map_err(|e| e.to_string())?,
+        // Already finished and pruned
+        None => Ok(()),
+    }
+}
+
+// Audiobook Export Commands
+
+/// Render a page range to an audio file; progress arrives as `audiobook-progress` events
+#[tauri::command]
+pub fn start_audiobook_export(
+    app: AppHandle,
+    options: AudiobookOptions,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let job = AudiobookJob::start(app, job_id.clone(), options)?;
+
+    let mut jobs = state.audiobook_jobs.lock().map_err(|e| e.to_string())?;
+    // Finished jobs already reported their outcome through events
+    jobs.retain(|_, job| !job.is_finished());
+    jobs.insert(job_id.clone(), job);
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub fn get_audiobook_export_status(
+    job_id: String,
+    state: State<AppState>,
+) -> Result<AudiobookProgress, String> {
+    let jobs = state.audiobook_jobs.lock().map_err(|e| e.to_string())?;
+    jobs.get(&job_id)
+        .ok_or_else(|| format!("Audiobook export not found: {}", job_id))?
+        .progress()
+}
+
+/// Stop an export; the job reports `cancelled` once partial output is removed
+#[tauri::command]
+pub fn cancel_audiobook_export(job_id: String, state: State<AppState>) -> Result<(), String> {
+    let jobs = state.audiobook_jobs.lock().map_err(|e| e.to_string())?;
+    jobs.get(&job_id)
+        .ok_or_else(|| format!("Audiobook export not found: {}", job_id))?
+        .cancel();
+    Ok(())
+}
+
+// Read-Aloud Alignment Commands
+
+#[tauri::command]
+pub fn start_reading_alignment(
+    reference_text: String,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let alignment_id = uuid::Uuid::new_v4().to_string();
+    let mut aligners = state.reading_aligners.lock().map_err(|e| e.to_string())?;
+    aligners.insert(alignment_id.clone(), ReadingAligner::new(&reference_text));
+    Ok(alignment_id)
+}
+
+#[tauri::command]
+pub fn update_reading_alignment(
+    alignment_id: String,
+    words: Vec<TranscriptionWord>,
+    is_final: bool,
+    state: State<AppState>,
+) -> Result<AlignmentUpdate, String> {
+    let mut aligners = state.reading_aligners.lock().map_err(|e| e.to_string())?;
+    let aligner = aligners
+        .get_mut(&alignment_id)
+        .ok_or_else(|| format!("Unknown reading alignment: {}", alignment_id))?;
+    Ok(aligner.update(&words, is_final))
+}
+
+#[tauri::command]
+pub fn seek_reading_alignment(
+    alignment_id: String,
+    word_index: usize,
+    state: State<AppState>,
+) -> Result<AlignmentUpdate, String> {
+    let mut aligners = state.reading_aligners.lock().map_err(|e| e.to_string())?;
+    let aligner = aligners
+        .get_mut(&alignment_id)
+        .ok_or_else(|| format!("Unknown reading alignment: {}", alignment_id))?;
+    Ok(aligner.seek(word_index))
+}
+
+#[tauri::command]
+pub fn stop_reading_alignment(
+    alignment_id: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let mut aligners = state.reading_aligners.lock().map_err(|e| e.to_string())?;
+    aligners
+        .remove(&alignment_id)
+        .map(|_| ())
+        .ok_or_else(|| format!("Unknown reading alignment: {}", alignment_id))
+}
+
+// Pronunciation Commands
+
+/// Where an assessed passage sits in a document, so results can feed `practice_words`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PracticeContext {
+    pub document_id: String,
+    pub page_number: i64,
+}
+
+/// Padding kept around a word when saving its recording
+const WORD_CLIP_PADDING_SECONDS: f32 = 0.1;
+
+/// The sentence of `text` that contains `word`, used as context for review cards
+fn context_sentence(text: &str, word: &str) -> Option<String> {
+    let target = normalize_word(word);
+    text.split_inclusive(['.', '!', '?', '\n'])
+        .map(str::trim)
+        .find(|sentence| {
+            sentence
+                .split_whitespace()
+                .any(|token| normalize_word(token) == target)
+        })
+        .map(str::to_string)
+}
+
+/// Save the learner's recording of one word as a WAV clip in the app data directory
+fn save_word_clip(
+    app: &AppHandle,
+    practice_word_id: &str,
+    samples: &[f32],
+    start: f32,
+    end: f32,
+) -> Result<String, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?
+        .join("practice_audio");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create recordings directory: {}", e))?;
+
+    let rate = WHISPER_SAMPLE_RATE as f32;
+    let from = (((start - WORD_CLIP_PADDING_SECONDS).max(0.0) * rate) as usize).min(samples.len());
+    let to = (((end + WORD_CLIP_PADDING_SECONDS) * rate) as usize).clamp(from, samples.len());
+
+    let path = dir.join(format!("{}.wav", practice_word_id));
+    std::fs::write(&path, encode_wav(&samples[from..to], WHISPER_SAMPLE_RATE))
+        .map_err(|e| format!("Failed to save recording: {}", e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn score_and_record(
+    app: &AppHandle,
+    state: &AppState,
+    expected_text: &str,
+    transcription: &TranscriptionResult,
+    language: &str,
+    practice: Option<PracticeContext>,
+    audio: Option<&[f32]>,
+) -> Result<PronunciationAssessment, String> {
+    let assessment = {
+        let mut pronunciation = state.pronunciation.lock().map_err(|e| e.to_string())?;
+        let service = match pronunciation.as_mut() {
+            Some(service) => service,
+            None => {
+                // Prefer a full CMUdict from the models directory when installed
+                let manager = ModelManager::new(app.clone());
+                let dict_path = manager.get_model_path(CMUDICT_FILE_NAME)?;
+                let dictionary = if dict_path.exists() {
+                    let _ = manager.mark_used(CMUDICT_FILE_NAME);
+                    PhonemeDictionary::load(&dict_path)?
+                } else {
+                    PhonemeDictionary::builtin()
+                };
+                pronunciation.insert(PronunciationService::new(dictionary))
+            }
+        };
+        service.assess(expected_text, transcription, language)
+    };
+
+    if let Some(practice) = practice {
+        state.with_database(|db| {
+            for word in &assessment.words {
+                let details = serde_json::to_string(word).map_err(|e| e.to_string())?;
+                let ipa = (assessment.unit == "arpabet" && !word.expected_phonemes.is_empty())
+                    .then(|| arpabet_to_ipa(&word.expected_phonemes));
+                let practice_word_id = db
+                    .record_practice_attempt(&PracticeAttempt {
+                        document_id: practice.document_id.clone(),
+                        word: normalize_word(&word.word),
+                        language: language.to_string(),
+                        page_number: practice.page_number,
+                        score: word.score,
+                        mispronounced: word.mispronounced,
+                        details,
+                        context: context_sentence(expected_text, &word.word),
+                        ipa,
+                    })
+                    .map_err(|e| e.to_string())?;
+
+                if let (Some(id), Some(samples), Some(start), Some(end)) =
+                    (practice_word_id, audio, word.start, word.end)
+                {
+                    if word.mispronounced {
+                        let audio_path = save_word_clip(app, &id, samples, start, end)?;
+                        db.save_practice_word_details(
+                            &id,
+                            &PracticeWordDetails {
+                                audio_path: Some(audio_path),
+                                ..Default::default()
+                            },
+                        )
+                        .map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(assessment)
+}
+
+#[tauri::command]
+pub async fn assess_pronunciation(
+    app: AppHandle,
+    audio_data: Vec<u8>,
+    format: Option<String>,
+    expected_text: String,
+    options: TranscriptionOptions,
+    practice: Option<PracticeContext>,
+    state: State<'_, AppState>,
+) -> Result<PronunciationAssessment, String> {
+    let whisper_app = app.clone();
+    let (transcription, samples) = tauri::async_runtime::spawn_blocking(move || {
+        let service = WhisperService::new(whisper_app);
+        let config = service.config_from_options(&options)?;
+        // Keep the decoded audio so mispronounced words can be saved for review
+        let samples = decode_audio_bytes(audio_data, format.as_deref())?;
+        let transcription = service.transcribe_samples(&samples, &config)?;
+        Ok::<_, String>((transcription, samples))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let language = transcription.language.clone();
+    score_and_record(
+        &app,
+        &state,
+        &expected_text,
+        &transcription,
+        &language,
+        practice,
+        Some(&samples),
+    )
+}
+
+#[tauri::command]
+pub fn score_pronunciation(
+    app: AppHandle,
+    expected_text: String,
+    transcription: TranscriptionResult,
+    practice: Option<PracticeContext>,
+    state: State<AppState>,
+) -> Result<PronunciationAssessment, String> {
+    let language = transcription.language.clone();
+    score_and_record(&app, &state, &expected_text, &transcription, &language, practice, None)
+}
+
+// Spaced Repetition Commands
+
+/// Practice words due for review today, optionally limited to one document or language
+#[tauri::command]
+pub fn get_due_practice_words(
+    filter: Option<DueQueueFilter>,
+    state: State<AppState>,
+) -> Result<Vec<DuePracticeWord>, String> {
+    state.with_database(|db| {
+        db.due_practice_words(&filter.unwrap_or_default(), end_of_today())
+            .map_err(|e| format!("Failed to load review queue: {}", e))
+    })
+}
+
+#[tauri::command]
+pub fn record_practice_review(
+    practice_word_id: String,
+    grade: ReviewGrade,
+    state: State<AppState>,
+) -> Result<ReviewSchedule, String> {
+    state.with_database(|db| {
+        db.record_practice_review(&practice_word_id, grade, chrono::Utc::now())
+            .map_err(|e| format!("Failed to record review: {}", e))
+    })
+}
+
+#[tauri::command]
+pub fn get_practice_review_log(
+    practice_word_id: String,
+    state: State<AppState>,
+) -> Result<Vec<PracticeReview>, String> {
+    state.with_database(|db| {
+        db.practice_reviews(&practice_word_id)
+            .map_err(|e| format!("Failed to load review log: {}", e))
+    })
+}
+
+// Vocabulary Export Commands
+
+#[tauri::command]
+pub fn update_practice_word_details(
+    practice_word_id: String,
+    details: PracticeWordDetails,
+    state: State<AppState>,
+) -> Result<(), String> {
+    state.with_database(|db| {
+        db.save_practice_word_details(&practice_word_id, &details)
+            .map_err(|e| format!("Failed to update practice word: {}", e))
+    })
+}
+
+/// Export practice words to CSV; returns the number of words written
+#[tauri::command]
+pub fn export_practice_words_csv(
+    path: String,
+    filter: Option<VocabularyFilter>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let entries = state.with_database(|db| {
+        db.vocabulary(&filter.unwrap_or_default())
+            .map_err(|e| format!("Failed to load practice words: {}", e))
+    })?;
+    export_vocabulary_csv(Path::new(&path), &entries)?;
+    Ok(entries.len())
+}
+
+/// Export practice words as an Anki deck; returns the number of notes written
+#[tauri::command]
+pub async fn export_practice_words_apkg(
+    app: AppHandle,
+    path: String,
+    deck_name: String,
+    filter: Option<VocabularyFilter>,
+) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let entries = app.state::<AppState>().with_database(|db| {
+            db.vocabulary(&filter.unwrap_or_default())
+                .map_err(|e| format!("Failed to load practice words: {}", e))
+        })?;
+        export_vocabulary_apkg(Path::new(&path), &deck_name, &entries)?;
+        Ok(entries.len())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn import_practice_words_csv(
+    path: String,
+    defaults: Option<VocabularyImportDefaults>,
+    state: State<AppState>,
+) -> Result<VocabularyImportSummary, String> {
+    state.with_database(|db| {
+        import_vocabulary_csv(db, Path::new(&path), &defaults.unwrap_or_default())
+    })
+}
+
+/// Location of the application database, for the frontend's SQL connection
+#[tauri::command]
+pub fn get_database_path(state: State<AppState>) -> Result<String, String> {
+    state.with_database(|db| Ok(db.path().to_string_lossy().to_string()))
+}
+
+// Document Library Commands
+
+/// Add an opened file to the library, following it if it was moved or renamed.
+/// Pass `total_pages` when already known to skip parsing the PDF.
+#[tauri::command]
+pub async fn register_document(
+    app: AppHandle,
+    path: String,
+    total_pages: Option<i64>,
+) -> Result<RegisteredDocument, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = DocumentFile::inspect(Path::new(&path), total_pages)?;
+        let state = app.state::<AppState>();
+        let (document, own_ids) = state.with_database(|db| {
+            let document = register_document_file(db, &file)?;
+            let own_ids: HashSet<String> = db
+                .annotations(&document.id, None, true)
+                .map_err(|e| format!("Failed to load annotations: {}", e))?
+                .into_iter()
+                .map(|annotation| annotation.id)
+                .collect();
+            Ok((document, own_ids))
+        })?;
+
+        // Annotations made in other readers; a PDF we cannot parse still opens
+        let imported = read_pdf_annotations(&file.path, &document.id, &own_ids).and_then(
+            |annotations| {
+                state.with_database(|db| {
+                    db.import_pdf_annotations(&annotations)
+                        .map_err(|e| format!("Failed to import annotations: {}", e))
+                })
+            },
+        );
+        Ok(RegisteredDocument {
+            document,
+            imported_annotations: imported.as_ref().map_or(0, |added| *added),
+            annotation_import_error: imported.err(),
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_document(
+    document_id: String,
+    state: State<AppState>,
+) -> Result<Option<DocumentRecord>, String> {
+    state.with_database(|db| {
+        db.document(&document_id)
+            .map_err(|e| format!("Failed to load document: {}", e))
+    })
+}
+
+#[tauri::command]
+pub fn list_documents(
+    query: Option<DocumentQuery>,
+    state: State<AppState>,
+) -> Result<DocumentPage, String> {
+    state.with_database(|db| {
+        db.list_documents(&query.unwrap_or_default())
+            .map_err(|e| format!("Failed to list documents: {}", e))
+    })
+}
+
+/// Mark a document opened without re-reading its file
+#[tauri::command]
+pub fn touch_document(document_id: String, state: State<AppState>) -> Result<bool, String> {
+    state.with_database(|db| {
+        db.touch_document(&document_id, chrono::Utc::now())
+            .map_err(|e| format!("Failed to update document: {}", e))
+    })
+}
+
+#[tauri::command]
+pub fn rename_document(
+    document_id: String,
+    title: String,
+    state: State<AppState>,
+) -> Result<bool, String> {
+    state.with_database(|db| {
+        db.rename_document(&document_id, title.trim())
+            .map_err(|e| format!("Failed to rename document: {}", e))
+    })
+}
+
+/// Point a document at the file it was moved to; the contents must match
+#[tauri::command]
+pub async fn relocate_document(
+    app: AppHandle,
+    document_id: String,
+    path: String,
+) -> Result<DocumentRecord, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let file = DocumentFile::inspect(Path::new(&path), None)?;
+        app.state::<AppState>()
+            .with_database(|db| relocate_document_file(db, &document_id, &file))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Remove a document from the library with its progress, annotations and
+/// practice history; the file itself is left alone
+#[tauri::command]
+pub fn delete_document(document_id: String, state: State<AppState>) -> Result<bool, String> {
+    state.with_database(|db| {
+        db.delete_document(&document_id)
+            .map_err(|e| format!("Failed to delete document: {}", e))
+    })
+}
+
+// Reading Progress Commands
+
+/// Days of history in reading statistics unless asked otherwise
+const DEFAULT_READING_STATS_DAYS: u32 = 30;
+
+/// Remember the reader's place; `scroll_offset` is the fraction of the page scrolled past
+#[tauri::command]
+pub fn save_reading_progress(
+    document_id: String,
+    page: i64,
+    scroll_offset: Option<f64>,
+    zoom: Option<f64>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let position = ReadingPosition {
+        page,
+        scroll_offset,
+        zoom,
+    };
+    state.with_database(|db| {
+        db.save_reading_position(&document_id, &position, chrono::Utc::now())
+            .map_err(|e| format!("Failed to save reading progress: {}", e))
+    })
+}
+
+#[tauri::command]
+pub fn get_reading_progress(
+    document_id: String,
+    state: State<AppState>,
+) -> Result<Option<ReadingProgress>, String> {
+    state.with_database(|db| {
+        db.reading_progress(&document_id)
+            .map_err(|e| format!("Failed to load reading progress: {}", e))
+    })
+}
+
+/// Count reading time; the reader sends this every 30 seconds or so while a
+/// document is open
+#[tauri::command]
+pub fn record_reading_heartbeat(
+    heartbeat: ReadingHeartbeat,
+    state: State<AppState>,
+) -> Result<ReadingSession, String> {
+    state.with_database(|db| {
+        db.record_reading_heartbeat(
+            &heartbeat,
+            chrono::Utc::now(),
+            chrono::Local::now().date_naive(),
+        )
+        .map_err(|e| format!("Failed to record reading time: {}", e))
+    })
+}
+
+/// Reading habits over the last `days` days, today included
+#[tauri::command]
+pub fn get_reading_stats(
+    days: Option<u32>,
+    state: State<AppState>,
+) -> Result<ReadingStats, String> {
+    let today = chrono::Local::now().date_naive();
+    let days = days.unwrap_or(DEFAULT_READING_STATS_DAYS).max(1);
+    let since = today - chrono::Duration::days(i64::from(days) - 1);
+    state.with_database(|db| {
+        db.reading_stats(since, today)
+            .map_err(|e| format!("Failed to load reading statistics: {}", e))
+    })
+}
+
+// Annotation Commands
+
+#[tauri::command]
+pub fn create_annotation(
+    annotation: NewAnnotation,
+    state: State<AppState>,
+) -> Result<Annotation, String> {
+    let annotation = annotation.into_annotation(chrono::Utc::now());
+    state.with_database(|db| {
+        annotation.validate_for(db)?;
+        db.insert_annotation(&annotation)
+            .map_err(|e| format!("Failed to save annotation: {}", e))?;
+        Ok(annotation)
+    })
+}
+
+#[tauri::command]
+pub fn update_annotation(
+    annotation_id: String,
+    update: AnnotationUpdate,
+    state: State<AppState>,
+) -> Result<Annotation, String> {
+    state.with_database(|db| {
+        let mut annotation = db
+            .annotation(&annotation_id)
+            .map_err(|e| format!("Failed to load annotation: {}", e))?
+            .filter(|annotation| annotation.deleted_at.is_none())
+            .ok_or_else(|| format!("Annotation not found: {}", annotation_id))?;
+        update.apply(&mut annotation, chrono::Utc::now());
+        annotation.validate_for(db)?;
+        db.update_annotation(&annotation)
+            .map_err(|e| format!("Failed to save annotation: {}", e))?;
+        Ok(annotation)
+    })
+}
+
+/// Move an annotation to the trash; `undo_annotation_change` brings it back
+#[tauri::command]
+pub fn delete_annotation(annotation_id: String, state: State<AppState>) -> Result<bool, String> {
+    state.with_database(|db| {
+        db.delete_annotation(&annotation_id, chrono::Utc::now())
+            .map_err(|e| format!("Failed to delete annotation: {}", e))
+    })
+}
+
+/// A document's annotations, or one page's when `page_number` is given
+#[tauri::command]
+pub fn get_annotations(
+    document_id: String,
+    page_number: Option<i64>,
+    include_deleted: Option<bool>,
+    state: State<AppState>,
+) -> Result<Vec<Annotation>, String> {
+    state.with_database(|db| {
+        db.annotations(&document_id, page_number, include_deleted.unwrap_or(false))
+            .map_err(|e| format!("Failed to load annotations: {}", e))
+    })
+}
+
+/// Annotations whose text or author matches `query`, in one document or all of them
+#[tauri::command]
+pub fn search_annotations(
+    query: String,
+    document_id: Option<String>,
+    limit: Option<u32>,
+    state: State<AppState>,
+) -> Result<Vec<Annotation>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    state.with_database(|db| {
+        db.search_annotations(query, document_id.as_deref(), limit.unwrap_or(100))
+            .map_err(|e| format!("Failed to search annotations: {}", e))
+    })
+}
+
+/// Revert the document's latest annotation change; `None` when there is nothing to undo
+#[tauri::command]
+pub fn undo_annotation_change(
+    document_id: String,
+    state: State<AppState>,
+) -> Result<Option<Annotation>, String> {
+    state.with_database(|db| {
+        db.undo_annotation_change(&document_id, chrono::Utc::now())
+            .map_err(|e| format!("Failed to undo annotation change: {}", e))
+    })
+}
+
+/// Permanently remove trashed annotations; returns how many were removed
+#[tauri::command]
+pub fn purge_deleted_annotations(
+    document_id: String,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    state.with_database(|db| {
+        db.purge_deleted_annotations(&document_id)
+            .map_err(|e| format!("Failed to empty annotation trash: {}", e))
+    })
+}
+
+/// Write the document's annotations into the PDF as standard annotations other
+/// readers can show. Without `output_path` the document's own file is updated.
+#[tauri::command]
+pub async fn export_annotations_to_pdf(
+    app: AppHandle,
+    document_id: String,
+    output_path: Option<String>,
+) -> Result<PdfAnnotationExport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        let (document, annotations) = state.with_database(|db| {
+            let document = db
+                .document(&document_id)
+                .map_err(|e| format!("Failed to load document: {}", e))?
+                .ok_or_else(|| format!("Document not found: {}", document_id))?;
+            let annotations = db
+                .annotations(&document_id, None, true)
+                .map_err(|e| format!("Failed to load annotations: {}", e))?;
+            Ok((document, annotations))
+        })?;
+
+        // Trashed annotations are not written, but earlier exports of them are removed
+        let known_ids: HashSet<String> = annotations.iter().map(|a| a.id.clone()).collect();
+        let source = PathBuf::from(&document.file_path);
+        let output = output_path
+            .map(PathBuf::from)
+            .unwrap_or_else(|| source.clone());
+        let export = write_pdf_annotations(&source, &output, &annotations, &known_ids)?;
+
+        if output == source {
+            let file_hash = calculate_file_hash(&document.file_path)
+                .map_err(|e| format!("Failed to hash {}: {}", document.file_path, e))?;
+            state.with_database(|db| {
+                db.update_document_hash(&document.id, &file_hash)
+                    .map_err(|e| format!("Failed to update document: {}", e))
+            })?;
+        }
+        Ok(export)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Write a document's annotations to an XFDF or FDF file, leaving the PDF as it
+/// is. The format follows the file extension unless given.
+#[tauri::command]
+pub async fn export_annotations_to_file(
+    app: AppHandle,
+    document_id: String,
+    path: String,
+    format: Option<AnnotationFileFormat>,
+) -> Result<usize, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (document, annotations) = app.state::<AppState>().with_database(|db| {
+            let document = db
+                .document(&document_id)
+                .map_err(|e| format!("Failed to load document: {}", e))?
+                .ok_or_else(|| format!("Document not found: {}", document_id))?;
+            let annotations = db
+                .annotations(&document_id, None, false)
+                .map_err(|e| format!("Failed to load annotations: {}", e))?;
+            Ok((document, annotations))
+        })?;
+
+        let path = PathBuf::from(path);
+        let format = format
+            .or_else(|| AnnotationFileFormat::from_path(&path))
+            .unwrap_or(AnnotationFileFormat::Xfdf);
+        let pdf_file_name = Path::new(&document.file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        export_annotations_file(&path, format, &pdf_file_name, &annotations)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Add the annotations in an XFDF or FDF file to a document
+#[tauri::command]
+pub fn import_annotations_from_file(
+    document_id: String,
+    path: String,
+    state: State<AppState>,
+) -> Result<AnnotationImportSummary, String> {
+    state.with_database(|db| import_annotations_file(db, &document_id, Path::new(&path)))
+}
+
+/// A document's highlights and notes grouped by chapter, as Markdown with page
+/// links or as HTML; also written to `output_path` when given
+#[tauri::command]
+pub async fn export_annotation_notes(
+    app: AppHandle,
+    document_id: String,
+    format: NotesFormat,
+    output_path: Option<String>,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (document, annotations) = app.state::<AppState>().with_database(|db| {
+            let document = db
+                .document(&document_id)
+                .map_err(|e| format!("Failed to load document: {}", e))?
+                .ok_or_else(|| format!("Document not found: {}", document_id))?;
+            let annotations = db
+                .annotations(&document_id, None, false)
+                .map_err(|e| format!("Failed to load annotations: {}", e))?;
+            Ok((document, annotations))
+        })?;
+
+        let source = Path::new(&document.file_path);
+        let pdf = load_pdf_document(source)?;
+        let pdf_file_name = source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let notes =
+            render_annotation_notes(&pdf, &document.title, &pdf_file_name, &annotations, format);
+        if let Some(output_path) = output_path {
+            std::fs::write(&output_path, &notes)
+                .map_err(|e| format!("Failed to write {}: {}", output_path, e))?;
+        }
+        Ok(notes)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Model Download Commands
+
+/// Download a catalog model or voice, resuming any partial file; progress
+/// arrives as `model-download-progress` events
+#[tauri::command]
+pub fn download_model(
+    app: AppHandle,
+    file_name: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let manager = ModelManager::new(app);
+    let model = manager
+        .find_model(&file_name)?
+        .ok_or_else(|| format!("Unknown model: {}", file_name))?;
+
+    let mut downloads = state.model_downloads.lock().map_err(|e| e.to_string())?;
+    downloads.retain(|_, download| !download.is_finished());
+    if downloads.contains_key(&file_name) {
+        return Err(format!("{} is already downloading", file_name));
+    }
+
+    let download = manager.start_download(model)?;
+    downloads.insert(file_name, download);
+    Ok(())
+}
+
+/// Continue a paused or interrupted download from where it stopped
+#[tauri::command]
+pub fn resume_model_download(
+    app: AppHandle,
+    file_name: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    download_model(app, file_name, state)
+}
+
+#[tauri::command]
+pub async fn pause_model_download(
+    file_name: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let download = state
+        .model_downloads
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&file_name);
+
+    match download {
+        Some(download) => tauri::async_runtime::spawn_blocking(move || download.pause())
+            .await
+            .map_err(|e| e.to_string())?,
+        None => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub async fn cancel_model_download(
+    app: AppHandle,
+    file_name: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let download = state
+        .model_downloads
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&file_name);
+
+    if let Some(download) = download {
+        tauri::async_runtime::spawn_blocking(move || download.cancel())
+            .await
+            .map_err(|e| e.to_string())??;
+    }
+    // A paused download only has its partial file left
+    ModelManager::new(app).discard_partial_download(&file_name)
+}
+
+/// Remove superseded or unused models and leftovers of old downloads,
+/// keeping anything currently downloading
+#[tauri::command]
+pub async fn collect_model_garbage(
+    app: AppHandle,
+    options: Option<GcOptions>,
+    state: State<'_, AppState>,
+) -> Result<GcReport, String> {
+    let active: Vec<String> = {
+        let mut downloads = state.model_downloads.lock().map_err(|e| e.to_string())?;
+        downloads.retain(|_, download| !download.is_finished());
+        downloads.keys().cloned().collect()
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        ModelManager::new(app).collect_garbage(&options.unwrap_or_default(), &active)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_model_download_progress(
+    app: AppHandle,
+    file_name: String,
+    state: State<AppState>,
+) -> Result<Option<DownloadProgress>, String> {
+    let downloads = state.model_downloads.lock().map_err(|e| e.to_string())?;
+    match downloads.get(&file_name) {
+        Some(download) => download.progress().map(Some),
+        None => ModelManager::new(app).get_partial_download(&file_name),
+    }
+}
@@ -1,14 +1,21 @@
 use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
 
 #[tauri::command]
 pub async fn open_file_dialog(app: AppHandle) -> Result<Option<String>, String> {
-    use tauri::Manager;
-    
-    let file_path = tauri::api::dialog::blocking::FileDialogBuilder::new()
+    let file_path = app
+        .dialog()
+        .file()
         .add_filter("PDF Files", &["pdf"])
-        .pick_file();
+        .blocking_pick_file();
 
-    Ok(file_path.map(|p| p.to_string_lossy().to_string()))
+    file_path
+        .map(|p| {
+            p.into_path()
+                .map(|p| p.to_string_lossy().to_string())
+                .map_err(|e| format!("Failed to read selected path: {}", e))
+        })
+        .transpose()
 }
 
 #[tauri::command]
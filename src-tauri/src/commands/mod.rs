@@ -1,7 +1,13 @@
+pub mod app;
 pub mod file;
 pub mod pdf;
 pub mod model_commands;
+pub mod whisper_commands;
+pub mod tts_commands;
 
+pub use app::*;
 pub use file::*;
 pub use pdf::*;
 pub use model_commands::*;
+pub use whisper_commands::*;
+pub use tts_commands::*;
@@ -1,9 +1,39 @@
-use crate::services::{ModelInfo, ModelManager};
+use crate::services::{
+    DownloadedModel, HardwareProfile, HardwareRecommendation, ImportMode, ModelInfo, ModelKind,
+    ModelManager, ModelRegistry, ModelVerification, RecommendationOptions, StorageUsage,
+};
+use std::path::{Path, PathBuf};
 use tauri::{AppHandle, command};
 
 #[command]
-pub fn get_available_models() -> Vec<ModelInfo> {
-    ModelManager::get_available_models()
+pub fn get_available_models(app: AppHandle) -> Result<Vec<ModelInfo>, String> {
+    ModelManager::new(app).get_available_models()
+}
+
+/// The whole model catalog, optionally narrowed to one kind
+#[command]
+pub fn get_model_registry(
+    app: AppHandle,
+    kind: Option<ModelKind>,
+) -> Result<ModelRegistry, String> {
+    let mut registry = ModelManager::new(app).registry()?;
+    if let Some(kind) = kind {
+        registry.models.retain(|model| model.kind == kind);
+    }
+    Ok(registry)
+}
+
+/// Fetch a newer registry manifest from `url`, or the registry's refresh URL
+#[command]
+pub async fn refresh_model_registry(
+    app: AppHandle,
+    url: Option<String>,
+) -> Result<ModelRegistry, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        ModelManager::new(app).refresh_registry(url.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
 #[command]
@@ -20,7 +50,7 @@ pub fn is_model_downloaded(app: AppHandle, file_name: String) -> Result<bool, St
 }
 
 #[command]
-pub fn list_downloaded_models(app: AppHandle) -> Result<Vec<String>, String> {
+pub fn list_downloaded_models(app: AppHandle) -> Result<Vec<DownloadedModel>, String> {
     let manager = ModelManager::new(app);
     manager.list_downloaded_models()
 }
@@ -43,3 +73,72 @@ pub fn get_model_size(app: AppHandle, file_name: String) -> Result<u64, String>
     let manager = ModelManager::new(app);
     manager.get_model_size(&file_name)
 }
+
+/// Hash a downloaded model and compare it to its published checksum
+#[command]
+pub async fn verify_model(app: AppHandle, file_name: String) -> Result<ModelVerification, String> {
+    tauri::async_runtime::spawn_blocking(move || ModelManager::new(app).verify_model(&file_name))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Register a model from a local file by copying, moving or linking it into
+/// the models directory
+#[command]
+pub async fn import_model(
+    app: AppHandle,
+    source_path: String,
+    mode: ImportMode,
+    file_name: Option<String>,
+) -> Result<DownloadedModel, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        ModelManager::new(app).import_model(Path::new(&source_path), mode, file_name.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub fn get_model_search_dirs(app: AppHandle) -> Result<Vec<String>, String> {
+    let dirs = ModelManager::new(app).get_search_dirs()?;
+    Ok(dirs.iter().map(|dir| dir.to_string_lossy().to_string()).collect())
+}
+
+/// Replace the read-only directories searched for models, e.g. a shared mount
+#[command]
+pub fn set_model_search_dirs(app: AppHandle, dirs: Vec<String>) -> Result<Vec<String>, String> {
+    let dirs: Vec<PathBuf> = dirs.iter().map(PathBuf::from).collect();
+    let dirs = ModelManager::new(app).set_search_dirs(&dirs)?;
+    Ok(dirs.iter().map(|dir| dir.to_string_lossy().to_string()).collect())
+}
+
+/// Disk use of the models directory against the quota and free space
+#[command]
+pub fn get_model_storage_usage(app: AppHandle) -> Result<StorageUsage, String> {
+    ModelManager::new(app).storage_usage()
+}
+
+/// Cap the models directory; downloads that would exceed it are refused
+#[command]
+pub fn set_model_quota(app: AppHandle, quota_mb: Option<u64>) -> Result<(), String> {
+    ModelManager::new(app).set_quota(quota_mb)
+}
+
+#[command]
+pub fn get_hardware_profile() -> HardwareProfile {
+    HardwareProfile::detect()
+}
+
+/// Suggest a model per task that this machine's memory and CPU can handle,
+/// with estimated real-time factors
+#[command]
+pub async fn recommend_models(
+    app: AppHandle,
+    options: Option<RecommendationOptions>,
+) -> Result<HardwareRecommendation, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        ModelManager::new(app).recommend_models(&options.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
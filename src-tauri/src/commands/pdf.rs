@@ -1,3 +1,4 @@
+use crate::services::{load_pdf_document, read_outline, OutlineEntry};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -55,3 +56,14 @@ pub async fn load_pdf(path: String) -> Result<PdfMetadata, String> {
 pub async fn get_pdf_info(path: String) -> Result<PdfMetadata, String> {
     load_pdf(path).await
 }
+
+/// Bookmarks of the PDF, used to pick chapter ranges for audiobook export
+#[tauri::command]
+pub async fn get_pdf_outline(path: String) -> Result<Vec<OutlineEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let doc = load_pdf_document(&PathBuf::from(&path))?;
+        Ok(read_outline(&doc))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
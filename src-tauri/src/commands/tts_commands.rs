@@ -0,0 +1,52 @@
+use crate::services::{
+    parse_ssml, ModelInfo, ModelManager, SynthesisResult, TtsOptions, TtsService, TtsVoice,
+};
+use tauri::{AppHandle, command};
+
+#[command]
+pub fn list_tts_voices(app: AppHandle) -> Result<Vec<TtsVoice>, String> {
+    TtsService::new(app).list_voices()
+}
+
+#[command]
+pub fn is_tts_available(app: AppHandle) -> bool {
+    TtsService::new(app).is_available()
+}
+
+#[command]
+pub fn get_available_voices(app: AppHandle) -> Result<Vec<ModelInfo>, String> {
+    ModelManager::new(app).get_available_voices()
+}
+
+/// Synthesize a passage in one go and return the WAV audio with word timings
+#[command]
+pub async fn synthesize_speech(
+    app: AppHandle,
+    text: String,
+    options: Option<TtsOptions>,
+) -> Result<SynthesisResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        TtsService::new(app)
+            .synthesize(&text, &options.unwrap_or_default())
+            .map(SynthesisResult::from)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Synthesize SSML in one go; word offsets point into the markup
+#[command]
+pub async fn synthesize_ssml(
+    app: AppHandle,
+    ssml: String,
+    options: Option<TtsOptions>,
+) -> Result<SynthesisResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let segments = parse_ssml(&ssml)?;
+        TtsService::new(app)
+            .synthesize_segments(&segments, &options.unwrap_or_default())
+            .map(SynthesisResult::from)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,44 @@
+use crate::services::{TranscriptionOptions, TranscriptionResult, WhisperService};
+use std::path::PathBuf;
+use tauri::{AppHandle, command};
+
+#[command]
+pub async fn transcribe_audio(
+    app: AppHandle,
+    audio_path: String,
+    options: TranscriptionOptions,
+) -> Result<TranscriptionResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let service = WhisperService::new(app);
+        let config = service.config_from_options(&options)?;
+        service.transcribe_audio(PathBuf::from(audio_path), config)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn transcribe_audio_data(
+    app: AppHandle,
+    audio_data: Vec<u8>,
+    format: Option<String>,
+    options: TranscriptionOptions,
+) -> Result<TranscriptionResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let service = WhisperService::new(app);
+        let config = service.config_from_options(&options)?;
+        service.transcribe_audio_data(audio_data, format.as_deref(), config)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub fn is_whisper_available(app: AppHandle) -> bool {
+    WhisperService::new(app).is_available()
+}
+
+#[command]
+pub fn get_supported_audio_formats() -> Vec<String> {
+    WhisperService::get_supported_formats()
+}
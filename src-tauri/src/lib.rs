@@ -4,6 +4,7 @@ mod services;
 mod utils;
 
 use commands::*;
+use tauri::Manager;
 
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -16,25 +17,113 @@ pub fn run() {
 
     tauri::Builder::default()
         .manage(app_state)
+        .setup(|app| {
+            // Migrations run here, before any command can touch the database
+            app.state::<AppState>().open_database(app.handle())?;
+            Ok(())
+        })
         .plugin(tauri_plugin_opener::init())
-        .plugin(
-            tauri_plugin_sql::Builder::default()
-                .add_migrations("sqlite:practice.db", vec![])
-                .build(),
-        )
+        .plugin(tauri_plugin_dialog::init())
+        // The frontend connects to the same database; its schema is migrated in Rust
+        .plugin(tauri_plugin_sql::Builder::default().build())
         .invoke_handler(tauri::generate_handler![
             greet,
             open_file_dialog,
             read_file,
             load_pdf,
             get_pdf_info,
+            get_pdf_outline,
             get_available_models,
+            get_model_registry,
+            refresh_model_registry,
             get_models_dir,
             is_model_downloaded,
             list_downloaded_models,
             get_model_path,
             delete_model,
             get_model_size,
+            verify_model,
+            import_model,
+            get_model_search_dirs,
+            set_model_search_dirs,
+            get_model_storage_usage,
+            set_model_quota,
+            get_hardware_profile,
+            recommend_models,
+            // Model download commands
+            download_model,
+            pause_model_download,
+            resume_model_download,
+            cancel_model_download,
+            get_model_download_progress,
+            collect_model_garbage,
+            // Whisper commands
+            transcribe_audio,
+            transcribe_audio_data,
+            is_whisper_available,
+            get_supported_audio_formats,
+            // Text-to-speech commands
+            list_tts_voices,
+            is_tts_available,
+            get_available_voices,
+            synthesize_speech,
+            synthesize_ssml,
+            start_speaking,
+            start_speaking_ssml,
+            generate_ssml,
+            stop_speaking,
+            // Streaming transcription commands
+            start_transcription_stream,
+            push_transcription_audio,
+            stop_transcription_stream,
+            // Read-aloud alignment commands
+            start_reading_alignment,
+            update_reading_alignment,
+            seek_reading_alignment,
+            stop_reading_alignment,
+            // Pronunciation commands
+            assess_pronunciation,
+            score_pronunciation,
+            // Spaced repetition commands
+            get_due_practice_words,
+            record_practice_review,
+            get_practice_review_log,
+            // Vocabulary export commands
+            update_practice_word_details,
+            export_practice_words_csv,
+            export_practice_words_apkg,
+            import_practice_words_csv,
+            // Database commands
+            get_database_path,
+            // Document library commands
+            register_document,
+            get_document,
+            list_documents,
+            touch_document,
+            rename_document,
+            relocate_document,
+            delete_document,
+            // Reading progress commands
+            save_reading_progress,
+            get_reading_progress,
+            record_reading_heartbeat,
+            get_reading_stats,
+            // Annotation commands
+            create_annotation,
+            update_annotation,
+            delete_annotation,
+            get_annotations,
+            search_annotations,
+            undo_annotation_change,
+            purge_deleted_annotations,
+            export_annotations_to_pdf,
+            export_annotations_to_file,
+            import_annotations_from_file,
+            export_annotation_notes,
+            // Audiobook export commands
+            start_audiobook_export,
+            get_audiobook_export_status,
+            cancel_audiobook_export,
             // Translation commands
             translate_text,
             translate_page,
@@ -0,0 +1,777 @@
+use super::annotations::{color_rgb, Annotation, AnnotationGeometry, Quad};
+use super::database::Database;
+use super::pdf_annotations::{
+    annotation_rect, collect_annotations, num, pdf_date, read_fdf_annotations,
+    write_fdf_annotations, PdfAnnotation,
+};
+use lopdf::{dictionary, text_string, Dictionary, Document, Object, ObjectId};
+use quick_xml::events::{BytesDecl, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+const XFDF_NAMESPACE: &str = "http://ns.adobe.com/xfdf/";
+
+/// Sidecar formats for exchanging annotations without changing the PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationFileFormat {
+    Xfdf,
+    Fdf,
+}
+
+impl AnnotationFileFormat {
+    /// Format named by the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "xfdf" => Some(Self::Xfdf),
+            "fdf" => Some(Self::Fdf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnnotationImportSummary {
+    pub imported: usize,
+    /// Annotations we had that the file has a newer version of
+    pub updated: usize,
+    /// Annotations we had already, at least as recent as the file's
+    pub duplicates: usize,
+    pub skipped: usize,
+    /// Why annotations were skipped
+    pub errors: Vec<String>,
+}
+
+/// Markup read from one XFDF annotation element
+#[derive(Debug, Default)]
+struct XfdfElement {
+    kind: String,
+    attributes: HashMap<String, String>,
+    contents: String,
+    gestures: Vec<String>,
+    appearance: String,
+    /// Nesting depth of the element, to find its end
+    depth: usize,
+}
+
+/// Write annotations to `path` as XFDF or FDF for the PDF named
+/// `pdf_file_name`, leaving out trashed ones. Returns how many were written.
+pub fn export_annotations_file(
+    path: &Path,
+    format: AnnotationFileFormat,
+    pdf_file_name: &str,
+    annotations: &[Annotation],
+) -> Result<usize, String> {
+    let annotations: Vec<Annotation> = annotations
+        .iter()
+        .filter(|a| a.deleted_at.is_none())
+        .cloned()
+        .collect();
+    let bytes = match format {
+        AnnotationFileFormat::Xfdf => write_xfdf(&annotations, pdf_file_name)?,
+        AnnotationFileFormat::Fdf => write_fdf_annotations(&annotations, pdf_file_name)?,
+    };
+    std::fs::write(path, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(annotations.len())
+}
+
+/// Import an XFDF or FDF file into a document.
+///
+/// Annotations are matched on their name, which is our id or the /NM they were
+/// imported with: ones we have are updated only when the file's copy says it
+/// was modified later, and new ones keep the name as their id where it is free, so
+/// exchanging the same file again does not duplicate them. Everything is
+/// stored in one transaction, so a failed import leaves the document as it was.
+pub fn import_annotations_file(
+    db: &Database,
+    document_id: &str,
+    path: &Path,
+) -> Result<AnnotationImportSummary, String> {
+    let document = db
+        .document(document_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Document not found: {}", document_id))?;
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut summary = AnnotationImportSummary::default();
+    let is_fdf = bytes.windows(5).take(1024).any(|window| window == b"%FDF-");
+    let incoming = if is_fdf {
+        read_fdf_annotations(&bytes, document_id)?
+    } else {
+        let (incoming, unsupported) = read_xfdf(&String::from_utf8_lossy(&bytes), document_id)?;
+        summary.skipped += unsupported.len();
+        summary.errors.extend(unsupported);
+        incoming
+    };
+
+    // Names in the file mapped to the ids the annotations are stored under
+    let mut ids: HashMap<String, String> = HashMap::new();
+    // Ids given to new annotations so far, which the database doesn't know yet
+    let mut assigned: HashSet<String> = HashSet::new();
+    let mut changes = Vec::new();
+    let mut added = Vec::new();
+    for PdfAnnotation {
+        mut annotation,
+        reply_to_key,
+        modified_at,
+    } in incoming
+    {
+        let name = annotation.source_key.take().unwrap_or_default();
+        if annotation.page_number > document.total_pages {
+            summary.skipped += 1;
+            summary.errors.push(format!(
+                "{} on page {}: past the end of the document",
+                annotation.geometry.kind(),
+                annotation.page_number
+            ));
+            continue;
+        }
+
+        let existing = db
+            .annotation_by_exchange_name(document_id, &name)
+            .map_err(|e| format!("Failed to import annotations: {}", e))?;
+        match existing {
+            Some(existing) => {
+                ids.insert(name, existing.id.clone());
+                // An undated copy can't be shown to be newer, so local edits win
+                let newer = modified_at.is_some_and(|modified| modified > existing.updated_at);
+                if existing.deleted_at.is_some() || !newer {
+                    summary.duplicates += 1;
+                    continue;
+                }
+                changes.push(Annotation {
+                    id: existing.id,
+                    created_at: existing.created_at,
+                    reply_to: existing.reply_to,
+                    source_key: existing.source_key,
+                    ..annotation
+                });
+                summary.updated += 1;
+            }
+            None => {
+                let taken = db
+                    .annotation(&name)
+                    .map_err(|e| format!("Failed to import annotations: {}", e))?
+                    .is_some();
+                // Otherwise the annotation keeps the fresh id it was read with
+                if !name.is_empty() && !taken && !assigned.contains(&name) {
+                    annotation.id = name.clone();
+                }
+                assigned.insert(annotation.id.clone());
+                ids.entry(name).or_insert_with(|| annotation.id.clone());
+                added.push((annotation, reply_to_key));
+            }
+        }
+    }
+
+    // Parents can come after their replies in the file
+    for (mut annotation, reply_to_key) in added {
+        annotation.reply_to = reply_to_key.and_then(|key| ids.get(&key).cloned());
+        changes.push(annotation);
+        summary.imported += 1;
+    }
+
+    db.import_annotations(&changes)
+        .map_err(|e| format!("Failed to import annotations: {}", e))?;
+    Ok(summary)
+}
+
+fn write_xfdf(annotations: &[Annotation], pdf_file_name: &str) -> Result<Vec<u8>, String> {
+    let names: HashMap<&str, &str> = annotations
+        .iter()
+        .map(|annotation| (annotation.id.as_str(), annotation.exchange_name()))
+        .collect();
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(|e| format!("Failed to write XFDF: {}", e))?;
+    writer
+        .create_element("xfdf")
+        .with_attributes([("xmlns", XFDF_NAMESPACE), ("xml:space", "preserve")])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("annots")
+                .write_inner_content(|writer| {
+                    for annotation in annotations {
+                        write_xfdf_annotation(writer, annotation, &names)?;
+                    }
+                    Ok(())
+                })?;
+            writer
+                .create_element("f")
+                .with_attribute(("href", pdf_file_name))
+                .write_empty()?;
+            Ok(())
+        })
+        .map_err(|e| format!("Failed to write XFDF: {}", e))?;
+    Ok(writer.into_inner())
+}
+
+fn write_xfdf_annotation(
+    writer: &mut Writer<Vec<u8>>,
+    annotation: &Annotation,
+    names: &HashMap<&str, &str>,
+) -> std::io::Result<()> {
+    let rect = annotation_rect(&annotation.geometry);
+    let [r, g, b] = color_rgb(&annotation.color).unwrap_or([255, 255, 0]);
+    let mut attributes = vec![
+        ("page", (annotation.page_number - 1).to_string()),
+        (
+            "rect",
+            [rect.x, rect.y, rect.x + rect.width, rect.y + rect.height]
+                .map(num)
+                .join(","),
+        ),
+        ("color", format!("#{:02X}{:02X}{:02X}", r, g, b)),
+        ("name", annotation.exchange_name().to_string()),
+        ("date", pdf_date(annotation.updated_at)),
+        ("creationdate", pdf_date(annotation.created_at)),
+        ("flags", "print".to_string()),
+    ];
+    if let Some(author) = &annotation.author {
+        attributes.push(("title", author.clone()));
+    }
+    if let Some(parent) = annotation.reply_to.as_deref().and_then(|id| names.get(id)) {
+        attributes.push(("inreplyto", parent.to_string()));
+    }
+
+    let quad_coords = |quads: &[Quad]| {
+        quads
+            .iter()
+            .flat_map(Quad::points)
+            .flat_map(|p| [num(p.x), num(p.y)])
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let kind = match &annotation.geometry {
+        AnnotationGeometry::Highlight { quads } => {
+            attributes.push(("coords", quad_coords(quads)));
+            "highlight"
+        }
+        AnnotationGeometry::Underline { quads } => {
+            attributes.push(("coords", quad_coords(quads)));
+            "underline"
+        }
+        AnnotationGeometry::Strikeout { quads } => {
+            attributes.push(("coords", quad_coords(quads)));
+            "strikeout"
+        }
+        AnnotationGeometry::Note { .. } => {
+            attributes.push(("icon", "Comment".to_string()));
+            "text"
+        }
+        AnnotationGeometry::Ink { line_width, .. } => {
+            attributes.push(("width", num(*line_width)));
+            "ink"
+        }
+        AnnotationGeometry::Rectangle { line_width, .. } => {
+            attributes.push(("width", num(*line_width)));
+            "square"
+        }
+        AnnotationGeometry::FreeText { .. } => "freetext",
+    };
+
+    writer
+        .create_element(kind)
+        .with_attributes(attributes.iter().map(|(key, value)| (*key, value.as_str())))
+        .write_inner_content(|writer| {
+            if let Some(content) = &annotation.content {
+                writer
+                    .create_element("contents")
+                    .write_text_content(BytesText::new(content))?;
+            }
+            match &annotation.geometry {
+                AnnotationGeometry::Ink { strokes, .. } => {
+                    writer
+                        .create_element("inklist")
+                        .write_inner_content(|writer| {
+                            for stroke in strokes {
+                                let gesture = stroke
+                                    .iter()
+                                    .map(|p| format!("{},{}", num(p.x), num(p.y)))
+                                    .collect::<Vec<_>>()
+                                    .join(";");
+                                writer
+                                    .create_element("gesture")
+                                    .write_text_content(BytesText::new(&gesture))?;
+                            }
+                            Ok(())
+                        })?;
+                }
+                AnnotationGeometry::FreeText { font_size, .. } => {
+                    let appearance = format!(
+                        "/Helv {} Tf {} {} {} rg",
+                        num(*font_size),
+                        num(r as f64 / 255.0),
+                        num(g as f64 / 255.0),
+                        num(b as f64 / 255.0)
+                    );
+                    writer
+                        .create_element("defaultappearance")
+                        .write_text_content(BytesText::new(&appearance))?;
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Annotations in an XFDF document, plus a note for each element of a kind we
+/// cannot represent
+fn read_xfdf(xml: &str, document_id: &str) -> Result<(Vec<PdfAnnotation>, Vec<String>), String> {
+    let mut reader = Reader::from_str(xml);
+    let mut doc = Document::new();
+    let mut annots: Vec<(u32, ObjectId)> = Vec::new();
+    let mut names: HashMap<String, ObjectId> = HashMap::new();
+    let mut replies: Vec<(ObjectId, String)> = Vec::new();
+    let mut unsupported = Vec::new();
+
+    let mut path: Vec<String> = Vec::new();
+    let mut current: Option<XfdfElement> = None;
+    loop {
+        let position = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid XFDF at byte {}: {}", position, e))?;
+        let (element, empty) = match &event {
+            Event::Start(element) => (Some(element), false),
+            Event::Empty(element) => (Some(element), true),
+            _ => (None, false),
+        };
+
+        if let Some(element) = element {
+            let name = local_name(element);
+            if current.is_none() && path.last().is_some_and(|parent| parent == "annots") {
+                current = Some(XfdfElement {
+                    attributes: xfdf_attributes(element),
+                    kind: name.clone(),
+                    depth: path.len(),
+                    ..Default::default()
+                });
+            } else if let Some(current) = current.as_mut().filter(|_| name == "gesture") {
+                current.gestures.push(String::new());
+            }
+            if !empty {
+                path.push(name);
+            }
+        }
+
+        match event {
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|e| format!("Invalid XFDF at byte {}: {}", position, e))?;
+                append_text(current.as_mut(), path.last(), &text);
+            }
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data.into_inner()).to_string();
+                append_text(current.as_mut(), path.last(), &text);
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+
+        let finished = current
+            .as_ref()
+            .is_some_and(|current| current.depth == path.len());
+        if let Some(element) = current.take_if(|_| finished) {
+            let Some((page_number, dict)) = xfdf_dictionary(&element) else {
+                let page = element.attributes.get("page").map_or("?", String::as_str);
+                unsupported.push(format!(
+                    "{} on page {}: not supported",
+                    element.kind,
+                    page.parse::<u32>()
+                        .map_or(page.to_string(), |page| (page + 1).to_string())
+                ));
+                continue;
+            };
+            let annot_id = doc.add_object(dict);
+            annots.push((page_number, annot_id));
+            if let Some(name) = element.attributes.get("name") {
+                names.insert(name.clone(), annot_id);
+            }
+            if let Some(parent) = element.attributes.get("inreplyto") {
+                replies.push((annot_id, parent.clone()));
+            }
+        }
+    }
+
+    for (annot_id, parent) in replies {
+        if let (Some(parent_id), Ok(dict)) = (names.get(&parent), doc.get_dictionary_mut(annot_id))
+        {
+            dict.set("IRT", *parent_id);
+        }
+    }
+    Ok((
+        collect_annotations(&doc, annots, document_id, &HashSet::new()),
+        unsupported,
+    ))
+}
+
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).to_string()
+}
+
+fn xfdf_attributes(element: &BytesStart) -> HashMap<String, String> {
+    element
+        .attributes()
+        .flatten()
+        .filter_map(|attribute| {
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).to_string();
+            let value = attribute.unescape_value().ok()?.to_string();
+            Some((key.to_ascii_lowercase(), value))
+        })
+        .collect()
+}
+
+fn append_text(current: Option<&mut XfdfElement>, within: Option<&String>, text: &str) {
+    let (Some(current), Some(within)) = (current, within) else {
+        return;
+    };
+    match within.as_str() {
+        "contents" => current.contents.push_str(text),
+        "gesture" => {
+            if let Some(gesture) = current.gestures.last_mut() {
+                gesture.push_str(text);
+            }
+        }
+        "defaultappearance" => current.appearance.push_str(text),
+        _ => {}
+    }
+}
+
+/// The PDF annotation dictionary an XFDF element describes, with its 1-based
+/// page, or `None` for kinds we do not support
+fn xfdf_dictionary(element: &XfdfElement) -> Option<(u32, Dictionary)> {
+    let subtype = match element.kind.as_str() {
+        "highlight" => "Highlight",
+        "underline" => "Underline",
+        "squiggly" => "Squiggly",
+        "strikeout" => "StrikeOut",
+        "text" => "Text",
+        "ink" => "Ink",
+        "square" => "Square",
+        "freetext" => "FreeText",
+        _ => return None,
+    };
+    let attribute = |key: &str| element.attributes.get(key).map(String::as_str);
+    let page_number = attribute("page")?.trim().parse::<u32>().ok()? + 1;
+
+    let mut dict = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => subtype,
+        "Rect" => reals(attribute("rect").unwrap_or_default()),
+    };
+    let texts = [
+        ("name", "NM"),
+        ("title", "T"),
+        ("date", "M"),
+        ("creationdate", "CreationDate"),
+        ("statemodel", "StateModel"),
+    ];
+    for (key, pdf_key) in texts {
+        if let Some(value) = attribute(key) {
+            dict.set(pdf_key, text_string(value));
+        }
+    }
+    if let Some([r, g, b]) = attribute("color").and_then(color_rgb) {
+        dict.set(
+            "C",
+            [r, g, b].map(|c| Object::Real(c as f32 / 255.0)).to_vec(),
+        );
+    }
+    if let Some(coords) = attribute("coords") {
+        dict.set("QuadPoints", reals(coords));
+    }
+    if let Some(width) = attribute("width").and_then(|width| width.trim().parse::<f32>().ok()) {
+        dict.set("BS", dictionary! { "W" => Object::Real(width) });
+    }
+    if !element.contents.is_empty() {
+        dict.set("Contents", text_string(&element.contents));
+    }
+    if !element.gestures.is_empty() {
+        let ink_list: Vec<Object> = element
+            .gestures
+            .iter()
+            .map(|gesture| Object::Array(reals(gesture)))
+            .collect();
+        dict.set("InkList", ink_list);
+    }
+    if !element.appearance.is_empty() {
+        dict.set("DA", Object::string_literal(element.appearance.as_str()));
+    }
+    Some((page_number, dict))
+}
+
+/// Numbers in an XFDF list such as `x1,y1,x2,y2` or `x,y;x,y`
+fn reals(list: &str) -> Vec<Object> {
+    list.split([',', ';'])
+        .filter_map(|value| value.trim().parse::<f32>().ok())
+        .map(Object::Real)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::annotations::Point;
+    use crate::services::database::{DocumentRecord, DATABASE_FILE_NAME};
+    use chrono::{Duration, Utc};
+
+    fn annotation(id: &str, geometry: AnnotationGeometry, content: Option<&str>) -> Annotation {
+        let now = Utc::now() - Duration::hours(1);
+        Annotation {
+            id: id.to_string(),
+            document_id: "source".to_string(),
+            page_number: 2,
+            geometry,
+            content: content.map(str::to_string),
+            color: "#ffeb3b".to_string(),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            author: Some("Reviewer".to_string()),
+            reply_to: None,
+            source_key: None,
+        }
+    }
+
+    fn document(db: &Database, id: &str) {
+        let now = Utc::now();
+        db.insert_document(&DocumentRecord {
+            id: id.to_string(),
+            file_path: format!("/{}.pdf", id),
+            file_hash: id.to_string(),
+            title: id.to_string(),
+            total_pages: 3,
+            created_at: now,
+            updated_at: now,
+            last_opened_at: now,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn round_trips_annotations_through_xfdf_and_fdf() {
+        let dir =
+            std::env::temp_dir().join(format!("annotation-exchange-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        document(&db, "copy");
+
+        let highlight = annotation(
+            "h1",
+            AnnotationGeometry::Highlight {
+                quads: vec![Quad {
+                    top_left: Point { x: 72.0, y: 710.0 },
+                    top_right: Point { x: 300.0, y: 710.0 },
+                    bottom_left: Point { x: 72.0, y: 698.0 },
+                    bottom_right: Point { x: 300.0, y: 698.0 },
+                }],
+            },
+            Some("Check <this> & that"),
+        );
+        let mut reply = annotation(
+            "r1",
+            AnnotationGeometry::Note {
+                anchor: Point { x: 72.0, y: 730.0 },
+            },
+            Some("Done"),
+        );
+        reply.reply_to = Some("h1".to_string());
+        let ink = annotation(
+            "i1",
+            AnnotationGeometry::Ink {
+                strokes: vec![vec![Point { x: 10.0, y: 10.0 }, Point { x: 20.0, y: 25.0 }]],
+                line_width: 2.0,
+            },
+            None,
+        );
+        let annotations = [reply, highlight, ink];
+
+        for (file_name, format) in [
+            ("review.xfdf", AnnotationFileFormat::Xfdf),
+            ("review.fdf", AnnotationFileFormat::Fdf),
+        ] {
+            let path = dir.join(file_name);
+            assert_eq!(AnnotationFileFormat::from_path(&path), Some(format));
+            let written = export_annotations_file(&path, format, "paper.pdf", &annotations);
+            assert_eq!(written.unwrap(), 3);
+
+            let summary = import_annotations_file(&db, "copy", &path).unwrap();
+            assert_eq!(summary.imported + summary.duplicates, 3, "{}", file_name);
+        }
+
+        let stored = db.annotations("copy", None, false).unwrap();
+        assert_eq!(stored.len(), 3);
+        let note = db.annotation("r1").unwrap().unwrap();
+        assert_eq!(note.reply_to.as_deref(), Some("h1"));
+        assert_eq!(note.geometry, annotations[0].geometry);
+        let highlight = db.annotation("h1").unwrap().unwrap();
+        assert_eq!(highlight.content.as_deref(), Some("Check <this> & that"));
+        assert_eq!(highlight.author.as_deref(), Some("Reviewer"));
+        assert_eq!(
+            db.annotation("i1").unwrap().unwrap().geometry,
+            annotations[2].geometry
+        );
+
+        // A newer copy of the highlight replaces ours; an unknown kind is reported
+        let xfdf = format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/" xml:space="preserve">
+  <annots>
+    <highlight page="1" rect="72,698,300,710" color="#FF0000" name="h1" date="{}"
+        coords="72,710,300,710,72,698,300,698">
+      <contents>Rephrased</contents>
+      <popup page="1" rect="300,600,500,700"/>
+    </highlight>
+    <circle page="0" rect="10,10,50,50" name="c1"/>
+  </annots>
+</xfdf>"##,
+            pdf_date(Utc::now() + Duration::minutes(1))
+        );
+        let path = dir.join("update.xfdf");
+        std::fs::write(&path, xfdf).unwrap();
+        let summary = import_annotations_file(&db, "copy", &path).unwrap();
+        assert_eq!((summary.updated, summary.skipped), (1, 1));
+        let highlight = db.annotation("h1").unwrap().unwrap();
+        assert_eq!(highlight.content.as_deref(), Some("Rephrased"));
+        assert_eq!(highlight.color, "#ff0000");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn imports_fdf_from_other_readers_without_undo_history() {
+        let dir =
+            std::env::temp_dir().join(format!("annotation-exchange-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        document(&db, "copy");
+
+        // As Acrobat writes it: no cross-reference table, a popup per markup,
+        // a reply through /IRT and a note without /NM
+        let fdf = "%FDF-1.2\n%\u{e2}\u{e3}\u{cf}\u{d3}\n\
+            1 0 obj\n<< /FDF << /Annots [2 0 R 3 0 R 4 0 R 5 0 R 6 0 R] /F (paper.pdf) \
+            /ID [<7A0631678ED475F0898815F0A818CFA1> <BEF7724317B311718E8675B677EF9B4E>] >> \
+            /Type /Catalog >>\nendobj\n\
+            2 0 obj\n<< /Type /Annot /Subtype /Highlight /Page 0 /Rect [72 698 300 710] \
+            /QuadPoints [72 710 300 710 72 698 300 698] /C [1 0.8 0] /CA 1 \
+            /NM (5b6c1f2e-acro) /T (Alice) /M (D:20240102030405Z) \
+            /CreationDate (D:20240102030405Z) /Contents (Important) /Popup 6 0 R >>\nendobj\n\
+            3 0 obj\n<< /Type /Annot /Subtype /Text /Page 0 /Rect [300 700 320 720] \
+            /IRT 2 0 R /NM (9d1e-reply) /T (Bob) /M (D:20240102040000Z) /Contents (Agreed) >>\n\
+            endobj\n\
+            4 0 obj\n<< /Type /Annot /Subtype /Text /Page 1 /Rect [100 500 120 520] \
+            /Name /Comment /C [0 0 1] /M (D:20240102050000Z) /Contents (See figure 2) >>\nendobj\n\
+            5 0 obj\n<< /Type /Annot /Subtype /Square /Page 7 /Rect [10 10 50 50] \
+            /NM (past-the-end) >>\nendobj\n\
+            6 0 obj\n<< /Type /Annot /Subtype /Popup /Page 0 /Rect [300 600 500 700] \
+            /Parent 2 0 R /Open false >>\nendobj\n\
+            trailer\n<< /Root 1 0 R >>\n%%EOF\n";
+        let path = dir.join("acrobat.fdf");
+        std::fs::write(&path, fdf).unwrap();
+
+        let summary = import_annotations_file(&db, "copy", &path).unwrap();
+        assert_eq!((summary.imported, summary.skipped), (3, 1));
+        assert_eq!(
+            summary.errors,
+            ["rectangle on page 8: past the end of the document"]
+        );
+
+        let highlight = db.annotation("5b6c1f2e-acro").unwrap().unwrap();
+        assert_eq!(highlight.page_number, 1);
+        assert_eq!(highlight.content.as_deref(), Some("Important"));
+        assert_eq!(highlight.author.as_deref(), Some("Alice"));
+        let reply = db.annotation("9d1e-reply").unwrap().unwrap();
+        assert_eq!(reply.reply_to.as_deref(), Some("5b6c1f2e-acro"));
+        let stored = db.annotations("copy", Some(2), false).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].content.as_deref(), Some("See figure 2"));
+
+        // Imports are not undone one annotation at a time
+        assert!(db
+            .undo_annotation_change("copy", Utc::now())
+            .unwrap()
+            .is_none());
+
+        // The note without /NM is recognised by its fingerprint
+        let again = import_annotations_file(&db, "copy", &path).unwrap();
+        assert_eq!((again.imported, again.duplicates), (0, 3));
+        assert_eq!(db.annotations("copy", None, false).unwrap().len(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn repeated_names_in_one_file_are_not_merged() {
+        let dir =
+            std::env::temp_dir().join(format!("annotation-exchange-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        document(&db, "copy");
+
+        let xfdf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/" xml:space="preserve">
+  <annots>
+    <text page="0" rect="72,700,92,720" name="dup"><contents>First</contents></text>
+    <text page="1" rect="72,700,92,720" name="dup"><contents>Second</contents></text>
+  </annots>
+</xfdf>"#;
+        let path = dir.join("dup.xfdf");
+        std::fs::write(&path, xfdf).unwrap();
+
+        let summary = import_annotations_file(&db, "copy", &path).unwrap();
+        assert_eq!(summary.imported, 2);
+        let stored = db.annotations("copy", None, false).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].id, "dup");
+        assert_eq!(stored[0].content.as_deref(), Some("First"));
+        assert_ne!(stored[1].id, "dup");
+        assert_eq!(stored[1].content.as_deref(), Some("Second"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn undated_annotations_do_not_replace_local_edits() {
+        let dir =
+            std::env::temp_dir().join(format!("annotation-exchange-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        document(&db, "copy");
+
+        let xfdf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xfdf xmlns="http://ns.adobe.com/xfdf/" xml:space="preserve">
+  <annots>
+    <text page="0" rect="72,700,92,720" name="n1"><contents>Original</contents></text>
+  </annots>
+</xfdf>"#;
+        let path = dir.join("undated.xfdf");
+        std::fs::write(&path, xfdf).unwrap();
+        let summary = import_annotations_file(&db, "copy", &path).unwrap();
+        assert_eq!(summary.imported, 1);
+
+        let mut note = db.annotation("n1").unwrap().unwrap();
+        note.content = Some("Edited here".to_string());
+        note.updated_at = Utc::now();
+        assert!(db.update_annotation(&note).unwrap());
+
+        let again = import_annotations_file(&db, "copy", &path).unwrap();
+        assert_eq!((again.updated, again.duplicates), (0, 1));
+        let note = db.annotation("n1").unwrap().unwrap();
+        assert_eq!(note.content.as_deref(), Some("Edited here"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,679 @@
+use super::annotations::{color_rgb, Annotation, AnnotationGeometry, Quad};
+use super::pdf_text::{chapters_for_range, extract_page_text, read_outline, PageText};
+use crate::utils::{escape_html, percent_encode_path};
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotesFormat {
+    /// Obsidian-style Markdown with `[[file.pdf#page=N]]` links
+    Markdown,
+    Html,
+}
+
+/// A highlight or note as it appears in the export
+#[derive(Debug, Clone)]
+struct NoteEntry<'a> {
+    annotation: &'a Annotation,
+    /// Marked-up text, for highlights, underlines and strikeouts
+    text: Option<String>,
+    replies: Vec<&'a Annotation>,
+}
+
+/// Entries under one outline chapter; untitled before the first chapter or
+/// when the PDF has no outline
+#[derive(Debug, Clone)]
+struct NotesChapter<'a> {
+    title: Option<String>,
+    entries: Vec<NoteEntry<'a>>,
+}
+
+/// Render a document's highlights and notes grouped by outline chapter.
+///
+/// Text markup is quoted with the text it covers, other annotations are listed
+/// when they have text, and replies follow the annotation they answer. Every
+/// entry links to its page in `pdf_file_name`.
+pub fn render_annotation_notes(
+    doc: &Document,
+    title: &str,
+    pdf_file_name: &str,
+    annotations: &[Annotation],
+    format: NotesFormat,
+) -> String {
+    let chapters = collect_notes(doc, annotations);
+    match format {
+        NotesFormat::Markdown => render_markdown(title, pdf_file_name, &chapters),
+        NotesFormat::Html => render_html(title, pdf_file_name, &chapters),
+    }
+}
+
+fn collect_notes<'a>(doc: &Document, annotations: &'a [Annotation]) -> Vec<NotesChapter<'a>> {
+    let live: Vec<&Annotation> = annotations
+        .iter()
+        .filter(|a| a.deleted_at.is_none())
+        .collect();
+    let mut replies: HashMap<&str, Vec<&Annotation>> = HashMap::new();
+    for annotation in &live {
+        let parent = annotation.reply_to.as_deref();
+        if let Some(parent) = parent.filter(|id| live.iter().any(|a| a.id == *id)) {
+            replies.entry(parent).or_default().push(annotation);
+        }
+    }
+
+    let mut top_level: Vec<&Annotation> = live
+        .iter()
+        .copied()
+        .filter(|a| {
+            let is_reply = a
+                .reply_to
+                .as_deref()
+                .is_some_and(|parent| live.iter().any(|other| other.id == parent));
+            !is_reply && (is_text_markup(&a.geometry) || has_text(&a.content))
+        })
+        .collect();
+    // Reading order: by page, then from the top of the page down
+    top_level.sort_by(|a, b| {
+        let top = |a: &Annotation| {
+            let bounds = a.geometry.bounds();
+            bounds.y + bounds.height
+        };
+        a.page_number
+            .cmp(&b.page_number)
+            .then(top(b).total_cmp(&top(a)))
+            .then(a.created_at.cmp(&b.created_at))
+    });
+
+    let mut pages: HashMap<i64, Option<PageText>> = HashMap::new();
+    let entries: Vec<NoteEntry> = top_level
+        .into_iter()
+        .map(|annotation| {
+            let text = match &annotation.geometry {
+                AnnotationGeometry::Highlight { quads }
+                | AnnotationGeometry::Underline { quads }
+                | AnnotationGeometry::Strikeout { quads } => pages
+                    .entry(annotation.page_number)
+                    .or_insert_with(|| {
+                        let page_number = u32::try_from(annotation.page_number).ok()?;
+                        extract_page_text(doc, page_number).ok()
+                    })
+                    .as_ref()
+                    .and_then(|page| marked_up_text(page, quads)),
+                _ => None,
+            };
+            let mut replies = replies.remove(annotation.id.as_str()).unwrap_or_default();
+            replies.sort_by_key(|reply| reply.created_at);
+            NoteEntry {
+                annotation,
+                text,
+                replies,
+            }
+        })
+        .collect();
+
+    let outline = read_outline(doc);
+    let page_count = doc.get_pages().len() as u32;
+    let ranges = if outline.is_empty() {
+        vec![(None, 1, page_count)]
+    } else {
+        chapters_for_range(&outline, 1, page_count)
+            .into_iter()
+            .map(|(title, start, end)| {
+                // Pages before the first entry get a made-up title; leave them untitled
+                let from_outline = outline.iter().any(|entry| entry.title == title);
+                (from_outline.then_some(title), start, end)
+            })
+            .collect()
+    };
+
+    let mut chapters: Vec<NotesChapter> = Vec::new();
+    for entry in entries {
+        let page = entry.annotation.page_number;
+        let title = ranges
+            .iter()
+            .find(|(_, start, end)| page >= i64::from(*start) && page <= i64::from(*end))
+            .and_then(|(title, _, _)| title.clone());
+        match chapters.last_mut() {
+            Some(chapter) if chapter.title == title => chapter.entries.push(entry),
+            _ => chapters.push(NotesChapter {
+                title,
+                entries: vec![entry],
+            }),
+        }
+    }
+    chapters
+}
+
+/// Words on the page covered by the quads, placed by their glyph positions
+fn marked_up_text(page: &PageText, quads: &[Quad]) -> Option<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    for quad in quads {
+        let points = quad.points();
+        let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) as f32;
+        let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max) as f32;
+        let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) as f32;
+        let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max) as f32;
+
+        for line in &page.lines {
+            // Middle of the lowercase letters, which a box around the line covers
+            let middle = line.y + line.font_size * 0.3;
+            if middle < min_y || middle > max_y {
+                continue;
+            }
+            let mut offset = 0;
+            let mut words = Vec::new();
+            for word in line.text.split(' ') {
+                let length = word.chars().count();
+                let edges = (line.char_x.get(offset), line.char_x.get(offset + length));
+                let center = match edges {
+                    (Some(left), Some(right)) => (left + right) / 2.0,
+                    _ => line.x,
+                };
+                if center >= min_x && center <= max_x {
+                    words.push(word);
+                }
+                offset += length + 1;
+            }
+            if !words.is_empty() {
+                pieces.push(words.join(" "));
+            }
+        }
+    }
+
+    let mut text = String::new();
+    for piece in pieces {
+        // Rejoin a word hyphenated across lines
+        let hyphenated = text.ends_with('-')
+            && piece.chars().next().is_some_and(char::is_lowercase)
+            && text[..text.len() - 1]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphabetic);
+        if hyphenated {
+            text.pop();
+        } else if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&piece);
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+fn is_text_markup(geometry: &AnnotationGeometry) -> bool {
+    matches!(
+        geometry,
+        AnnotationGeometry::Highlight { .. }
+            | AnnotationGeometry::Underline { .. }
+            | AnnotationGeometry::Strikeout { .. }
+    )
+}
+
+fn has_text(content: &Option<String>) -> bool {
+    content
+        .as_deref()
+        .is_some_and(|text| !text.trim().is_empty())
+}
+
+fn kind_label(geometry: &AnnotationGeometry) -> &'static str {
+    match geometry {
+        AnnotationGeometry::Highlight { .. } => "Highlight",
+        AnnotationGeometry::Underline { .. } => "Underline",
+        AnnotationGeometry::Strikeout { .. } => "Strikeout",
+        AnnotationGeometry::Note { .. } => "Note",
+        AnnotationGeometry::Ink { .. } => "Drawing",
+        AnnotationGeometry::Rectangle { .. } => "Box",
+        AnnotationGeometry::FreeText { .. } => "Text",
+    }
+}
+
+fn render_markdown(title: &str, pdf_file_name: &str, chapters: &[NotesChapter]) -> String {
+    let mut out = format!("# {}\n", escape_markdown(title));
+    for chapter in chapters {
+        if let Some(title) = &chapter.title {
+            let _ = write!(out, "\n## {}\n", escape_markdown(title));
+        }
+        for entry in &chapter.entries {
+            let annotation = entry.annotation;
+            let mut source = page_link(pdf_file_name, annotation.page_number);
+            if let Some(author) = &annotation.author {
+                let _ = write!(source, ", {}", escape_markdown(author));
+            }
+
+            out.push('\n');
+            match &entry.text {
+                Some(text) => {
+                    let text = escape_markdown(text);
+                    let text = match annotation.geometry {
+                        AnnotationGeometry::Strikeout { .. } => format!("~~{}~~", text),
+                        _ => text,
+                    };
+                    let _ = writeln!(out, "> {} ({})", text, source);
+                    if let Some(comment) = annotation
+                        .content
+                        .as_deref()
+                        .filter(|c| !c.trim().is_empty())
+                    {
+                        let _ = writeln!(out, "\n{}", escape_markdown(comment.trim()));
+                    }
+                }
+                None => {
+                    let content = annotation.content.as_deref().unwrap_or_default().trim();
+                    let _ = writeln!(
+                        out,
+                        "**{}** ({}): {}",
+                        kind_label(&annotation.geometry),
+                        source,
+                        escape_markdown(content)
+                    );
+                }
+            }
+            for reply in &entry.replies {
+                let _ = writeln!(
+                    out,
+                    "- **{}:** {}",
+                    escape_markdown(reply.author.as_deref().unwrap_or("Reply")),
+                    escape_markdown(reply.content.as_deref().unwrap_or_default().trim())
+                );
+            }
+        }
+    }
+    out
+}
+
+fn render_html(title: &str, pdf_file_name: &str, chapters: &[NotesChapter]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; max-width: 48em; margin: 2em auto; line-height: 1.5; }}\n\
+         blockquote {{ margin: 1em 0; padding-left: 1em; border-left: 4px solid; }}\n\
+         .source {{ color: #666; font-size: 0.9em; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape_html(title),
+        escape_html(title)
+    );
+    for chapter in chapters {
+        if let Some(title) = &chapter.title {
+            let _ = writeln!(out, "<h2>{}</h2>", escape_html(title));
+        }
+        for entry in &chapter.entries {
+            let annotation = entry.annotation;
+            let mut source = format!(
+                "<a href=\"{}#page={}\">p. {}</a>",
+                percent_encode_path(pdf_file_name),
+                annotation.page_number,
+                annotation.page_number
+            );
+            if let Some(author) = &annotation.author {
+                let _ = write!(source, ", {}", escape_html(author));
+            }
+            let [r, g, b] = color_rgb(&annotation.color).unwrap_or([255, 235, 59]);
+
+            match &entry.text {
+                Some(text) => {
+                    let text = html_paragraphs(text);
+                    let text = match annotation.geometry {
+                        AnnotationGeometry::Strikeout { .. } => format!("<del>{}</del>", text),
+                        _ => text,
+                    };
+                    let _ = writeln!(
+                        out,
+                        "<blockquote style=\"border-left-color: #{:02x}{:02x}{:02x}\">\
+                         <p>{}</p><p class=\"source\">{}</p></blockquote>",
+                        r, g, b, text, source
+                    );
+                    if let Some(comment) = annotation
+                        .content
+                        .as_deref()
+                        .filter(|c| !c.trim().is_empty())
+                    {
+                        let _ = writeln!(out, "<p>{}</p>", html_paragraphs(comment.trim()));
+                    }
+                }
+                None => {
+                    let content = annotation.content.as_deref().unwrap_or_default().trim();
+                    let _ = writeln!(
+                        out,
+                        "<p><strong>{}</strong> <span class=\"source\">({})</span>: {}</p>",
+                        kind_label(&annotation.geometry),
+                        source,
+                        html_paragraphs(content)
+                    );
+                }
+            }
+            if !entry.replies.is_empty() {
+                out.push_str("<ul>\n");
+                for reply in &entry.replies {
+                    let _ = writeln!(
+                        out,
+                        "<li><strong>{}:</strong> {}</li>",
+                        escape_html(reply.author.as_deref().unwrap_or("Reply")),
+                        html_paragraphs(reply.content.as_deref().unwrap_or_default().trim())
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Escape characters Markdown would treat as formatting or links, and keep
+/// multi-line text inside its block
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Obsidian link to a page of the PDF. Characters that end or split a
+/// `[[…]]` target can't be escaped there, so a file name with any of them is
+/// linked with a percent-encoded Markdown link instead.
+fn page_link(file_name: &str, page_number: i64) -> String {
+    if file_name.contains(['[', ']', '|', '#', '^']) {
+        format!(
+            "[p. {}]({}#page={})",
+            page_number,
+            percent_encode_path(file_name),
+            page_number
+        )
+    } else {
+        format!("[[{}#page={}|p. {}]]", file_name, page_number, page_number)
+    }
+}
+
+fn html_paragraphs(text: &str) -> String {
+    escape_html(text).replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::annotations::Point;
+    use chrono::{Duration, Utc};
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Object, Stream};
+
+    /// Two pages of one line each, with an outline entry per page
+    fn outlined_pdf() -> Document {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let mut page_ids = Vec::new();
+        for text in ["The quick brown fox jumps", "Results are pre-"] {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![72.into(), 700.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(text)]),
+                    Operation::new("Td", vec![0.into(), (-14).into()]),
+                    Operation::new("Tj", vec![Object::string_literal("liminary only")]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            page_ids.push(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+            }));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => page_ids.iter().map(|&id| id.into()).collect::<Vec<Object>>(),
+                "Count" => 2,
+            }),
+        );
+
+        let outlines_id = doc.new_object_id();
+        let intro_id = doc.new_object_id();
+        let results_id = doc.new_object_id();
+        doc.objects.insert(
+            intro_id,
+            Object::Dictionary(dictionary! {
+                "Title" => Object::string_literal("Introduction"),
+                "Parent" => outlines_id,
+                "Next" => results_id,
+                "Dest" => vec![page_ids[0].into(), "Fit".into()],
+            }),
+        );
+        doc.objects.insert(
+            results_id,
+            Object::Dictionary(dictionary! {
+                "Title" => Object::string_literal("Results"),
+                "Parent" => outlines_id,
+                "Prev" => intro_id,
+                "Dest" => vec![page_ids[1].into(), "Fit".into()],
+            }),
+        );
+        doc.objects.insert(
+            outlines_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Outlines",
+                "First" => intro_id,
+                "Last" => results_id,
+                "Count" => 2,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Outlines" => outlines_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+
+        // Round trip so the document looks like one loaded from disk
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        Document::load_mem(&bytes).unwrap()
+    }
+
+    /// Box from `left` to `right` around a 12pt line at `y`
+    fn quad(left: f64, right: f64, y: f64) -> Quad {
+        Quad {
+            top_left: Point {
+                x: left,
+                y: y + 10.0,
+            },
+            top_right: Point {
+                x: right,
+                y: y + 10.0,
+            },
+            bottom_left: Point {
+                x: left,
+                y: y - 3.0,
+            },
+            bottom_right: Point {
+                x: right,
+                y: y - 3.0,
+            },
+        }
+    }
+
+    fn annotation(id: &str, page_number: i64, geometry: AnnotationGeometry) -> Annotation {
+        let now = Utc::now();
+        Annotation {
+            id: id.to_string(),
+            document_id: "doc".to_string(),
+            page_number,
+            geometry,
+            content: None,
+            color: "yellow".to_string(),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            author: None,
+            reply_to: None,
+            source_key: None,
+        }
+    }
+
+    #[test]
+    fn groups_highlights_and_notes_by_chapter() {
+        let doc = outlined_pdf();
+        let mut highlight = annotation(
+            "h1",
+            1,
+            AnnotationGeometry::Highlight {
+                // "quick brown" in Helvetica
+                quads: vec![quad(95.0, 161.0, 700.0)],
+            },
+        );
+        highlight.content = Some("Classic *pangram*".to_string());
+        let mut reply = annotation(
+            "r1",
+            1,
+            AnnotationGeometry::Note {
+                anchor: Point { x: 72.0, y: 720.0 },
+            },
+        );
+        reply.content = Some("Agreed".to_string());
+        reply.author = Some("Sam".to_string());
+        reply.reply_to = Some("h1".to_string());
+        reply.created_at += Duration::seconds(1);
+        // Spans the hyphenated line break on page 2
+        let results = annotation(
+            "h2",
+            2,
+            AnnotationGeometry::Highlight {
+                quads: vec![quad(135.0, 158.0, 700.0), quad(72.0, 114.0, 686.0)],
+            },
+        );
+        let mut note = annotation(
+            "n1",
+            2,
+            AnnotationGeometry::Note {
+                anchor: Point { x: 500.0, y: 600.0 },
+            },
+        );
+        note.content = Some("Check <table> 2".to_string());
+        let mut deleted = note.clone();
+        deleted.id = "n2".to_string();
+        deleted.deleted_at = Some(Utc::now());
+        let annotations = [note, deleted, reply, results, highlight];
+
+        let markdown = render_annotation_notes(
+            &doc,
+            "Paper",
+            "paper.pdf",
+            &annotations,
+            NotesFormat::Markdown,
+        );
+        assert_eq!(
+            markdown,
+            "# Paper\n\
+             \n## Introduction\n\
+             \n> quick brown ([[paper.pdf#page=1|p. 1]])\n\
+             \nClassic \\*pangram\\*\n\
+             - **Sam:** Agreed\n\
+             \n## Results\n\
+             \n> preliminary ([[paper.pdf#page=2|p. 2]])\n\
+             \n**Note** ([[paper.pdf#page=2|p. 2]]): Check \\<table\\> 2\n"
+        );
+
+        let markdown = render_annotation_notes(
+            &doc,
+            "Paper",
+            "paper #2 [draft|final].pdf",
+            &annotations,
+            NotesFormat::Markdown,
+        );
+        assert!(markdown.contains("([p. 1](paper%20%232%20%5Bdraft%7Cfinal%5D.pdf#page=1))"));
+
+        let html =
+            render_annotation_notes(&doc, "Paper", "paper.pdf", &annotations, NotesFormat::Html);
+        assert!(html.contains("<h2>Results</h2>"));
+        assert!(html.contains("<a href=\"paper.pdf#page=2\">p. 2</a>"));
+        let html = render_annotation_notes(
+            &doc,
+            "Paper",
+            "paper #2 & notes.pdf",
+            &annotations,
+            NotesFormat::Html,
+        );
+        assert!(html.contains("<a href=\"paper%20%232%20%26%20notes.pdf#page=2\">p. 2</a>"));
+        assert!(html.contains("Check &lt;table&gt; 2"));
+        assert!(html.contains("<li><strong>Sam:</strong> Agreed</li>"));
+    }
+
+    #[test]
+    fn places_words_by_their_glyph_widths() {
+        // Narrow and wide glyphs, so character counts say little about position
+        let mut widths: Vec<Object> = vec![500.into(); 95];
+        widths[0] = 250.into();
+        widths[(b'W' - 32) as usize] = 1000.into();
+        widths[(b'i' - 32) as usize] = 200.into();
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Uneven",
+            "Encoding" => "WinAnsiEncoding",
+            "FirstChar" => 32,
+            "LastChar" => 126,
+            "Widths" => widths,
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 10.into()]),
+                Operation::new("Td", vec![72.into(), 700.into()]),
+                Operation::new("Tj", vec![Object::string_literal("iii WWW iii")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+
+        let page = extract_page_text(&doc, 1).unwrap();
+        // "iii " is 8.5pt wide and "WWW " 32.5pt, so the last word spans 113 to 119
+        assert_eq!(page.lines[0].char_x[8], 113.0);
+        assert_eq!(
+            marked_up_text(&page, &[quad(112.0, 120.0, 700.0)]).as_deref(),
+            Some("iii")
+        );
+        assert_eq!(
+            marked_up_text(&page, &[quad(80.0, 112.0, 700.0)]).as_deref(),
+            Some("WWW")
+        );
+    }
+}
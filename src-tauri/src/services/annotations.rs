@@ -0,0 +1,467 @@
+use super::database::Database;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Named colours offered by the annotation toolbar, with their RGB values
+const NAMED_COLORS: [(&str, [u8; 3]); 7] = [
+    ("yellow", [255, 235, 59]),
+    ("green", [129, 199, 132]),
+    ("blue", [100, 181, 246]),
+    ("pink", [240, 98, 146]),
+    ("purple", [186, 104, 200]),
+    ("red", [229, 57, 53]),
+    ("orange", [255, 167, 38]),
+];
+
+/// Starts the source key of a PDF annotation that had no /NM to identify it
+pub const FINGERPRINT_KEY_PREFIX: &str = "pdf:";
+
+/// A point in PDF user space: points from the bottom-left corner of the page
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Axis-aligned box in PDF user space; `x`, `y` is the bottom-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// One line of marked-up text, possibly rotated; corners follow the PDF
+/// QuadPoints order
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quad {
+    pub top_left: Point,
+    pub top_right: Point,
+    pub bottom_left: Point,
+    pub bottom_right: Point,
+}
+
+impl Quad {
+    pub fn points(&self) -> [Point; 4] {
+        [
+            self.top_left,
+            self.top_right,
+            self.bottom_left,
+            self.bottom_right,
+        ]
+    }
+}
+
+/// What an annotation looks like and where it sits on the page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnnotationGeometry {
+    Highlight {
+        quads: Vec<Quad>,
+    },
+    Underline {
+        quads: Vec<Quad>,
+    },
+    Strikeout {
+        quads: Vec<Quad>,
+    },
+    /// Sticky note anchored at a point; the text is the annotation content
+    Note {
+        anchor: Point,
+    },
+    Ink {
+        strokes: Vec<Vec<Point>>,
+        line_width: f64,
+    },
+    Rectangle {
+        rect: Rect,
+        line_width: f64,
+    },
+    FreeText {
+        rect: Rect,
+        font_size: f64,
+    },
+}
+
+impl AnnotationGeometry {
+    /// Stored in the `type` column
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Highlight { .. } => "highlight",
+            Self::Underline { .. } => "underline",
+            Self::Strikeout { .. } => "strikeout",
+            Self::Note { .. } => "note",
+            Self::Ink { .. } => "ink",
+            Self::Rectangle { .. } => "rectangle",
+            Self::FreeText { .. } => "free_text",
+        }
+    }
+
+    /// Smallest box containing the whole annotation
+    pub fn bounds(&self) -> Rect {
+        let points: Vec<Point> = match self {
+            Self::Highlight { quads } | Self::Underline { quads } | Self::Strikeout { quads } => {
+                quads.iter().flat_map(Quad::points).collect()
+            }
+            Self::Note { anchor } => vec![*anchor],
+            Self::Ink { strokes, .. } => strokes.iter().flatten().copied().collect(),
+            Self::Rectangle { rect, .. } | Self::FreeText { rect, .. } => return *rect,
+        };
+
+        let min_x = points.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+        let min_y = points.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+        let max_x = points.iter().map(|p| p.x).fold(f64::NEG_INFINITY, f64::max);
+        let max_y = points.iter().map(|p| p.y).fold(f64::NEG_INFINITY, f64::max);
+        Rect {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x,
+            height: max_y - min_y,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let finite = |point: &Point| point.x.is_finite() && point.y.is_finite();
+        let valid_rect = |rect: &Rect| {
+            [rect.x, rect.y, rect.width, rect.height]
+                .iter()
+                .all(|value| value.is_finite())
+                && rect.width > 0.0
+                && rect.height > 0.0
+        };
+        let valid_width = |width: f64| width.is_finite() && width > 0.0;
+
+        let valid = match self {
+            Self::Highlight { quads } | Self::Underline { quads } | Self::Strikeout { quads } => {
+                !quads.is_empty() && quads.iter().flat_map(Quad::points).all(|p| finite(&p))
+            }
+            Self::Note { anchor } => finite(anchor),
+            Self::Ink {
+                strokes,
+                line_width,
+            } => {
+                valid_width(*line_width)
+                    && !strokes.is_empty()
+                    && strokes
+                        .iter()
+                        .all(|stroke| !stroke.is_empty() && stroke.iter().all(finite))
+            }
+            Self::Rectangle { rect, line_width } => valid_rect(rect) && valid_width(*line_width),
+            Self::FreeText { rect, font_size } => valid_rect(rect) && valid_width(*font_size),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid {} geometry", self.kind()))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: String,
+    pub document_id: String,
+    pub page_number: i64,
+    pub geometry: AnnotationGeometry,
+    /// Note text, free text, or a comment on marked-up text
+    pub content: Option<String>,
+    /// `#rrggbb` or a toolbar colour name such as "yellow"
+    pub color: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Set while the annotation is in the trash
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Who wrote it, for annotations imported from the PDF
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Id of the annotation this one replies to
+    #[serde(default)]
+    pub reply_to: Option<String>,
+    /// Identifies the PDF annotation this was imported from; such annotations
+    /// are already in the file and are not written back on export
+    #[serde(default)]
+    pub source_key: Option<String>,
+}
+
+impl Annotation {
+    /// Name other readers know the annotation by: the /NM it was imported with,
+    /// or else its id
+    pub fn exchange_name(&self) -> &str {
+        match self.source_key.as_deref() {
+            Some(key) if !key.starts_with(FINGERPRINT_KEY_PREFIX) => key,
+            _ => &self.id,
+        }
+    }
+
+    /// Check the geometry and colour, and that the page exists in the document
+    pub fn validate_for(&self, db: &Database) -> Result<(), String> {
+        self.validate()?;
+        let document = db
+            .document(&self.document_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Document not found: {}", self.document_id))?;
+        if self.page_number > document.total_pages {
+            return Err(format!(
+                "Page {} is past the end of \"{}\" ({} pages)",
+                self.page_number, document.title, document.total_pages
+            ));
+        }
+        if let Some(parent_id) = &self.reply_to {
+            let parent = db.annotation(parent_id).map_err(|e| e.to_string())?;
+            if parent.is_none_or(|parent| parent.document_id != self.document_id) {
+                return Err(format!("Annotation not found: {}", parent_id));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.page_number < 1 {
+            return Err(format!("Invalid page number: {}", self.page_number));
+        }
+        if color_rgb(&self.color).is_none() {
+            return Err(format!("Invalid annotation colour: {}", self.color));
+        }
+        if matches!(self.geometry, AnnotationGeometry::FreeText { .. })
+            && self
+                .content
+                .as_deref()
+                .is_none_or(|text| text.trim().is_empty())
+        {
+            return Err("Free text annotations need text".to_string());
+        }
+        self.geometry.validate()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewAnnotation {
+    pub document_id: String,
+    pub page_number: i64,
+    pub geometry: AnnotationGeometry,
+    pub content: Option<String>,
+    pub color: String,
+    #[serde(default)]
+    pub reply_to: Option<String>,
+}
+
+impl NewAnnotation {
+    pub fn into_annotation(self, now: DateTime<Utc>) -> Annotation {
+        Annotation {
+            id: uuid::Uuid::new_v4().to_string(),
+            document_id: self.document_id,
+            page_number: self.page_number,
+            geometry: self.geometry,
+            content: self.content,
+            color: self.color,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            author: None,
+            reply_to: self.reply_to,
+            source_key: None,
+        }
+    }
+}
+
+/// Changes to an annotation; unset fields are left alone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnotationUpdate {
+    pub page_number: Option<i64>,
+    pub geometry: Option<AnnotationGeometry>,
+    /// `Some(None)` clears the content
+    #[serde(deserialize_with = "present_or_null")]
+    pub content: Option<Option<String>>,
+    pub color: Option<String>,
+}
+
+impl AnnotationUpdate {
+    pub fn apply(self, annotation: &mut Annotation, now: DateTime<Utc>) {
+        if let Some(page_number) = self.page_number {
+            annotation.page_number = page_number;
+        }
+        if let Some(geometry) = self.geometry {
+            annotation.geometry = geometry;
+        }
+        if let Some(content) = self.content {
+            annotation.content = content;
+        }
+        if let Some(color) = self.color {
+            annotation.color = color;
+        }
+        annotation.updated_at = now;
+    }
+}
+
+/// Tells an explicit `null` (clear) apart from a missing field (keep)
+fn present_or_null<'de, D>(deserializer: D) -> Result<Option<Option<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+/// A change that can be undone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AnnotationAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "create" => Some(Self::Create),
+            "update" => Some(Self::Update),
+            "delete" => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// RGB of a `#rrggbb` or named colour
+pub fn color_rgb(color: &str) -> Option<[u8; 3]> {
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some([channel(0)?, channel(2)?, channel(4)?]);
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(color))
+        .map(|(_, rgb)| *rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{DocumentRecord, DATABASE_FILE_NAME};
+
+    fn quad(x: f64, y: f64) -> Quad {
+        Quad {
+            top_left: Point { x, y: y + 10.0 },
+            top_right: Point {
+                x: x + 50.0,
+                y: y + 10.0,
+            },
+            bottom_left: Point { x, y },
+            bottom_right: Point { x: x + 50.0, y },
+        }
+    }
+
+    fn highlight(document_id: &str) -> NewAnnotation {
+        NewAnnotation {
+            document_id: document_id.to_string(),
+            page_number: 2,
+            geometry: AnnotationGeometry::Highlight {
+                quads: vec![quad(72.0, 700.0), quad(72.0, 688.0)],
+            },
+            content: None,
+            color: "yellow".to_string(),
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn validates_geometry_and_colours() {
+        let now = Utc::now();
+        let annotation = highlight("doc").into_annotation(now);
+        assert!(annotation.validate().is_ok());
+        assert_eq!(annotation.geometry.bounds().height, 22.0);
+
+        let mut invalid = annotation.clone();
+        invalid.geometry = AnnotationGeometry::Highlight { quads: vec![] };
+        assert!(invalid.validate().is_err());
+
+        invalid.geometry = AnnotationGeometry::Ink {
+            strokes: vec![vec![Point {
+                x: f64::NAN,
+                y: 0.0,
+            }]],
+            line_width: 1.0,
+        };
+        assert!(invalid.validate().is_err());
+
+        invalid = annotation.clone();
+        invalid.color = "#12345g".to_string();
+        assert!(invalid.validate().is_err());
+        assert_eq!(color_rgb("#FF8000"), Some([255, 128, 0]));
+
+        // Round-trips through the stored JSON form
+        let json = serde_json::to_string(&annotation.geometry).unwrap();
+        assert!(json.contains("\"type\":\"highlight\""));
+        assert_eq!(
+            serde_json::from_str::<AnnotationGeometry>(&json).unwrap(),
+            annotation.geometry
+        );
+    }
+
+    #[test]
+    fn undoes_create_update_and_delete() {
+        let dir = std::env::temp_dir().join(format!("annotations-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        let now = Utc::now();
+        db.insert_document(&DocumentRecord {
+            id: "doc".to_string(),
+            file_path: "/book.pdf".to_string(),
+            file_hash: "hash".to_string(),
+            title: "Book".to_string(),
+            total_pages: 3,
+            created_at: now,
+            updated_at: now,
+            last_opened_at: now,
+        })
+        .unwrap();
+
+        let annotation = highlight("doc").into_annotation(now);
+        annotation.validate_for(&db).unwrap();
+        db.insert_annotation(&annotation).unwrap();
+
+        let mut edited = annotation.clone();
+        AnnotationUpdate {
+            color: Some("#00ff00".to_string()),
+            content: Some(Some("key idea".to_string())),
+            ..Default::default()
+        }
+        .apply(&mut edited, now);
+        db.update_annotation(&edited).unwrap();
+        db.delete_annotation(&annotation.id, now).unwrap();
+        assert!(db.annotations("doc", Some(2), false).unwrap().is_empty());
+
+        // Undo the delete, then the edit, then the creation
+        let restored = db.undo_annotation_change("doc", now).unwrap().unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(restored.content.as_deref(), Some("key idea"));
+        let reverted = db.undo_annotation_change("doc", now).unwrap().unwrap();
+        assert_eq!(reverted.color, "yellow");
+        assert_eq!(reverted.content, None);
+        let removed = db.undo_annotation_change("doc", now).unwrap().unwrap();
+        assert!(removed.deleted_at.is_some());
+        assert!(db.undo_annotation_change("doc", now).unwrap().is_none());
+
+        assert_eq!(db.annotations("doc", None, true).unwrap().len(), 1);
+        assert_eq!(db.purge_deleted_annotations("doc").unwrap(), 1);
+
+        let mut past_end = highlight("doc").into_annotation(now);
+        past_end.page_number = 4;
+        assert!(past_end.validate_for(&db).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
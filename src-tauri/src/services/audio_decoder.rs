@@ -0,0 +1,447 @@
+use rubato::{FftFixedIn, Resampler};
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Sample rate expected by Whisper models
+pub const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Number of input frames fed to the resampler per call
+const RESAMPLER_CHUNK_SIZE: usize = 1024;
+
+/// Audio file extensions that can be decoded
+pub const SUPPORTED_AUDIO_FORMATS: [&str; 5] = ["wav", "mp3", "ogg", "flac", "m4a"];
+
+/// Decode an audio file to mono f32 PCM at 16 kHz
+pub fn decode_audio_file(path: &Path) -> Result<Vec<f32>, String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+
+    check_supported_format(extension.as_deref())?;
+
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open audio file {}: {}", path.display(), e))?;
+
+    decode_source(Box::new(file), extension.as_deref())
+}
+
+/// Decode an in-memory audio buffer (e.g. a recording sent from the frontend)
+/// to mono f32 PCM at 16 kHz
+pub fn decode_audio_bytes(data: Vec<u8>, extension: Option<&str>) -> Result<Vec<f32>, String> {
+    let extension = extension.map(|ext| ext.trim_start_matches('.').to_lowercase());
+
+    if extension.is_some() {
+        check_supported_format(extension.as_deref())?;
+    }
+
+    decode_source(Box::new(Cursor::new(data)), extension.as_deref())
+}
+
+fn check_supported_format(extension: Option<&str>) -> Result<(), String> {
+    match extension {
+        Some(ext) if SUPPORTED_AUDIO_FORMATS.contains(&ext) => Ok(()),
+        Some(ext) => Err(format!(
+            "Unsupported audio format: .{} (supported: {})",
+            ext,
+            SUPPORTED_AUDIO_FORMATS.join(", ")
+        )),
+        None => Err(format!(
+            "Audio file has no extension (supported: {})",
+            SUPPORTED_AUDIO_FORMATS.join(", ")
+        )),
+    }
+}
+
+fn decode_source(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<Vec<f32>, String> {
+    let stream = MediaSourceStream::new(source, Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = extension {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| match e {
+            SymphoniaError::Unsupported(what) => {
+                format!("Unsupported audio container: {}", what)
+            }
+            other => format!("Failed to read audio container: {}", other),
+        })?;
+
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| "No audio track found in file".to_string())?;
+
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&codec_params, &DecoderOptions::default())
+        .map_err(|e| match e {
+            SymphoniaError::Unsupported(_) => {
+                let codec = symphonia::default::get_codecs()
+                    .get_codec(codec_params.codec)
+                    .map(|d| d.short_name.to_string())
+                    .unwrap_or_else(|| format!("{}", codec_params.codec));
+                format!("Unsupported audio codec: {}", codec)
+            }
+            other => format!("Failed to create audio decoder: {}", other),
+        })?;
+
+    let mut sample_rate = codec_params.sample_rate;
+    let mut mono: Vec<f32> = Vec::new();
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break;
+            }
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(format!("Failed to read audio packet: {}", e)),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Corrupt frames are skipped rather than failing the whole file
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Failed to decode audio: {}", e)),
+        };
+
+        let spec = *decoded.spec();
+        sample_rate.get_or_insert(spec.rate);
+
+        let buf = sample_buf.get_or_insert_with(|| {
+            SampleBuffer::<f32>::new(decoded.capacity() as u64, spec)
+        });
+        if buf.capacity() < decoded.capacity() * spec.channels.count() {
+            *buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        }
+        buf.copy_interleaved_ref(decoded);
+
+        downmix_into(buf.samples(), spec.channels.count(), &mut mono);
+    }
+
+    let sample_rate =
+        sample_rate.ok_or_else(|| "Could not determine audio sample rate".to_string())?;
+
+    if mono.is_empty() {
+        return Err("Audio file contains no samples".to_string());
+    }
+
+    resample(&mono, sample_rate, WHISPER_SAMPLE_RATE)
+}
+
+/// Average interleaved channels into a single mono channel
+fn downmix_into(interleaved: &[f32], channels: usize, out: &mut Vec<f32>) {
+    if channels <= 1 {
+        out.extend_from_slice(interleaved);
+        return;
+    }
+
+    out.extend(
+        interleaved
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+    );
+}
+
+/// Resample mono PCM between sample rates using an FFT-based resampler
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Result<Vec<f32>, String> {
+    if from_rate == to_rate || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+
+    let mut resampler = FftFixedIn::<f32>::new(
+        from_rate as usize,
+        to_rate as usize,
+        RESAMPLER_CHUNK_SIZE,
+        2,
+        1,
+    )
+    .map_err(|e| format!("Failed to create resampler: {}", e))?;
+
+    let expected_len =
+        (samples.len() as u64 * to_rate as u64).div_ceil(from_rate as u64) as usize;
+    let delay = resampler.output_delay();
+    let mut output = Vec::with_capacity(expected_len + delay);
+
+    let mut position = 0;
+    while samples.len() - position >= resampler.input_frames_next() {
+        let frames = resampler.input_frames_next();
+        let chunk = &samples[position..position + frames];
+        let processed = resampler
+            .process(&[chunk], None)
+            .map_err(|e| format!("Failed to resample audio: {}", e))?;
+        output.extend_from_slice(&processed[0]);
+        position += frames;
+    }
+
+    if position < samples.len() {
+        let processed = resampler
+            .process_partial(Some(&[&samples[position..]]), None)
+            .map_err(|e| format!("Failed to resample audio: {}", e))?;
+        output.extend_from_slice(&processed[0]);
+    }
+
+    // Flush the resampler until the delayed tail has been emitted
+    while output.len() < expected_len + delay {
+        let processed = resampler
+            .process_partial::<&[f32]>(None, None)
+            .map_err(|e| format!("Failed to resample audio: {}", e))?;
+        if processed[0].is_empty() {
+            break;
+        }
+        output.extend_from_slice(&processed[0]);
+    }
+
+    let end = (delay + expected_len).min(output.len());
+    Ok(output[delay.min(end)..end].to_vec())
+}
+
+/// Incremental resampler for audio that arrives in arbitrary-sized chunks
+/// (e.g. microphone buffers pushed from the frontend)
+pub struct StreamResampler {
+    resampler: Option<FftFixedIn<f32>>,
+    pending: Vec<f32>,
+    /// Output samples still to be dropped to compensate for the filter delay
+    skip: usize,
+    from_rate: u32,
+    to_rate: u32,
+    /// Samples pushed and emitted so far, to size the tail on flush
+    input_len: usize,
+    output_len: usize,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Result<Self, String> {
+        if from_rate == to_rate {
+            return Ok(Self {
+                resampler: None,
+                pending: Vec::new(),
+                skip: 0,
+                from_rate,
+                to_rate,
+                input_len: 0,
+                output_len: 0,
+            });
+        }
+
+        let resampler = FftFixedIn::<f32>::new(
+            from_rate as usize,
+            to_rate as usize,
+            RESAMPLER_CHUNK_SIZE,
+            2,
+            1,
+        )
+        .map_err(|e| format!("Failed to create resampler: {}", e))?;
+
+        Ok(Self {
+            skip: resampler.output_delay(),
+            resampler: Some(resampler),
+            pending: Vec::new(),
+            from_rate,
+            to_rate,
+            input_len: 0,
+            output_len: 0,
+        })
+    }
+
+    /// Resample a chunk; output may lag the input by up to one resampler block
+    pub fn push(&mut self, samples: &[f32]) -> Result<Vec<f32>, String> {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(samples.to_vec());
+        };
+
+        self.pending.extend_from_slice(samples);
+        self.input_len += samples.len();
+        let mut output = Vec::new();
+        let mut position = 0;
+
+        while self.pending.len() - position >= resampler.input_frames_next() {
+            let frames = resampler.input_frames_next();
+            let processed = resampler
+                .process(&[&self.pending[position..position + frames]], None)
+                .map_err(|e| format!("Failed to resample audio: {}", e))?;
+            output.extend_from_slice(&processed[0]);
+            position += frames;
+        }
+
+        self.pending.drain(..position);
+        let output = self.trim_delay(output);
+        self.output_len += output.len();
+        Ok(output)
+    }
+
+    /// Resample whatever input is still buffered and drain the filter delay,
+    /// so the total output matches resampling the whole input at once
+    pub fn flush(&mut self) -> Result<Vec<f32>, String> {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(Vec::new());
+        };
+
+        let mut output = Vec::new();
+        if !self.pending.is_empty() {
+            let processed = resampler
+                .process_partial(Some(&[&self.pending[..]]), None)
+                .map_err(|e| format!("Failed to resample audio: {}", e))?;
+            self.pending.clear();
+            output.extend_from_slice(&processed[0]);
+        }
+
+        let expected_len = (self.input_len as u64 * self.to_rate as u64)
+            .div_ceil(self.from_rate as u64) as usize;
+        while self.output_len + output.len() < expected_len + self.skip {
+            let processed = resampler
+                .process_partial::<&[f32]>(None, None)
+                .map_err(|e| format!("Failed to resample audio: {}", e))?;
+            if processed[0].is_empty() {
+                break;
+            }
+            output.extend_from_slice(&processed[0]);
+        }
+
+        let mut output = self.trim_delay(output);
+        output.truncate(expected_len.saturating_sub(self.output_len));
+        self.output_len += output.len();
+        Ok(output)
+    }
+
+    fn trim_delay(&mut self, mut output: Vec<f32>) -> Vec<f32> {
+        let drop = self.skip.min(output.len());
+        self.skip -= drop;
+        output.drain(..drop);
+        output
+    }
+}
+
+/// Encode mono f32 PCM as a 16-bit WAV file
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        wav.extend_from_slice(&value.to_le_bytes());
+    }
+
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_wav(sample_rate: u32, channels: u16, seconds: f32) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+            let frames = (sample_rate as f32 * seconds) as usize;
+            for i in 0..frames {
+                let t = i as f32 / sample_rate as f32;
+                let value = (t * 440.0 * std::f32::consts::TAU).sin() * 0.5;
+                for _ in 0..channels {
+                    writer.write_sample((value * i16::MAX as f32) as i16).unwrap();
+                }
+            }
+            writer.finalize().unwrap();
+        }
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn decodes_stereo_wav_to_16khz_mono() {
+        let wav = write_wav(44_100, 2, 1.0);
+        let samples = decode_audio_bytes(wav, Some("wav")).unwrap();
+
+        assert_eq!(samples.len(), WHISPER_SAMPLE_RATE as usize);
+        let peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.05, "unexpected peak {}", peak);
+    }
+
+    #[test]
+    fn keeps_16khz_input_untouched() {
+        let wav = write_wav(16_000, 1, 0.5);
+        let samples = decode_audio_bytes(wav, Some("wav")).unwrap();
+        assert_eq!(samples.len(), 8_000);
+    }
+
+    #[test]
+    fn rejects_unsupported_extension() {
+        let err = decode_audio_file(Path::new("recording.aiff")).unwrap_err();
+        assert!(err.contains("Unsupported audio format: .aiff"));
+    }
+
+    #[test]
+    fn reports_garbage_input() {
+        let err = decode_audio_bytes(vec![0u8; 512], Some("mp3")).unwrap_err();
+        assert!(!err.is_empty());
+    }
+
+    #[test]
+    fn stream_resampler_matches_one_shot_resample() {
+        let input: Vec<f32> = (0..57_330)
+            .map(|i| (i as f32 / 44_100.0 * 440.0 * std::f32::consts::TAU).sin() * 0.5)
+            .collect();
+        let expected = resample(&input, 44_100, WHISPER_SAMPLE_RATE).unwrap();
+
+        let mut resampler = StreamResampler::new(44_100, WHISPER_SAMPLE_RATE).unwrap();
+        let mut streamed = Vec::new();
+        // Odd-sized chunks like microphone buffers, not aligned to resampler blocks
+        for chunk in input.chunks(997) {
+            streamed.extend(resampler.push(chunk).unwrap());
+        }
+        streamed.extend(resampler.flush().unwrap());
+
+        assert_eq!(streamed.len(), expected.len());
+        let max_diff = streamed
+            .iter()
+            .zip(&expected)
+            .fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+        assert!(max_diff < 1e-3, "streamed output differs by {}", max_diff);
+    }
+}
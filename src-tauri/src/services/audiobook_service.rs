@@ -0,0 +1,674 @@
+use super::audio_decoder::resample;
+use super::pdf_text::{
+    chapters_for_range, load_pdf_document, read_outline, read_pages, ReadingTextOptions,
+    SkippedPage,
+};
+use super::tts_service::{TtsOptions, TtsService};
+use super::tts_stream::split_chunks;
+use crate::utils::find_executable;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Emitter};
+
+/// Event reporting export progress
+pub const AUDIOBOOK_PROGRESS_EVENT: &str = "audiobook-progress";
+
+/// Silence after a chapter title and between chapters, in seconds
+const CHAPTER_PAUSE_SECONDS: f32 = 1.0;
+/// Silence between sentences, in seconds
+const SENTENCE_PAUSE_SECONDS: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudiobookFormat {
+    Wav,
+    Mp3,
+    Ogg,
+    M4b,
+}
+
+impl AudiobookFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudiobookFormat::Wav => "wav",
+            AudiobookFormat::Mp3 => "mp3",
+            AudiobookFormat::Ogg => "ogg",
+            AudiobookFormat::M4b => "m4b",
+        }
+    }
+
+    /// ffmpeg encoder arguments; WAV is written directly
+    fn encoder_args(self) -> &'static [&'static str] {
+        match self {
+            AudiobookFormat::Wav => &[],
+            AudiobookFormat::Mp3 => &["-c:a", "libmp3lame", "-q:a", "4", "-id3v2_version", "3"],
+            AudiobookFormat::Ogg => &["-c:a", "libvorbis", "-q:a", "4"],
+            AudiobookFormat::M4b => &["-c:a", "aac", "-b:a", "64k", "-f", "mp4"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudiobookOptions {
+    pub pdf_path: String,
+    pub output_path: String,
+    pub format: AudiobookFormat,
+    /// 1-based, inclusive; defaults to the whole document
+    pub start_page: Option<u32>,
+    pub end_page: Option<u32>,
+    #[serde(default)]
+    pub tts: TtsOptions,
+    #[serde(default)]
+    pub reading: ReadingTextOptions,
+    /// Speak each chapter title before its text
+    #[serde(default = "default_true")]
+    pub announce_chapters: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudiobookChapter {
+    pub title: String,
+    pub start_page: u32,
+    pub end_page: u32,
+    /// Seconds from the start of the audiobook
+    pub start: f32,
+    pub end: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudiobookStatus {
+    Preparing,
+    Synthesizing,
+    Encoding,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudiobookProgress {
+    pub job_id: String,
+    pub status: AudiobookStatus,
+    pub current_page: Option<u32>,
+    pub current_chapter: Option<String>,
+    pub percentage: f64,
+    /// Seconds of audio rendered so far
+    pub duration: f32,
+    pub chapters: Vec<AudiobookChapter>,
+    /// Pages left out because they could not be read
+    pub skipped_pages: Vec<SkippedPage>,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// A running audiobook export. Progress is kept for polling and emitted as
+/// `audiobook-progress` events.
+pub struct AudiobookJob {
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<Mutex<AudiobookProgress>>,
+    worker: JoinHandle<()>,
+}
+
+impl AudiobookJob {
+    pub fn start(
+        app_handle: AppHandle,
+        job_id: String,
+        options: AudiobookOptions,
+    ) -> Result<Self, String> {
+        if options.format != AudiobookFormat::Wav && find_executable("ffmpeg").is_none() {
+            return Err(format!(
+                "Exporting to {} needs ffmpeg on the PATH; export as WAV instead",
+                options.format.extension().to_uppercase()
+            ));
+        }
+
+        let service = TtsService::new(app_handle.clone());
+        if !service.is_available() {
+            return Err(
+                "No text-to-speech engine found. Install espeak-ng or download a Piper voice."
+                    .to_string(),
+            );
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let progress = Arc::new(Mutex::new(AudiobookProgress {
+            job_id: job_id.clone(),
+            status: AudiobookStatus::Preparing,
+            current_page: None,
+            current_chapter: None,
+            percentage: 0.0,
+            duration: 0.0,
+            chapters: Vec::new(),
+            skipped_pages: Vec::new(),
+            output_path: None,
+            error: None,
+        }));
+
+        let mut export = Export {
+            app_handle,
+            service,
+            options,
+            cancelled: cancelled.clone(),
+            progress: progress.clone(),
+        };
+        let worker = std::thread::Builder::new()
+            .name("audiobook-export".to_string())
+            .spawn(move || {
+                let result = export.run();
+                export.update(|progress| match result {
+                    Ok(output_path) => {
+                        progress.status = AudiobookStatus::Completed;
+                        progress.percentage = 100.0;
+                        progress.output_path = Some(output_path);
+                    }
+                    Err(_) if export.is_cancelled() => {
+                        progress.status = AudiobookStatus::Cancelled;
+                    }
+                    Err(message) => {
+                        progress.status = AudiobookStatus::Failed;
+                        progress.error = Some(message);
+                    }
+                });
+            })
+            .map_err(|e| format!("Failed to start audiobook export: {}", e))?;
+
+        Ok(Self {
+            cancelled,
+            progress,
+            worker,
+        })
+    }
+
+    pub fn progress(&self) -> Result<AudiobookProgress, String> {
+        self.progress
+            .lock()
+            .map(|progress| progress.clone())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Ask the export to stop; partial output is removed
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Export {
+    app_handle: AppHandle,
+    service: TtsService,
+    options: AudiobookOptions,
+    cancelled: Arc<AtomicBool>,
+    progress: Arc<Mutex<AudiobookProgress>>,
+}
+
+impl Export {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn update(&self, change: impl FnOnce(&mut AudiobookProgress)) {
+        let snapshot = match self.progress.lock() {
+            Ok(mut progress) => {
+                change(&mut progress);
+                progress.clone()
+            }
+            Err(_) => return,
+        };
+        let _ = self.app_handle.emit(AUDIOBOOK_PROGRESS_EVENT, snapshot);
+    }
+
+    fn run(&mut self) -> Result<String, String> {
+        let doc = load_pdf_document(Path::new(&self.options.pdf_path))?;
+        let page_count = doc.get_pages().len() as u32;
+        if page_count == 0 {
+            return Err("The PDF has no pages".to_string());
+        }
+
+        let start_page = self.options.start_page.unwrap_or(1).max(1);
+        let end_page = self.options.end_page.unwrap_or(page_count).min(page_count);
+        if start_page > end_page {
+            return Err(format!("Invalid page range {}-{}", start_page, end_page));
+        }
+
+        let (pages, skipped) = read_pages(&doc, start_page, end_page, &self.options.reading);
+        if pages.is_empty() {
+            let error = skipped.first().map(|page| page.error.as_str());
+            return Err(format!(
+                "None of pages {}-{} could be read: {}",
+                start_page,
+                end_page,
+                error.unwrap_or_default()
+            ));
+        }
+        self.update(|progress| progress.skipped_pages = skipped);
+        let chapters = chapters_for_range(&read_outline(&doc), start_page, end_page);
+        let total_chars: usize = pages
+            .iter()
+            .map(|(_, text)| text.len())
+            .sum::<usize>()
+            .max(1);
+
+        let output_path = PathBuf::from(&self.options.output_path);
+        let wav_path = if self.options.format == AudiobookFormat::Wav {
+            output_path.with_extension("wav.part")
+        } else {
+            output_path.with_extension("render.wav")
+        };
+
+        let mut writer: Option<StreamingWav> = None;
+        let mut sample_rate = 0;
+        let mut rendered_chars = 0;
+        let mut rendered: Vec<AudiobookChapter> = Vec::new();
+
+        self.update(|progress| progress.status = AudiobookStatus::Synthesizing);
+
+        let result = (|| {
+            for (title, chapter_start, chapter_end) in &chapters {
+                let chapter_offset = writer.as_ref().map(|w| w.duration()).unwrap_or(0.0);
+                self.update(|progress| progress.current_chapter = Some(title.clone()));
+
+                let chapter_pages: Vec<(Option<u32>, String)> = pages
+                    .iter()
+                    .filter(|(page, _)| page >= chapter_start && page <= chapter_end)
+                    .map(|(page, text)| (Some(*page), text.clone()))
+                    .collect();
+                // Headings printed on the page are read anyway
+                let title_on_page = chapter_pages.first().is_some_and(|(_, text)| {
+                    text.to_lowercase().starts_with(&title.to_lowercase())
+                });
+
+                let mut segments = Vec::new();
+                if self.options.announce_chapters && chapters.len() > 1 && !title_on_page {
+                    segments.push((None, title.clone()));
+                }
+                segments.extend(chapter_pages);
+
+                for (page, text) in segments {
+                    if let Some(page) = page {
+                        self.update(|progress| progress.current_page = Some(page));
+                    }
+
+                    for (_, sentence) in split_chunks(&text) {
+                        if self.is_cancelled() {
+                            return Err("Export cancelled".to_string());
+                        }
+
+                        let speech = self.service.synthesize(sentence, &self.options.tts)?;
+                        if writer.is_none() {
+                            sample_rate = speech.sample_rate;
+                            writer = Some(StreamingWav::create(&wav_path, sample_rate)?);
+                        }
+                        // Voices can differ in rate between engines; keep one stream rate
+                        let samples = if speech.sample_rate == sample_rate {
+                            speech.samples
+                        } else {
+                            resample(&speech.samples, speech.sample_rate, sample_rate)?
+                        };
+
+                        let wav = writer.as_mut().expect("writer created above");
+                        wav.write(&samples)?;
+                        wav.write_silence(SENTENCE_PAUSE_SECONDS)?;
+
+                        if page.is_some() {
+                            rendered_chars += sentence.len();
+                        }
+                        let duration = wav.duration();
+                        self.update(|progress| {
+                            progress.duration = duration;
+                            progress.percentage =
+                                (rendered_chars as f64 / total_chars as f64 * 95.0).min(95.0);
+                        });
+                    }
+
+                    if page.is_none() {
+                        if let Some(wav) = writer.as_mut() {
+                            wav.write_silence(CHAPTER_PAUSE_SECONDS)?;
+                        }
+                    }
+                }
+
+                let Some(wav) = writer.as_mut() else {
+                    // Nothing readable in this chapter yet
+                    continue;
+                };
+                wav.write_silence(CHAPTER_PAUSE_SECONDS)?;
+                rendered.push(AudiobookChapter {
+                    title: title.clone(),
+                    start_page: *chapter_start,
+                    end_page: *chapter_end,
+                    start: chapter_offset,
+                    end: wav.duration(),
+                });
+                let chapters_so_far = rendered.clone();
+                let duration = wav.duration();
+                self.update(|progress| {
+                    progress.chapters = chapters_so_far;
+                    progress.duration = duration;
+                });
+            }
+
+            let wav = writer
+                .take()
+                .ok_or_else(|| "No readable text in the selected pages".to_string())?;
+            wav.finish(&rendered)?;
+
+            if self.options.format == AudiobookFormat::Wav {
+                std::fs::rename(&wav_path, &output_path)
+                    .map_err(|e| format!("Failed to save audiobook: {}", e))?;
+            } else {
+                self.update(|progress| progress.status = AudiobookStatus::Encoding);
+                let encoded = encode_with_ffmpeg(
+                    &wav_path,
+                    &output_path,
+                    self.options.format,
+                    &rendered,
+                    &self.cancelled,
+                );
+                let _ = std::fs::remove_file(&wav_path);
+                encoded?;
+            }
+
+            Ok(output_path.to_string_lossy().to_string())
+        })();
+
+        if result.is_err() {
+            drop(writer);
+            let _ = std::fs::remove_file(&wav_path);
+        }
+        result
+    }
+}
+
+/// WAV file written incrementally, with chapters stored as cue points
+struct StreamingWav {
+    file: BufWriter<File>,
+    sample_rate: u32,
+    samples_written: u64,
+}
+
+impl StreamingWav {
+    fn create(path: &Path, sample_rate: u32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create audio file: {}", e))?;
+        let mut wav = Self {
+            file: BufWriter::new(file),
+            sample_rate,
+            samples_written: 0,
+        };
+
+        // Sizes are patched in `finish`
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data\0\0\0\0");
+        wav.put(&header)?;
+        Ok(wav)
+    }
+
+    fn put(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.file
+            .write_all(bytes)
+            .map_err(|e| format!("Failed to write audio file: {}", e))
+    }
+
+    fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+            .collect();
+        self.put(&bytes)?;
+        self.samples_written += samples.len() as u64;
+        Ok(())
+    }
+
+    fn write_silence(&mut self, seconds: f32) -> Result<(), String> {
+        let silence = vec![0.0; (seconds * self.sample_rate as f32) as usize];
+        self.write(&silence)
+    }
+
+    fn duration(&self) -> f32 {
+        self.samples_written as f32 / self.sample_rate as f32
+    }
+
+    fn finish(mut self, chapters: &[AudiobookChapter]) -> Result<(), String> {
+        let data_len = (self.samples_written * 2) as u32;
+
+        // `cue ` chunk with one point per chapter, labelled in a LIST/adtl chunk
+        let mut cue = Vec::new();
+        cue.extend_from_slice(&(chapters.len() as u32).to_le_bytes());
+        let mut labels = b"adtl".to_vec();
+        for (index, chapter) in chapters.iter().enumerate() {
+            let id = index as u32 + 1;
+            let position = (chapter.start * self.sample_rate as f32) as u32;
+            cue.extend_from_slice(&id.to_le_bytes());
+            cue.extend_from_slice(&position.to_le_bytes());
+            cue.extend_from_slice(b"data");
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&position.to_le_bytes());
+
+            let mut text = chapter.title.as_bytes().to_vec();
+            text.push(0);
+            labels.extend_from_slice(b"labl");
+            labels.extend_from_slice(&(4 + text.len() as u32).to_le_bytes());
+            labels.extend_from_slice(&id.to_le_bytes());
+            labels.extend_from_slice(&text);
+            if text.len() % 2 == 1 {
+                labels.push(0);
+            }
+        }
+
+        let mut trailer = Vec::new();
+        if !chapters.is_empty() {
+            trailer.extend_from_slice(b"cue ");
+            trailer.extend_from_slice(&(cue.len() as u32).to_le_bytes());
+            trailer.extend_from_slice(&cue);
+            trailer.extend_from_slice(b"LIST");
+            trailer.extend_from_slice(&(labels.len() as u32).to_le_bytes());
+            trailer.extend_from_slice(&labels);
+        }
+        self.put(&trailer)?;
+
+        let riff_len = 36 + data_len + (data_len % 2) + trailer.len() as u32;
+        let write_u32 = |file: &mut BufWriter<File>, offset: u64, value: u32| {
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.write_all(&value.to_le_bytes()))
+                .map_err(|e| format!("Failed to write audio file: {}", e))
+        };
+        write_u32(&mut self.file, 4, riff_len)?;
+        write_u32(&mut self.file, 40, data_len)?;
+        self.file
+            .flush()
+            .map_err(|e| format!("Failed to write audio file: {}", e))
+    }
+}
+
+/// Encode the rendered WAV with ffmpeg, adding chapters through an ffmetadata file
+fn encode_with_ffmpeg(
+    wav_path: &Path,
+    output_path: &Path,
+    format: AudiobookFormat,
+    chapters: &[AudiobookChapter],
+    cancelled: &AtomicBool,
+) -> Result<(), String> {
+    let ffmpeg = find_executable("ffmpeg").ok_or_else(|| "ffmpeg is not installed".to_string())?;
+
+    let metadata_path = wav_path.with_extension("ffmeta");
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0) as u64,
+            (chapter.end * 1000.0) as u64,
+            escape_ffmetadata(&chapter.title)
+        ));
+    }
+    std::fs::write(&metadata_path, metadata)
+        .map_err(|e| format!("Failed to write chapter metadata: {}", e))?;
+
+    let mut child = Command::new(ffmpeg)
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(wav_path)
+        .arg("-i")
+        .arg(&metadata_path)
+        .args(["-map", "0:a", "-map_metadata", "1", "-map_chapters", "1"])
+        .args(format.encoder_args())
+        .arg(output_path)
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let status = loop {
+        if cancelled.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = std::fs::remove_file(&metadata_path);
+            let _ = std::fs::remove_file(output_path);
+            return Err("Export cancelled".to_string());
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(200)),
+            Err(e) => return Err(format!("ffmpeg failed: {}", e)),
+        }
+    };
+
+    let _ = std::fs::remove_file(&metadata_path);
+    if !status.success() {
+        let _ = std::fs::remove_file(output_path);
+        return Err(format!("ffmpeg failed with {}", status));
+    }
+    Ok(())
+}
+
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn chapter(title: &str, start: f32) -> AudiobookChapter {
+        AudiobookChapter {
+            title: title.to_string(),
+            start_page: 1,
+            end_page: 1,
+            start,
+            end: start + 0.5,
+        }
+    }
+
+    #[test]
+    fn wav_has_patched_header_and_chapter_cue_points() {
+        let dir = std::env::temp_dir().join(format!("audiobook-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("book.wav");
+
+        let mut wav = StreamingWav::create(&path, 16_000).unwrap();
+        wav.write(&[0.5; 8_000]).unwrap();
+        wav.write_silence(0.5).unwrap();
+        assert_eq!(wav.duration(), 1.0);
+        wav.finish(&[chapter("Intro", 0.0), chapter("Chapter One", 0.5)])
+            .unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&bytes, 16), 16);
+        assert_eq!(u16_at(&bytes, 20), 1);
+        assert_eq!(u16_at(&bytes, 22), 1);
+        assert_eq!(u32_at(&bytes, 24), 16_000);
+        assert_eq!(u32_at(&bytes, 28), 32_000);
+        assert_eq!(u16_at(&bytes, 32), 2);
+        assert_eq!(u16_at(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 32_000);
+        assert_eq!(u16_at(&bytes, 44) as i16, 16_383);
+
+        let cue = 44 + 32_000;
+        assert_eq!(&bytes[cue..cue + 4], b"cue ");
+        assert_eq!(u32_at(&bytes, cue + 4), 4 + 2 * 24);
+        assert_eq!(u32_at(&bytes, cue + 8), 2);
+        let second_point = cue + 12 + 24;
+        assert_eq!(u32_at(&bytes, second_point), 2);
+        assert_eq!(u32_at(&bytes, second_point + 4), 8_000);
+        assert_eq!(&bytes[second_point + 8..second_point + 12], b"data");
+        assert_eq!(u32_at(&bytes, second_point + 20), 8_000);
+
+        let list = cue + 8 + 52;
+        assert_eq!(&bytes[list..list + 4], b"LIST");
+        assert_eq!(u32_at(&bytes, list + 4) as usize, bytes.len() - list - 8);
+        assert_eq!(&bytes[list + 8..list + 12], b"adtl");
+        assert_eq!(&bytes[list + 12..list + 16], b"labl");
+        assert_eq!(u32_at(&bytes, list + 16), 4 + 6);
+        assert_eq!(u32_at(&bytes, list + 20), 1);
+        assert_eq!(&bytes[list + 24..list + 30], b"Intro\0");
+        assert_eq!(&bytes[list + 30..list + 34], b"labl");
+        assert_eq!(u32_at(&bytes, list + 34), 4 + 12);
+        assert_eq!(u32_at(&bytes, list + 38), 2);
+        assert_eq!(&bytes[list + 42..], b"Chapter One\0");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn wav_without_chapters_has_no_cue_chunk() {
+        let dir = std::env::temp_dir().join(format!("audiobook-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("book.wav");
+
+        let mut wav = StreamingWav::create(&path, 22_050).unwrap();
+        wav.write(&[0.0; 3]).unwrap();
+        wav.finish(&[]).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(u32_at(&bytes, 4), 36 + 6);
+        assert_eq!(u32_at(&bytes, 40), 6);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn escapes_ffmetadata_special_characters() {
+        assert_eq!(
+            escape_ffmetadata(r"Part 1; a=b #2\"),
+            r"Part 1\; a\=b \#2\\"
+        );
+    }
+}
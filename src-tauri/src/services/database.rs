@@ -1,19 +1,175 @@
-use rusqlite::{Connection, Result};
-use std::path::PathBuf;
+use super::annotations::{Annotation, AnnotationAction};
+use super::pdf_annotations::PdfAnnotation;
+use super::reading_progress::{
+    estimate_seconds_remaining, heartbeat_credit, reading_streaks, DailyReading,
+    DocumentReadingTime, ReadingHeartbeat, ReadingPosition, ReadingProgress, ReadingSession,
+    ReadingStats,
+};
+use super::spaced_repetition::{ReviewGrade, ReviewSchedule};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-pub struct Database {
-    conn: Connection,
+/// File name of the application database inside the app data directory
+pub const DATABASE_FILE_NAME: &str = "library.db";
+/// Practice history the frontend kept in the app config directory before it
+/// moved into the application database
+pub const LEGACY_PRACTICE_DATABASE_FILE_NAME: &str = "practice.db";
+/// Undoable annotation changes kept per document
+const ANNOTATION_HISTORY_LIMIT: i64 = 100;
+
+/// One scored pronunciation of a word during practice
+#[derive(Debug, Clone)]
+pub struct PracticeAttempt {
+    pub document_id: String,
+    pub word: String,
+    pub language: String,
+    pub page_number: i64,
+    pub score: f32,
+    pub mispronounced: bool,
+    /// Serialized per-phoneme result, kept for review
+    pub details: String,
+    /// Sentence the word was read in
+    pub context: Option<String>,
+    pub ipa: Option<String>,
 }
 
-impl Database {
-    pub fn new(path: PathBuf) -> Result<Self> {
-        let conn = Connection::open(path)?;
-        Ok(Self { conn })
-    }
+/// Extra information attached to a practice word for review and export.
+/// `None` fields leave the stored value unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PracticeWordDetails {
+    pub context: Option<String>,
+    pub translation: Option<String>,
+    pub ipa: Option<String>,
+    /// Recording of the learner saying the word
+    pub audio_path: Option<String>,
+}
+
+/// Selects practice words for export; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VocabularyFilter {
+    pub document_id: Option<String>,
+    pub language: Option<String>,
+}
+
+/// A practice word with everything needed to export it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocabularyEntry {
+    pub id: String,
+    pub document_id: String,
+    pub document_title: Option<String>,
+    pub word: String,
+    pub language: String,
+    pub page_number: i64,
+    pub attempts: i64,
+    pub details: PracticeWordDetails,
+    /// `None` until the word has been reviewed
+    pub schedule: Option<ReviewSchedule>,
+}
 
-    pub fn init_schema(&self) -> Result<()> {
-        self.conn.execute_batch(
-            r#"
+/// A practice word read from an import file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedPracticeWord {
+    pub document_id: String,
+    pub word: String,
+    pub language: String,
+    pub page_number: i64,
+    pub details: PracticeWordDetails,
+}
+
+/// A practice word together with its review schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuePracticeWord {
+    pub id: String,
+    pub document_id: String,
+    pub word: String,
+    pub language: String,
+    pub page_number: i64,
+    pub attempts: i64,
+    pub schedule: ReviewSchedule,
+    /// True until the word has been reviewed for the first time
+    pub is_new: bool,
+}
+
+/// Filter for the review queue; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DueQueueFilter {
+    pub document_id: Option<String>,
+    pub language: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// One graded review, with the schedule it produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PracticeReview {
+    pub id: String,
+    pub practice_word_id: String,
+    pub grade: ReviewGrade,
+    pub ease_factor: f64,
+    pub interval_days: f64,
+    pub due_at: DateTime<Utc>,
+    pub reviewed_at: DateTime<Utc>,
+}
+
+/// A file in the document library
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentRecord {
+    pub id: String,
+    pub file_path: String,
+    /// SHA-256 of the file contents, used to find the file again after a move
+    pub file_hash: String,
+    pub title: String,
+    pub total_pages: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_opened_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSort {
+    #[default]
+    LastOpened,
+    Title,
+    Created,
+}
+
+/// Selects a page of the document library; unset fields match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentQuery {
+    /// Case-insensitive match against the title or path
+    pub search: Option<String>,
+    pub sort: DocumentSort,
+    /// Oldest or A-Z first instead of newest or Z-A first
+    pub ascending: bool,
+    pub offset: u32,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentPage {
+    pub documents: Vec<DocumentRecord>,
+    /// Matching documents across all pages
+    pub total: u32,
+}
+
+/// One forward-only schema change, applied in order of `version`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every schema change so far; append new ones, never edit applied ones.
+/// The applied version is kept in SQLite's `user_version`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        // `IF NOT EXISTS` adopts databases created before versioning
+        description: "Documents, annotations and spaced-repetition practice",
+        sql: r#"
             CREATE TABLE IF NOT EXISTS documents (
                 id TEXT PRIMARY KEY,
                 file_path TEXT NOT NULL,
@@ -58,13 +214,1806 @@ impl Database {
                 created_at TEXT NOT NULL,
                 FOREIGN KEY (document_id) REFERENCES documents(id)
             );
+
+            CREATE TABLE IF NOT EXISTS pronunciation_attempts (
+                id TEXT PRIMARY KEY,
+                practice_word_id TEXT NOT NULL,
+                score REAL NOT NULL,
+                details TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (practice_word_id) REFERENCES practice_words(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS practice_schedule (
+                practice_word_id TEXT PRIMARY KEY,
+                ease_factor REAL NOT NULL,
+                interval_days REAL NOT NULL,
+                repetitions INTEGER NOT NULL,
+                lapses INTEGER NOT NULL,
+                due_at TEXT NOT NULL,
+                last_reviewed_at TEXT,
+                FOREIGN KEY (practice_word_id) REFERENCES practice_words(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS practice_reviews (
+                id TEXT PRIMARY KEY,
+                practice_word_id TEXT NOT NULL,
+                grade TEXT NOT NULL,
+                ease_factor REAL NOT NULL,
+                interval_days REAL NOT NULL,
+                due_at TEXT NOT NULL,
+                reviewed_at TEXT NOT NULL,
+                FOREIGN KEY (practice_word_id) REFERENCES practice_words(id)
+            );
+
+            CREATE TABLE IF NOT EXISTS practice_word_details (
+                practice_word_id TEXT PRIMARY KEY,
+                context TEXT,
+                translation TEXT,
+                ipa TEXT,
+                audio_path TEXT,
+                FOREIGN KEY (practice_word_id) REFERENCES practice_words(id)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_practice_schedule_due
+                ON practice_schedule(due_at);
             "#,
-        )?;
+    },
+    Migration {
+        version: 2,
+        description: "Practice sessions and word history from the frontend",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS practice_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                document_hash TEXT NOT NULL,
+                page_number INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                accuracy REAL NOT NULL,
+                duration INTEGER NOT NULL,
+                timestamp INTEGER NOT NULL,
+                UNIQUE(document_hash, page_number, timestamp)
+            );
+
+            CREATE TABLE IF NOT EXISTS word_practice (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                word TEXT NOT NULL,
+                document_hash TEXT NOT NULL,
+                page_number INTEGER NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                success_count INTEGER NOT NULL DEFAULT 0,
+                last_accuracy REAL NOT NULL,
+                average_accuracy REAL NOT NULL,
+                marked INTEGER NOT NULL DEFAULT 0,
+                last_practiced INTEGER NOT NULL,
+                UNIQUE(word, document_hash, page_number)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_sessions_document
+                ON practice_sessions(document_hash, page_number);
+            CREATE INDEX IF NOT EXISTS idx_word_practice
+                ON word_practice(word, document_hash);
+            CREATE INDEX IF NOT EXISTS idx_marked_words
+                ON word_practice(marked, last_practiced);
+            "#,
+    },
+    Migration {
+        version: 3,
+        description: "Find documents by path and by contents",
+        sql: r#"
+            CREATE INDEX IF NOT EXISTS idx_documents_file_path ON documents(file_path);
+            CREATE INDEX IF NOT EXISTS idx_documents_file_hash ON documents(file_hash);
+            "#,
+    },
+    Migration {
+        version: 4,
+        description: "Reading position, sessions and pages read per day",
+        sql: r#"
+            ALTER TABLE reading_progress ADD COLUMN scroll_offset REAL;
+            ALTER TABLE reading_progress ADD COLUMN zoom REAL;
+            CREATE UNIQUE INDEX idx_reading_progress_document
+                ON reading_progress(document_id);
+
+            CREATE TABLE reading_sessions (
+                id TEXT PRIMARY KEY,
+                document_id TEXT NOT NULL,
+                day TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                last_active_at TEXT NOT NULL,
+                active_seconds INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (document_id) REFERENCES documents(id)
+            );
+            CREATE INDEX idx_reading_sessions_document
+                ON reading_sessions(document_id, last_active_at);
+            CREATE INDEX idx_reading_sessions_day ON reading_sessions(day);
+
+            CREATE TABLE reading_pages (
+                document_id TEXT NOT NULL,
+                day TEXT NOT NULL,
+                page_number INTEGER NOT NULL,
+                PRIMARY KEY (document_id, day, page_number),
+                FOREIGN KEY (document_id) REFERENCES documents(id)
+            );
+            "#,
+    },
+    Migration {
+        version: 5,
+        description: "Annotation trash and undo history",
+        sql: r#"
+            ALTER TABLE annotations ADD COLUMN deleted_at TEXT;
+            CREATE INDEX idx_annotations_page ON annotations(document_id, page_number);
+
+            CREATE TABLE annotation_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                document_id TEXT NOT NULL,
+                annotation_id TEXT NOT NULL,
+                action TEXT NOT NULL,
+                previous TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (document_id) REFERENCES documents(id)
+            );
+            CREATE INDEX idx_annotation_history_document
+                ON annotation_history(document_id, id);
+            "#,
+    },
+    Migration {
+        version: 6,
+        description: "Annotations imported from PDF files",
+        sql: r#"
+            ALTER TABLE annotations ADD COLUMN author TEXT;
+            ALTER TABLE annotations ADD COLUMN reply_to TEXT;
+            ALTER TABLE annotations ADD COLUMN source_key TEXT;
+            CREATE UNIQUE INDEX idx_annotations_source ON annotations(document_id, source_key);
+            "#,
+    },
+];
+
+/// Schema version this build migrates to
+pub fn latest_schema_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+pub struct Database {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl Database {
+    /// Open the database at `path`, creating it if needed, and bring its
+    /// schema up to date
+    pub fn open(path: PathBuf) -> std::result::Result<Self, String> {
+        let conn = Connection::open(&path)
+            .map_err(|e| format!("Failed to open database {}: {}", path.display(), e))?;
+        // The frontend opens the same file; WAL lets both read while one writes
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.busy_timeout(std::time::Duration::from_secs(5)))
+            .map_err(|e| format!("Failed to configure database: {}", e))?;
+
+        let mut db = Self { conn, path };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn schema_version(&self) -> Result<u32> {
+        self.conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    /// Apply pending migrations in order, each in its own transaction.
+    /// A database that already holds tables is backed up before every step.
+    fn migrate(&mut self) -> std::result::Result<(), String> {
+        let current = self.schema_version().map_err(|e| e.to_string())?;
+        if current > latest_schema_version() {
+            return Err(format!(
+                "Database schema {} is newer than this version of the app supports ({})",
+                current,
+                latest_schema_version()
+            ));
+        }
+
+        let mut version = current;
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            if self.has_tables().map_err(|e| e.to_string())? {
+                self.backup(version)?;
+            }
+
+            let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+            tx.execute_batch(migration.sql)
+                .and_then(|_| tx.pragma_update(None, "user_version", migration.version))
+                .and_then(|_| tx.commit())
+                .map_err(|e| {
+                    format!(
+                        "Failed to migrate database to version {} ({}): {}",
+                        migration.version, migration.description, e
+                    )
+                })?;
+            version = migration.version;
+        }
 
         Ok(())
     }
 
+    fn has_tables(&self) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Snapshot the database next to it as `<file>.v<version>.bak`
+    fn backup(&self, version: u32) -> std::result::Result<PathBuf, String> {
+        let mut backup_path = self.path.as_os_str().to_owned();
+        backup_path.push(format!(".v{}.bak", version));
+        let backup_path = PathBuf::from(backup_path);
+        // VACUUM INTO refuses to overwrite, so replace a stale backup explicitly
+        if backup_path.exists() {
+            std::fs::remove_file(&backup_path)
+                .map_err(|e| format!("Failed to replace database backup: {}", e))?;
+        }
+
+        self.conn
+            .execute(
+                "VACUUM INTO ?1",
+                params![backup_path.to_string_lossy().to_string()],
+            )
+            .map_err(|e| format!("Failed to back up database: {}", e))?;
+        Ok(backup_path)
+    }
+
+    /// Copy practice sessions and word history out of the frontend's old
+    /// `practice.db`, then rename it to `practice.db.imported` so this runs
+    /// once. Rows already present are kept. Returns how many rows were copied.
+    pub fn import_legacy_practice(&self, legacy_path: &Path) -> std::result::Result<usize, String> {
+        if !legacy_path.exists() {
+            return Ok(0);
+        }
+
+        self.conn
+            .execute(
+                "ATTACH DATABASE ?1 AS legacy",
+                params![legacy_path.to_string_lossy().to_string()],
+            )
+            .map_err(|e| format!("Failed to open {}: {}", legacy_path.display(), e))?;
+        let imported = self.copy_legacy_practice();
+        let detached = self.conn.execute("DETACH DATABASE legacy", []);
+        let imported = imported.map_err(|e| format!("Failed to import practice history: {}", e))?;
+        detached.map_err(|e| format!("Failed to import practice history: {}", e))?;
+
+        let mut imported_path = legacy_path.as_os_str().to_owned();
+        imported_path.push(".imported");
+        std::fs::rename(legacy_path, PathBuf::from(imported_path))
+            .map_err(|e| format!("Failed to retire {}: {}", legacy_path.display(), e))?;
+        Ok(imported)
+    }
+
+    fn copy_legacy_practice(&self) -> Result<usize> {
+        let legacy_table = |name: &str| -> Result<bool> {
+            self.conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM legacy.sqlite_master
+                 WHERE type = 'table' AND name = ?1)",
+                params![name],
+                |row| row.get(0),
+            )
+        };
+
+        let tx = self.conn.unchecked_transaction()?;
+        let mut imported = 0;
+        if legacy_table("practice_sessions")? {
+            imported += tx.execute(
+                "INSERT OR IGNORE INTO practice_sessions
+                 (document_hash, page_number, text, accuracy, duration, timestamp)
+                 SELECT document_hash, page_number, text, accuracy, duration, timestamp
+                 FROM legacy.practice_sessions ORDER BY id",
+                [],
+            )?;
+        }
+        if legacy_table("word_practice")? {
+            imported += tx.execute(
+                "INSERT OR IGNORE INTO word_practice
+                 (word, document_hash, page_number, attempts, success_count,
+                  last_accuracy, average_accuracy, marked, last_practiced)
+                 SELECT word, document_hash, page_number, attempts, success_count,
+                        last_accuracy, average_accuracy, marked, last_practiced
+                 FROM legacy.word_practice ORDER BY id",
+                [],
+            )?;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    #[cfg(test)]
     pub fn get_connection(&self) -> &Connection {
         &self.conn
     }
+
+    /// Record a pronunciation attempt against `practice_words`.
+    ///
+    /// Mispronounced words are added to the practice list; words already on it
+    /// get their attempt counted either way. Returns the practice word id, or
+    /// `None` when a correctly pronounced word isn't being practiced.
+    pub fn record_practice_attempt(&self, attempt: &PracticeAttempt) -> Result<Option<String>> {
+        let now = timestamp(Utc::now());
+        let word = attempt.word.to_lowercase();
+
+        let tx = self.conn.unchecked_transaction()?;
+        let existing: Option<String> = tx
+            .query_row(
+                "SELECT id FROM practice_words
+                 WHERE document_id = ?1 AND word = ?2 AND language = ?3",
+                params![attempt.document_id, word, attempt.language],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let id = match existing {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE practice_words
+                     SET attempts = attempts + 1, last_practiced = ?1
+                     WHERE id = ?2",
+                    params![now, id],
+                )?;
+                id
+            }
+            None if attempt.mispronounced => {
+                let id = uuid::Uuid::new_v4().to_string();
+                tx.execute(
+                    "INSERT INTO practice_words
+                     (id, document_id, word, language, page_number, attempts, last_practiced, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6, ?6)",
+                    params![
+                        id,
+                        attempt.document_id,
+                        word,
+                        attempt.language,
+                        attempt.page_number,
+                        now
+                    ],
+                )?;
+                id
+            }
+            None => return Ok(None),
+        };
+
+        self.save_practice_word_details(
+            &id,
+            &PracticeWordDetails {
+                context: attempt.context.clone(),
+                ipa: attempt.ipa.clone(),
+                ..Default::default()
+            },
+        )?;
+
+        tx.execute(
+            "INSERT INTO pronunciation_attempts (id, practice_word_id, score, details, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                id,
+                attempt.score as f64,
+                attempt.details,
+                now
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(Some(id))
+    }
+
+    /// Practice words due before `until`, most overdue first. Words that have
+    /// never been reviewed are always due and come after scheduled reviews.
+    pub fn due_practice_words(
+        &self,
+        filter: &DueQueueFilter,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<DuePracticeWord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.id, p.document_id, p.word, p.language, p.page_number, p.attempts,
+                    p.created_at, s.ease_factor, s.interval_days, s.repetitions, s.lapses,
+                    s.due_at, s.last_reviewed_at
+             FROM practice_words p
+             LEFT JOIN practice_schedule s ON s.practice_word_id = p.id
+             WHERE (s.due_at IS NULL OR s.due_at <= ?1)
+               AND (?2 IS NULL OR p.document_id = ?2)
+               AND (?3 IS NULL OR p.language = ?3)
+             ORDER BY s.due_at IS NULL, s.due_at, p.created_at
+             LIMIT ?4",
+        )?;
+
+        let limit = filter.limit.map(i64::from).unwrap_or(-1);
+        let rows = stmt.query_map(
+            params![timestamp(until), filter.document_id, filter.language, limit],
+            due_word_from_row,
+        )?;
+        rows.collect()
+    }
+
+    /// Current schedule of a practice word; unreviewed words get a fresh one
+    pub fn practice_schedule(&self, practice_word_id: &str) -> Result<ReviewSchedule> {
+        let scheduled = self
+            .conn
+            .query_row(
+                "SELECT ease_factor, interval_days, repetitions, lapses, due_at, last_reviewed_at
+                 FROM practice_schedule WHERE practice_word_id = ?1",
+                params![practice_word_id],
+                |row| schedule_from_row(row, 0),
+            )
+            .optional()?;
+
+        match scheduled {
+            Some(schedule) => Ok(schedule),
+            None => {
+                // Fail on unknown ids rather than scheduling a word that doesn't exist
+                let created_at = self.conn.query_row(
+                    "SELECT created_at FROM practice_words WHERE id = ?1",
+                    params![practice_word_id],
+                    |row| timestamp_column(row, 0),
+                )?;
+                Ok(ReviewSchedule::new_card(created_at))
+            }
+        }
+    }
+
+    /// Grade a review, reschedule the word and append it to the review log
+    pub fn record_practice_review(
+        &self,
+        practice_word_id: &str,
+        grade: ReviewGrade,
+        now: DateTime<Utc>,
+    ) -> Result<ReviewSchedule> {
+        let tx = self.conn.unchecked_transaction()?;
+        let next = self.practice_schedule(practice_word_id)?.review(grade, now);
+
+        tx.execute(
+            "INSERT INTO practice_schedule
+             (practice_word_id, ease_factor, interval_days, repetitions, lapses, due_at, last_reviewed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(practice_word_id) DO UPDATE SET
+                ease_factor = excluded.ease_factor,
+                interval_days = excluded.interval_days,
+                repetitions = excluded.repetitions,
+                lapses = excluded.lapses,
+                due_at = excluded.due_at,
+                last_reviewed_at = excluded.last_reviewed_at",
+            params![
+                practice_word_id,
+                next.ease_factor,
+                next.interval_days,
+                next.repetitions,
+                next.lapses,
+                timestamp(next.due_at),
+                timestamp(now)
+            ],
+        )?;
+
+        tx.execute(
+            "INSERT INTO practice_reviews
+             (id, practice_word_id, grade, ease_factor, interval_days, due_at, reviewed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                practice_word_id,
+                grade.as_str(),
+                next.ease_factor,
+                next.interval_days,
+                timestamp(next.due_at),
+                timestamp(now)
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(next)
+    }
+
+    /// Review history of a practice word, oldest first
+    pub fn practice_reviews(&self, practice_word_id: &str) -> Result<Vec<PracticeReview>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, practice_word_id, grade, ease_factor, interval_days, due_at, reviewed_at
+             FROM practice_reviews WHERE practice_word_id = ?1
+             ORDER BY reviewed_at",
+        )?;
+
+        let rows = stmt.query_map(params![practice_word_id], |row| {
+            let grade: String = row.get(2)?;
+            let grade = ReviewGrade::parse(&grade).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    format!("Unknown review grade: {}", grade).into(),
+                )
+            })?;
+            Ok(PracticeReview {
+                id: row.get(0)?,
+                practice_word_id: row.get(1)?,
+                grade,
+                ease_factor: row.get(3)?,
+                interval_days: row.get(4)?,
+                due_at: timestamp_column(row, 5)?,
+                reviewed_at: timestamp_column(row, 6)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Merge details into a practice word, keeping stored values for `None` fields
+    pub fn save_practice_word_details(
+        &self,
+        practice_word_id: &str,
+        details: &PracticeWordDetails,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO practice_word_details (practice_word_id, context, translation, ipa, audio_path)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(practice_word_id) DO UPDATE SET
+                context = COALESCE(excluded.context, context),
+                translation = COALESCE(excluded.translation, translation),
+                ipa = COALESCE(excluded.ipa, ipa),
+                audio_path = COALESCE(excluded.audio_path, audio_path)",
+            params![
+                practice_word_id,
+                details.context,
+                details.translation,
+                details.ipa,
+                details.audio_path
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Practice words with their details and schedule, in the order they were added
+    pub fn vocabulary(&self, filter: &VocabularyFilter) -> Result<Vec<VocabularyEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.id, p.document_id, d.title, p.word, p.language, p.page_number, p.attempts,
+                    x.context, x.translation, x.ipa, x.audio_path,
+                    s.ease_factor, s.interval_days, s.repetitions, s.lapses,
+                    s.due_at, s.last_reviewed_at
+             FROM practice_words p
+             LEFT JOIN documents d ON d.id = p.document_id
+             LEFT JOIN practice_word_details x ON x.practice_word_id = p.id
+             LEFT JOIN practice_schedule s ON s.practice_word_id = p.id
+             WHERE (?1 IS NULL OR p.document_id = ?1)
+               AND (?2 IS NULL OR p.language = ?2)
+             ORDER BY p.created_at",
+        )?;
+
+        let rows = stmt.query_map(params![filter.document_id, filter.language], |row| {
+            let ease_factor: Option<f64> = row.get(11)?;
+            Ok(VocabularyEntry {
+                id: row.get(0)?,
+                document_id: row.get(1)?,
+                document_title: row.get(2)?,
+                word: row.get(3)?,
+                language: row.get(4)?,
+                page_number: row.get(5)?,
+                attempts: row.get(6)?,
+                details: PracticeWordDetails {
+                    context: row.get(7)?,
+                    translation: row.get(8)?,
+                    ipa: row.get(9)?,
+                    audio_path: row.get(10)?,
+                },
+                schedule: match ease_factor {
+                    Some(_) => Some(schedule_from_row(row, 11)?),
+                    None => None,
+                },
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Add an imported word unless the document already has it in that language.
+    /// Duplicates only gain details they were missing. Returns whether a new
+    /// practice word was created.
+    pub fn import_practice_word(&self, imported: &ImportedPracticeWord) -> Result<bool> {
+        let word = imported.word.trim().to_lowercase();
+
+        let existing: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT id FROM practice_words
+                 WHERE document_id = ?1 AND word = ?2 AND language = ?3",
+                params![imported.document_id, word, imported.language],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let (id, created) = match existing {
+            Some(id) => (id, false),
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                self.conn.execute(
+                    "INSERT INTO practice_words
+                     (id, document_id, word, language, page_number, attempts, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)",
+                    params![
+                        id,
+                        imported.document_id,
+                        word,
+                        imported.language,
+                        imported.page_number,
+                        chrono::Utc::now().to_rfc3339()
+                    ],
+                )?;
+                (id, true)
+            }
+        };
+
+        // Don't overwrite what the learner already has
+        self.conn.execute(
+            "INSERT INTO practice_word_details (practice_word_id, context, translation, ipa, audio_path)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(practice_word_id) DO UPDATE SET
+                context = COALESCE(context, excluded.context),
+                translation = COALESCE(translation, excluded.translation),
+                ipa = COALESCE(ipa, excluded.ipa),
+                audio_path = COALESCE(audio_path, excluded.audio_path)",
+            params![
+                id,
+                imported.details.context,
+                imported.details.translation,
+                imported.details.ipa,
+                imported.details.audio_path
+            ],
+        )?;
+
+        Ok(created)
+    }
+
+    pub fn document_exists(&self, document_id: &str) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM documents WHERE id = ?1)",
+            params![document_id],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn document(&self, document_id: &str) -> Result<Option<DocumentRecord>> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM documents WHERE id = ?1", DOCUMENT_COLUMNS),
+                params![document_id],
+                document_from_row,
+            )
+            .optional()
+    }
+
+    pub fn document_by_path(&self, file_path: &str) -> Result<Option<DocumentRecord>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM documents WHERE file_path = ?1",
+                    DOCUMENT_COLUMNS
+                ),
+                params![file_path],
+                document_from_row,
+            )
+            .optional()
+    }
+
+    /// Documents whose file had these contents when last seen, most recently opened first
+    pub fn documents_by_hash(&self, file_hash: &str) -> Result<Vec<DocumentRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM documents WHERE file_hash = ?1 ORDER BY last_opened_at DESC",
+            DOCUMENT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![file_hash], document_from_row)?;
+        rows.collect()
+    }
+
+    pub fn insert_document(&self, document: &DocumentRecord) -> Result<()> {
+        self.conn.execute(
+            &format!(
+                "INSERT INTO documents ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                DOCUMENT_COLUMNS
+            ),
+            params![
+                document.id,
+                document.file_path,
+                document.file_hash,
+                document.title,
+                document.total_pages,
+                timestamp(document.created_at),
+                timestamp(document.updated_at),
+                timestamp(document.last_opened_at)
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Record where a document's file is now and what it contains, and mark it opened
+    pub fn update_document_file(
+        &self,
+        document_id: &str,
+        file_path: &str,
+        file_hash: &str,
+        total_pages: i64,
+        opened_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE documents
+             SET file_path = ?2, file_hash = ?3, total_pages = ?4,
+                 updated_at = CASE WHEN file_path = ?2 AND file_hash = ?3 AND total_pages = ?4
+                                   THEN updated_at ELSE ?5 END,
+                 last_opened_at = ?5
+             WHERE id = ?1",
+            params![
+                document_id,
+                file_path,
+                file_hash,
+                total_pages,
+                timestamp(opened_at)
+            ],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Record new contents after the app itself rewrote the file
+    pub fn update_document_hash(&self, document_id: &str, file_hash: &str) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE documents SET file_hash = ?2, updated_at = ?3 WHERE id = ?1",
+            params![document_id, file_hash, timestamp(Utc::now())],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn touch_document(&self, document_id: &str, opened_at: DateTime<Utc>) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE documents SET last_opened_at = ?2 WHERE id = ?1",
+            params![document_id, timestamp(opened_at)],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn rename_document(&self, document_id: &str, title: &str) -> Result<bool> {
+        let changed = self.conn.execute(
+            "UPDATE documents SET title = ?2, updated_at = ?3 WHERE id = ?1",
+            params![document_id, title, timestamp(Utc::now())],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Remove a document with its progress, annotations and practice history
+    pub fn delete_document(&self, document_id: &str) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        for table in [
+            "pronunciation_attempts",
+            "practice_schedule",
+            "practice_reviews",
+            "practice_word_details",
+        ] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE practice_word_id IN
+                     (SELECT id FROM practice_words WHERE document_id = ?1)",
+                    table
+                ),
+                params![document_id],
+            )?;
+        }
+        for table in [
+            "practice_words",
+            "annotation_history",
+            "annotations",
+            "reading_progress",
+            "reading_sessions",
+            "reading_pages",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE document_id = ?1", table),
+                params![document_id],
+            )?;
+        }
+        let deleted = tx.execute("DELETE FROM documents WHERE id = ?1", params![document_id])?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    pub fn reading_progress(&self, document_id: &str) -> Result<Option<ReadingProgress>> {
+        self.conn
+            .query_row(
+                "SELECT document_id, current_page, scroll_offset, zoom, total_time_seconds,
+                        updated_at
+                 FROM reading_progress WHERE document_id = ?1",
+                params![document_id],
+                |row| {
+                    Ok(ReadingProgress {
+                        document_id: row.get(0)?,
+                        current_page: row.get(1)?,
+                        scroll_offset: row.get(2)?,
+                        zoom: row.get(3)?,
+                        total_time_seconds: row.get(4)?,
+                        updated_at: timestamp_column(row, 5)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Remember where the reader is; an unset zoom keeps the saved one
+    pub fn save_reading_position(
+        &self,
+        document_id: &str,
+        position: &ReadingPosition,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO reading_progress
+             (id, document_id, current_page, scroll_offset, zoom, total_time_seconds, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6)
+             ON CONFLICT(document_id) DO UPDATE SET
+                 current_page = excluded.current_page,
+                 scroll_offset = excluded.scroll_offset,
+                 zoom = COALESCE(excluded.zoom, zoom),
+                 updated_at = excluded.updated_at",
+            params![
+                uuid::Uuid::new_v4().to_string(),
+                document_id,
+                position.page,
+                position.scroll_offset,
+                position.zoom,
+                timestamp(now)
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Extend the document's latest session, or start one after a long gap.
+    /// Active time is added to the session and the document's total.
+    pub fn record_reading_heartbeat(
+        &self,
+        heartbeat: &ReadingHeartbeat,
+        now: DateTime<Utc>,
+        today: NaiveDate,
+    ) -> Result<ReadingSession> {
+        let tx = self.conn.unchecked_transaction()?;
+        let latest = tx
+            .query_row(
+                "SELECT id, document_id, day, started_at, last_active_at, active_seconds
+                 FROM reading_sessions WHERE document_id = ?1
+                 ORDER BY last_active_at DESC LIMIT 1",
+                params![heartbeat.document_id],
+                session_from_row,
+            )
+            .optional()?;
+
+        let continued = latest.and_then(|session| {
+            heartbeat_credit(session.last_active_at, now).map(|credit| (session, credit))
+        });
+        let session = match continued {
+            Some((mut session, credit)) => {
+                let credit = if heartbeat.active { credit } else { 0 };
+                session.active_seconds += credit;
+                session.last_active_at = now;
+                tx.execute(
+                    "UPDATE reading_sessions SET last_active_at = ?2, active_seconds = ?3
+                     WHERE id = ?1",
+                    params![session.id, timestamp(now), session.active_seconds],
+                )?;
+                tx.execute(
+                    "INSERT INTO reading_progress
+                     (id, document_id, current_page, total_time_seconds, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT(document_id) DO UPDATE SET
+                         total_time_seconds = total_time_seconds + excluded.total_time_seconds",
+                    params![
+                        uuid::Uuid::new_v4().to_string(),
+                        heartbeat.document_id,
+                        heartbeat.page,
+                        credit,
+                        timestamp(now)
+                    ],
+                )?;
+                session
+            }
+            None => {
+                let session = ReadingSession {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    document_id: heartbeat.document_id.clone(),
+                    day: today,
+                    started_at: now,
+                    last_active_at: now,
+                    active_seconds: 0,
+                };
+                tx.execute(
+                    "INSERT INTO reading_sessions
+                     (id, document_id, day, started_at, last_active_at, active_seconds)
+                     VALUES (?1, ?2, ?3, ?4, ?4, 0)",
+                    params![
+                        session.id,
+                        session.document_id,
+                        today.to_string(),
+                        timestamp(now)
+                    ],
+                )?;
+                session
+            }
+        };
+
+        if heartbeat.active {
+            tx.execute(
+                "INSERT OR IGNORE INTO reading_pages (document_id, day, page_number)
+                 VALUES (?1, ?2, ?3)",
+                params![heartbeat.document_id, today.to_string(), heartbeat.page],
+            )?;
+        }
+        tx.commit()?;
+        Ok(session)
+    }
+
+    /// Reading habits from `since` through `today`, plus per-document totals
+    pub fn reading_stats(&self, since: NaiveDate, today: NaiveDate) -> Result<ReadingStats> {
+        let mut stmt = self.conn.prepare(
+            "SELECT day, SUM(seconds), SUM(pages) FROM (
+                 SELECT day, active_seconds AS seconds, 0 AS pages FROM reading_sessions
+                 UNION ALL
+                 SELECT day, 0, 1 FROM reading_pages
+             )
+             GROUP BY day
+             HAVING SUM(seconds) > 0 OR SUM(pages) > 0
+             ORDER BY day",
+        )?;
+        let all_days = stmt
+            .query_map([], |row| {
+                Ok(DailyReading {
+                    day: day_column(row, 0)?,
+                    seconds: row.get(1)?,
+                    pages: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        let (current_streak, longest_streak) = reading_streaks(
+            &all_days.iter().map(|day| day.day).collect::<Vec<_>>(),
+            today,
+        );
+        let days: Vec<DailyReading> = all_days
+            .into_iter()
+            .filter(|day| day.day >= since && day.day <= today)
+            .collect();
+        let period_days = ((today - since).num_days() + 1).max(1);
+        let pages: i64 = days.iter().map(|day| day.pages).sum();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT d.id, d.title, d.total_pages, p.current_page, p.total_time_seconds,
+                    (SELECT COUNT(DISTINCT page_number) FROM reading_pages r
+                     WHERE r.document_id = d.id)
+             FROM documents d
+             JOIN reading_progress p ON p.document_id = d.id
+             WHERE p.total_time_seconds > 0
+             ORDER BY p.total_time_seconds DESC",
+        )?;
+        let documents = stmt
+            .query_map([], |row| {
+                let total_pages: i64 = row.get(2)?;
+                let current_page: i64 = row.get(3)?;
+                let total_seconds: i64 = row.get(4)?;
+                let pages_read: i64 = row.get(5)?;
+                Ok(DocumentReadingTime {
+                    document_id: row.get(0)?,
+                    title: row.get(1)?,
+                    total_seconds,
+                    current_page,
+                    total_pages,
+                    pages_read,
+                    estimated_seconds_remaining: estimate_seconds_remaining(
+                        total_seconds,
+                        pages_read,
+                        current_page,
+                        total_pages,
+                    ),
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ReadingStats {
+            total_seconds: days.iter().map(|day| day.seconds).sum(),
+            average_pages_per_day: pages as f64 / period_days as f64,
+            days,
+            current_streak,
+            longest_streak,
+            documents,
+        })
+    }
+
+    pub fn annotation(&self, annotation_id: &str) -> Result<Option<Annotation>> {
+        read_annotation(&self.conn, annotation_id)
+    }
+
+    /// The document's annotation known to other readers as `name`: either its
+    /// id or the /NM it was imported with
+    pub fn annotation_by_exchange_name(
+        &self,
+        document_id: &str,
+        name: &str,
+    ) -> Result<Option<Annotation>> {
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM annotations
+                     WHERE document_id = ?1 AND (id = ?2 OR source_key = ?2)
+                     ORDER BY id = ?2 DESC LIMIT 1",
+                    ANNOTATION_COLUMNS
+                ),
+                params![document_id, name],
+                annotation_from_row,
+            )
+            .optional()
+    }
+
+    /// A document's annotations in page order, optionally one page only
+    pub fn annotations(
+        &self,
+        document_id: &str,
+        page_number: Option<i64>,
+        include_deleted: bool,
+    ) -> Result<Vec<Annotation>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM annotations
+             WHERE document_id = ?1
+               AND (?2 IS NULL OR page_number = ?2)
+               AND (?3 OR deleted_at IS NULL)
+             ORDER BY page_number, created_at, id",
+            ANNOTATION_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![document_id, page_number, include_deleted],
+            annotation_from_row,
+        )?;
+        rows.collect()
+    }
+
+    pub fn insert_annotation(&self, annotation: &Annotation) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        write_annotation(&tx, annotation)?;
+        record_annotation_change(
+            &tx,
+            annotation,
+            AnnotationAction::Create,
+            None,
+            annotation.updated_at,
+        )?;
+        tx.commit()
+    }
+
+    /// Save an edited annotation, remembering the previous version for undo
+    pub fn update_annotation(&self, annotation: &Annotation) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let Some(previous) = read_annotation(&tx, &annotation.id)? else {
+            return Ok(false);
+        };
+        write_annotation(&tx, annotation)?;
+        record_annotation_change(
+            &tx,
+            annotation,
+            AnnotationAction::Update,
+            Some(&previous),
+            annotation.updated_at,
+        )?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Move an annotation to the trash; undo restores it
+    pub fn delete_annotation(&self, annotation_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let tx = self.conn.unchecked_transaction()?;
+        let Some(annotation) = read_annotation(&tx, annotation_id)? else {
+            return Ok(false);
+        };
+        if annotation.deleted_at.is_some() {
+            return Ok(false);
+        }
+        tx.execute(
+            "UPDATE annotations SET deleted_at = ?2 WHERE id = ?1",
+            params![annotation_id, timestamp(now)],
+        )?;
+        record_annotation_change(&tx, &annotation, AnnotationAction::Delete, None, now)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Revert the document's most recent annotation change. Returns the
+    /// annotation as it is afterwards, or `None` when there is nothing to undo.
+    pub fn undo_annotation_change(
+        &self,
+        document_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Annotation>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let latest: Option<(i64, String, String, Option<String>)> = tx
+            .query_row(
+                "SELECT id, annotation_id, action, previous FROM annotation_history
+                 WHERE document_id = ?1 ORDER BY id DESC LIMIT 1",
+                params![document_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()?;
+        let Some((history_id, annotation_id, action, previous)) = latest else {
+            return Ok(None);
+        };
+
+        match AnnotationAction::parse(&action) {
+            Some(AnnotationAction::Create) => {
+                tx.execute(
+                    "UPDATE annotations SET deleted_at = ?2 WHERE id = ?1",
+                    params![annotation_id, timestamp(now)],
+                )?;
+            }
+            Some(AnnotationAction::Update) => {
+                let previous: Annotation =
+                    serde_json::from_str(previous.as_deref().unwrap_or_default()).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            3,
+                            rusqlite::types::Type::Text,
+                            Box::new(e),
+                        )
+                    })?;
+                write_annotation(&tx, &previous)?;
+            }
+            Some(AnnotationAction::Delete) => {
+                tx.execute(
+                    "UPDATE annotations SET deleted_at = NULL WHERE id = ?1",
+                    params![annotation_id],
+                )?;
+            }
+            None => {}
+        }
+        tx.execute(
+            "DELETE FROM annotation_history WHERE id = ?1",
+            params![history_id],
+        )?;
+        tx.commit()?;
+
+        self.annotation(&annotation_id)
+    }
+
+    /// Permanently remove a document's trashed annotations; their changes can
+    /// no longer be undone. Trashed annotations imported from the PDF are kept
+    /// while their history goes, so reopening the file does not bring them back.
+    pub fn purge_deleted_annotations(&self, document_id: &str) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM annotation_history WHERE annotation_id IN
+             (SELECT id FROM annotations WHERE document_id = ?1 AND deleted_at IS NOT NULL)",
+            params![document_id],
+        )?;
+        let purged = tx.execute(
+            "DELETE FROM annotations
+             WHERE document_id = ?1 AND deleted_at IS NOT NULL AND source_key IS NULL",
+            params![document_id],
+        )?;
+        tx.commit()?;
+        Ok(purged)
+    }
+
+    /// Store annotations read from a document's PDF. Ones imported before,
+    /// matched on their source key, are left alone so changes made here survive
+    /// reopening the file. Not recorded for undo. Returns how many were added.
+    pub fn import_pdf_annotations(&self, imported: &[PdfAnnotation]) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut added = 0;
+        for PdfAnnotation { annotation, .. } in imported {
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM annotations
+                               WHERE document_id = ?1 AND source_key = ?2)",
+                params![annotation.document_id, annotation.source_key],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                write_annotation(&tx, annotation)?;
+                added += 1;
+            }
+        }
+
+        // Parents may come after their replies, so link once all are stored
+        for PdfAnnotation {
+            annotation,
+            reply_to_key,
+            ..
+        } in imported
+        {
+            let Some(parent_key) = reply_to_key else {
+                continue;
+            };
+            tx.execute(
+                "UPDATE annotations SET reply_to =
+                     (SELECT id FROM annotations WHERE document_id = ?1 AND source_key = ?3)
+                 WHERE document_id = ?1 AND source_key = ?2 AND reply_to IS NULL",
+                params![annotation.document_id, annotation.source_key, parent_key],
+            )?;
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Store annotations read from an XFDF or FDF file in one transaction,
+    /// adding new ones and replacing the ones they update. Not recorded for
+    /// undo, so an import doesn't push the user's own changes out of history.
+    pub fn import_annotations(&self, annotations: &[Annotation]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for annotation in annotations {
+            write_annotation(&tx, annotation)?;
+        }
+        tx.commit()
+    }
+
+    /// Annotations whose text or author contains `query`, across the library or
+    /// within one document
+    pub fn search_annotations(
+        &self,
+        query: &str,
+        document_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<Annotation>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM annotations
+             WHERE deleted_at IS NULL
+               AND (?2 IS NULL OR document_id = ?2)
+               AND (instr(lower(coalesce(content, '')), lower(?1)) > 0
+                    OR instr(lower(coalesce(author, '')), lower(?1)) > 0)
+             ORDER BY document_id, page_number, created_at, id
+             LIMIT ?3",
+            ANNOTATION_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![query, document_id, limit], annotation_from_row)?;
+        rows.collect()
+    }
+
+    pub fn list_documents(&self, query: &DocumentQuery) -> Result<DocumentPage> {
+        let filter = "?1 IS NULL
+             OR instr(lower(title), lower(?1)) > 0
+             OR instr(lower(file_path), lower(?1)) > 0";
+        let search = query.search.as_deref().filter(|search| !search.is_empty());
+
+        let total: u32 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM documents WHERE {}", filter),
+            params![search],
+            |row| row.get(0),
+        )?;
+
+        let column = match query.sort {
+            DocumentSort::LastOpened => "last_opened_at",
+            DocumentSort::Title => "title COLLATE NOCASE",
+            DocumentSort::Created => "created_at",
+        };
+        let direction = if query.ascending { "ASC" } else { "DESC" };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM documents WHERE {} ORDER BY {} {}, id LIMIT ?2 OFFSET ?3",
+            DOCUMENT_COLUMNS, filter, column, direction
+        ))?;
+        let limit = query.limit.map(i64::from).unwrap_or(-1);
+        let documents = stmt
+            .query_map(params![search, limit, query.offset], document_from_row)?
+            .collect::<Result<_>>()?;
+
+        Ok(DocumentPage { documents, total })
+    }
+}
+
+/// Schedule timestamps are stored in a fixed-width UTC form so they compare as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Read an RFC 3339 timestamp column. A value that doesn't parse is a
+/// conversion error rather than a made-up time.
+fn timestamp_column(row: &Row, index: usize) -> Result<DateTime<Utc>> {
+    let value: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                Box::new(e),
+            )
+        })
+}
+
+fn optional_timestamp_column(row: &Row, index: usize) -> Result<Option<DateTime<Utc>>> {
+    match row.get_ref(index)? {
+        rusqlite::types::ValueRef::Null => Ok(None),
+        _ => timestamp_column(row, index).map(Some),
+    }
+}
+
+const DOCUMENT_COLUMNS: &str =
+    "id, file_path, file_hash, title, total_pages, created_at, updated_at, last_opened_at";
+
+fn document_from_row(row: &Row) -> Result<DocumentRecord> {
+    Ok(DocumentRecord {
+        id: row.get(0)?,
+        file_path: row.get(1)?,
+        file_hash: row.get(2)?,
+        title: row.get(3)?,
+        total_pages: row.get(4)?,
+        created_at: timestamp_column(row, 5)?,
+        updated_at: timestamp_column(row, 6)?,
+        last_opened_at: timestamp_column(row, 7)?,
+    })
+}
+
+const ANNOTATION_COLUMNS: &str = "id, document_id, page_number, position, content, color, \
+     created_at, updated_at, deleted_at, author, reply_to, source_key";
+
+fn annotation_from_row(row: &Row) -> Result<Annotation> {
+    let position: String = row.get(3)?;
+    let geometry = serde_json::from_str(&position).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(Annotation {
+        id: row.get(0)?,
+        document_id: row.get(1)?,
+        page_number: row.get(2)?,
+        geometry,
+        content: row.get(4)?,
+        color: row.get(5)?,
+        created_at: timestamp_column(row, 6)?,
+        updated_at: timestamp_column(row, 7)?,
+        deleted_at: optional_timestamp_column(row, 8)?,
+        author: row.get(9)?,
+        reply_to: row.get(10)?,
+        source_key: row.get(11)?,
+    })
+}
+
+/// Insert or overwrite an annotation row
+fn write_annotation(conn: &Connection, annotation: &Annotation) -> Result<()> {
+    let position = serde_json::to_string(&annotation.geometry)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO annotations
+         (id, document_id, page_number, type, content, color, position, created_at,
+          updated_at, deleted_at, author, reply_to, source_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+         ON CONFLICT(id) DO UPDATE SET
+             page_number = excluded.page_number,
+             type = excluded.type,
+             content = excluded.content,
+             color = excluded.color,
+             position = excluded.position,
+             updated_at = excluded.updated_at,
+             deleted_at = excluded.deleted_at",
+        params![
+            annotation.id,
+            annotation.document_id,
+            annotation.page_number,
+            annotation.geometry.kind(),
+            annotation.content,
+            annotation.color,
+            position,
+            timestamp(annotation.created_at),
+            timestamp(annotation.updated_at),
+            annotation.deleted_at.map(timestamp),
+            annotation.author,
+            annotation.reply_to,
+            annotation.source_key
+        ],
+    )?;
+    Ok(())
+}
+
+fn read_annotation(conn: &Connection, annotation_id: &str) -> Result<Option<Annotation>> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM annotations WHERE id = ?1",
+            ANNOTATION_COLUMNS
+        ),
+        params![annotation_id],
+        annotation_from_row,
+    )
+    .optional()
+}
+
+/// Append to the document's undo history, dropping the oldest entries past the limit
+fn record_annotation_change(
+    conn: &Connection,
+    annotation: &Annotation,
+    action: AnnotationAction,
+    previous: Option<&Annotation>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let previous = previous
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    conn.execute(
+        "INSERT INTO annotation_history (document_id, annotation_id, action, previous, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            annotation.document_id,
+            annotation.id,
+            action.as_str(),
+            previous,
+            timestamp(now)
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM annotation_history
+         WHERE document_id = ?1 AND id NOT IN
+             (SELECT id FROM annotation_history WHERE document_id = ?1
+              ORDER BY id DESC LIMIT ?2)",
+        params![annotation.document_id, ANNOTATION_HISTORY_LIMIT],
+    )?;
+    Ok(())
+}
+
+/// Read a `YYYY-MM-DD` day column, failing like `timestamp_column` on a bad value
+fn day_column(row: &Row, index: usize) -> Result<NaiveDate> {
+    let value: String = row.get(index)?;
+    value.parse().map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn session_from_row(row: &Row) -> Result<ReadingSession> {
+    Ok(ReadingSession {
+        id: row.get(0)?,
+        document_id: row.get(1)?,
+        day: day_column(row, 2)?,
+        started_at: timestamp_column(row, 3)?,
+        last_active_at: timestamp_column(row, 4)?,
+        active_seconds: row.get(5)?,
+    })
+}
+
+fn schedule_from_row(row: &Row, offset: usize) -> Result<ReviewSchedule> {
+    Ok(ReviewSchedule {
+        ease_factor: row.get(offset)?,
+        interval_days: row.get(offset + 1)?,
+        repetitions: row.get(offset + 2)?,
+        lapses: row.get(offset + 3)?,
+        due_at: timestamp_column(row, offset + 4)?,
+        last_reviewed_at: optional_timestamp_column(row, offset + 5)?,
+    })
+}
+
+fn due_word_from_row(row: &Row) -> Result<DuePracticeWord> {
+    let ease_factor: Option<f64> = row.get(7)?;
+    let schedule = match ease_factor {
+        Some(_) => schedule_from_row(row, 7)?,
+        None => ReviewSchedule::new_card(timestamp_column(row, 6)?),
+    };
+
+    Ok(DuePracticeWord {
+        id: row.get(0)?,
+        document_id: row.get(1)?,
+        word: row.get(2)?,
+        language: row.get(3)?,
+        page_number: row.get(4)?,
+        attempts: row.get(5)?,
+        is_new: ease_factor.is_none(),
+        schedule,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::annotations::{AnnotationGeometry, Point};
+    use chrono::Duration;
+
+    fn temp_db_path() -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("database-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        (dir.join(DATABASE_FILE_NAME), dir)
+    }
+
+    fn table_exists(db: &Database, name: &str) -> bool {
+        db.get_connection()
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                params![name],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn creates_latest_schema_without_backup() {
+        let (path, dir) = temp_db_path();
+        let db = Database::open(path.clone()).unwrap();
+
+        assert_eq!(db.schema_version().unwrap(), latest_schema_version());
+        assert!(table_exists(&db, "practice_schedule"));
+        assert!(table_exists(&db, "word_practice"));
+        assert!(!dir.join("library.db.v0.bak").exists());
+
+        // Reopening an up-to-date database changes nothing
+        drop(db);
+        let db = Database::open(path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), latest_schema_version());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn upgrades_unversioned_database_and_backs_it_up() {
+        let (path, dir) = temp_db_path();
+        {
+            // Schema as created before migrations existed, with user_version 0
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(MIGRATIONS[0].sql).unwrap();
+            conn.execute(
+                "INSERT INTO documents VALUES ('d1', '/book.pdf', 'abc', 'Book', 10,
+                 '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z')",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO practice_words
+                 (id, document_id, word, language, page_number, attempts, created_at)
+                 VALUES ('w1', 'd1', 'thought', 'en', 3, 2, '2024-01-01T00:00:00Z')",
+                [],
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(path).unwrap();
+        assert_eq!(db.schema_version().unwrap(), latest_schema_version());
+        assert!(table_exists(&db, "practice_sessions"));
+        let attempts: i64 = db
+            .get_connection()
+            .query_row(
+                "SELECT attempts FROM practice_words WHERE id = 'w1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(attempts, 2);
+
+        // One backup per step, each holding the schema before that step
+        let before_v1 = Connection::open(dir.join("library.db.v0.bak")).unwrap();
+        let version: u32 = before_v1
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, 0);
+        let backed_up: i64 = before_v1
+            .query_row("SELECT COUNT(*) FROM practice_words", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(backed_up, 1);
+        assert!(dir.join("library.db.v1.bak").exists());
+        assert!(dir.join("library.db.v2.bak").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn imports_practice_history_from_legacy_database_once() {
+        let (path, dir) = temp_db_path();
+        let legacy_path = dir.join(LEGACY_PRACTICE_DATABASE_FILE_NAME);
+        {
+            // As the frontend created it next to its config
+            let conn = Connection::open(&legacy_path).unwrap();
+            conn.execute_batch(MIGRATIONS[1].sql).unwrap();
+            conn.execute_batch(
+                "INSERT INTO practice_sessions
+                 (document_hash, page_number, text, accuracy, duration, timestamp)
+                 VALUES ('abc', 3, 'The cat sat', 0.9, 12, 1700000000000),
+                        ('abc', 4, 'On the mat', 0.7, 8, 1700000100000);
+                 INSERT INTO word_practice
+                 (word, document_hash, page_number, attempts, success_count,
+                  last_accuracy, average_accuracy, marked, last_practiced)
+                 VALUES ('thought', 'abc', 3, 4, 1, 0.5, 0.6, 1, 1700000000000);",
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(path.clone()).unwrap();
+        // A session recorded in the new database before the import is kept
+        db.get_connection()
+            .execute(
+                "INSERT INTO practice_sessions
+                 (document_hash, page_number, text, accuracy, duration, timestamp)
+                 VALUES ('abc', 3, 'The cat sat', 0.9, 12, 1700000000000)",
+                [],
+            )
+            .unwrap();
+        assert_eq!(db.import_legacy_practice(&legacy_path).unwrap(), 2);
+
+        let count = |table: &str| -> i64 {
+            db.get_connection()
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(count("practice_sessions"), 2);
+        assert_eq!(count("word_practice"), 1);
+        let (attempts, marked): (i64, bool) = db
+            .get_connection()
+            .query_row(
+                "SELECT attempts, marked FROM word_practice WHERE word = 'thought'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((attempts, marked), (4, true));
+
+        // The old file is retired, so the next start does not import again
+        assert!(!legacy_path.exists());
+        assert!(dir.join("practice.db.imported").exists());
+        assert_eq!(db.import_legacy_practice(&legacy_path).unwrap(), 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_due_date_is_an_error_not_due_now() {
+        let (path, dir) = temp_db_path();
+        let db = Database::open(path).unwrap();
+        db.get_connection()
+            .execute_batch(
+                "INSERT INTO documents VALUES ('d1', '/book.pdf', 'abc', 'Book', 10,
+                 '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z');
+                 INSERT INTO practice_words
+                 (id, document_id, word, language, page_number, attempts, created_at)
+                 VALUES ('w1', 'd1', 'thought', 'en', 3, 2, '2024-01-01T00:00:00Z');
+                 INSERT INTO practice_schedule VALUES ('w1', 2.5, 6, 2, 0, '03/01/2024', NULL);",
+            )
+            .unwrap();
+
+        let error = db.practice_schedule("w1").unwrap_err();
+        assert!(
+            matches!(error, rusqlite::Error::FromSqlConversionFailure(4, _, _)),
+            "unexpected error {:?}",
+            error
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_reading_day_is_an_error() {
+        let (path, dir) = temp_db_path();
+        let db = Database::open(path).unwrap();
+        db.get_connection()
+            .execute_batch(
+                "INSERT INTO documents VALUES ('d1', '/book.pdf', 'abc', 'Book', 10,
+                 '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z');
+                 INSERT INTO reading_sessions VALUES ('s1', 'd1', '03/01/2024',
+                 '2024-03-01T10:00:00Z', '2024-03-01T10:05:00Z', 300);",
+            )
+            .unwrap();
+
+        let day = |value: &str| value.parse::<NaiveDate>().unwrap();
+        let error = db
+            .reading_stats(day("2024-01-01"), day("2024-03-31"))
+            .unwrap_err();
+        assert!(
+            matches!(error, rusqlite::Error::FromSqlConversionFailure(0, _, _)),
+            "unexpected error {:?}",
+            error
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn practice_reviews_are_recorded_whole_and_read_strictly() {
+        let (path, dir) = temp_db_path();
+        let db = Database::open(path).unwrap();
+        db.get_connection()
+            .execute_batch(
+                "INSERT INTO documents VALUES ('d1', '/book.pdf', 'abc', 'Book', 10,
+                 '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z');
+                 INSERT INTO practice_words
+                 (id, document_id, word, language, page_number, attempts, created_at)
+                 VALUES ('w1', 'd1', 'thought', 'en', 3, 2, '2024-01-01T00:00:00Z');
+                 CREATE TEMP TRIGGER log_full BEFORE INSERT ON practice_reviews
+                 BEGIN SELECT RAISE(ABORT, 'log full'); END;",
+            )
+            .unwrap();
+        let now = "2024-03-01T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        // A review that can't be logged leaves the schedule as it was
+        assert!(db
+            .record_practice_review("w1", ReviewGrade::Good, now)
+            .is_err());
+        let schedule = db.practice_schedule("w1").unwrap();
+        assert_eq!(schedule.repetitions, 0);
+        assert_eq!(schedule.last_reviewed_at, None);
+
+        db.get_connection()
+            .execute_batch("DROP TRIGGER log_full")
+            .unwrap();
+        db.record_practice_review("w1", ReviewGrade::Good, now)
+            .unwrap();
+        assert_eq!(
+            db.practice_reviews("w1").unwrap()[0].grade,
+            ReviewGrade::Good
+        );
+
+        db.get_connection()
+            .execute("UPDATE practice_reviews SET grade = 'perfect'", [])
+            .unwrap();
+        let error = db.practice_reviews("w1").unwrap_err();
+        assert!(
+            matches!(error, rusqlite::Error::FromSqlConversionFailure(2, _, _)),
+            "unexpected error {:?}",
+            error
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn annotation_history_is_stamped_with_the_callers_time() {
+        let (path, dir) = temp_db_path();
+        let db = Database::open(path).unwrap();
+        let created = "2024-03-01T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        db.insert_document(&DocumentRecord {
+            id: "d1".to_string(),
+            file_path: "/book.pdf".to_string(),
+            file_hash: "abc".to_string(),
+            title: "Book".to_string(),
+            total_pages: 10,
+            created_at: created,
+            updated_at: created,
+            last_opened_at: created,
+        })
+        .unwrap();
+
+        let mut annotation = Annotation {
+            id: "a1".to_string(),
+            document_id: "d1".to_string(),
+            page_number: 1,
+            geometry: AnnotationGeometry::Note {
+                anchor: Point { x: 72.0, y: 720.0 },
+            },
+            content: Some("first".to_string()),
+            color: "yellow".to_string(),
+            created_at: created,
+            updated_at: created,
+            deleted_at: None,
+            author: None,
+            reply_to: None,
+            source_key: None,
+        };
+        db.insert_annotation(&annotation).unwrap();
+        annotation.content = Some("edited".to_string());
+        annotation.updated_at = created + Duration::hours(1);
+        assert!(db.update_annotation(&annotation).unwrap());
+        assert!(db
+            .delete_annotation("a1", created + Duration::hours(2))
+            .unwrap());
+
+        let mut missing = annotation.clone();
+        missing.id = "a2".to_string();
+        assert!(!db.update_annotation(&missing).unwrap());
+
+        let history: Vec<(String, String)> = db
+            .get_connection()
+            .prepare("SELECT action, created_at FROM annotation_history ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let expected: Vec<(String, String)> = [("create", 0), ("update", 1), ("delete", 2)]
+            .into_iter()
+            .map(|(action, hours)| {
+                (
+                    action.to_string(),
+                    timestamp(created + Duration::hours(hours)),
+                )
+            })
+            .collect();
+        assert_eq!(history, expected);
+        assert!(db.annotation("a2").unwrap().is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_downgrade() {
+        let (path, dir) = temp_db_path();
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", latest_schema_version() + 1)
+            .unwrap();
+
+        assert!(Database::open(path).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,244 @@
+use super::database::{Database, DocumentRecord};
+use super::pdf_text::load_pdf_document;
+use crate::utils::calculate_file_hash;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A file about to be registered, inspected outside the database lock
+#[derive(Debug, Clone)]
+pub struct DocumentFile {
+    pub path: PathBuf,
+    pub file_hash: String,
+    pub total_pages: i64,
+}
+
+impl DocumentFile {
+    /// Hash `path` and count its pages, unless the caller already knows the count
+    pub fn inspect(path: &Path, total_pages: Option<i64>) -> Result<Self, String> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+        let file_hash = calculate_file_hash(&path.to_string_lossy())
+            .map_err(|e| format!("Failed to hash {}: {}", path.display(), e))?;
+        let total_pages = match total_pages {
+            Some(total_pages) => total_pages,
+            None => load_pdf_document(&path)?.get_pages().len() as i64,
+        };
+
+        Ok(Self {
+            path,
+            file_hash,
+            total_pages,
+        })
+    }
+
+    fn path_string(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+/// A document as registered, with what was imported from its file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredDocument {
+    #[serde(flatten)]
+    pub document: DocumentRecord,
+    /// Annotations found in the PDF that were not imported before
+    pub imported_annotations: usize,
+    /// Why the PDF's annotations could not be read; the document is registered anyway
+    pub annotation_import_error: Option<String>,
+}
+
+/// Add an opened file to the library, or mark its existing entry opened.
+///
+/// A known path keeps its document even when the contents changed. Otherwise
+/// a document with the same contents whose file is gone is taken to have been
+/// moved or renamed, so its progress and annotations follow the file.
+pub fn register_document_file(
+    db: &Database,
+    file: &DocumentFile,
+) -> Result<DocumentRecord, String> {
+    let now = Utc::now();
+    let file_path = file.path_string();
+
+    let existing = match db.document_by_path(&file_path).map_err(|e| e.to_string())? {
+        Some(document) => Some(document),
+        None => db
+            .documents_by_hash(&file.file_hash)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|document| !Path::new(&document.file_path).exists()),
+    };
+
+    let id = match existing {
+        Some(document) => {
+            db.update_document_file(
+                &document.id,
+                &file_path,
+                &file.file_hash,
+                file.total_pages,
+                now,
+            )
+            .map_err(|e| format!("Failed to update document: {}", e))?;
+            document.id
+        }
+        None => {
+            let document = DocumentRecord {
+                id: uuid::Uuid::new_v4().to_string(),
+                file_path: file_path.clone(),
+                file_hash: file.file_hash.clone(),
+                title: default_title(&file.path),
+                total_pages: file.total_pages,
+                created_at: now,
+                updated_at: now,
+                last_opened_at: now,
+            };
+            db.insert_document(&document)
+                .map_err(|e| format!("Failed to register document: {}", e))?;
+            document.id
+        }
+    };
+
+    db.document(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Document not found: {}", id))
+}
+
+/// Point a document at the file it was moved to. The file must have the
+/// contents last seen, so progress and annotations still line up.
+pub fn relocate_document_file(
+    db: &Database,
+    document_id: &str,
+    file: &DocumentFile,
+) -> Result<DocumentRecord, String> {
+    let document = db
+        .document(document_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Document not found: {}", document_id))?;
+    if document.file_hash != file.file_hash {
+        return Err(format!(
+            "{} is not the same file as \"{}\"",
+            file.path.display(),
+            document.title
+        ));
+    }
+    let file_path = file.path_string();
+    if let Some(other) = db.document_by_path(&file_path).map_err(|e| e.to_string())? {
+        if other.id != document.id {
+            return Err(format!(
+                "{} already belongs to \"{}\"",
+                file.path.display(),
+                other.title
+            ));
+        }
+    }
+
+    db.update_document_file(
+        &document.id,
+        &file_path,
+        &file.file_hash,
+        file.total_pages,
+        Utc::now(),
+    )
+    .map_err(|e| format!("Failed to update document: {}", e))?;
+    db.document(&document.id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Document not found: {}", document.id))
+}
+
+fn default_title(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{DocumentQuery, DocumentSort, DATABASE_FILE_NAME};
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("document-library-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn register(db: &Database, path: &Path) -> DocumentRecord {
+        register_document_file(db, &DocumentFile::inspect(path, Some(10)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn follows_moved_files_and_keeps_copies_apart() {
+        let dir = temp_dir();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        let original = dir.join("Grammar.pdf");
+        std::fs::write(&original, "contents").unwrap();
+
+        let document = register(&db, &original);
+        assert_eq!(document.title, "Grammar");
+        assert_eq!(register(&db, &original).id, document.id);
+
+        // Edited in place: same document, new hash
+        std::fs::write(&original, "annotated contents").unwrap();
+        let edited = register(&db, &original);
+        assert_eq!(edited.id, document.id);
+        assert_ne!(edited.file_hash, document.file_hash);
+
+        // A copy next to the original is a separate document
+        let copy = dir.join("Copy.pdf");
+        std::fs::copy(&original, &copy).unwrap();
+        assert_ne!(register(&db, &copy).id, document.id);
+
+        // Once the original is gone, a file with its contents is the same document
+        let moved = dir.join("Renamed.pdf");
+        std::fs::rename(&original, &moved).unwrap();
+        let relocated = register(&db, &moved);
+        assert_eq!(relocated.id, document.id);
+        assert_eq!(
+            relocated.file_path,
+            moved.canonicalize().unwrap().to_string_lossy()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lists_searches_and_deletes_documents() {
+        let dir = temp_dir();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        let mut ids = Vec::new();
+        for (name, contents) in [("b.pdf", "1"), ("A.pdf", "2"), ("c.pdf", "3")] {
+            std::fs::write(dir.join(name), contents).unwrap();
+            ids.push(register(&db, &dir.join(name)).id);
+        }
+
+        let page = db
+            .list_documents(&DocumentQuery {
+                sort: DocumentSort::Title,
+                ascending: true,
+                offset: 1,
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.documents[0].title, "b");
+
+        let found = db
+            .list_documents(&DocumentQuery {
+                search: Some("C.PDF".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(found.total, 1);
+
+        assert!(db.delete_document(&ids[0]).unwrap());
+        assert!(db.document(&ids[0]).unwrap().is_none());
+        assert_eq!(
+            db.list_documents(&DocumentQuery::default()).unwrap().total,
+            2
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
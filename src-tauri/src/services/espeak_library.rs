@@ -0,0 +1,338 @@
+use super::tts_service::SpeechBoundary;
+use libloading::Library;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_short, c_uint, c_void, CString};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Shared library names tried when the espeak-ng library is not next to its executable
+const LIBRARY_NAMES: &[&str] = &[
+    "libespeak-ng.so.1",
+    "libespeak-ng.so",
+    "libespeak-ng.1.dylib",
+    "libespeak-ng.dylib",
+    "libespeak-ng.dll",
+];
+
+// Values from espeak-ng's speak_lib.h
+const AUDIO_OUTPUT_SYNCHRONOUS: c_int = 2;
+const INITIALIZE_DONT_EXIT: c_int = 0x8000;
+const EVENT_LIST_TERMINATED: c_int = 0;
+const EVENT_WORD: c_int = 1;
+const POS_CHARACTER: c_int = 1;
+const CHARS_UTF8: c_uint = 1;
+const PHONEMES: c_uint = 0x100;
+const END_PAUSE: c_uint = 0x1000;
+const PARAMETER_RATE: c_int = 1;
+const PARAMETER_PITCH: c_int = 3;
+
+/// `espeak_EVENT`
+#[repr(C)]
+struct EspeakEvent {
+    kind: c_int,
+    unique_identifier: c_uint,
+    /// 1-based character position in the input
+    text_position: c_int,
+    /// Characters in the word
+    length: c_int,
+    /// Milliseconds from the start of the synthesis
+    audio_position: c_int,
+    sample: c_int,
+    user_data: *mut c_void,
+    id: EspeakEventId,
+}
+
+#[repr(C)]
+union EspeakEventId {
+    number: c_int,
+    name: *const c_char,
+    string: [c_char; 8],
+}
+
+type SynthCallback = unsafe extern "C" fn(*mut c_short, c_int, *mut EspeakEvent) -> c_int;
+
+/// The loaded library. espeak-ng keeps global state, so calls are serialized.
+struct EspeakLibrary {
+    _library: Library,
+    sample_rate: u32,
+    set_voice_by_name: unsafe extern "C" fn(*const c_char) -> c_int,
+    set_parameter: unsafe extern "C" fn(c_int, c_int, c_int) -> c_int,
+    synth: unsafe extern "C" fn(
+        *const c_void,
+        usize,
+        c_uint,
+        c_int,
+        c_uint,
+        c_uint,
+        *mut c_uint,
+        *mut c_void,
+    ) -> c_int,
+}
+
+// The function pointers stay valid while `_library` is loaded
+unsafe impl Send for EspeakLibrary {}
+
+static LIBRARY: OnceLock<Option<Mutex<EspeakLibrary>>> = OnceLock::new();
+
+thread_local! {
+    /// Samples and word events from the synthesis running on this thread
+    static COLLECTED: RefCell<(Vec<f32>, Vec<WordEvent>)> =
+        const { RefCell::new((Vec::new(), Vec::new())) };
+}
+
+/// A word as espeak-ng reports it while speaking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WordEvent {
+    /// Character (not byte) index of the word in the input
+    pub char_index: usize,
+    pub char_length: usize,
+    /// Seconds from the start of the audio
+    pub start: f32,
+}
+
+/// Speech from the espeak-ng library with the word timings it reported
+pub struct EspeakSpeech {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+    pub words: Vec<WordEvent>,
+}
+
+/// Speak `text` through the espeak-ng library. `None` when the library can't
+/// be loaded, so the caller can run the command line instead.
+pub fn espeak_synthesize(
+    executable: Option<&Path>,
+    voice: &str,
+    text: &str,
+    words_per_minute: u32,
+    pitch: u32,
+) -> Option<Result<EspeakSpeech, String>> {
+    let library = LIBRARY
+        .get_or_init(|| EspeakLibrary::load(executable).map(Mutex::new))
+        .as_ref()?;
+    let library = match library.lock() {
+        Ok(library) => library,
+        Err(_) => return Some(Err("espeak-ng library lock was poisoned".to_string())),
+    };
+    Some(library.synthesize(voice, text, words_per_minute, pitch))
+}
+
+/// Boundaries from the word events of a synthesis of `text`, each word
+/// running until the next one starts
+pub fn word_event_boundaries(
+    text: &str,
+    words: &[WordEvent],
+    duration: f32,
+) -> Vec<SpeechBoundary> {
+    // UTF-16 offset of every character, plus the end of the text
+    let mut utf16_offsets = Vec::with_capacity(text.len() + 1);
+    let mut byte_offsets = Vec::with_capacity(text.len() + 1);
+    let mut offset = 0;
+    for (index, c) in text.char_indices() {
+        utf16_offsets.push(offset);
+        byte_offsets.push(index);
+        offset += c.len_utf16();
+    }
+    utf16_offsets.push(offset);
+    byte_offsets.push(text.len());
+    let last = utf16_offsets.len() - 1;
+
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| word.char_index < last)
+        .map(|(index, word)| {
+            let end_char = (word.char_index + word.char_length).min(last);
+            let end = words
+                .get(index + 1)
+                .map_or(duration, |next| next.start)
+                .max(word.start);
+            SpeechBoundary {
+                word: text[byte_offsets[word.char_index]..byte_offsets[end_char]].to_string(),
+                char_offset: utf16_offsets[word.char_index],
+                char_length: utf16_offsets[end_char] - utf16_offsets[word.char_index],
+                start: word.start,
+                end,
+                estimated: false,
+            }
+        })
+        .collect()
+}
+
+impl EspeakLibrary {
+    fn load(executable: Option<&Path>) -> Option<Self> {
+        // Windows installs the library next to the executable, Homebrew
+        // under the prefix's lib directory
+        let mut candidates: Vec<PathBuf> = Vec::new();
+        if let Some(bin_dir) = executable.and_then(Path::parent) {
+            for name in LIBRARY_NAMES {
+                candidates.push(bin_dir.join(name));
+                candidates.push(bin_dir.join("..").join("lib").join(name));
+            }
+        }
+        candidates.extend(LIBRARY_NAMES.iter().map(PathBuf::from));
+
+        let library = candidates.iter().find_map(|candidate| {
+            // SAFETY: loading espeak-ng runs no initialization code with
+            // preconditions of its own
+            unsafe { Library::new(candidate) }.ok()
+        })?;
+
+        // SAFETY: the signatures match speak_lib.h
+        unsafe {
+            let initialize = *library
+                .get::<unsafe extern "C" fn(c_int, c_int, *const c_char, c_int) -> c_int>(
+                    b"espeak_Initialize\0",
+                )
+                .ok()?;
+            let set_callback = *library
+                .get::<unsafe extern "C" fn(SynthCallback)>(b"espeak_SetSynthCallback\0")
+                .ok()?;
+            let set_voice_by_name = *library.get(b"espeak_SetVoiceByName\0").ok()?;
+            let set_parameter = *library.get(b"espeak_SetParameter\0").ok()?;
+            let synth = *library.get(b"espeak_Synth\0").ok()?;
+
+            let sample_rate = initialize(
+                AUDIO_OUTPUT_SYNCHRONOUS,
+                0,
+                std::ptr::null(),
+                INITIALIZE_DONT_EXIT,
+            );
+            if sample_rate <= 0 {
+                return None;
+            }
+            set_callback(collect_audio);
+
+            Some(Self {
+                _library: library,
+                sample_rate: sample_rate as u32,
+                set_voice_by_name,
+                set_parameter,
+                synth,
+            })
+        }
+    }
+
+    fn synthesize(
+        &self,
+        voice: &str,
+        text: &str,
+        words_per_minute: u32,
+        pitch: u32,
+    ) -> Result<EspeakSpeech, String> {
+        let voice_name =
+            CString::new(voice).map_err(|_| format!("Invalid espeak-ng voice: {}", voice))?;
+        let input = CString::new(text).map_err(|_| "Text contains a NUL character".to_string())?;
+        COLLECTED.with(|collected| *collected.borrow_mut() = Default::default());
+
+        // SAFETY: the strings outlive the calls, and synchronous output
+        // runs the callback on this thread before `espeak_Synth` returns
+        let status = unsafe {
+            if (self.set_voice_by_name)(voice_name.as_ptr()) != 0 {
+                return Err(format!("espeak-ng has no voice {}", voice));
+            }
+            (self.set_parameter)(PARAMETER_RATE, words_per_minute as c_int, 0);
+            (self.set_parameter)(PARAMETER_PITCH, pitch as c_int, 0);
+            (self.synth)(
+                input.as_ptr().cast(),
+                input.as_bytes_with_nul().len(),
+                0,
+                POS_CHARACTER,
+                0,
+                CHARS_UTF8 | PHONEMES | END_PAUSE,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+
+        let (samples, words) = COLLECTED.with(|collected| collected.take());
+        if status != 0 {
+            return Err(format!("espeak-ng failed with error {}", status));
+        }
+        Ok(EspeakSpeech {
+            sample_rate: self.sample_rate,
+            samples,
+            words,
+        })
+    }
+}
+
+/// `t_espeak_callback`: gathers audio and word events; returning 0 continues
+unsafe extern "C" fn collect_audio(
+    wav: *mut c_short,
+    count: c_int,
+    events: *mut EspeakEvent,
+) -> c_int {
+    COLLECTED.with(|collected| {
+        let (samples, words) = &mut *collected.borrow_mut();
+        if !wav.is_null() && count > 0 {
+            // SAFETY: espeak-ng passes `count` samples at `wav`
+            let wav = unsafe { std::slice::from_raw_parts(wav, count as usize) };
+            samples.extend(wav.iter().map(|&sample| sample as f32 / 32768.0));
+        }
+
+        let mut event = events;
+        // SAFETY: the event list ends with a LIST_TERMINATED entry
+        while !event.is_null() && unsafe { (*event).kind } != EVENT_LIST_TERMINATED {
+            let current = unsafe { &*event };
+            if current.kind == EVENT_WORD && current.text_position > 0 {
+                words.push(WordEvent {
+                    char_index: current.text_position as usize - 1,
+                    char_length: current.length.max(0) as usize,
+                    start: current.audio_position.max(0) as f32 / 1000.0,
+                });
+            }
+            event = unsafe { event.add(1) };
+        }
+    });
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_events_become_boundaries_with_utf16_offsets() {
+        let text = "Naïve 𝄞 café";
+        let words = [
+            WordEvent {
+                char_index: 0,
+                char_length: 5,
+                start: 0.05,
+            },
+            WordEvent {
+                char_index: 6,
+                char_length: 1,
+                start: 0.4,
+            },
+            WordEvent {
+                char_index: 8,
+                char_length: 4,
+                start: 0.6,
+            },
+        ];
+
+        let boundaries = word_event_boundaries(text, &words, 1.2);
+        let spans: Vec<(&str, usize, usize, f32, f32)> = boundaries
+            .iter()
+            .map(|b| {
+                (
+                    b.word.as_str(),
+                    b.char_offset,
+                    b.char_length,
+                    b.start,
+                    b.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("Naïve", 0, 5, 0.05, 0.4),
+                ("𝄞", 6, 2, 0.4, 0.6),
+                ("café", 9, 4, 0.6, 1.2),
+            ]
+        );
+        assert!(boundaries.iter().all(|boundary| !boundary.estimated));
+    }
+}
@@ -1,15 +1,7 @@
 use anyhow::{Result, anyhow};
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct APIKey {
-    pub service: String,
-    pub key: String,
-    pub created_at: String,
-}
-
 /// Keychain service for secure API key storage
 /// Mock implementation - in production, use OS keychain:
 /// - Windows: Windows Credential Manager
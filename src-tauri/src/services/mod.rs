@@ -1,13 +1,62 @@
+pub mod annotation_exchange;
+pub mod annotation_notes;
+pub mod annotations;
+pub mod audio_decoder;
 pub mod database;
+pub mod document_library;
 pub mod model_manager;
+pub mod model_download;
+pub mod model_import;
+pub mod model_integrity;
+pub mod model_recommendation;
+pub mod model_registry;
+pub mod model_storage;
 pub mod whisper_service;
+pub mod whisper_stream;
+pub mod vad;
+pub mod reading_aligner;
+pub mod phonemes;
+pub mod pronunciation_service;
+pub mod reading_progress;
+pub mod spaced_repetition;
+pub mod vocabulary_export;
+pub mod ssml;
+pub mod espeak_library;
+pub mod tts_service;
+pub mod tts_stream;
+pub mod pdf_annotations;
+pub mod pdf_text;
+pub mod audiobook_service;
 pub mod translation_service;
 pub mod ocr_service;
 pub mod keychain_service;
 
+pub use annotation_exchange::*;
+pub use annotation_notes::*;
+pub use annotations::*;
+pub use audio_decoder::*;
 pub use database::*;
+pub use document_library::*;
 pub use model_manager::*;
+pub use model_import::*;
+pub use model_integrity::*;
+pub use model_recommendation::*;
+pub use model_registry::*;
+pub use model_storage::*;
 pub use whisper_service::*;
+pub use whisper_stream::*;
+pub use reading_aligner::*;
+pub use phonemes::*;
+pub use pronunciation_service::*;
+pub use reading_progress::*;
+pub use spaced_repetition::*;
+pub use vocabulary_export::*;
+pub use ssml::*;
+pub use tts_service::*;
+pub use tts_stream::*;
+pub use pdf_annotations::*;
+pub use pdf_text::*;
+pub use audiobook_service::*;
 pub use translation_service::*;
 pub use ocr_service::*;
 pub use keychain_service::*;
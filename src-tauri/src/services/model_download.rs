@@ -0,0 +1,737 @@
+use super::model_manager::{DownloadProgress, DownloadStatus};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Data is downloaded to `<file>.part` and renamed into place once complete
+pub const PARTIAL_DOWNLOAD_SUFFIX: &str = ".part";
+/// Sidecar next to the partial file recording what it is part of
+pub const PARTIAL_STATE_SUFFIX: &str = ".part.json";
+
+/// Minimum time between progress callbacks
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+/// Shared flag a download checks between reads
+#[derive(Debug, Clone, Default)]
+pub struct DownloadControl(Arc<AtomicU8>);
+
+impl DownloadControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop and keep the partial file so the download can resume later
+    pub fn pause(&self) {
+        self.0.store(PAUSED, Ordering::Relaxed);
+    }
+
+    /// Stop and delete the partial file
+    pub fn cancel(&self) {
+        self.0.store(CANCELLED, Ordering::Relaxed);
+    }
+
+    fn state(&self) -> u8 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadOutcome {
+    Completed,
+    Paused,
+    Cancelled,
+}
+
+/// What the partial file belongs to, so a resumed request can ask the
+/// server to send the rest only if the file has not changed
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PartialState {
+    url: String,
+    /// Strong ETag or Last-Modified of the response the data came from
+    validator: Option<String>,
+    total: Option<u64>,
+}
+
+pub fn partial_path(destination: &Path) -> PathBuf {
+    with_suffix(destination, PARTIAL_DOWNLOAD_SUFFIX)
+}
+
+/// Progress of an interrupted download of `destination`, if any
+pub fn partial_download(destination: &Path) -> Option<DownloadProgress> {
+    let downloaded = std::fs::metadata(partial_path(destination)).ok()?.len();
+    let total = read_partial_state(destination)
+        .and_then(|state| state.total)
+        .unwrap_or(0);
+    Some(progress(
+        destination,
+        downloaded,
+        total,
+        DownloadStatus::Paused,
+    ))
+}
+
+/// Delete the partial file and its sidecar
+pub fn discard_partial_download(destination: &Path) -> Result<(), String> {
+    for path in [
+        partial_path(destination),
+        with_suffix(destination, PARTIAL_STATE_SUFFIX),
+    ] {
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove partial download: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Download `url` to `destination`, resuming from an earlier partial file when
+/// the server supports range requests. Data goes to `<destination>.part`,
+/// which is renamed into place only once every byte has arrived. On error the
+/// partial file is kept so a later call can pick up where this one stopped.
+pub fn download_file(
+    url: &str,
+    destination: &Path,
+    control: &DownloadControl,
+    on_progress: &mut dyn FnMut(&DownloadProgress),
+) -> Result<DownloadOutcome, String> {
+    let part_path = partial_path(destination);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(30))
+        .timeout_read(Duration::from_secs(60))
+        .build();
+
+    // A second pass is only needed when the partial file turns out to be unusable
+    for _ in 0..2 {
+        let offset = std::fs::metadata(&part_path).map(|m| m.len()).unwrap_or(0);
+        let state = read_partial_state(destination).filter(|state| state.url == url);
+        if offset > 0 && state.is_none() {
+            discard_partial_download(destination)?;
+            continue;
+        }
+
+        let mut request = agent.get(url);
+        if offset > 0 {
+            request = request.set("Range", &format!("bytes={}-", offset));
+            if let Some(validator) = state.as_ref().and_then(|s| s.validator.as_deref()) {
+                request = request.set("If-Range", validator);
+            }
+        }
+
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(416, response)) => {
+                // Nothing left to send: the partial file may already be complete
+                let total = response
+                    .header("Content-Range")
+                    .and_then(content_range_total)
+                    .or_else(|| state.as_ref().and_then(|s| s.total));
+                if total == Some(offset) {
+                    finish(destination, &part_path)?;
+                    on_progress(&progress(
+                        destination,
+                        offset,
+                        offset,
+                        DownloadStatus::Completed,
+                    ));
+                    return Ok(DownloadOutcome::Completed);
+                }
+                discard_partial_download(destination)?;
+                continue;
+            }
+            Err(ureq::Error::Status(code, _)) => {
+                return Err(format!("Download failed: HTTP {}", code));
+            }
+            Err(e) => return Err(format!("Download failed: {}", e)),
+        };
+
+        let resumed = response.status() == 206;
+        let (mut downloaded, total) = if resumed {
+            let range = response.header("Content-Range").and_then(content_range);
+            match range {
+                Some((start, total)) if start == offset => (offset, total),
+                // The server sent a different range than asked for; start over
+                _ => {
+                    discard_partial_download(destination)?;
+                    continue;
+                }
+            }
+        } else {
+            let length = response
+                .header("Content-Length")
+                .and_then(|value| value.parse().ok());
+            (0, length)
+        };
+
+        write_partial_state(
+            destination,
+            &PartialState {
+                url: url.to_string(),
+                validator: validator(&response),
+                total,
+            },
+        )?;
+
+        let mut file = if resumed {
+            OpenOptions::new().append(true).open(&part_path)
+        } else {
+            File::create(&part_path)
+        }
+        .map_err(|e| format!("Failed to open download file: {}", e))?;
+
+        let total_or_zero = total.unwrap_or(0);
+        on_progress(&progress(
+            destination,
+            downloaded,
+            total_or_zero,
+            DownloadStatus::Downloading,
+        ));
+
+        let mut reader = response.into_reader();
+        let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+        let mut last_report = Instant::now();
+        loop {
+            match control.state() {
+                RUNNING => {}
+                PAUSED => {
+                    file.flush()
+                        .map_err(|e| format!("Failed to write download: {}", e))?;
+                    on_progress(&progress(
+                        destination,
+                        downloaded,
+                        total_or_zero,
+                        DownloadStatus::Paused,
+                    ));
+                    return Ok(DownloadOutcome::Paused);
+                }
+                _ => {
+                    drop(file);
+                    discard_partial_download(destination)?;
+                    on_progress(&progress(
+                        destination,
+                        downloaded,
+                        total_or_zero,
+                        DownloadStatus::Cancelled,
+                    ));
+                    return Ok(DownloadOutcome::Cancelled);
+                }
+            }
+
+            let read = reader
+                .read(&mut buffer)
+                .map_err(|e| format!("Download interrupted: {}", e))?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read])
+                .map_err(|e| format!("Failed to write download: {}", e))?;
+            downloaded += read as u64;
+
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                last_report = Instant::now();
+                on_progress(&progress(
+                    destination,
+                    downloaded,
+                    total_or_zero,
+                    DownloadStatus::Downloading,
+                ));
+            }
+        }
+
+        if total.is_some_and(|total| downloaded != total) {
+            return Err(format!(
+                "Download ended early after {} of {} bytes",
+                downloaded, total_or_zero
+            ));
+        }
+
+        file.sync_all()
+            .map_err(|e| format!("Failed to write download: {}", e))?;
+        drop(file);
+        finish(destination, &part_path)?;
+        on_progress(&progress(
+            destination,
+            downloaded,
+            total.unwrap_or(downloaded),
+            DownloadStatus::Completed,
+        ));
+        return Ok(DownloadOutcome::Completed);
+    }
+
+    Err("Download failed: the server keeps rejecting the resumed request".to_string())
+}
+
+/// SHA-256 and size a host publishes for `url` without sending the file.
+/// Hugging Face answers `resolve` URLs of LFS files with a redirect carrying
+/// them as `X-Linked-Etag` and `X-Linked-Size`.
+pub fn published_checksum(url: &str) -> (Option<String>, Option<u64>) {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(30))
+        .redirects(0)
+        .build();
+    let response = match agent.head(url).call() {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(_) => return (None, None),
+    };
+
+    let sha256 = response
+        .header("X-Linked-Etag")
+        .map(|etag| etag.trim_start_matches("W/").trim_matches('"').to_lowercase())
+        .filter(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()));
+    let size = response
+        .header("X-Linked-Size")
+        .and_then(|size| size.trim().parse().ok());
+    (sha256, size)
+}
+
+fn finish(destination: &Path, part_path: &Path) -> Result<(), String> {
+    std::fs::rename(part_path, destination)
+        .map_err(|e| format!("Failed to move download into place: {}", e))?;
+    let _ = std::fs::remove_file(with_suffix(destination, PARTIAL_STATE_SUFFIX));
+    Ok(())
+}
+
+fn progress(
+    destination: &Path,
+    downloaded: u64,
+    total: u64,
+    status: DownloadStatus,
+) -> DownloadProgress {
+    DownloadProgress {
+        file_name: destination
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        downloaded,
+        total,
+        percentage: if total > 0 {
+            downloaded as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        },
+        status,
+        error: None,
+    }
+}
+
+/// `If-Range` needs a strong ETag or a date
+fn validator(response: &ureq::Response) -> Option<String> {
+    response
+        .header("ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| response.header("Last-Modified"))
+        .map(str::to_string)
+}
+
+/// Start offset and total size from `Content-Range: bytes 100-199/1000`
+fn content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (span, total) = range.split_once('/')?;
+    let start = span.split_once('-')?.0.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
+
+/// Total size from `Content-Range: bytes */1000`
+fn content_range_total(value: &str) -> Option<u64> {
+    value.trim().rsplit_once('/')?.1.trim().parse().ok()
+}
+
+fn read_partial_state(destination: &Path) -> Option<PartialState> {
+    let content = std::fs::read_to_string(with_suffix(destination, PARTIAL_STATE_SUFFIX)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_partial_state(destination: &Path, state: &PartialState) -> Result<(), String> {
+    let json = serde_json::to_string(state).map_err(|e| e.to_string())?;
+    std::fs::write(with_suffix(destination, PARTIAL_STATE_SUFFIX), json)
+        .map_err(|e| format!("Failed to save download state: {}", e))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::BufRead;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Mutex;
+
+    const ETAG: &str = "\"v1\"";
+
+    /// Minimal HTTP/1.1 file server standing in for the model host
+    struct TestServer {
+        url: String,
+        requests: Arc<Mutex<Vec<HashMap<String, String>>>>,
+    }
+
+    struct ServerOptions {
+        support_ranges: bool,
+        chunk_delay: Duration,
+    }
+
+    impl TestServer {
+        fn start(body: Vec<u8>, options: ServerOptions) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/model.bin", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let body = Arc::new(body);
+            let options = Arc::new(options);
+
+            let server_requests = requests.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let body = body.clone();
+                    let options = options.clone();
+                    let requests = server_requests.clone();
+                    std::thread::spawn(move || {
+                        let _ = serve(stream, &body, &options, &requests);
+                    });
+                }
+            });
+
+            Self { url, requests }
+        }
+
+        fn requests(&self) -> Vec<HashMap<String, String>> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn serve(
+        mut stream: TcpStream,
+        body: &[u8],
+        options: &ServerOptions,
+        requests: &Mutex<Vec<HashMap<String, String>>>,
+    ) -> std::io::Result<()> {
+        let mut reader = std::io::BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("/")
+            .to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+        requests.lock().unwrap().push(headers.clone());
+
+        if path != "/model.bin" {
+            return stream.write_all(
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+        }
+
+        let range_start = headers
+            .get("range")
+            .filter(|_| options.support_ranges)
+            .filter(|_| headers.get("if-range").is_none_or(|tag| tag == ETAG))
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+
+        let (status, start) = match range_start {
+            Some(start) if start >= body.len() => {
+                let head = format!(
+                    "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                return stream.write_all(head.as_bytes());
+            }
+            Some(start) => ("206 Partial Content", start),
+            None => ("200 OK", 0),
+        };
+
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nETag: {}\r\nConnection: close\r\n",
+            status,
+            body.len() - start,
+            ETAG
+        );
+        if start > 0 {
+            head.push_str(&format!(
+                "Content-Range: bytes {}-{}/{}\r\n",
+                start,
+                body.len() - 1,
+                body.len()
+            ));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+
+        for chunk in body[start..].chunks(8 * 1024) {
+            stream.write_all(chunk)?;
+            std::thread::sleep(options.chunk_delay);
+        }
+        Ok(())
+    }
+
+    fn test_body(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn temp_destination(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("model-download-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    fn fast() -> ServerOptions {
+        ServerOptions {
+            support_ranges: true,
+            chunk_delay: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn downloads_into_place_and_reports_completion() {
+        let body = test_body(300_000);
+        let server = TestServer::start(body.clone(), fast());
+        let destination = temp_destination("model.bin");
+
+        let mut statuses = Vec::new();
+        let outcome = download_file(
+            &server.url,
+            &destination,
+            &DownloadControl::new(),
+            &mut |p| statuses.push((p.status.clone(), p.downloaded, p.total)),
+        )
+        .unwrap();
+
+        assert_eq!(outcome, DownloadOutcome::Completed);
+        assert_eq!(std::fs::read(&destination).unwrap(), body);
+        assert!(!partial_path(&destination).exists());
+        assert!(partial_download(&destination).is_none());
+        let last = statuses.last().unwrap();
+        assert!(matches!(last.0, DownloadStatus::Completed));
+        assert_eq!((last.1, last.2), (300_000, 300_000));
+    }
+
+    #[test]
+    fn resumes_partial_file_with_range_request() {
+        let body = test_body(200_000);
+        let server = TestServer::start(body.clone(), fast());
+        let destination = temp_destination("model.bin");
+        std::fs::write(partial_path(&destination), &body[..50_000]).unwrap();
+        write_partial_state(
+            &destination,
+            &PartialState {
+                url: server.url.clone(),
+                validator: Some(ETAG.to_string()),
+                total: Some(200_000),
+            },
+        )
+        .unwrap();
+
+        let mut first_downloaded = None;
+        download_file(
+            &server.url,
+            &destination,
+            &DownloadControl::new(),
+            &mut |p| {
+                first_downloaded.get_or_insert(p.downloaded);
+            },
+        )
+        .unwrap();
+
+        assert_eq!(std::fs::read(&destination).unwrap(), body);
+        assert_eq!(first_downloaded, Some(50_000));
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].get("range").map(String::as_str),
+            Some("bytes=50000-")
+        );
+        assert_eq!(requests[0].get("if-range").map(String::as_str), Some(ETAG));
+    }
+
+    #[test]
+    fn restarts_when_server_ignores_range() {
+        let body = test_body(120_000);
+        let server = TestServer::start(
+            body.clone(),
+            ServerOptions {
+                support_ranges: false,
+                chunk_delay: Duration::ZERO,
+            },
+        );
+        let destination = temp_destination("model.bin");
+        std::fs::write(partial_path(&destination), &body[..40_000]).unwrap();
+        write_partial_state(
+            &destination,
+            &PartialState {
+                url: server.url.clone(),
+                validator: Some(ETAG.to_string()),
+                total: Some(120_000),
+            },
+        )
+        .unwrap();
+
+        download_file(
+            &server.url,
+            &destination,
+            &DownloadControl::new(),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), body);
+    }
+
+    #[test]
+    fn restarts_when_file_changed_on_server() {
+        let body = test_body(100_000);
+        let server = TestServer::start(body.clone(), fast());
+        let destination = temp_destination("model.bin");
+        std::fs::write(partial_path(&destination), vec![0xAA; 30_000]).unwrap();
+        write_partial_state(
+            &destination,
+            &PartialState {
+                url: server.url.clone(),
+                validator: Some("\"stale\"".to_string()),
+                total: Some(100_000),
+            },
+        )
+        .unwrap();
+
+        download_file(
+            &server.url,
+            &destination,
+            &DownloadControl::new(),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), body);
+    }
+
+    #[test]
+    fn completes_when_partial_file_already_has_everything() {
+        let body = test_body(64_000);
+        let server = TestServer::start(body.clone(), fast());
+        let destination = temp_destination("model.bin");
+        std::fs::write(partial_path(&destination), &body).unwrap();
+        write_partial_state(
+            &destination,
+            &PartialState {
+                url: server.url.clone(),
+                validator: Some(ETAG.to_string()),
+                total: Some(64_000),
+            },
+        )
+        .unwrap();
+
+        let outcome = download_file(
+            &server.url,
+            &destination,
+            &DownloadControl::new(),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(outcome, DownloadOutcome::Completed);
+        assert_eq!(std::fs::read(&destination).unwrap(), body);
+    }
+
+    #[test]
+    fn pause_keeps_partial_file_and_resume_finishes() {
+        let body = test_body(400_000);
+        let server = TestServer::start(
+            body.clone(),
+            ServerOptions {
+                support_ranges: true,
+                chunk_delay: Duration::from_millis(5),
+            },
+        );
+        let destination = temp_destination("model.bin");
+
+        let control = DownloadControl::new();
+        let worker_control = control.clone();
+        let (url, worker_destination) = (server.url.clone(), destination.clone());
+        let worker = std::thread::spawn(move || {
+            download_file(&url, &worker_destination, &worker_control, &mut |_| {})
+        });
+        while std::fs::metadata(partial_path(&destination)).map_or(0, |m| m.len()) < 50_000 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        control.pause();
+
+        assert_eq!(worker.join().unwrap().unwrap(), DownloadOutcome::Paused);
+        assert!(!destination.exists());
+        let paused = partial_download(&destination).unwrap();
+        assert!(matches!(paused.status, DownloadStatus::Paused));
+        assert!(paused.downloaded > 0 && paused.downloaded < 400_000);
+        assert_eq!(paused.total, 400_000);
+
+        let outcome = download_file(
+            &server.url,
+            &destination,
+            &DownloadControl::new(),
+            &mut |_| {},
+        )
+        .unwrap();
+        assert_eq!(outcome, DownloadOutcome::Completed);
+        assert_eq!(std::fs::read(&destination).unwrap(), body);
+        assert_eq!(
+            server.requests()[1].get("range"),
+            Some(&format!("bytes={}-", paused.downloaded))
+        );
+    }
+
+    #[test]
+    fn cancel_removes_partial_file() {
+        let server = TestServer::start(
+            test_body(400_000),
+            ServerOptions {
+                support_ranges: true,
+                chunk_delay: Duration::from_millis(5),
+            },
+        );
+        let destination = temp_destination("model.bin");
+
+        let control = DownloadControl::new();
+        let worker_control = control.clone();
+        let (url, worker_destination) = (server.url.clone(), destination.clone());
+        let worker = std::thread::spawn(move || {
+            download_file(&url, &worker_destination, &worker_control, &mut |_| {})
+        });
+        while !partial_path(&destination).exists() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        control.cancel();
+
+        assert_eq!(worker.join().unwrap().unwrap(), DownloadOutcome::Cancelled);
+        assert!(!destination.exists());
+        assert!(partial_download(&destination).is_none());
+    }
+
+    #[test]
+    fn reports_http_errors() {
+        let server = TestServer::start(test_body(10), fast());
+        let destination = temp_destination("model.bin");
+        let url = server.url.replace("model.bin", "missing.bin");
+
+        let error =
+            download_file(&url, &destination, &DownloadControl::new(), &mut |_| {}).unwrap_err();
+        assert!(error.contains("404"), "{}", error);
+        assert!(!destination.exists());
+    }
+}
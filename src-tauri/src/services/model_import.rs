@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Read-only directories searched for models after the models directory
+const SEARCH_DIRS_FILE_NAME: &str = ".search_dirs.json";
+/// Suffix of a copy that is still being written
+pub const IMPORT_SUFFIX: &str = ".import";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    Copy,
+    /// Falls back to copy and delete across file systems
+    Move,
+    /// Link to the file where it is; it must stay there
+    Symlink,
+}
+
+/// Configured search directories, in lookup order
+pub fn read_search_dirs(models_dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(models_dir.join(SEARCH_DIRS_FILE_NAME))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Replace the search directories. Each must be an existing directory when
+/// configured; one that later goes missing (e.g. an unmounted share) is skipped.
+pub fn write_search_dirs(models_dir: &Path, dirs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut search_dirs: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !dir.is_dir() {
+            return Err(format!("Not a directory: {}", dir.display()));
+        }
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", dir.display(), e))?;
+        let is_models_dir = models_dir
+            .canonicalize()
+            .is_ok_and(|models_dir| models_dir == dir);
+        if !is_models_dir && !search_dirs.contains(&dir) {
+            search_dirs.push(dir);
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&search_dirs).map_err(|e| e.to_string())?;
+    std::fs::write(models_dir.join(SEARCH_DIRS_FILE_NAME), json)
+        .map_err(|e| format!("Failed to save model search directories: {}", e))?;
+    Ok(search_dirs)
+}
+
+/// First search directory holding `file_name`
+pub fn find_in_search_dirs(models_dir: &Path, file_name: &str) -> Option<PathBuf> {
+    read_search_dirs(models_dir)
+        .into_iter()
+        .map(|dir| dir.join(file_name))
+        .find(|path| path.is_file())
+}
+
+/// Bring `source` into the models directory as `file_name`
+pub fn import_model_file(
+    models_dir: &Path,
+    source: &Path,
+    file_name: &str,
+    mode: ImportMode,
+) -> Result<PathBuf, String> {
+    if !source.is_file() {
+        return Err(format!("Not a file: {}", source.display()));
+    }
+    let destination = models_dir.join(file_name);
+    // `symlink_metadata` also catches dangling links
+    if destination.symlink_metadata().is_ok() {
+        return Err(format!("{} is already installed", file_name));
+    }
+
+    match mode {
+        ImportMode::Copy => copy_into(source, &destination)?,
+        ImportMode::Move => {
+            if std::fs::rename(source, &destination).is_err() {
+                copy_into(source, &destination)?;
+                std::fs::remove_file(source)
+                    .map_err(|e| format!("Failed to remove {}: {}", source.display(), e))?;
+            }
+        }
+        ImportMode::Symlink => {
+            let target = source
+                .canonicalize()
+                .map_err(|e| format!("Failed to resolve {}: {}", source.display(), e))?;
+            #[cfg(unix)]
+            let linked = std::os::unix::fs::symlink(&target, &destination);
+            #[cfg(windows)]
+            let linked = std::os::windows::fs::symlink_file(&target, &destination);
+            linked.map_err(|e| format!("Failed to link model: {}", e))?;
+        }
+    }
+
+    Ok(destination)
+}
+
+/// Take back an import: a moved file goes back to `source`, a copy or link
+/// is removed
+pub fn undo_import(source: &Path, destination: &Path, mode: ImportMode) -> Result<(), String> {
+    if mode == ImportMode::Move && std::fs::rename(destination, source).is_err() {
+        std::fs::copy(destination, source)
+            .map_err(|e| format!("Failed to restore {}: {}", source.display(), e))?;
+    }
+    if destination.symlink_metadata().is_ok() {
+        std::fs::remove_file(destination)
+            .map_err(|e| format!("Failed to remove {}: {}", destination.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Copy under a temporary name so an interrupted copy is never mistaken for a model
+fn copy_into(source: &Path, destination: &Path) -> Result<(), String> {
+    let mut temp_path = destination.as_os_str().to_owned();
+    temp_path.push(IMPORT_SUFFIX);
+    let temp_path = PathBuf::from(temp_path);
+
+    let copied =
+        std::fs::copy(source, &temp_path).and_then(|_| std::fs::rename(&temp_path, destination));
+    if let Err(e) = copied {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(format!("Failed to copy model: {}", e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn imports_by_copy_move_and_symlink() {
+        let models_dir = temp_dir("model-import");
+        let source_dir = temp_dir("model-import-source");
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            std::fs::write(source_dir.join(name), name).unwrap();
+        }
+
+        import_model_file(
+            &models_dir,
+            &source_dir.join("a.bin"),
+            "a.bin",
+            ImportMode::Copy,
+        )
+        .unwrap();
+        assert!(source_dir.join("a.bin").exists());
+
+        import_model_file(
+            &models_dir,
+            &source_dir.join("b.bin"),
+            "b.bin",
+            ImportMode::Move,
+        )
+        .unwrap();
+        assert!(!source_dir.join("b.bin").exists());
+
+        let linked = import_model_file(
+            &models_dir,
+            &source_dir.join("c.bin"),
+            "c.bin",
+            ImportMode::Symlink,
+        )
+        .unwrap();
+        assert!(linked.symlink_metadata().unwrap().file_type().is_symlink());
+
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            assert_eq!(
+                std::fs::read_to_string(models_dir.join(name)).unwrap(),
+                name
+            );
+        }
+        assert!(import_model_file(
+            &models_dir,
+            &source_dir.join("a.bin"),
+            "a.bin",
+            ImportMode::Copy
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(models_dir).unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[test]
+    fn undoing_a_move_puts_the_file_back() {
+        let models_dir = temp_dir("model-import");
+        let source_dir = temp_dir("model-import-source");
+        let (source, copied) = (source_dir.join("a.bin"), source_dir.join("b.bin"));
+        std::fs::write(&source, "a").unwrap();
+        std::fs::write(&copied, "b").unwrap();
+
+        let destination =
+            import_model_file(&models_dir, &source, "a.bin", ImportMode::Move).unwrap();
+        undo_import(&source, &destination, ImportMode::Move).unwrap();
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "a");
+        assert!(!destination.exists());
+
+        let destination =
+            import_model_file(&models_dir, &copied, "b.bin", ImportMode::Copy).unwrap();
+        undo_import(&copied, &destination, ImportMode::Copy).unwrap();
+        assert!(copied.exists());
+        assert!(!destination.exists());
+
+        std::fs::remove_dir_all(models_dir).unwrap();
+        std::fs::remove_dir_all(source_dir).unwrap();
+    }
+
+    #[test]
+    fn finds_models_in_search_dirs() {
+        let models_dir = temp_dir("model-import");
+        let shared = temp_dir("model-import-shared");
+        std::fs::write(shared.join("ggml-base.bin"), "model").unwrap();
+
+        assert!(write_search_dirs(&models_dir, &[shared.join("missing")]).is_err());
+        let dirs = write_search_dirs(&models_dir, &[shared.clone(), shared.clone()]).unwrap();
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(read_search_dirs(&models_dir), dirs);
+
+        assert_eq!(
+            find_in_search_dirs(&models_dir, "ggml-base.bin"),
+            Some(dirs[0].join("ggml-base.bin"))
+        );
+        assert_eq!(find_in_search_dirs(&models_dir, "ggml-tiny.bin"), None);
+
+        std::fs::remove_dir_all(models_dir).unwrap();
+        std::fs::remove_dir_all(shared).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+use super::model_manager::ModelInfo;
+use crate::utils::calculate_file_hash;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// Verification records for the files in the models directory
+const RECORDS_FILE_NAME: &str = ".verification.json";
+/// Subdirectory of the models directory that corrupt files are moved to
+pub const QUARANTINE_DIR_NAME: &str = "quarantine";
+
+/// Serializes read-modify-write of the records file across download threads
+static RECORDS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationState {
+    /// SHA-256 matched and the file has not changed since
+    Verified,
+    /// No checksum is known for the file, or it has not been checked yet
+    Unverified,
+    /// Changed on disk since it was verified
+    Modified,
+    /// Size or checksum does not match what was published
+    Corrupt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelVerification {
+    pub file_name: String,
+    pub state: VerificationState,
+    pub size: u64,
+    pub expected_size: Option<u64>,
+    pub sha256: Option<String>,
+    pub expected_sha256: Option<String>,
+    /// Where a corrupt file was moved to
+    pub quarantined_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct VerificationRecord {
+    /// Published by the host at download time; catalog values take precedence
+    expected_sha256: Option<String>,
+    expected_size: Option<u64>,
+    /// Size and modification time of the file when its checksum last matched
+    verified_size: Option<u64>,
+    verified_modified: Option<u64>,
+    verified_at: Option<String>,
+}
+
+/// Remember the checksum a host published for a file it is serving
+pub fn record_published_checksum(
+    models_dir: &Path,
+    file_name: &str,
+    sha256: Option<String>,
+    size: Option<u64>,
+) -> Result<(), String> {
+    update_records(models_dir, |records| {
+        let record = records.entry(file_name.to_string()).or_default();
+        record.expected_sha256 = sha256.map(|hash| hash.to_lowercase());
+        record.expected_size = size;
+        record.verified_size = None;
+        record.verified_modified = None;
+        record.verified_at = None;
+    })
+}
+
+/// Cheap state from the size and the last verification, without hashing.
+/// `path` is the model file, in the models directory or a search directory.
+pub fn verification_state(
+    models_dir: &Path,
+    path: &Path,
+    catalog: Option<&ModelInfo>,
+) -> Result<VerificationState, String> {
+    let file_name = record_key(path)?;
+    let (size, modified) = file_stamp(path)?;
+    let _guard = RECORDS_LOCK.lock().map_err(|e| e.to_string())?;
+    let record = read_records(models_dir)
+        .remove(file_name)
+        .unwrap_or_default();
+    let (_, expected_size) = expected(catalog, &record);
+
+    Ok(if expected_size.is_some_and(|expected| expected != size) {
+        VerificationState::Corrupt
+    } else if record.verified_at.is_none() {
+        VerificationState::Unverified
+    } else if record.verified_size != Some(size) || record.verified_modified != Some(modified) {
+        VerificationState::Modified
+    } else {
+        VerificationState::Verified
+    })
+}
+
+/// Hash a model file and compare it to the catalog or published checksum.
+/// Files in the models directory that do not match are moved to the
+/// quarantine directory; files elsewhere are left in place.
+pub fn verify_model_file(
+    models_dir: &Path,
+    path: &Path,
+    catalog: Option<&ModelInfo>,
+) -> Result<ModelVerification, String> {
+    let file_name = record_key(path)?;
+    let mut verification = check_file(models_dir, path, file_name, catalog)?;
+
+    match verification.state {
+        VerificationState::Corrupt => {
+            if path.parent() == Some(models_dir) {
+                verification.quarantined_path = Some(
+                    quarantine(models_dir, file_name)?
+                        .to_string_lossy()
+                        .to_string(),
+                );
+            }
+            update_records(models_dir, |records| {
+                if let Some(record) = records.get_mut(file_name) {
+                    record.verified_at = None;
+                }
+            })?;
+        }
+        VerificationState::Verified => record_verified(models_dir, path)?,
+        _ => {}
+    }
+
+    Ok(verification)
+}
+
+/// Check a file that is about to be imported as `file_name`, without moving
+/// or recording anything, so a file that does not match stays where it is
+pub fn check_import(
+    models_dir: &Path,
+    source: &Path,
+    file_name: &str,
+    catalog: Option<&ModelInfo>,
+) -> Result<ModelVerification, String> {
+    check_file(models_dir, source, file_name, catalog)
+}
+
+/// Record that the file at `path` matches its checksum as it is now
+pub fn record_verified(models_dir: &Path, path: &Path) -> Result<(), String> {
+    let file_name = record_key(path)?;
+    let (size, modified) = file_stamp(path)?;
+    update_records(models_dir, |records| {
+        let record = records.entry(file_name.to_string()).or_default();
+        record.verified_size = Some(size);
+        record.verified_modified = Some(modified);
+        record.verified_at = Some(chrono::Utc::now().to_rfc3339());
+    })
+}
+
+/// Compare `path` to what is expected of `file_name`; `Verified` only when
+/// there was a checksum to compare against
+fn check_file(
+    models_dir: &Path,
+    path: &Path,
+    file_name: &str,
+    catalog: Option<&ModelInfo>,
+) -> Result<ModelVerification, String> {
+    if !path.exists() {
+        return Err("Model file not found".to_string());
+    }
+
+    let record = {
+        let _guard = RECORDS_LOCK.lock().map_err(|e| e.to_string())?;
+        read_records(models_dir)
+            .remove(file_name)
+            .unwrap_or_default()
+    };
+    let (expected_sha256, expected_size) = expected(catalog, &record);
+
+    let (size, _) = file_stamp(path)?;
+    let sha256 = if expected_size.is_some_and(|expected| expected != size) {
+        // A truncated file cannot match; skip hashing gigabytes
+        None
+    } else {
+        Some(
+            calculate_file_hash(&path.to_string_lossy())
+                .map_err(|e| format!("Failed to hash model: {}", e))?,
+        )
+    };
+
+    let size_matches = expected_size.is_none_or(|expected| expected == size);
+    let hash_matches = match (&sha256, &expected_sha256) {
+        (Some(actual), Some(expected)) => actual.eq_ignore_ascii_case(expected),
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+    let state = if !size_matches || !hash_matches {
+        VerificationState::Corrupt
+    } else if expected_sha256.is_some() {
+        VerificationState::Verified
+    } else {
+        VerificationState::Unverified
+    };
+
+    Ok(ModelVerification {
+        file_name: file_name.to_string(),
+        state,
+        size,
+        expected_size,
+        sha256,
+        expected_sha256,
+        quarantined_path: None,
+    })
+}
+
+/// Verify a file that was just downloaded. Unlike `verify_model_file`, a file
+/// with neither a pinned nor a published checksum is not accepted: it stays
+/// `Unverified` but is quarantined like a corrupt one, so a failed checksum
+/// lookup can't leave an unchecked download in use.
+pub fn verify_download(
+    models_dir: &Path,
+    path: &Path,
+    catalog: Option<&ModelInfo>,
+) -> Result<ModelVerification, String> {
+    let mut verification = verify_model_file(models_dir, path, catalog)?;
+    if verification.state == VerificationState::Unverified && path.parent() == Some(models_dir) {
+        verification.quarantined_path = Some(
+            quarantine(models_dir, &verification.file_name)?
+                .to_string_lossy()
+                .to_string(),
+        );
+    }
+    Ok(verification)
+}
+
+/// Drop the record of a file that was deleted
+pub fn forget_verification(models_dir: &Path, file_name: &str) -> Result<(), String> {
+    update_records(models_dir, |records| {
+        records.remove(file_name);
+    })
+}
+
+/// Move a file out of the models directory so it is no longer used, replacing
+/// any earlier quarantined copy of the same file
+fn quarantine(models_dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    let quarantine_dir = models_dir.join(QUARANTINE_DIR_NAME);
+    std::fs::create_dir_all(&quarantine_dir)
+        .map_err(|e| format!("Failed to create quarantine directory: {}", e))?;
+
+    let target = quarantine_dir.join(file_name);
+    std::fs::rename(models_dir.join(file_name), &target)
+        .map_err(|e| format!("Failed to quarantine corrupt model: {}", e))?;
+    Ok(target)
+}
+
+fn record_key(path: &Path) -> Result<&str, String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid model path: {}", path.display()))
+}
+
+/// Catalog values win over what the host published
+fn expected(
+    catalog: Option<&ModelInfo>,
+    record: &VerificationRecord,
+) -> (Option<String>, Option<u64>) {
+    let sha256 = catalog
+        .and_then(|model| model.sha256.clone())
+        .or_else(|| record.expected_sha256.clone());
+    let size = catalog
+        .and_then(|model| model.size_bytes)
+        .or(record.expected_size);
+    (sha256, size)
+}
+
+fn file_stamp(path: &Path) -> Result<(u64, u64), String> {
+    let metadata =
+        std::fs::metadata(path).map_err(|e| format!("Failed to get model metadata: {}", e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    Ok((metadata.len(), modified))
+}
+
+fn read_records(models_dir: &Path) -> HashMap<String, VerificationRecord> {
+    std::fs::read_to_string(models_dir.join(RECORDS_FILE_NAME))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn update_records(
+    models_dir: &Path,
+    change: impl FnOnce(&mut HashMap<String, VerificationRecord>),
+) -> Result<(), String> {
+    let _guard = RECORDS_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut records = read_records(models_dir);
+    change(&mut records);
+
+    let json = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+    std::fs::write(models_dir.join(RECORDS_FILE_NAME), json)
+        .map_err(|e| format!("Failed to save model verification: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 of "hello"
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn models_dir_with(content: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("model-integrity-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("model.bin"), content).unwrap();
+        dir
+    }
+
+    #[test]
+    fn verifies_against_published_checksum() {
+        let dir = models_dir_with(b"hello");
+        assert_eq!(
+            verification_state(&dir, &dir.join("model.bin"), None).unwrap(),
+            VerificationState::Unverified
+        );
+
+        record_published_checksum(
+            &dir,
+            "model.bin",
+            Some(HELLO_SHA256.to_uppercase()),
+            Some(5),
+        )
+        .unwrap();
+        let verification = verify_model_file(&dir, &dir.join("model.bin"), None).unwrap();
+        assert_eq!(verification.state, VerificationState::Verified);
+        assert_eq!(
+            verification_state(&dir, &dir.join("model.bin"), None).unwrap(),
+            VerificationState::Verified
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_file_is_corrupt_without_hashing() {
+        let dir = models_dir_with(b"hel");
+        record_published_checksum(&dir, "model.bin", Some(HELLO_SHA256.to_string()), Some(5))
+            .unwrap();
+        assert_eq!(
+            verification_state(&dir, &dir.join("model.bin"), None).unwrap(),
+            VerificationState::Corrupt
+        );
+
+        let verification = verify_model_file(&dir, &dir.join("model.bin"), None).unwrap();
+        assert_eq!(verification.state, VerificationState::Corrupt);
+        assert_eq!(verification.sha256, None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn download_without_any_checksum_is_quarantined() {
+        let dir = models_dir_with(b"hello");
+
+        let verification = verify_download(&dir, &dir.join("model.bin"), None).unwrap();
+        assert_eq!(verification.state, VerificationState::Unverified);
+        assert!(verification.quarantined_path.is_some());
+        assert!(!dir.join("model.bin").exists());
+
+        // A published checksum is enough without a catalog pin
+        std::fs::write(dir.join("model.bin"), b"hello").unwrap();
+        record_published_checksum(&dir, "model.bin", Some(HELLO_SHA256.to_string()), None).unwrap();
+        let verification = verify_download(&dir, &dir.join("model.bin"), None).unwrap();
+        assert_eq!(verification.state, VerificationState::Verified);
+        assert!(dir.join("model.bin").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mismatched_file_is_quarantined() {
+        let dir = models_dir_with(b"hellx");
+        record_published_checksum(&dir, "model.bin", Some(HELLO_SHA256.to_string()), Some(5))
+            .unwrap();
+
+        let verification = verify_model_file(&dir, &dir.join("model.bin"), None).unwrap();
+        assert_eq!(verification.state, VerificationState::Corrupt);
+        assert!(!dir.join("model.bin").exists());
+        assert!(dir.join(QUARANTINE_DIR_NAME).join("model.bin").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn import_check_leaves_the_source_alone() {
+        // An earlier copy is installed and has not been checked
+        let dir = models_dir_with(b"hello");
+        let source = dir.join("downloads-model.bin");
+        std::fs::write(&source, b"hellx").unwrap();
+        record_published_checksum(&dir, "model.bin", Some(HELLO_SHA256.to_string()), Some(5))
+            .unwrap();
+
+        let verification = check_import(&dir, &source, "model.bin", None).unwrap();
+        assert_eq!(verification.state, VerificationState::Corrupt);
+        assert!(source.exists());
+        assert!(!dir.join(QUARANTINE_DIR_NAME).exists());
+
+        std::fs::write(&source, b"hello").unwrap();
+        let verification = check_import(&dir, &source, "model.bin", None).unwrap();
+        assert_eq!(verification.state, VerificationState::Verified);
+        // Nothing is recorded until the import is done
+        assert_eq!(
+            verification_state(&dir, &dir.join("model.bin"), None).unwrap(),
+            VerificationState::Unverified
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,6 +1,32 @@
+use super::model_download::{
+    discard_partial_download, download_file, partial_download, published_checksum,
+    DownloadControl, DownloadOutcome,
+};
+use super::model_integrity::{
+    check_import, forget_verification, record_published_checksum, record_verified,
+    verification_state, verify_download, verify_model_file, ModelVerification, VerificationState,
+};
+use super::model_import::{
+    find_in_search_dirs, import_model_file, read_search_dirs, undo_import, write_search_dirs,
+    ImportMode,
+};
+use super::model_recommendation::{
+    recommend_for_hardware, HardwareProfile, HardwareRecommendation, RecommendationOptions,
+};
+use super::model_registry::{ModelKind, ModelRegistry};
+use super::model_storage::{
+    collect_garbage, ensure_space, forget_usage, mark_used, set_quota, storage_usage,
+    GcOptions, GcReport, StorageUsage,
+};
+use super::tts_service::PIPER_CONFIG_SUFFIX;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use tauri::AppHandle;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Event carrying `DownloadProgress` while a model downloads
+pub const MODEL_DOWNLOAD_PROGRESS_EVENT: &str = "model-download-progress";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
@@ -9,18 +35,78 @@ pub struct ModelInfo {
     pub download_url: String,
     pub file_name: String,
     pub description: String,
+    /// Display name of the language(s)
     pub language: String,
+    #[serde(default)]
+    pub kind: ModelKind,
+    /// What the model is used for, e.g. "transcription" or "text-to-speech"
+    #[serde(default)]
+    pub task: String,
+    /// BCP 47 tags; empty for multilingual models
+    #[serde(default)]
+    pub languages: Vec<String>,
+    #[serde(default)]
+    pub quantization: Option<String>,
+    #[serde(default)]
+    pub license: Option<String>,
+    #[serde(default)]
+    pub min_ram_mb: Option<u64>,
+    /// File names of registry models that must be installed alongside
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// File names of older versions this model replaces, which garbage
+    /// collection removes once this model is installed
+    #[serde(default)]
+    pub supersedes: Vec<String>,
+    /// Pinned SHA-256; when unset, the checksum the host publishes is recorded
+    /// at download time and used instead. A download with neither fails.
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+}
+
+impl ModelInfo {
+    /// Exact size when the registry pins it, else the approximate `size_mb`
+    pub fn download_size(&self) -> u64 {
+        self.size_bytes.unwrap_or(self.size_mb * 1024 * 1024)
+    }
+
+    /// Whether the model handles `language`; "en" matches "en-US" and vice versa
+    pub fn supports_language(&self, language: &str) -> bool {
+        let primary = |tag: &str| tag.split(['-', '_']).next().unwrap_or("").to_lowercase();
+        self.languages.is_empty()
+            || self
+                .languages
+                .iter()
+                .any(|supported| primary(supported) == primary(language))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadedModel {
+    pub file_name: String,
+    pub size: u64,
+    pub verification: VerificationState,
+    /// From the registry, or guessed from the file name for other files
+    pub kind: Option<ModelKind>,
+    pub path: String,
+    /// Found in a search directory rather than the models directory
+    pub read_only: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadProgress {
+    pub file_name: String,
     pub downloaded: u64,
+    /// 0 when the server did not send a size
     pub total: u64,
     pub percentage: f64,
     pub status: DownloadStatus,
+    pub error: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DownloadStatus {
     Pending,
@@ -28,6 +114,47 @@ pub enum DownloadStatus {
     Completed,
     Failed,
     Paused,
+    Cancelled,
+    /// Downloaded and being checked against its checksum
+    Verifying,
+}
+
+/// A model download running on its own thread
+pub struct ModelDownload {
+    control: DownloadControl,
+    progress: Arc<Mutex<DownloadProgress>>,
+    worker: JoinHandle<()>,
+}
+
+impl ModelDownload {
+    pub fn progress(&self) -> Result<DownloadProgress, String> {
+        self.progress
+            .lock()
+            .map(|progress| progress.clone())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Stop, keeping the partial file for `ModelManager::start_download` to resume
+    pub fn pause(self) -> Result<(), String> {
+        self.control.pause();
+        self.join()
+    }
+
+    /// Stop and delete the partial file
+    pub fn cancel(self) -> Result<(), String> {
+        self.control.cancel();
+        self.join()
+    }
+
+    fn join(self) -> Result<(), String> {
+        self.worker
+            .join()
+            .map_err(|_| "Download worker panicked".to_string())
+    }
 }
 
 pub struct ModelManager {
@@ -61,95 +188,410 @@ impl ModelManager {
         Ok(models_dir)
     }
 
-    /// Check if a model is already downloaded
+    /// Check if a model is already downloaded. A file whose size does not
+    /// match the published size (e.g. a truncated download) does not count.
     pub fn is_model_downloaded(&self, file_name: &str) -> Result<bool, String> {
         let models_dir = self.get_models_dir()?;
-        let model_path = models_dir.join(file_name);
-        Ok(model_path.exists())
+        let model_path = self.get_model_path(file_name)?;
+        if !model_path.exists() {
+            return Ok(false);
+        }
+        let catalog = self.find_model(file_name)?;
+        let state = verification_state(&models_dir, &model_path, catalog.as_ref())?;
+        Ok(state != VerificationState::Corrupt)
     }
 
-    /// Get the full path to a model file
+    /// Get the full path to a model file: in the models directory, else in
+    /// the first search directory that has it
     pub fn get_model_path(&self, file_name: &str) -> Result<PathBuf, String> {
         let models_dir = self.get_models_dir()?;
-        Ok(models_dir.join(file_name))
+        let local_path = models_dir.join(file_name);
+        if local_path.exists() {
+            return Ok(local_path);
+        }
+        Ok(find_in_search_dirs(&models_dir, file_name).unwrap_or(local_path))
     }
 
-    /// List all downloaded models
-    pub fn list_downloaded_models(&self) -> Result<Vec<String>, String> {
+    /// Read-only directories searched for models after the models directory
+    pub fn get_search_dirs(&self) -> Result<Vec<PathBuf>, String> {
+        Ok(read_search_dirs(&self.get_models_dir()?))
+    }
+
+    pub fn set_search_dirs(&self, dirs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+        write_search_dirs(&self.get_models_dir()?, dirs)
+    }
+
+    /// List all downloaded models with their verification state: registry
+    /// files plus any other file recognisable as a model, from the models
+    /// directory and then the search directories
+    pub fn list_downloaded_models(&self) -> Result<Vec<DownloadedModel>, String> {
         let models_dir = self.get_models_dir()?;
-        let mut models = Vec::new();
+        let registry = ModelRegistry::load(&models_dir);
+        let mut models: Vec<DownloadedModel> = Vec::new();
 
-        if models_dir.exists() {
-            let entries = std::fs::read_dir(&models_dir)
-                .map_err(|e| format!("Failed to read models directory: {}", e))?;
+        let dirs = std::iter::once(models_dir.clone()).chain(read_search_dirs(&models_dir));
+        for (index, dir) in dirs.enumerate() {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if index == 0 => {
+                    return Err(format!("Failed to read models directory: {}", e))
+                }
+                // An unmounted share should not hide the local models
+                Err(_) => continue,
+            };
 
             for entry in entries.flatten() {
+                let path = entry.path();
+                // Follows symlinks, so imported links count
+                if !path.is_file() {
+                    continue;
+                }
                 if let Ok(file_name) = entry.file_name().into_string() {
-                    // Only include .bin files (Whisper model format)
-                    if file_name.ends_with(".bin") {
-                        models.push(file_name);
+                    if models.iter().any(|model| model.file_name == file_name) {
+                        continue;
+                    }
+                    let catalog = registry.find(&file_name);
+                    let kind = catalog
+                        .map(|model| model.kind)
+                        .or_else(|| ModelKind::from_file_name(&file_name));
+                    if kind.is_some() {
+                        models.push(DownloadedModel {
+                            size: path.metadata().map(|m| m.len()).unwrap_or(0),
+                            verification: verification_state(&models_dir, &path, catalog)?,
+                            kind,
+                            path: path.to_string_lossy().to_string(),
+                            read_only: index > 0,
+                            file_name,
+                        });
                     }
                 }
             }
         }
 
+        models.sort_by(|a, b| a.file_name.cmp(&b.file_name));
         Ok(models)
     }
 
+    /// Installed models of one kind, skipping files that failed verification
+    pub fn installed_models(&self, kind: ModelKind) -> Result<Vec<DownloadedModel>, String> {
+        Ok(self
+            .list_downloaded_models()?
+            .into_iter()
+            .filter(|model| model.kind == Some(kind))
+            .filter(|model| model.verification != VerificationState::Corrupt)
+            .collect())
+    }
+
+    /// The installed model for `task` in `language`, for services that pick
+    /// their model through the catalog rather than being given a file
+    pub fn model_for(
+        &self,
+        kind: ModelKind,
+        task: &str,
+        language: Option<&str>,
+    ) -> Result<DownloadedModel, String> {
+        let installed = self.installed_models(kind)?;
+        let model = self
+            .registry()?
+            .select(&installed, kind, task, language)
+            .cloned()
+            .ok_or_else(|| match language {
+                Some(language) => format!("No {} model for {} is installed", task, language),
+                None => format!("No {} model is installed", task),
+            })?;
+        // Usage tracking is best effort; a read-only models directory is fine
+        let _ = self.mark_used(&model.file_name);
+        Ok(model)
+    }
+
+    /// Hash a downloaded model and compare it to its catalog or published
+    /// checksum; a file that does not match is moved to quarantine
+    pub fn verify_model(&self, file_name: &str) -> Result<ModelVerification, String> {
+        let models_dir = self.get_models_dir()?;
+        let catalog = ModelRegistry::load(&models_dir).find(file_name).cloned();
+        verify_model_file(&models_dir, &self.get_model_path(file_name)?, catalog.as_ref())
+    }
+
+    /// Register a model from a local file, for machines that cannot download.
+    /// The file must be a registry model or have a recognisable name; a Piper
+    /// voice brings its `.onnx.json` config along. Registry models are
+    /// verified before anything is copied or moved, so a file that fails is
+    /// left where it was.
+    pub fn import_model(
+        &self,
+        source: &Path,
+        mode: ImportMode,
+        file_name: Option<&str>,
+    ) -> Result<DownloadedModel, String> {
+        let models_dir = self.get_models_dir()?;
+        let file_name = match file_name {
+            Some(file_name) => file_name.to_string(),
+            None => source
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| format!("Invalid model path: {}", source.display()))?
+                .to_string(),
+        };
+        if file_name.starts_with('.') || file_name.contains(['/', '\\']) {
+            return Err(format!("Invalid model file name: {}", file_name));
+        }
+
+        let registry = ModelRegistry::load(&models_dir);
+        let catalog = registry.find(&file_name);
+        let kind = catalog
+            .map(|model| model.kind)
+            .or_else(|| ModelKind::from_file_name(&file_name))
+            .ok_or_else(|| {
+                format!(
+                    "Unrecognised model file: {}. Rename it to its registry file name.",
+                    file_name
+                )
+            })?;
+
+        let mut config_source = source.as_os_str().to_owned();
+        config_source.push(PIPER_CONFIG_SUFFIX);
+        let config_source = PathBuf::from(config_source);
+        if kind == ModelKind::Voice && !config_source.is_file() {
+            return Err(format!(
+                "Piper voices need their {} config next to the model",
+                PIPER_CONFIG_SUFFIX
+            ));
+        }
+
+        // Check before importing, so a file that fails stays with the user
+        let checked = match catalog {
+            Some(model) => {
+                // Imports work offline, so an unpinned model stays unverified
+                let verification = check_import(&models_dir, source, &file_name, Some(model))?;
+                if verification.state == VerificationState::Corrupt {
+                    return Err(format!(
+                        "{} does not match its catalog checksum and was not imported",
+                        model.file_name
+                    ));
+                }
+                verification.state
+            }
+            None => VerificationState::Unverified,
+        };
+
+        let destination = import_model_file(&models_dir, source, &file_name, mode)?;
+        if kind == ModelKind::Voice {
+            let config_name = format!("{}{}", file_name, PIPER_CONFIG_SUFFIX);
+            if !models_dir.join(&config_name).exists() {
+                if let Err(e) = import_model_file(&models_dir, &config_source, &config_name, mode)
+                {
+                    undo_import(source, &destination, mode)?;
+                    return Err(e);
+                }
+            }
+        }
+
+        if checked == VerificationState::Verified {
+            record_verified(&models_dir, &destination)?;
+        }
+        let verification = verification_state(&models_dir, &destination, catalog)?;
+        Ok(DownloadedModel {
+            file_name,
+            size: destination.metadata().map(|m| m.len()).unwrap_or(0),
+            verification,
+            kind: Some(kind),
+            path: destination.to_string_lossy().to_string(),
+            read_only: false,
+        })
+    }
+
+    /// The model catalog: the bundled registry, or a newer refreshed copy
+    pub fn registry(&self) -> Result<ModelRegistry, String> {
+        Ok(ModelRegistry::load(&self.get_models_dir()?))
+    }
+
+    /// Fetch a newer registry from `url`, or the registry's own refresh URL
+    pub fn refresh_registry(&self, url: Option<&str>) -> Result<ModelRegistry, String> {
+        ModelRegistry::refresh(&self.get_models_dir()?, url)
+    }
+
     /// Get available Whisper models
-    pub fn get_available_models() -> Vec<ModelInfo> {
-        vec![
-            ModelInfo {
-                name: "Whisper Tiny".to_string(),
-                size_mb: 75,
-                download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin".to_string(),
-                file_name: "ggml-tiny.bin".to_string(),
-                description: "Fastest, least accurate. Good for quick tests.".to_string(),
-                language: "Multilingual".to_string(),
-            },
-            ModelInfo {
-                name: "Whisper Base".to_string(),
-                size_mb: 142,
-                download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin".to_string(),
-                file_name: "ggml-base.bin".to_string(),
-                description: "Balanced speed and accuracy.".to_string(),
-                language: "Multilingual".to_string(),
-            },
-            ModelInfo {
-                name: "Whisper Small".to_string(),
-                size_mb: 466,
-                download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin".to_string(),
-                file_name: "ggml-small.bin".to_string(),
-                description: "Good accuracy, reasonable speed. Recommended.".to_string(),
-                language: "Multilingual".to_string(),
-            },
-            ModelInfo {
-                name: "Whisper Medium".to_string(),
-                size_mb: 1500,
-                download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin".to_string(),
-                file_name: "ggml-medium.bin".to_string(),
-                description: "High accuracy, slower. For better quality.".to_string(),
-                language: "Multilingual".to_string(),
-            },
-            ModelInfo {
-                name: "Whisper Large".to_string(),
-                size_mb: 2900,
-                download_url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin".to_string(),
-                file_name: "ggml-large-v3.bin".to_string(),
-                description: "Best accuracy, slowest. Requires significant resources.".to_string(),
-                language: "Multilingual".to_string(),
-            },
-        ]
-    }
-
-    /// Delete a model file
+    pub fn get_available_models(&self) -> Result<Vec<ModelInfo>, String> {
+        self.models_of_kind(ModelKind::Speech)
+    }
+
+    /// Get downloadable Piper voices. Each voice also needs its `.onnx.json`
+    /// config, found at `download_url` + `PIPER_CONFIG_SUFFIX`.
+    pub fn get_available_voices(&self) -> Result<Vec<ModelInfo>, String> {
+        self.models_of_kind(ModelKind::Voice)
+    }
+
+    pub fn models_of_kind(&self, kind: ModelKind) -> Result<Vec<ModelInfo>, String> {
+        Ok(self.registry()?.of_kind(kind).cloned().collect())
+    }
+
+    /// Registry entry for a model file
+    pub fn find_model(&self, file_name: &str) -> Result<Option<ModelInfo>, String> {
+        Ok(self.registry()?.find(file_name).cloned())
+    }
+
+    /// Download a model into the models directory, resuming a partial file
+    /// left by an earlier paused or interrupted download. Progress is emitted
+    /// as `model-download-progress` events.
+    pub fn start_download(&self, model: ModelInfo) -> Result<ModelDownload, String> {
+        let models_dir = self.get_models_dir()?;
+        let destination = models_dir.join(&model.file_name);
+        let dependencies: Vec<ModelInfo> = ModelRegistry::load(&models_dir)
+            .dependencies_of(&model)
+            .into_iter()
+            .filter(|dependency| !models_dir.join(&dependency.file_name).exists())
+            .cloned()
+            .collect();
+
+        // Only what is still to come needs to fit
+        let partial = partial_download(&destination);
+        let needed_bytes = dependencies
+            .iter()
+            .chain(std::iter::once(&model))
+            .map(ModelInfo::download_size)
+            .sum::<u64>()
+            .saturating_sub(partial.as_ref().map_or(0, |partial| partial.downloaded));
+        ensure_space(&models_dir, &model.file_name, needed_bytes)?;
+
+        let control = DownloadControl::new();
+        let progress = Arc::new(Mutex::new(
+            partial.unwrap_or(DownloadProgress {
+                file_name: model.file_name.clone(),
+                downloaded: 0,
+                total: model.download_size(),
+                percentage: 0.0,
+                status: DownloadStatus::Pending,
+                error: None,
+            }),
+        ));
+
+        let app_handle = self.app_handle.clone();
+        let worker_control = control.clone();
+        let worker_progress = progress.clone();
+        let worker = std::thread::Builder::new()
+            .name("model-download".to_string())
+            .spawn(move || {
+                let report = |update: &DownloadProgress| {
+                    if let Ok(mut progress) = worker_progress.lock() {
+                        *progress = update.clone();
+                    }
+                    let _ = app_handle.emit(MODEL_DOWNLOAD_PROGRESS_EVENT, update);
+                };
+                // The download is only complete once it has been verified
+                let mut report_transfer = |update: &DownloadProgress| {
+                    if update.status == DownloadStatus::Completed {
+                        report(&DownloadProgress {
+                            status: DownloadStatus::Verifying,
+                            ..update.clone()
+                        });
+                    } else {
+                        report(update);
+                    }
+                };
+
+                if model.sha256.is_none() {
+                    let (sha256, size) = published_checksum(&model.download_url);
+                    if sha256.is_some() || size.is_some() {
+                        let _ =
+                            record_published_checksum(&models_dir, &model.file_name, sha256, size);
+                    }
+                }
+
+                // Dependencies and Piper's voice config are fetched first; they
+                // are small, so the model download carries the progress
+                let mut prerequisites: Vec<(String, PathBuf, Option<&ModelInfo>)> = dependencies
+                    .iter()
+                    .map(|dependency| {
+                        let path = models_dir.join(&dependency.file_name);
+                        (dependency.download_url.clone(), path, Some(dependency))
+                    })
+                    .collect();
+                if model.kind == ModelKind::Voice {
+                    let mut config_path = destination.as_os_str().to_owned();
+                    config_path.push(PIPER_CONFIG_SUFFIX);
+                    let config_url = format!("{}{}", model.download_url, PIPER_CONFIG_SUFFIX);
+                    prerequisites.push((config_url, PathBuf::from(config_path), None));
+                }
+
+                let mut result = Ok(DownloadOutcome::Completed);
+                for (url, path, dependency) in &prerequisites {
+                    if path.exists() {
+                        continue;
+                    }
+                    result = download_file(url, path, &worker_control, &mut |_| {});
+                    if let (Ok(DownloadOutcome::Completed), Some(dependency)) =
+                        (&result, dependency)
+                    {
+                        if let Err(message) = check_download(&models_dir, dependency) {
+                            result = Err(message);
+                        }
+                    }
+                    if !matches!(result, Ok(DownloadOutcome::Completed)) {
+                        break;
+                    }
+                }
+
+                let result = match result {
+                    Ok(DownloadOutcome::Completed) => download_file(
+                        &model.download_url,
+                        &destination,
+                        &worker_control,
+                        &mut report_transfer,
+                    ),
+                    other => other,
+                };
+
+                let status = match result {
+                    Ok(DownloadOutcome::Completed) => match check_download(&models_dir, &model) {
+                        Ok(()) => (DownloadStatus::Completed, None),
+                        Err(message) => (DownloadStatus::Failed, Some(message)),
+                    },
+                    // Only reached here when stopped while fetching a prerequisite
+                    Ok(DownloadOutcome::Paused) => (DownloadStatus::Paused, None),
+                    Ok(DownloadOutcome::Cancelled) => (DownloadStatus::Cancelled, None),
+                    Err(message) => (DownloadStatus::Failed, Some(message)),
+                };
+                let mut update = worker_progress
+                    .lock()
+                    .map(|progress| progress.clone())
+                    .unwrap_or_else(|e| e.into_inner().clone());
+                if update.status != status.0 {
+                    (update.status, update.error) = status;
+                    report(&update);
+                }
+            })
+            .map_err(|e| format!("Failed to start download: {}", e))?;
+
+        Ok(ModelDownload {
+            control,
+            progress,
+            worker,
+        })
+    }
+
+    /// Progress of a paused or interrupted download, if a partial file exists
+    pub fn get_partial_download(&self, file_name: &str) -> Result<Option<DownloadProgress>, String> {
+        Ok(partial_download(&self.get_models_dir()?.join(file_name)))
+    }
+
+    /// Delete what a paused or interrupted download left behind
+    pub fn discard_partial_download(&self, file_name: &str) -> Result<(), String> {
+        discard_partial_download(&self.get_models_dir()?.join(file_name))
+    }
+
+    /// Delete a model file; an imported symlink is removed, not its target.
+    /// Models in search directories are read-only.
     pub fn delete_model(&self, file_name: &str) -> Result<(), String> {
-        let model_path = self.get_model_path(file_name)?;
+        let models_dir = self.get_models_dir()?;
+        let model_path = models_dir.join(file_name);
 
-        if model_path.exists() {
+        if model_path.symlink_metadata().is_ok() {
             std::fs::remove_file(&model_path)
                 .map_err(|e| format!("Failed to delete model: {}", e))?;
-            Ok(())
+            forget_usage(&models_dir, file_name)?;
+            forget_verification(&models_dir, file_name)
+        } else if find_in_search_dirs(&models_dir, file_name).is_some() {
+            Err(format!("{} is in a read-only search directory", file_name))
         } else {
             Err("Model file not found".to_string())
         }
@@ -167,4 +609,70 @@ impl ModelManager {
             Err("Model file not found".to_string())
         }
     }
+
+    /// Recommend a model per task for this machine's CPU and memory
+    pub fn recommend_models(
+        &self,
+        options: &RecommendationOptions,
+    ) -> Result<HardwareRecommendation, String> {
+        let installed: Vec<String> = self
+            .list_downloaded_models()?
+            .into_iter()
+            .map(|model| model.file_name)
+            .collect();
+        Ok(recommend_for_hardware(
+            &self.registry()?,
+            &HardwareProfile::detect(),
+            &installed,
+            options,
+        ))
+    }
+
+    /// Record that a model was loaded, for garbage collection of unused models
+    pub fn mark_used(&self, file_name: &str) -> Result<(), String> {
+        mark_used(&self.get_models_dir()?, file_name)
+    }
+
+    /// Disk use of the models directory against the quota and free space
+    pub fn storage_usage(&self) -> Result<StorageUsage, String> {
+        let installed = self.list_downloaded_models()?;
+        Ok(storage_usage(&self.get_models_dir()?, &installed))
+    }
+
+    /// Limit the models directory to `quota_mb`; downloads that would exceed
+    /// it are refused. `None` removes the limit.
+    pub fn set_quota(&self, quota_mb: Option<u64>) -> Result<(), String> {
+        set_quota(&self.get_models_dir()?, quota_mb)
+    }
+
+    /// Remove superseded or unused models and download leftovers, keeping
+    /// the files of downloads in `active`
+    pub fn collect_garbage(
+        &self,
+        options: &GcOptions,
+        active: &[String],
+    ) -> Result<GcReport, String> {
+        let models_dir = self.get_models_dir()?;
+        let installed = self.list_downloaded_models()?;
+        let registry = ModelRegistry::load(&models_dir);
+        collect_garbage(&models_dir, &registry, &installed, active, options)
+    }
+}
+
+/// Verify a finished download against its registry entry; one that can't be
+/// checked at all fails like a corrupt one
+fn check_download(models_dir: &Path, model: &ModelInfo) -> Result<(), String> {
+    let path = models_dir.join(&model.file_name);
+    match verify_download(models_dir, &path, Some(model))?.state {
+        VerificationState::Corrupt => Err(format!(
+            "{} failed verification and was moved to quarantine",
+            model.file_name
+        )),
+        VerificationState::Unverified => Err(format!(
+            "{} could not be verified: the catalog pins no checksum and the host \
+             published none, so it was moved to quarantine",
+            model.file_name
+        )),
+        _ => Ok(()),
+    }
 }
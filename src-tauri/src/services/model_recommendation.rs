@@ -0,0 +1,314 @@
+use super::model_manager::ModelInfo;
+use super::model_registry::{ModelKind, ModelRegistry};
+use serde::{Deserialize, Serialize};
+use sysinfo::{CpuRefreshKind, System};
+
+/// Beyond this many threads whisper.cpp and onnxruntime gain little
+const MAX_USEFUL_THREADS: usize = 8;
+/// Seconds of compute per second of audio, per MB of model, on one AVX2 core.
+/// Rough fits to published whisper.cpp and Piper CPU timings (Whisper Base at
+/// about 0.1x real time on 8 cores); estimates, not measurements.
+const SPEECH_COST_PER_MB: f64 = 0.0037;
+const VOICE_COST_PER_MB: f64 = 0.0048;
+/// Share of available memory a model may take, leaving room for the app
+const MEMORY_HEADROOM: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareProfile {
+    pub cpu_brand: String,
+    pub arch: String,
+    pub logical_cores: usize,
+    pub physical_cores: Option<usize>,
+    /// Detected vector extensions, e.g. "avx2", "avx512f", "neon"
+    pub simd: Vec<String>,
+    pub total_ram_mb: u64,
+    pub available_ram_mb: u64,
+}
+
+impl HardwareProfile {
+    pub fn detect() -> Self {
+        let mut system = System::new();
+        system.refresh_memory();
+        system.refresh_cpu_list(CpuRefreshKind::nothing());
+
+        Self {
+            cpu_brand: system
+                .cpus()
+                .first()
+                .map(|cpu| cpu.brand().trim().to_string())
+                .unwrap_or_default(),
+            arch: std::env::consts::ARCH.to_string(),
+            logical_cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            physical_cores: System::physical_core_count(),
+            simd: detect_simd(),
+            total_ram_mb: system.total_memory() / (1024 * 1024),
+            available_ram_mb: system.available_memory() / (1024 * 1024),
+        }
+    }
+
+    /// Relative throughput against a single AVX2 core
+    fn compute_units(&self) -> f64 {
+        let threads = self
+            .physical_cores
+            .unwrap_or(self.logical_cores)
+            .clamp(1, MAX_USEFUL_THREADS);
+        let simd = if self.has_simd("avx512f") {
+            1.3
+        } else if self.has_simd("avx2") || self.has_simd("neon") {
+            1.0
+        } else {
+            0.35
+        };
+        (threads as f64).powf(0.8) * simd
+    }
+
+    fn has_simd(&self, feature: &str) -> bool {
+        self.simd.iter().any(|detected| detected == feature)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecommendationOptions {
+    /// Slowest acceptable real-time factor (processing time / audio time)
+    pub max_real_time_factor: f64,
+    /// Prefer models for this BCP 47 language
+    pub language: Option<String>,
+}
+
+impl Default for RecommendationOptions {
+    fn default() -> Self {
+        Self {
+            max_real_time_factor: 1.0,
+            language: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelAssessment {
+    pub file_name: String,
+    pub name: String,
+    pub fits_memory: bool,
+    /// Estimated processing time per second of audio, for speech and voices
+    pub estimated_rtf: Option<f64>,
+    pub installed: bool,
+    /// Why the model is not suitable, if it is not
+    pub warning: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskRecommendation {
+    pub kind: ModelKind,
+    /// File name of the most capable suitable model
+    pub recommended: Option<String>,
+    pub candidates: Vec<ModelAssessment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareRecommendation {
+    pub hardware: HardwareProfile,
+    pub tasks: Vec<TaskRecommendation>,
+}
+
+/// Recommend a model per kind in the registry. The most capable (largest)
+/// model that fits in memory and runs fast enough wins; ties keep registry order.
+pub fn recommend_for_hardware(
+    registry: &ModelRegistry,
+    hardware: &HardwareProfile,
+    installed: &[String],
+    options: &RecommendationOptions,
+) -> HardwareRecommendation {
+    let mut kinds: Vec<ModelKind> = Vec::new();
+    for model in &registry.models {
+        if !kinds.contains(&model.kind) {
+            kinds.push(model.kind);
+        }
+    }
+
+    let tasks = kinds
+        .into_iter()
+        .map(|kind| {
+            let candidates: Vec<(&ModelInfo, ModelAssessment)> = registry
+                .of_kind(kind)
+                .filter(|model| {
+                    options
+                        .language
+                        .as_deref()
+                        .is_none_or(|language| model.supports_language(language))
+                })
+                .map(|model| (model, assess(model, hardware, installed, options)))
+                .collect();
+
+            let recommended = candidates
+                .iter()
+                .filter(|(_, assessment)| assessment.warning.is_none())
+                .fold(None::<&ModelInfo>, |best, (model, _)| match best {
+                    Some(best) if best.size_mb >= model.size_mb => Some(best),
+                    _ => Some(model),
+                })
+                .map(|model| model.file_name.clone());
+
+            TaskRecommendation {
+                kind,
+                recommended,
+                candidates: candidates
+                    .into_iter()
+                    .map(|(_, assessment)| assessment)
+                    .collect(),
+            }
+        })
+        .collect();
+
+    HardwareRecommendation {
+        hardware: hardware.clone(),
+        tasks,
+    }
+}
+
+fn assess(
+    model: &ModelInfo,
+    hardware: &HardwareProfile,
+    installed: &[String],
+    options: &RecommendationOptions,
+) -> ModelAssessment {
+    let budget_mb = (hardware.available_ram_mb as f64 * MEMORY_HEADROOM) as u64;
+    let fits_memory = model.min_ram_mb.is_none_or(|needed| needed <= budget_mb);
+    let estimated_rtf = estimate_rtf(model, hardware);
+
+    let warning = if !fits_memory {
+        Some(format!(
+            "Needs about {} MB of RAM; {} MB is available",
+            model.min_ram_mb.unwrap_or_default(),
+            hardware.available_ram_mb
+        ))
+    } else if estimated_rtf.is_some_and(|rtf| rtf > options.max_real_time_factor) {
+        Some(format!(
+            "Likely too slow on this machine (about {:.1}x real time)",
+            estimated_rtf.unwrap_or_default()
+        ))
+    } else {
+        None
+    };
+
+    ModelAssessment {
+        file_name: model.file_name.clone(),
+        name: model.name.clone(),
+        fits_memory,
+        estimated_rtf,
+        installed: installed.contains(&model.file_name),
+        warning,
+    }
+}
+
+/// Compute scales with model size; quantized files are smaller and faster
+fn estimate_rtf(model: &ModelInfo, hardware: &HardwareProfile) -> Option<f64> {
+    let cost_per_mb = match model.kind {
+        ModelKind::Speech => SPEECH_COST_PER_MB,
+        ModelKind::Voice => VOICE_COST_PER_MB,
+        _ => return None,
+    };
+    let rtf = model.size_mb as f64 * cost_per_mb / hardware.compute_units();
+    Some((rtf * 100.0).round() / 100.0)
+}
+
+fn detect_simd() -> Vec<String> {
+    let mut simd = Vec::new();
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if std::arch::is_x86_feature_detected!("avx") {
+            simd.push("avx".to_string());
+        }
+        if std::arch::is_x86_feature_detected!("avx2") {
+            simd.push("avx2".to_string());
+        }
+        if std::arch::is_x86_feature_detected!("fma") {
+            simd.push("fma".to_string());
+        }
+        if std::arch::is_x86_feature_detected!("f16c") {
+            simd.push("f16c".to_string());
+        }
+        if std::arch::is_x86_feature_detected!("avx512f") {
+            simd.push("avx512f".to_string());
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            simd.push("neon".to_string());
+        }
+        if std::arch::is_aarch64_feature_detected!("dotprod") {
+            simd.push("dotprod".to_string());
+        }
+    }
+    simd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(cores: usize, ram_mb: u64, simd: &[&str]) -> HardwareProfile {
+        HardwareProfile {
+            cpu_brand: String::new(),
+            arch: "x86_64".to_string(),
+            logical_cores: cores * 2,
+            physical_cores: Some(cores),
+            simd: simd.iter().map(|feature| feature.to_string()).collect(),
+            total_ram_mb: ram_mb,
+            available_ram_mb: ram_mb / 2,
+        }
+    }
+
+    fn recommended_speech(hardware: &HardwareProfile) -> Option<String> {
+        recommend_for_hardware(
+            &ModelRegistry::bundled(),
+            hardware,
+            &[],
+            &RecommendationOptions::default(),
+        )
+        .tasks
+        .into_iter()
+        .find(|task| task.kind == ModelKind::Speech)
+        .and_then(|task| task.recommended)
+    }
+
+    #[test]
+    fn large_models_need_memory_and_cores() {
+        let laptop = machine(4, 8 * 1024, &["avx2"]);
+        let workstation = machine(16, 64 * 1024, &["avx2", "avx512f"]);
+        let old_pc = machine(2, 4 * 1024, &[]);
+
+        assert_eq!(
+            recommended_speech(&laptop).as_deref(),
+            Some("ggml-small.bin")
+        );
+        assert_eq!(
+            recommended_speech(&workstation).as_deref(),
+            Some("ggml-medium.bin")
+        );
+        assert_eq!(
+            recommended_speech(&old_pc).as_deref(),
+            Some("ggml-base.bin")
+        );
+    }
+
+    #[test]
+    fn warns_about_models_that_do_not_fit() {
+        let recommendation = recommend_for_hardware(
+            &ModelRegistry::bundled(),
+            &machine(8, 8 * 1024, &["avx2"]),
+            &["ggml-large-v3.bin".to_string()],
+            &RecommendationOptions::default(),
+        );
+        let large = recommendation
+            .tasks
+            .iter()
+            .flat_map(|task| &task.candidates)
+            .find(|model| model.file_name == "ggml-large-v3.bin")
+            .unwrap();
+        assert!(!large.fits_memory);
+        assert!(large.installed);
+        assert!(large.warning.is_some());
+    }
+}
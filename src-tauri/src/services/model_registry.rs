@@ -0,0 +1,348 @@
+use super::model_manager::{DownloadedModel, ModelInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+/// Newest manifest format this build understands
+pub const REGISTRY_SCHEMA_VERSION: u32 = 1;
+/// Manifest shipped with the app
+const BUNDLED_REGISTRY: &str = include_str!("../../resources/model_registry.json");
+/// Copy of the last manifest fetched from a refresh URL, in the models directory
+const CACHED_REGISTRY_FILE_NAME: &str = ".registry.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelKind {
+    /// Speech recognition (whisper.cpp)
+    #[default]
+    Speech,
+    Translation,
+    Ocr,
+    /// Piper text-to-speech voice
+    Voice,
+    Embedding,
+    /// Pronunciation dictionary
+    Dictionary,
+}
+
+impl ModelKind {
+    /// Best guess for a file that is not in the registry
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        if file_name.starts_with("ggml-") && file_name.ends_with(".bin") {
+            Some(Self::Speech)
+        } else if file_name.ends_with(".onnx") {
+            Some(Self::Voice)
+        } else if file_name.ends_with(".traineddata") {
+            Some(Self::Ocr)
+        } else if file_name.ends_with(".dict") {
+            Some(Self::Dictionary)
+        } else {
+            None
+        }
+    }
+}
+
+/// Versioned catalog of every model the app can download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRegistry {
+    pub schema_version: u32,
+    /// Increases with every published change; the newer of the bundled and
+    /// cached manifests is used
+    pub revision: u64,
+    /// Where `refresh` fetches a newer manifest from by default
+    #[serde(default)]
+    pub refresh_url: Option<String>,
+    pub models: Vec<ModelInfo>,
+}
+
+impl ModelRegistry {
+    /// The manifest shipped with the app
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_REGISTRY).expect("bundled model registry is invalid")
+    }
+
+    /// The cached manifest when it is newer than the bundled one
+    pub fn load(models_dir: &Path) -> Self {
+        let bundled = Self::bundled();
+        std::fs::read_to_string(models_dir.join(CACHED_REGISTRY_FILE_NAME))
+            .ok()
+            .and_then(|json| Self::parse(&json).ok())
+            .filter(|cached| cached.revision > bundled.revision)
+            .unwrap_or(bundled)
+    }
+
+    /// Parse and validate a manifest
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let registry: Self = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse model registry: {}", e))?;
+
+        if registry.schema_version > REGISTRY_SCHEMA_VERSION {
+            return Err(format!(
+                "Model registry schema {} is newer than the supported {}",
+                registry.schema_version, REGISTRY_SCHEMA_VERSION
+            ));
+        }
+
+        let mut file_names = HashSet::new();
+        for model in &registry.models {
+            if model.file_name.is_empty()
+                || model.file_name.starts_with('.')
+                || model.file_name.contains(['/', '\\'])
+            {
+                return Err(format!("Invalid model file name: {:?}", model.file_name));
+            }
+            if !file_names.insert(model.file_name.as_str()) {
+                return Err(format!("Duplicate model in registry: {}", model.file_name));
+            }
+        }
+        for model in &registry.models {
+            if let Some(missing) = model
+                .dependencies
+                .iter()
+                .find(|dependency| !file_names.contains(dependency.as_str()))
+            {
+                return Err(format!(
+                    "{} depends on {}, which is not in the registry",
+                    model.file_name, missing
+                ));
+            }
+        }
+
+        Ok(registry)
+    }
+
+    /// Fetch a manifest from `url`, or the current `refresh_url`, and cache it
+    /// when it is newer than the one in use
+    pub fn refresh(models_dir: &Path, url: Option<&str>) -> Result<Self, String> {
+        let current = Self::load(models_dir);
+        let url = url
+            .map(str::to_string)
+            .or_else(|| current.refresh_url.clone())
+            .ok_or_else(|| "No model registry URL configured".to_string())?;
+
+        let json = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .get(&url)
+            .call()
+            .map_err(|e| format!("Failed to fetch model registry: {}", e))?
+            .into_string()
+            .map_err(|e| format!("Failed to read model registry: {}", e))?;
+        let fetched = Self::parse(&json)?;
+        if fetched.revision <= current.revision {
+            return Ok(current);
+        }
+
+        let cache_path = models_dir.join(CACHED_REGISTRY_FILE_NAME);
+        let temp_path = models_dir.join(format!("{}.tmp", CACHED_REGISTRY_FILE_NAME));
+        std::fs::write(&temp_path, &json)
+            .and_then(|_| std::fs::rename(&temp_path, &cache_path))
+            .map_err(|e| format!("Failed to save model registry: {}", e))?;
+        Ok(fetched)
+    }
+
+    pub fn find(&self, file_name: &str) -> Option<&ModelInfo> {
+        self.models
+            .iter()
+            .find(|model| model.file_name == file_name)
+    }
+
+    pub fn of_kind(&self, kind: ModelKind) -> impl Iterator<Item = &ModelInfo> {
+        self.models.iter().filter(move |model| model.kind == kind)
+    }
+
+    /// The installed model a service should use for `task`, e.g. "ocr" for
+    /// `language`. Catalog models are matched on kind, task and languages;
+    /// other files of the right kind are only a fallback, as their task and
+    /// languages are unknown.
+    pub fn select<'a>(
+        &self,
+        installed: &'a [DownloadedModel],
+        kind: ModelKind,
+        task: &str,
+        language: Option<&str>,
+    ) -> Option<&'a DownloadedModel> {
+        let of_kind = installed.iter().filter(|model| model.kind == Some(kind));
+        of_kind
+            .clone()
+            .find(|model| {
+                self.find(&model.file_name).is_some_and(|catalog| {
+                    catalog.task == task
+                        && language.is_none_or(|language| catalog.supports_language(language))
+                })
+            })
+            .or_else(|| {
+                of_kind
+                    .clone()
+                    .find(|model| self.find(&model.file_name).is_none())
+            })
+    }
+
+    /// Everything `model` needs installed first, dependencies before dependents
+    pub fn dependencies_of(&self, model: &ModelInfo) -> Vec<&ModelInfo> {
+        fn visit<'a>(
+            registry: &'a ModelRegistry,
+            model: &ModelInfo,
+            seen: &mut HashSet<String>,
+            order: &mut Vec<&'a ModelInfo>,
+        ) {
+            for file_name in &model.dependencies {
+                if !seen.insert(file_name.clone()) {
+                    continue;
+                }
+                if let Some(dependency) = registry.find(file_name) {
+                    visit(registry, dependency, seen, order);
+                    order.push(dependency);
+                }
+            }
+        }
+
+        let mut seen = HashSet::from([model.file_name.clone()]);
+        let mut order = Vec::new();
+        visit(self, model, &mut seen, &mut order);
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::model_integrity::VerificationState;
+
+    fn manifest(models: serde_json::Value) -> String {
+        serde_json::json!({ "schema_version": 1, "revision": 2, "models": models }).to_string()
+    }
+
+    fn model(file_name: &str, dependencies: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "name": file_name,
+            "kind": "embedding",
+            "size_mb": 1,
+            "download_url": format!("https://example.invalid/{}", file_name),
+            "file_name": file_name,
+            "description": "",
+            "language": "English",
+            "languages": ["en"],
+            "dependencies": dependencies,
+        })
+    }
+
+    #[test]
+    fn bundled_registry_is_valid() {
+        let registry = ModelRegistry::bundled();
+        assert!(registry.of_kind(ModelKind::Speech).count() >= 5);
+        assert!(registry
+            .of_kind(ModelKind::Voice)
+            .filter(|voice| voice.supports_language("de"))
+            .any(|voice| voice.file_name == "de_DE-thorsten-medium.onnx"));
+    }
+
+    #[test]
+    fn bundled_models_bring_their_tokenizers() {
+        let registry = ModelRegistry::bundled();
+        for file_name in ["all-MiniLM-L6-v2.onnx", "nllb-200-distilled-600M.bin"] {
+            let model = registry.find(file_name).unwrap();
+            let tokenizers: Vec<&str> = registry
+                .dependencies_of(model)
+                .iter()
+                .filter(|dependency| dependency.task == "tokenizer")
+                .map(|dependency| dependency.file_name.as_str())
+                .collect();
+            assert_eq!(tokenizers.len(), 1, "{}", file_name);
+        }
+        assert_eq!(
+            registry
+                .of_kind(ModelKind::Translation)
+                .filter(|model| model.task == "translation")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn selects_installed_models_by_task_and_language() {
+        let registry = ModelRegistry::bundled();
+        let installed = |file_name: &str, kind: ModelKind| DownloadedModel {
+            file_name: file_name.to_string(),
+            size: 1,
+            verification: VerificationState::Verified,
+            kind: Some(kind),
+            path: format!("/models/{}", file_name),
+            read_only: false,
+        };
+        let models = [
+            installed("nllb-200-sentencepiece.bpe.model", ModelKind::Translation),
+            installed("nllb-200-distilled-600M.bin", ModelKind::Translation),
+            installed("eng.traineddata", ModelKind::Ocr),
+        ];
+        let select = |kind, task, language| {
+            registry
+                .select(&models, kind, task, language)
+                .map(|model| model.file_name.as_str())
+        };
+
+        // The tokenizer is installed first but is not a translation model
+        assert_eq!(
+            select(ModelKind::Translation, "translation", Some("de")),
+            Some("nllb-200-distilled-600M.bin")
+        );
+        assert_eq!(
+            select(ModelKind::Ocr, "ocr", Some("en-GB")),
+            Some("eng.traineddata")
+        );
+        assert_eq!(select(ModelKind::Ocr, "ocr", Some("de")), None);
+
+        // A file the catalog does not know is used when nothing else fits
+        let mut models = models.to_vec();
+        models.push(installed("deu.traineddata", ModelKind::Ocr));
+        assert_eq!(
+            registry
+                .select(&models, ModelKind::Ocr, "ocr", Some("de"))
+                .map(|model| model.file_name.as_str()),
+            Some("deu.traineddata")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies_and_duplicates() {
+        let missing = manifest(serde_json::json!([model("a.onnx", &["b.onnx"])]));
+        assert!(ModelRegistry::parse(&missing).is_err());
+
+        let duplicate = manifest(serde_json::json!([
+            model("a.onnx", &[]),
+            model("a.onnx", &[])
+        ]));
+        assert!(ModelRegistry::parse(&duplicate).is_err());
+    }
+
+    #[test]
+    fn orders_dependencies_before_dependents() {
+        let json = manifest(serde_json::json!([
+            model("app.onnx", &["tokenizer.json", "base.onnx"]),
+            model("base.onnx", &["tokenizer.json"]),
+            model("tokenizer.json", &[]),
+        ]));
+        let registry = ModelRegistry::parse(&json).unwrap();
+        let order: Vec<&str> = registry
+            .dependencies_of(registry.find("app.onnx").unwrap())
+            .iter()
+            .map(|model| model.file_name.as_str())
+            .collect();
+        assert_eq!(order, ["tokenizer.json", "base.onnx"]);
+    }
+
+    #[test]
+    fn newer_cached_registry_wins() {
+        let dir = std::env::temp_dir().join(format!("model-registry-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cached = manifest(serde_json::json!([model("a.onnx", &[])]))
+            .replace("\"revision\":2", &format!("\"revision\":{}", u64::MAX));
+        std::fs::write(dir.join(CACHED_REGISTRY_FILE_NAME), cached).unwrap();
+
+        let registry = ModelRegistry::load(&dir);
+        assert_eq!(registry.revision, u64::MAX);
+        assert!(registry.find("a.onnx").is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,557 @@
+use super::model_download::{
+    discard_partial_download, PARTIAL_DOWNLOAD_SUFFIX, PARTIAL_STATE_SUFFIX,
+};
+use super::model_import::IMPORT_SUFFIX;
+use super::model_integrity::{forget_verification, QUARANTINE_DIR_NAME};
+use super::model_manager::DownloadedModel;
+use super::model_registry::{ModelKind, ModelRegistry};
+use super::tts_service::PIPER_CONFIG_SUFFIX;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Quota and last-used times for the models directory
+const STORAGE_FILE_NAME: &str = ".storage.json";
+/// Free space left on the disk after a download, so it is not filled to the brim
+const RESERVED_FREE_SPACE_MB: u64 = 256;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Serializes read-modify-write of the storage file
+static STORAGE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StorageRecords {
+    #[serde(default)]
+    quota_mb: Option<u64>,
+    /// RFC 3339 time each model was last loaded
+    #[serde(default)]
+    last_used: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub file_name: String,
+    pub size: u64,
+    /// `None` if never loaded since usage tracking began
+    pub last_used: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUsage {
+    /// Everything in the models directory, including partial downloads and quarantine
+    pub used_bytes: u64,
+    pub quota_mb: Option<u64>,
+    /// Free space on the disk holding the models directory
+    pub available_bytes: Option<u64>,
+    pub partial_bytes: u64,
+    pub quarantine_bytes: u64,
+    pub models: Vec<ModelUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GcOptions {
+    /// Remove models not loaded for this many days
+    pub unused_days: Option<u32>,
+    /// Remove models the registry marks as replaced by an installed model
+    pub remove_superseded: bool,
+    /// Remove partial downloads untouched for this many days
+    pub stale_partial_days: Option<u32>,
+    pub empty_quarantine: bool,
+    /// Report what would be removed without deleting anything
+    pub dry_run: bool,
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        Self {
+            unused_days: None,
+            remove_superseded: true,
+            stale_partial_days: Some(7),
+            empty_quarantine: true,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GcReason {
+    Superseded,
+    Unused,
+    StalePartial,
+    Quarantined,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedFile {
+    pub file_name: String,
+    pub bytes: u64,
+    pub reason: GcReason,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcReport {
+    pub removed: Vec<RemovedFile>,
+    pub freed_bytes: u64,
+    pub dry_run: bool,
+}
+
+fn get_quota(models_dir: &Path) -> Option<u64> {
+    read_records(models_dir).quota_mb
+}
+
+/// Limit the models directory to `quota_mb`; `None` removes the limit
+pub fn set_quota(models_dir: &Path, quota_mb: Option<u64>) -> Result<(), String> {
+    update_records(models_dir, |records| records.quota_mb = quota_mb)
+}
+
+/// Record that a model was just loaded. Services call this on every load, so
+/// the file is only rewritten when the recorded time is over a minute old.
+pub fn mark_used(models_dir: &Path, file_name: &str) -> Result<(), String> {
+    let now = Utc::now();
+    let recent = read_records(models_dir)
+        .last_used
+        .get(file_name)
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .is_some_and(|time| now - time.with_timezone(&Utc) < Duration::minutes(1));
+    if recent {
+        return Ok(());
+    }
+
+    update_records(models_dir, |records| {
+        records
+            .last_used
+            .insert(file_name.to_string(), now.to_rfc3339());
+    })
+}
+
+pub fn forget_usage(models_dir: &Path, file_name: &str) -> Result<(), String> {
+    update_records(models_dir, |records| {
+        records.last_used.remove(file_name);
+    })
+}
+
+/// Fail if `needed_bytes` more would not fit on the disk or within the quota
+pub fn ensure_space(models_dir: &Path, file_name: &str, needed_bytes: u64) -> Result<(), String> {
+    if let Ok(available) = fs4::available_space(models_dir) {
+        let reserve = RESERVED_FREE_SPACE_MB * BYTES_PER_MB;
+        if needed_bytes.saturating_add(reserve) > available {
+            return Err(format!(
+                "Not enough disk space for {}: needs {} MB, {} MB free",
+                file_name,
+                to_mb(needed_bytes),
+                to_mb(available.saturating_sub(reserve))
+            ));
+        }
+    }
+
+    if let Some(quota_mb) = get_quota(models_dir) {
+        let used = dir_size(models_dir);
+        if used.saturating_add(needed_bytes) > quota_mb * BYTES_PER_MB {
+            return Err(format!(
+                "{} ({} MB) would exceed the models quota of {} MB; {} MB is in use",
+                file_name,
+                to_mb(needed_bytes),
+                quota_mb,
+                to_mb(used)
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn storage_usage(models_dir: &Path, installed: &[DownloadedModel]) -> StorageUsage {
+    let last_used = read_records(models_dir).last_used;
+    let models = installed
+        .iter()
+        .filter(|model| !model.read_only)
+        .map(|model| ModelUsage {
+            file_name: model.file_name.clone(),
+            size: local_size(&models_dir.join(&model.file_name)),
+            last_used: last_used.get(&model.file_name).cloned(),
+        })
+        .collect();
+
+    StorageUsage {
+        used_bytes: dir_size(models_dir),
+        quota_mb: get_quota(models_dir),
+        available_bytes: fs4::available_space(models_dir).ok(),
+        partial_bytes: leftovers(models_dir)
+            .iter()
+            .map(|path| local_size(path))
+            .sum(),
+        quarantine_bytes: dir_size(&models_dir.join(QUARANTINE_DIR_NAME)),
+        models,
+    }
+}
+
+/// Remove superseded or unused models, stale partial downloads and quarantined
+/// files. Models in `active` (being downloaded) and models an installed model
+/// depends on are kept.
+pub fn collect_garbage(
+    models_dir: &Path,
+    registry: &ModelRegistry,
+    installed: &[DownloadedModel],
+    active: &[String],
+    options: &GcOptions,
+) -> Result<GcReport, String> {
+    let now = Utc::now();
+    let last_used = read_records(models_dir).last_used;
+    let installed_names: HashSet<&str> = installed
+        .iter()
+        .map(|model| model.file_name.as_str())
+        .collect();
+
+    let mut removals: Vec<(PathBuf, RemovedFile)> = Vec::new();
+    for model in installed.iter().filter(|model| !model.read_only) {
+        if active.contains(&model.file_name) {
+            continue;
+        }
+        let superseded = options.remove_superseded
+            && registry.models.iter().any(|newer| {
+                newer.supersedes.contains(&model.file_name)
+                    && installed_names.contains(newer.file_name.as_str())
+            });
+        let unused = options.unused_days.is_some_and(|days| {
+            let path = models_dir.join(&model.file_name);
+            last_used
+                .get(&model.file_name)
+                .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+                .map(|time| time.with_timezone(&Utc))
+                .or_else(|| modified_time(&path))
+                .is_some_and(|time| now - time > Duration::days(days.into()))
+        });
+
+        let reason = if superseded {
+            GcReason::Superseded
+        } else if unused {
+            GcReason::Unused
+        } else {
+            continue;
+        };
+        let path = models_dir.join(&model.file_name);
+        removals.push((
+            path.clone(),
+            RemovedFile {
+                file_name: model.file_name.clone(),
+                bytes: local_size(&path),
+                reason,
+            },
+        ));
+    }
+
+    // Keep whatever a remaining model needs
+    let removed_names: HashSet<String> = removals
+        .iter()
+        .map(|(_, removed)| removed.file_name.clone())
+        .collect();
+    let required: HashSet<&str> = installed
+        .iter()
+        .filter(|model| !removed_names.contains(&model.file_name))
+        .filter_map(|model| registry.find(&model.file_name))
+        .flat_map(|model| model.dependencies.iter().map(String::as_str))
+        .collect();
+    removals.retain(|(_, removed)| !required.contains(removed.file_name.as_str()));
+
+    if let Some(days) = options.stale_partial_days {
+        // A partial download and its state file are one leftover, stale only
+        // when the newer of the two is
+        let mut groups: BTreeMap<(String, bool), Vec<PathBuf>> = BTreeMap::new();
+        for path in leftovers(models_dir) {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let target = strip_leftover_suffix(&file_name).to_string();
+            let is_import = file_name.ends_with(IMPORT_SUFFIX);
+            groups.entry((target, is_import)).or_default().push(path);
+        }
+        for ((target, _), mut paths) in groups {
+            let stale = paths
+                .iter()
+                .filter_map(|path| modified_time(path))
+                .max()
+                .is_some_and(|time| now - time > Duration::days(days.into()));
+            if !stale || active.contains(&target) {
+                continue;
+            }
+            // Report the .part file itself when there is one
+            paths.sort();
+            removals.push((
+                paths[0].clone(),
+                RemovedFile {
+                    file_name: paths[0]
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                    bytes: paths.iter().map(|path| local_size(path)).sum(),
+                    reason: GcReason::StalePartial,
+                },
+            ));
+        }
+    }
+
+    if options.empty_quarantine {
+        if let Ok(entries) = std::fs::read_dir(models_dir.join(QUARANTINE_DIR_NAME)) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                removals.push((
+                    path.clone(),
+                    RemovedFile {
+                        file_name: entry.file_name().to_string_lossy().to_string(),
+                        bytes: local_size(&path),
+                        reason: GcReason::Quarantined,
+                    },
+                ));
+            }
+        }
+    }
+
+    if !options.dry_run {
+        for (path, removed) in &removals {
+            remove(models_dir, path, removed, registry)?;
+        }
+    }
+
+    Ok(GcReport {
+        freed_bytes: removals.iter().map(|(_, removed)| removed.bytes).sum(),
+        removed: removals.into_iter().map(|(_, removed)| removed).collect(),
+        dry_run: options.dry_run,
+    })
+}
+
+fn remove(
+    models_dir: &Path,
+    path: &Path,
+    removed: &RemovedFile,
+    registry: &ModelRegistry,
+) -> Result<(), String> {
+    match removed.reason {
+        GcReason::Superseded | GcReason::Unused => {
+            std::fs::remove_file(path)
+                .map_err(|e| format!("Failed to remove {}: {}", removed.file_name, e))?;
+            let is_voice = registry
+                .find(&removed.file_name)
+                .map(|model| model.kind)
+                .or_else(|| ModelKind::from_file_name(&removed.file_name))
+                == Some(ModelKind::Voice);
+            if is_voice {
+                let config =
+                    models_dir.join(format!("{}{}", removed.file_name, PIPER_CONFIG_SUFFIX));
+                let _ = std::fs::remove_file(config);
+            }
+            forget_verification(models_dir, &removed.file_name)?;
+            forget_usage(models_dir, &removed.file_name)
+        }
+        GcReason::StalePartial => {
+            let target = strip_leftover_suffix(&removed.file_name);
+            if removed.file_name.ends_with(IMPORT_SUFFIX) {
+                std::fs::remove_file(path)
+                    .map_err(|e| format!("Failed to remove {}: {}", removed.file_name, e))
+            } else {
+                discard_partial_download(&models_dir.join(target))
+            }
+        }
+        GcReason::Quarantined => if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        }
+        .map_err(|e| format!("Failed to remove {}: {}", removed.file_name, e)),
+    }
+}
+
+/// Partial downloads, their sidecars and interrupted imports
+fn leftovers(models_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(models_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.ends_with(PARTIAL_DOWNLOAD_SUFFIX)
+                || name.ends_with(PARTIAL_STATE_SUFFIX)
+                || name.ends_with(IMPORT_SUFFIX)
+        })
+        .collect()
+}
+
+/// File name of the model a leftover belongs to
+fn strip_leftover_suffix(file_name: &str) -> &str {
+    [PARTIAL_STATE_SUFFIX, PARTIAL_DOWNLOAD_SUFFIX, IMPORT_SUFFIX]
+        .iter()
+        .find_map(|suffix| file_name.strip_suffix(suffix))
+        .unwrap_or(file_name)
+}
+
+/// Bytes the path takes in the models directory; symlinks count as nothing
+fn local_size(path: &Path) -> u64 {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => dir_size(path),
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => 0,
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| local_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn modified_time(path: &Path) -> Option<DateTime<Utc>> {
+    std::fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Utc>::from)
+}
+
+fn to_mb(bytes: u64) -> u64 {
+    bytes.div_ceil(BYTES_PER_MB)
+}
+
+fn read_records(models_dir: &Path) -> StorageRecords {
+    std::fs::read_to_string(models_dir.join(STORAGE_FILE_NAME))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn update_records(
+    models_dir: &Path,
+    change: impl FnOnce(&mut StorageRecords),
+) -> Result<(), String> {
+    let _guard = STORAGE_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut records = read_records(models_dir);
+    change(&mut records);
+
+    let json = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+    std::fs::write(models_dir.join(STORAGE_FILE_NAME), json)
+        .map_err(|e| format!("Failed to save model storage settings: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::model_integrity::VerificationState;
+    use std::time::SystemTime;
+
+    fn installed(file_name: &str) -> DownloadedModel {
+        DownloadedModel {
+            file_name: file_name.to_string(),
+            size: 0,
+            verification: VerificationState::Unverified,
+            kind: ModelKind::from_file_name(file_name),
+            path: String::new(),
+            read_only: false,
+        }
+    }
+
+    fn age(path: &Path, days: u64) {
+        let time = SystemTime::now() - std::time::Duration::from_secs(days * 24 * 60 * 60);
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(time))
+            .unwrap();
+    }
+
+    fn models_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("model-storage-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn quota_refuses_downloads_that_do_not_fit() {
+        let dir = models_dir();
+        std::fs::write(dir.join("ggml-tiny.bin"), vec![0u8; BYTES_PER_MB as usize]).unwrap();
+        assert!(ensure_space(&dir, "ggml-base.bin", BYTES_PER_MB).is_ok());
+
+        set_quota(&dir, Some(2)).unwrap();
+        assert!(ensure_space(&dir, "ggml-base.bin", BYTES_PER_MB / 2).is_ok());
+        assert!(ensure_space(&dir, "ggml-base.bin", BYTES_PER_MB).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn collects_superseded_unused_and_stale_files() {
+        let dir = models_dir();
+        for name in [
+            "ggml-large-v2.bin",
+            "ggml-large-v3.bin",
+            "ggml-tiny.bin",
+            "ggml-base.bin",
+            "ggml-small.bin.part",
+            "ggml-small.bin.part.json",
+            "ggml-medium.bin.part",
+            "ggml-large-v1.bin.part",
+            "ggml-large-v1.bin.part.json",
+        ] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        age(&dir.join("ggml-tiny.bin"), 90);
+        age(&dir.join("ggml-base.bin"), 90);
+        mark_used(&dir, "ggml-base.bin").unwrap();
+        age(&dir.join("ggml-small.bin.part"), 30);
+        age(&dir.join("ggml-small.bin.part.json"), 30);
+        age(&dir.join("ggml-medium.bin.part"), 30);
+        // Only the state file was touched recently, so the download is still going
+        age(&dir.join("ggml-large-v1.bin.part"), 30);
+
+        let models: Vec<DownloadedModel> = [
+            "ggml-large-v2.bin",
+            "ggml-large-v3.bin",
+            "ggml-tiny.bin",
+            "ggml-base.bin",
+        ]
+        .into_iter()
+        .map(installed)
+        .collect();
+        let options = GcOptions {
+            unused_days: Some(30),
+            ..GcOptions::default()
+        };
+        let active = ["ggml-medium.bin".to_string()];
+
+        let report =
+            collect_garbage(&dir, &ModelRegistry::bundled(), &models, &active, &options).unwrap();
+        let mut removed: Vec<(&str, GcReason)> = report
+            .removed
+            .iter()
+            .map(|file| (file.file_name.as_str(), file.reason))
+            .collect();
+        removed.sort_by_key(|(file_name, _)| *file_name);
+        assert_eq!(
+            removed,
+            [
+                ("ggml-large-v2.bin", GcReason::Superseded),
+                ("ggml-small.bin.part", GcReason::StalePartial),
+                ("ggml-tiny.bin", GcReason::Unused),
+            ]
+        );
+        let partial = report
+            .removed
+            .iter()
+            .find(|file| file.reason == GcReason::StalePartial)
+            .unwrap();
+        assert_eq!(
+            partial.bytes,
+            ("ggml-small.bin.part".len() + "ggml-small.bin.part.json".len()) as u64
+        );
+        assert!(!dir.join("ggml-small.bin.part.json").exists());
+        assert!(dir.join("ggml-large-v1.bin.part").exists());
+        assert!(!dir.join("ggml-large-v2.bin").exists());
+        assert!(dir.join("ggml-base.bin").exists());
+        assert!(dir.join("ggml-medium.bin.part").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use super::model_manager::DownloadedModel;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +39,8 @@ pub struct OCRResult {
     pub confidence: f32,
     pub language: String,
     pub page_number: usize,
+    /// File name of the catalog model that recognized the text
+    pub model: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,7 +58,8 @@ pub struct DocumentOCRResult {
 }
 
 /// Mock OCR service
-/// In production, integrate with PaddleOCR, Tesseract.js, or cloud OCR APIs
+/// In production, integrate with PaddleOCR, Tesseract.js, or cloud OCR APIs.
+/// Callers pick the language data through `ModelManager::model_for`.
 pub struct OCRService;
 
 impl OCRService {
@@ -71,7 +75,7 @@ impl OCRService {
     }
 
     /// Perform OCR on page
-    pub fn ocr_page(&self, _image_data: &[u8], page_number: usize, options: &OCROptions) -> OCRResult {
+    pub fn ocr_page(&self, _image_data: &[u8], page_number: usize, options: &OCROptions, model: &DownloadedModel) -> OCRResult {
         // Mock OCR implementation
         // In production: Use PaddleOCR, Tesseract, or cloud APIs
         let mock_text = format!("Mock OCR text for page {}", page_number);
@@ -102,11 +106,12 @@ impl OCRService {
             confidence: 0.92,
             language: options.language.clone().unwrap_or_else(|| "en".to_string()),
             page_number,
+            model: model.file_name.clone(),
         }
     }
 
     /// Process entire document for OCR
-    pub fn ocr_document(&self, pages: Vec<(usize, Vec<u8>)>, options: &OCROptions) -> DocumentOCRResult {
+    pub fn ocr_document(&self, pages: Vec<(usize, Vec<u8>)>, options: &OCROptions, model: &DownloadedModel) -> DocumentOCRResult {
         let mut page_results = Vec::new();
         let mut scanned_count = 0;
 
@@ -115,7 +120,7 @@ impl OCRService {
             
             let result = if is_scanned {
                 scanned_count += 1;
-                Some(self.ocr_page(&page_data, page_number, options))
+                Some(self.ocr_page(&page_data, page_number, options, model))
             } else {
                 None
             };
@@ -135,8 +140,8 @@ impl OCRService {
     }
 
     /// Extract text from image
-    pub fn extract_text(&self, image_data: &[u8], options: &OCROptions) -> String {
-        let result = self.ocr_page(image_data, 0, options);
+    pub fn extract_text(&self, image_data: &[u8], options: &OCROptions, model: &DownloadedModel) -> String {
+        let result = self.ocr_page(image_data, 0, options, model);
         result.text
     }
 }
@@ -0,0 +1,1344 @@
+use super::annotations::{
+    color_rgb, Annotation, AnnotationGeometry, Point, Quad, Rect, FINGERPRINT_KEY_PREFIX,
+};
+use super::pdf_text::load_pdf_document;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use lopdf::{
+    dictionary, text_string, Dictionary, Document, IncrementalDocument, Object, ObjectId, Stream,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Opacity of highlight appearances, so the text underneath stays readable
+const HIGHLIGHT_OPACITY: f32 = 0.4;
+/// Size of the icon drawn for sticky notes
+const NOTE_ICON_SIZE: f64 = 20.0;
+/// Annotation flag: print with the page
+const FLAG_PRINT: i64 = 4;
+/// Font size assumed for free text without a usable /DA
+const DEFAULT_FONT_SIZE: f64 = 12.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfAnnotationExport {
+    pub output_path: String,
+    pub written: usize,
+    /// Annotations written by an earlier export that were replaced or removed
+    pub replaced: usize,
+    /// Annotations read from the file that were edited here and rewritten
+    pub updated: usize,
+    /// Annotations read from the file that were deleted here
+    pub removed: usize,
+}
+
+/// An annotation found in a PDF file, ready to be stored
+#[derive(Debug, Clone)]
+pub struct PdfAnnotation {
+    /// Has `source_key` set and a fresh id
+    pub annotation: Annotation,
+    /// Source key of the annotation this one replies to (/IRT)
+    pub reply_to_key: Option<String>,
+    /// When the file says it was last modified (/M); without one it is never
+    /// taken to be newer than a copy we already have
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Write `annotations` into a copy of `source` at `output` as standard PDF
+/// annotations. The original bytes are kept and the annotations appended as an
+/// incremental update, so existing signatures still cover their revision.
+///
+/// Annotations from an earlier export carry their id in /NM; any whose id is in
+/// `known_ids` is dropped first, so exporting again replaces rather than duplicates.
+/// Annotations imported from the file are found again by their source key:
+/// edited ones are rewritten in place, keeping their object number so replies
+/// and popups still point at them, and trashed ones are taken off the page.
+pub fn write_pdf_annotations(
+    source: &Path,
+    output: &Path,
+    annotations: &[Annotation],
+    known_ids: &HashSet<String>,
+) -> Result<PdfAnnotationExport, String> {
+    let mut pdf = IncrementalDocument::load(source)
+        .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    if pdf.get_prev_documents().is_encrypted() {
+        return Err("Cannot add annotations to an encrypted PDF".to_string());
+    }
+
+    let pages = pdf.get_prev_documents().get_pages();
+    // A rewritten annotation without /NM gets our id, so it is known by both
+    let sourced: HashMap<&str, &Annotation> = annotations
+        .iter()
+        .filter_map(|a| Some((a.source_key.as_deref()?, a)))
+        .flat_map(|(key, a)| [(key, a), (a.exchange_name(), a)])
+        .collect();
+    let mut by_page: BTreeMap<u32, Vec<&Annotation>> = BTreeMap::new();
+    for annotation in annotations
+        .iter()
+        .filter(|a| a.deleted_at.is_none() && a.source_key.is_none())
+    {
+        let page_number = u32::try_from(annotation.page_number).unwrap_or(0);
+        if !pages.contains_key(&page_number) {
+            return Err(format!(
+                "Page {} is not in {}",
+                annotation.page_number,
+                source.display()
+            ));
+        }
+        by_page.entry(page_number).or_default().push(annotation);
+    }
+
+    let mut export = PdfAnnotationExport {
+        output_path: output.to_string_lossy().to_string(),
+        written: 0,
+        replaced: 0,
+        updated: 0,
+        removed: 0,
+    };
+    for (page_number, page_id) in &pages {
+        let page_annotations = by_page.remove(page_number).unwrap_or_default();
+        let changes = sourced_changes(pdf.get_prev_documents(), *page_number, *page_id, &sourced);
+        let previous: Vec<ObjectId> =
+            previously_exported(pdf.get_prev_documents(), *page_id, known_ids)
+                .into_iter()
+                .filter(|id| !changes.matched.contains(id))
+                .collect();
+
+        for (annot_id, annotation, original) in changes.edited {
+            let mut dict = annotation_dictionary(&mut pdf.new_document, annotation)?;
+            for key in [b"P".as_slice(), b"Popup", b"IRT"] {
+                if let Ok(value) = original.get(key) {
+                    dict.set(key, value.clone());
+                }
+            }
+            pdf.new_document
+                .objects
+                .insert(annot_id, Object::Dictionary(dict));
+            export.updated += 1;
+        }
+        if page_annotations.is_empty() && previous.is_empty() && changes.removed.is_empty() {
+            continue;
+        }
+
+        let mut refs = Vec::new();
+        for annotation in page_annotations {
+            let mut dict = annotation_dictionary(&mut pdf.new_document, annotation)?;
+            dict.set("P", *page_id);
+            refs.push(Object::Reference(pdf.new_document.add_object(dict)));
+        }
+        export.written += refs.len();
+        export.replaced += previous.len();
+        export.removed += changes.deleted;
+        let removed: Vec<ObjectId> = previous.into_iter().chain(changes.removed).collect();
+        set_page_annotations(&mut pdf, *page_id, &removed, refs)?;
+    }
+
+    let temp_path = PathBuf::from(format!("{}.part", output.display()));
+    let saved = pdf
+        .save(&temp_path)
+        .map_err(|e| e.to_string())
+        .and_then(|_| std::fs::rename(&temp_path, output).map_err(|e| e.to_string()));
+    if let Err(e) = saved {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(format!("Failed to save {}: {}", output.display(), e));
+    }
+    Ok(export)
+}
+
+/// Read the markup annotations already in the PDF at `path`: highlights, text
+/// markup, notes and their replies, ink, rectangles and free text. Popups, links,
+/// form fields and review-state markers are skipped, as are annotations whose /NM
+/// is in `own_ids` because we wrote them.
+///
+/// Each gets a source key, its /NM or else a fingerprint of its content, so that
+/// reading the same file again finds the same keys.
+pub fn read_pdf_annotations(
+    path: &Path,
+    document_id: &str,
+    own_ids: &HashSet<String>,
+) -> Result<Vec<PdfAnnotation>, String> {
+    let doc = load_pdf_document(path)?;
+    if doc.is_encrypted() {
+        return Err("Cannot read annotations from an encrypted PDF".to_string());
+    }
+
+    let annots = doc
+        .get_pages()
+        .into_iter()
+        .flat_map(|(page_number, page_id)| {
+            page_annotation_ids(&doc, page_id)
+                .into_iter()
+                .map(move |annot_id| (page_number, annot_id))
+        })
+        .collect();
+    Ok(collect_annotations(&doc, annots, document_id, own_ids))
+}
+
+/// Read the annotations in an FDF file; see `read_pdf_annotations`
+pub fn read_fdf_annotations(bytes: &[u8], document_id: &str) -> Result<Vec<PdfAnnotation>, String> {
+    // FDF uses PDF syntax under its own header, which lopdf does not recognise
+    let mut bytes = bytes.to_vec();
+    let header = bytes
+        .windows(5)
+        .take(1024)
+        .position(|window| window == b"%FDF-")
+        .ok_or("Not an FDF file")?;
+    bytes[header..header + 5].copy_from_slice(b"%PDF-");
+    let doc = Document::load_mem(&bytes).map_err(|e| format!("Failed to read FDF: {}", e))?;
+
+    let fdf = doc
+        .trailer
+        .get(b"Root")
+        .map(|root| resolve(&doc, root))
+        .and_then(Object::as_dict)
+        .and_then(|root| root.get(b"FDF"))
+        .map(|fdf| resolve(&doc, fdf))
+        .and_then(Object::as_dict)
+        .map_err(|_| "FDF file has no /FDF dictionary".to_string())?;
+    let annots = fdf
+        .get(b"Annots")
+        .map(|annots| resolve(&doc, annots))
+        .and_then(Object::as_array)
+        .map(|annots| annots.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|annot| annot.as_reference().ok())
+        .filter_map(|annot_id| {
+            // FDF pages count from 0
+            let page = doc.get_dictionary(annot_id).ok()?.get(b"Page").ok()?;
+            let page_number = u32::try_from(resolve(&doc, page).as_i64().ok()?).ok()?;
+            Some((page_number + 1, annot_id))
+        })
+        .collect();
+    Ok(collect_annotations(
+        &doc,
+        annots,
+        document_id,
+        &HashSet::new(),
+    ))
+}
+
+/// Write `annotations` as an FDF file for the PDF named `pdf_file_name`, which
+/// other readers can import without the PDF itself being changed
+pub fn write_fdf_annotations(
+    annotations: &[Annotation],
+    pdf_file_name: &str,
+) -> Result<Vec<u8>, String> {
+    let mut doc = Document::with_version("1.2");
+    let annotations: Vec<&Annotation> = annotations
+        .iter()
+        .filter(|a| a.deleted_at.is_none())
+        .collect();
+    let ids: HashMap<&str, ObjectId> = annotations
+        .iter()
+        .map(|annotation| (annotation.id.as_str(), doc.new_object_id()))
+        .collect();
+
+    let mut refs = Vec::new();
+    for annotation in &annotations {
+        let mut dict = annotation_dictionary(&mut doc, annotation)?;
+        dict.set("Page", annotation.page_number - 1);
+        if let Some(parent) = annotation.reply_to.as_deref().and_then(|id| ids.get(id)) {
+            dict.set("IRT", *parent);
+        }
+        let id = ids[annotation.id.as_str()];
+        doc.objects.insert(id, Object::Dictionary(dict));
+        refs.push(Object::Reference(id));
+    }
+    let root = doc.add_object(dictionary! {
+        "FDF" => dictionary! {
+            "F" => Object::string_literal(pdf_file_name),
+            "Annots" => refs,
+        },
+    });
+    doc.trailer.set("Root", root);
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes)
+        .map_err(|e| format!("Failed to write FDF: {}", e))?;
+    bytes[..5].copy_from_slice(b"%FDF-");
+    Ok(bytes)
+}
+
+/// Turn annotation dictionaries, with the 1-based page each is on, into
+/// annotations keyed by /NM or a fingerprint, resolving /IRT replies to keys
+pub(super) fn collect_annotations(
+    doc: &Document,
+    annots: Vec<(u32, ObjectId)>,
+    document_id: &str,
+    own_ids: &HashSet<String>,
+) -> Vec<PdfAnnotation> {
+    let mut keys: HashMap<ObjectId, String> = HashMap::new();
+    let mut found: Vec<(Annotation, Option<ObjectId>, Option<DateTime<Utc>>)> = Vec::new();
+    for (page_number, annot_id) in annots {
+        let Ok(dict) = doc.get_dictionary(annot_id) else {
+            continue;
+        };
+        let Some(mut annotation) = read_annotation(doc, dict, document_id, page_number) else {
+            continue;
+        };
+        let name = text_value(doc, dict, b"NM").filter(|name| !name.is_empty());
+        if name.as_ref().is_some_and(|name| own_ids.contains(name)) {
+            continue;
+        }
+
+        let key = name.unwrap_or_else(|| fingerprint(&annotation));
+        keys.insert(annot_id, key.clone());
+        annotation.source_key = Some(key);
+        let parent = dict.get(b"IRT").and_then(Object::as_reference).ok();
+        let modified_at = text_value(doc, dict, b"M").and_then(|date| parse_pdf_date(&date));
+        found.push((annotation, parent, modified_at));
+    }
+
+    found
+        .into_iter()
+        .map(|(annotation, parent, modified_at)| PdfAnnotation {
+            annotation,
+            reply_to_key: parent.and_then(|id| keys.get(&id).cloned()),
+            modified_at,
+        })
+        .collect()
+}
+
+fn read_annotation(
+    doc: &Document,
+    dict: &Dictionary,
+    document_id: &str,
+    page_number: u32,
+) -> Option<Annotation> {
+    if dict.has(b"StateModel") {
+        return None;
+    }
+    let subtype = dict.get(b"Subtype").and_then(Object::as_name).ok()?;
+    let rect = dict
+        .get(b"Rect")
+        .ok()
+        .and_then(|rect| numbers(doc, rect))
+        .filter(|values| values.len() == 4)
+        .map(|values| Rect {
+            x: values[0].min(values[2]),
+            y: values[1].min(values[3]),
+            width: (values[2] - values[0]).abs(),
+            height: (values[3] - values[1]).abs(),
+        });
+    let content = text_value(doc, dict, b"Contents").filter(|text| !text.trim().is_empty());
+
+    let geometry = match subtype {
+        b"Highlight" => AnnotationGeometry::Highlight {
+            quads: read_quads(doc, dict, rect)?,
+        },
+        // Squiggly underlines have no shape of their own here
+        b"Underline" | b"Squiggly" => AnnotationGeometry::Underline {
+            quads: read_quads(doc, dict, rect)?,
+        },
+        b"StrikeOut" => AnnotationGeometry::Strikeout {
+            quads: read_quads(doc, dict, rect)?,
+        },
+        b"Text" => {
+            let rect = rect?;
+            AnnotationGeometry::Note {
+                anchor: Point {
+                    x: rect.x,
+                    y: rect.y + rect.height,
+                },
+            }
+        }
+        b"Ink" => {
+            let strokes = resolve(doc, dict.get(b"InkList").ok()?)
+                .as_array()
+                .ok()?
+                .iter()
+                .filter_map(|stroke| numbers(doc, stroke))
+                .map(|values| {
+                    values
+                        .chunks_exact(2)
+                        .map(|xy| Point { x: xy[0], y: xy[1] })
+                        .collect()
+                })
+                .collect();
+            AnnotationGeometry::Ink {
+                strokes,
+                line_width: border_width(doc, dict),
+            }
+        }
+        b"Square" => AnnotationGeometry::Rectangle {
+            rect: rect?,
+            line_width: border_width(doc, dict),
+        },
+        b"FreeText" => AnnotationGeometry::FreeText {
+            rect: rect?,
+            font_size: text_value(doc, dict, b"DA")
+                .and_then(|da| font_size(&da))
+                .unwrap_or(DEFAULT_FONT_SIZE),
+        },
+        _ => return None,
+    };
+
+    let color = dict
+        .get(b"C")
+        .ok()
+        .and_then(|color| numbers(doc, color))
+        .and_then(|components| hex_color(&components))
+        .unwrap_or_else(|| match geometry {
+            AnnotationGeometry::Highlight { .. } | AnnotationGeometry::Note { .. } => {
+                "yellow".to_string()
+            }
+            _ => "#000000".to_string(),
+        });
+    let date = |key: &[u8]| text_value(doc, dict, key).and_then(|date| parse_pdf_date(&date));
+    // Undated annotations are stored as of when they were read
+    let updated_at = date(b"M")
+        .or_else(|| date(b"CreationDate"))
+        .unwrap_or_else(Utc::now);
+
+    let annotation = Annotation {
+        id: uuid::Uuid::new_v4().to_string(),
+        document_id: document_id.to_string(),
+        page_number: i64::from(page_number),
+        geometry,
+        content,
+        color,
+        created_at: date(b"CreationDate").unwrap_or(updated_at),
+        updated_at,
+        deleted_at: None,
+        author: text_value(doc, dict, b"T").filter(|author| !author.trim().is_empty()),
+        reply_to: None,
+        source_key: None,
+    };
+    annotation.validate().is_ok().then_some(annotation)
+}
+
+/// Marked-up lines from /QuadPoints, or the whole /Rect when there are none
+fn read_quads(doc: &Document, dict: &Dictionary, rect: Option<Rect>) -> Option<Vec<Quad>> {
+    let point = |values: &[f64], i: usize| Point {
+        x: values[i],
+        y: values[i + 1],
+    };
+    let quads: Vec<Quad> = dict
+        .get(b"QuadPoints")
+        .ok()
+        .and_then(|quads| numbers(doc, quads))
+        .unwrap_or_default()
+        .chunks_exact(8)
+        .map(|values| Quad {
+            top_left: point(values, 0),
+            top_right: point(values, 2),
+            bottom_left: point(values, 4),
+            bottom_right: point(values, 6),
+        })
+        .collect();
+    if !quads.is_empty() {
+        return Some(quads);
+    }
+
+    let rect = rect?;
+    let (left, right) = (rect.x, rect.x + rect.width);
+    let (bottom, top) = (rect.y, rect.y + rect.height);
+    Some(vec![Quad {
+        top_left: Point { x: left, y: top },
+        top_right: Point { x: right, y: top },
+        bottom_left: Point { x: left, y: bottom },
+        bottom_right: Point {
+            x: right,
+            y: bottom,
+        },
+    }])
+}
+
+/// Stable key for an annotation without /NM
+fn fingerprint(annotation: &Annotation) -> String {
+    let bounds = annotation.geometry.bounds();
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "{}|{}|{:.1} {:.1} {:.1} {:.1}|{}",
+        annotation.page_number,
+        annotation.geometry.kind(),
+        bounds.x,
+        bounds.y,
+        bounds.width,
+        bounds.height,
+        annotation.content.as_deref().unwrap_or_default()
+    ));
+    format!("{}{:x}", FINGERPRINT_KEY_PREFIX, hasher.finalize())
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+    doc.dereference(object)
+        .map_or(object, |(_, resolved)| resolved)
+}
+
+fn numbers(doc: &Document, object: &Object) -> Option<Vec<f64>> {
+    resolve(doc, object)
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|value| resolve(doc, value).as_float().ok().map(f64::from))
+        .collect()
+}
+
+fn text_value(doc: &Document, dict: &Dictionary, key: &[u8]) -> Option<String> {
+    let value = resolve(doc, dict.get(key).ok()?);
+    lopdf::decode_text_string(value).ok()
+}
+
+/// Line width from /BS, or the old /Border array; 1 when unset or zero
+fn border_width(doc: &Document, dict: &Dictionary) -> f64 {
+    let from_style = dict
+        .get(b"BS")
+        .ok()
+        .and_then(|style| resolve(doc, style).as_dict().ok())
+        .and_then(|style| style.get(b"W").ok())
+        .and_then(|width| resolve(doc, width).as_float().ok());
+    let from_border = || {
+        dict.get(b"Border")
+            .ok()
+            .and_then(|border| numbers(doc, border))
+            .and_then(|border| border.get(2).map(|&width| width as f32))
+    };
+    from_style
+        .or_else(from_border)
+        .map(f64::from)
+        .filter(|width| *width > 0.0)
+        .unwrap_or(1.0)
+}
+
+/// Size operand of the `Tf` operator in a default appearance string
+fn font_size(da: &str) -> Option<f64> {
+    let tokens: Vec<&str> = da.split_whitespace().collect();
+    let tf = tokens.iter().position(|&token| token == "Tf")?;
+    let size: f64 = tokens.get(tf.checked_sub(1)?)?.parse().ok()?;
+    (size > 0.0).then_some(size)
+}
+
+/// `#rrggbb` from gray, RGB or CMYK components
+fn hex_color(components: &[f64]) -> Option<String> {
+    let rgb = match *components {
+        [gray] => [gray, gray, gray],
+        [r, g, b] => [r, g, b],
+        [c, m, y, k] => [
+            (1.0 - c) * (1.0 - k),
+            (1.0 - m) * (1.0 - k),
+            (1.0 - y) * (1.0 - k),
+        ],
+        _ => return None,
+    };
+    let [r, g, b] = rgb.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+/// Parse a PDF date such as `D:20240315093000+01'00'`; missing fields default
+/// to the start of the period and a missing offset to UTC
+fn parse_pdf_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.strip_prefix("D:").unwrap_or(value);
+    let digits = value.bytes().take_while(u8::is_ascii_digit).count().min(14);
+    if digits < 4 {
+        return None;
+    }
+    let padded = format!("{}{}", &value[..digits], &"0101000000"[digits - 4..]);
+    let local = NaiveDateTime::parse_from_str(&padded, "%Y%m%d%H%M%S").ok()?;
+
+    let zone = value[digits..].trim_start_matches(|c: char| c.is_ascii_digit());
+    let offset = match zone.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let fields: Vec<i32> = zone[1..]
+                .split('\'')
+                .filter_map(|field| field.parse().ok())
+                .collect();
+            let seconds = fields.first().copied().unwrap_or(0) * 3600
+                + fields.get(1).copied().unwrap_or(0) * 60;
+            if sign == '-' {
+                -seconds
+            } else {
+                seconds
+            }
+        }
+        _ => 0,
+    };
+    FixedOffset::east_opt(offset)?
+        .from_local_datetime(&local)
+        .single()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// What became of the annotations on a page that were imported from it
+#[derive(Default)]
+struct SourcedChanges<'a> {
+    /// Every annotation on the page that one of ours came from
+    matched: Vec<ObjectId>,
+    /// Edited here: the object, our annotation and the dictionary it replaces
+    edited: Vec<(ObjectId, &'a Annotation, Dictionary)>,
+    /// Deleted here, with their popups
+    removed: Vec<ObjectId>,
+    deleted: usize,
+}
+
+/// Match the page's annotations to ours by source key, the way they were
+/// keyed when they were imported
+fn sourced_changes<'a>(
+    doc: &Document,
+    page_number: u32,
+    page_id: ObjectId,
+    sourced: &HashMap<&str, &'a Annotation>,
+) -> SourcedChanges<'a> {
+    let mut changes = SourcedChanges::default();
+    if sourced.is_empty() {
+        return changes;
+    }
+    for annot_id in page_annotation_ids(doc, page_id) {
+        let Ok(dict) = doc.get_dictionary(annot_id) else {
+            continue;
+        };
+        let Some(original) = read_annotation(doc, dict, "", page_number) else {
+            continue;
+        };
+        let key = text_value(doc, dict, b"NM")
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| fingerprint(&original));
+        let Some(annotation) = sourced.get(key.as_str()) else {
+            continue;
+        };
+
+        changes.matched.push(annot_id);
+        if annotation.deleted_at.is_some() {
+            changes.removed.push(annot_id);
+            changes
+                .removed
+                .extend(dict.get(b"Popup").and_then(Object::as_reference).ok());
+            changes.deleted += 1;
+        } else if annotation.geometry != original.geometry
+            || annotation.content != original.content
+            // A named colour is written as the RGB it stands for
+            || color_rgb(&annotation.color) != color_rgb(&original.color)
+            || annotation.author != original.author
+        {
+            changes.edited.push((annot_id, *annotation, dict.clone()));
+        }
+    }
+    changes
+}
+
+/// References in the page's /Annots to annotations we wrote before
+fn previously_exported(
+    doc: &Document,
+    page_id: ObjectId,
+    known_ids: &HashSet<String>,
+) -> Vec<ObjectId> {
+    page_annotation_ids(doc, page_id)
+        .into_iter()
+        .filter(|id| {
+            doc.get_dictionary(*id)
+                .and_then(|annot| annot.get(b"NM"))
+                .and_then(lopdf::decode_text_string)
+                .is_ok_and(|name| known_ids.contains(&name))
+        })
+        .collect()
+}
+
+/// Indirect annotations listed in the page's /Annots
+fn page_annotation_ids(doc: &Document, page_id: ObjectId) -> Vec<ObjectId> {
+    let Ok(page) = doc.get_dictionary(page_id) else {
+        return Vec::new();
+    };
+    let annots = match page.get(b"Annots") {
+        Ok(Object::Reference(id)) => doc.get_object(*id).and_then(Object::as_array).ok(),
+        Ok(Object::Array(annots)) => Some(annots),
+        _ => None,
+    };
+
+    annots
+        .into_iter()
+        .flatten()
+        .filter_map(|annot| annot.as_reference().ok())
+        .collect()
+}
+
+/// Replace the page's /Annots with its other annotations plus `added`
+fn set_page_annotations(
+    pdf: &mut IncrementalDocument,
+    page_id: ObjectId,
+    removed: &[ObjectId],
+    added: Vec<Object>,
+) -> Result<(), String> {
+    pdf.opt_clone_object_to_new_document(page_id)
+        .map_err(|e| e.to_string())?;
+    let existing = {
+        let page = pdf
+            .new_document
+            .get_dictionary(page_id)
+            .map_err(|e| e.to_string())?;
+        match page.get(b"Annots") {
+            Ok(Object::Reference(id)) => pdf
+                .get_prev_documents()
+                .get_object(*id)
+                .and_then(Object::as_array)
+                .cloned()
+                .unwrap_or_default(),
+            Ok(Object::Array(annots)) => annots.clone(),
+            _ => Vec::new(),
+        }
+    };
+
+    let mut annots: Vec<Object> = existing
+        .into_iter()
+        .filter(|annot| {
+            annot
+                .as_reference()
+                .map_or(true, |id| !removed.contains(&id))
+        })
+        .collect();
+    annots.extend(added);
+
+    // Written inline on the page copy; an indirect array stays untouched in the old revision
+    pdf.new_document
+        .get_dictionary_mut(page_id)
+        .map_err(|e| e.to_string())?
+        .set("Annots", Object::Array(annots));
+    Ok(())
+}
+
+/// Annotation dictionary with its appearance stream, which is added to `doc`;
+/// the caller sets the page it belongs to
+fn annotation_dictionary(
+    doc: &mut Document,
+    annotation: &Annotation,
+) -> Result<Dictionary, String> {
+    let [r, g, b] = color_rgb(&annotation.color)
+        .ok_or_else(|| format!("Invalid annotation colour: {}", annotation.color))?;
+    let rgb = [r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0];
+    let color = format!("{} {} {}", num(rgb[0]), num(rgb[1]), num(rgb[2]));
+
+    let mut dict = dictionary! {
+        "Type" => "Annot",
+        "NM" => text_string(annotation.exchange_name()),
+        "M" => Object::string_literal(pdf_date(annotation.updated_at)),
+        "CreationDate" => Object::string_literal(pdf_date(annotation.created_at)),
+        "F" => FLAG_PRINT,
+        "C" => rgb.iter().map(|&c| real(c)).collect::<Vec<Object>>(),
+    };
+    if let Some(content) = &annotation.content {
+        dict.set("Contents", text_string(content));
+    }
+    if let Some(author) = &annotation.author {
+        dict.set("T", text_string(author));
+    }
+
+    let rect = annotation_rect(&annotation.geometry);
+    let mut resources = Dictionary::new();
+    let (subtype, content) = match &annotation.geometry {
+        AnnotationGeometry::Highlight { quads } => {
+            dict.set("QuadPoints", quad_points(quads));
+            dict.set("CA", Object::Real(HIGHLIGHT_OPACITY));
+            let gs = doc.add_object(dictionary! {
+                "Type" => "ExtGState",
+                "BM" => "Multiply",
+                "ca" => Object::Real(HIGHLIGHT_OPACITY),
+            });
+            resources.set("ExtGState", dictionary! { "GS0" => gs });
+            let mut ops = format!("/GS0 gs {} rg\n", color);
+            for quad in quads {
+                let [tl, tr, bl, br] = quad.points();
+                let _ = writeln!(
+                    ops,
+                    "{} {} m {} {} l {} {} l {} {} l h f",
+                    num(tl.x),
+                    num(tl.y),
+                    num(tr.x),
+                    num(tr.y),
+                    num(br.x),
+                    num(br.y),
+                    num(bl.x),
+                    num(bl.y)
+                );
+            }
+            ("Highlight", ops)
+        }
+        AnnotationGeometry::Underline { quads } | AnnotationGeometry::Strikeout { quads } => {
+            dict.set("QuadPoints", quad_points(quads));
+            let strikeout = matches!(annotation.geometry, AnnotationGeometry::Strikeout { .. });
+            let mut ops = format!("{} RG\n", color);
+            for quad in quads {
+                // Line along the bottom of the text, or through its middle
+                let (from, to) = if strikeout {
+                    (
+                        midpoint(quad.top_left, quad.bottom_left),
+                        midpoint(quad.top_right, quad.bottom_right),
+                    )
+                } else {
+                    (quad.bottom_left, quad.bottom_right)
+                };
+                let height = distance(quad.top_left, quad.bottom_left);
+                let _ = writeln!(
+                    ops,
+                    "{} w {} {} m {} {} l S",
+                    num((height / 14.0).max(0.5)),
+                    num(from.x),
+                    num(from.y),
+                    num(to.x),
+                    num(to.y)
+                );
+            }
+            let subtype = if strikeout { "StrikeOut" } else { "Underline" };
+            (subtype, ops)
+        }
+        AnnotationGeometry::Note { .. } => {
+            dict.set("Name", "Comment");
+            dict.set("Open", false);
+            let mut ops = format!(
+                "{} rg 0 G 0.75 w {} {} {} {} re B\n",
+                color,
+                num(rect.x + 1.0),
+                num(rect.y + 4.0),
+                num(rect.width - 2.0),
+                num(rect.height - 5.0)
+            );
+            for line in 0..3 {
+                let y = rect.y + 14.0 - line as f64 * 3.5;
+                let _ = writeln!(
+                    ops,
+                    "{} {} m {} {} l S",
+                    num(rect.x + 4.0),
+                    num(y),
+                    num(rect.x + rect.width - 4.0),
+                    num(y)
+                );
+            }
+            ("Text", ops)
+        }
+        AnnotationGeometry::Ink {
+            strokes,
+            line_width,
+        } => {
+            let ink_list: Vec<Object> = strokes
+                .iter()
+                .map(|stroke| {
+                    Object::Array(stroke.iter().flat_map(|p| [real(p.x), real(p.y)]).collect())
+                })
+                .collect();
+            dict.set("InkList", ink_list);
+            dict.set("BS", dictionary! { "W" => real(*line_width) });
+            let mut ops = format!("{} RG {} w 1 J 1 j\n", color, num(*line_width));
+            for stroke in strokes {
+                let mut points = stroke.iter();
+                if let Some(first) = points.next() {
+                    let _ = write!(ops, "{} {} m", num(first.x), num(first.y));
+                    if stroke.len() == 1 {
+                        // A dot: a zero-length line drawn with round caps
+                        let _ = write!(ops, " {} {} l", num(first.x), num(first.y));
+                    }
+                    for point in points {
+                        let _ = write!(ops, " {} {} l", num(point.x), num(point.y));
+                    }
+                    ops.push_str(" S\n");
+                }
+            }
+            ("Ink", ops)
+        }
+        AnnotationGeometry::Rectangle { rect, line_width } => {
+            dict.set("BS", dictionary! { "W" => real(*line_width) });
+            let inset = line_width / 2.0;
+            let ops = format!(
+                "{} RG {} w {} {} {} {} re S\n",
+                color,
+                num(*line_width),
+                num(rect.x + inset),
+                num(rect.y + inset),
+                num((rect.width - line_width).max(0.0)),
+                num((rect.height - line_width).max(0.0))
+            );
+            ("Square", ops)
+        }
+        AnnotationGeometry::FreeText { rect, font_size } => {
+            let font = doc.add_object(dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => "Helvetica",
+                "Encoding" => "WinAnsiEncoding",
+            });
+            resources.set("Font", dictionary! { "Helv" => font });
+            dict.set(
+                "DA",
+                Object::string_literal(format!("/Helv {} Tf {} rg", num(*font_size), color)),
+            );
+            let text = annotation.content.as_deref().unwrap_or_default();
+            ("FreeText", free_text_ops(text, rect, *font_size, &color))
+        }
+    };
+
+    dict.set("Subtype", subtype);
+    dict.set("Rect", rect_array(rect));
+    let appearance = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => rect_array(rect),
+            "Resources" => resources,
+        },
+        content.into_bytes(),
+    );
+    let appearance_id = doc.add_object(appearance);
+    dict.set("AP", dictionary! { "N" => appearance_id });
+    Ok(dict)
+}
+
+/// Box an annotation covers on the page
+pub(super) fn annotation_rect(geometry: &AnnotationGeometry) -> Rect {
+    match geometry {
+        // The anchor is the top-left corner of the icon
+        AnnotationGeometry::Note { anchor } => Rect {
+            x: anchor.x,
+            y: anchor.y - NOTE_ICON_SIZE,
+            width: NOTE_ICON_SIZE,
+            height: NOTE_ICON_SIZE,
+        },
+        AnnotationGeometry::Ink { line_width, .. } => pad(geometry.bounds(), line_width / 2.0),
+        _ => geometry.bounds(),
+    }
+}
+
+/// Text laid out from the top of `rect`, wrapped on words and clipped to the box
+fn free_text_ops(text: &str, rect: &Rect, font_size: f64, color: &str) -> String {
+    // Average Helvetica glyph width, close enough for wrapping
+    let max_chars = ((rect.width - 4.0) / (font_size * 0.5)).floor().max(1.0) as usize;
+    let leading = font_size * 1.2;
+
+    let mut ops = format!(
+        "{} {} {} {} re W n\nBT /Helv {} Tf {} rg {} TL {} {} Td\n",
+        num(rect.x),
+        num(rect.y),
+        num(rect.width),
+        num(rect.height),
+        num(font_size),
+        color,
+        num(leading),
+        num(rect.x + 2.0),
+        num(rect.y + rect.height - font_size - 2.0)
+    );
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                let _ = writeln!(ops, "({}) Tj T*", pdf_literal(&line));
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        let _ = writeln!(ops, "({}) Tj T*", pdf_literal(&line));
+    }
+    ops.push_str("ET\n");
+    ops
+}
+
+/// Escape text for a literal string shown with a WinAnsi font; characters
+/// outside Latin-1 become '?'
+fn pdf_literal(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(escaped, "\\{:03o}", c as u32);
+            }
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+fn quad_points(quads: &[Quad]) -> Vec<Object> {
+    quads
+        .iter()
+        .flat_map(Quad::points)
+        .flat_map(|p| [real(p.x), real(p.y)])
+        .collect()
+}
+
+fn rect_array(rect: Rect) -> Vec<Object> {
+    vec![
+        real(rect.x),
+        real(rect.y),
+        real(rect.x + rect.width),
+        real(rect.y + rect.height),
+    ]
+}
+
+fn pad(rect: Rect, by: f64) -> Rect {
+    Rect {
+        x: rect.x - by,
+        y: rect.y - by,
+        width: rect.width + 2.0 * by,
+        height: rect.height + 2.0 * by,
+    }
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point {
+        x: (a.x + b.x) / 2.0,
+        y: (a.y + b.y) / 2.0,
+    }
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+fn real(value: f64) -> Object {
+    Object::Real(value as f32)
+}
+
+/// Number for a content stream, without needless trailing zeros
+pub(super) fn num(value: f64) -> String {
+    let formatted = format!("{:.3}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+pub(super) fn pdf_date(time: DateTime<Utc>) -> String {
+    time.format("D:%Y%m%d%H%M%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{Database, DocumentRecord, DATABASE_FILE_NAME};
+    use lopdf::content::Content;
+
+    fn blank_pdf(path: &Path) {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let content = doc.add_object(Stream::new(
+            Dictionary::new(),
+            Content { operations: vec![] }.encode().unwrap(),
+        ));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    fn annotation(id: &str, geometry: AnnotationGeometry, content: Option<&str>) -> Annotation {
+        let now = Utc::now();
+        Annotation {
+            id: id.to_string(),
+            document_id: "doc".to_string(),
+            page_number: 1,
+            geometry,
+            content: content.map(str::to_string),
+            color: "#ffeb3b".to_string(),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            author: None,
+            reply_to: None,
+            source_key: None,
+        }
+    }
+
+    fn page_subtypes(path: &Path) -> Vec<String> {
+        let doc = Document::load(path).unwrap();
+        let page_id = doc.get_pages()[&1];
+        doc.get_page_annotations(page_id)
+            .unwrap()
+            .iter()
+            .map(|annot| {
+                String::from_utf8_lossy(annot.get(b"Subtype").unwrap().as_name().unwrap())
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn appends_annotations_and_replaces_earlier_exports() {
+        let dir = std::env::temp_dir().join(format!("pdf-annotations-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.pdf");
+        let output = dir.join("annotated.pdf");
+        blank_pdf(&source);
+        let original = std::fs::read(&source).unwrap();
+
+        let annotations = vec![
+            annotation(
+                "h1",
+                AnnotationGeometry::Highlight {
+                    quads: vec![Quad {
+                        top_left: Point { x: 72.0, y: 710.0 },
+                        top_right: Point { x: 300.0, y: 710.0 },
+                        bottom_left: Point { x: 72.0, y: 698.0 },
+                        bottom_right: Point { x: 300.0, y: 698.0 },
+                    }],
+                },
+                Some("Key (idea)"),
+            ),
+            annotation(
+                "n1",
+                AnnotationGeometry::Note {
+                    anchor: Point { x: 500.0, y: 700.0 },
+                },
+                Some("Überprüfen"),
+            ),
+            annotation(
+                "f1",
+                AnnotationGeometry::FreeText {
+                    rect: Rect {
+                        x: 72.0,
+                        y: 100.0,
+                        width: 200.0,
+                        height: 40.0,
+                    },
+                    font_size: 12.0,
+                },
+                Some("Remember this"),
+            ),
+        ];
+        let known: HashSet<String> = annotations.iter().map(|a| a.id.clone()).collect();
+
+        let export = write_pdf_annotations(&source, &output, &annotations, &known).unwrap();
+        assert_eq!(export.written, 3);
+        let written = std::fs::read(&output).unwrap();
+        assert!(written.starts_with(&original));
+        assert_eq!(page_subtypes(&output), ["Highlight", "Text", "FreeText"]);
+
+        // Exporting the annotated file again replaces our annotations in place
+        let again = write_pdf_annotations(&output, &output, &annotations[..1], &known).unwrap();
+        assert_eq!(again.replaced, 3);
+        assert!(std::fs::read(&output).unwrap().starts_with(&written));
+        assert_eq!(page_subtypes(&output), ["Highlight"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn imports_annotations_made_elsewhere_once() {
+        let dir = std::env::temp_dir().join(format!("pdf-import-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reviewed.pdf");
+        blank_pdf(&path);
+
+        let mut doc = Document::load(&path).unwrap();
+        let page_id = doc.get_pages()[&1];
+        let highlight = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Highlight",
+            "NM" => text_string("acro-1"),
+            "T" => text_string("Reviewer"),
+            "Contents" => text_string("Check this claim"),
+            "M" => Object::string_literal("D:20240315093000+01'00'"),
+            "C" => vec![1.into(), 1.into(), 0.into()],
+            "Rect" => vec![72.into(), 698.into(), 300.into(), 710.into()],
+            "QuadPoints" => vec![
+                72.into(), 710.into(), 300.into(), 710.into(),
+                72.into(), 698.into(), 300.into(), 698.into(),
+            ],
+        });
+        let reply = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "IRT" => highlight,
+            "T" => text_string("Author"),
+            "Contents" => text_string("Agreed, fixed"),
+            "Rect" => vec![72.into(), 698.into(), 92.into(), 718.into()],
+        });
+        let status = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "IRT" => highlight,
+            "StateModel" => text_string("Review"),
+            "State" => text_string("Accepted"),
+            "Rect" => vec![72.into(), 698.into(), 92.into(), 718.into()],
+        });
+        let popup = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Popup",
+            "Parent" => highlight,
+            "Rect" => vec![400.into(), 600.into(), 600.into(), 700.into()],
+        });
+        let ours = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Square",
+            "NM" => text_string("local-1"),
+            "Rect" => vec![100.into(), 100.into(), 200.into(), 200.into()],
+        });
+        doc.get_dictionary_mut(page_id).unwrap().set(
+            "Annots",
+            vec![
+                reply.into(),
+                highlight.into(),
+                status.into(),
+                popup.into(),
+                ours.into(),
+            ],
+        );
+        doc.save(&path).unwrap();
+
+        let own_ids = HashSet::from(["local-1".to_string()]);
+        let found = read_pdf_annotations(&path, "doc", &own_ids).unwrap();
+        assert_eq!(found.len(), 2);
+        let reply = &found[0];
+        assert_eq!(reply.reply_to_key.as_deref(), Some("acro-1"));
+        assert!(reply
+            .annotation
+            .source_key
+            .as_deref()
+            .unwrap()
+            .starts_with("pdf:"));
+        let highlight = &found[1].annotation;
+        assert_eq!(highlight.color, "#ffff00");
+        assert_eq!(highlight.author.as_deref(), Some("Reviewer"));
+        assert_eq!(
+            highlight.updated_at.to_rfc3339(),
+            "2024-03-15T08:30:00+00:00"
+        );
+        assert_eq!(highlight.geometry.bounds().width, 228.0);
+
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        let now = Utc::now();
+        db.insert_document(&DocumentRecord {
+            id: "doc".to_string(),
+            file_path: path.to_string_lossy().to_string(),
+            file_hash: "hash".to_string(),
+            title: "Reviewed".to_string(),
+            total_pages: 1,
+            created_at: now,
+            updated_at: now,
+            last_opened_at: now,
+        })
+        .unwrap();
+        assert_eq!(db.import_pdf_annotations(&found).unwrap(), 2);
+        // Reopening reads fresh ids but the same keys, so nothing is added
+        let again = read_pdf_annotations(&path, "doc", &own_ids).unwrap();
+        assert_eq!(db.import_pdf_annotations(&again).unwrap(), 0);
+
+        let stored = db.annotations("doc", None, false).unwrap();
+        assert_eq!(stored.len(), 2);
+        let parent = stored
+            .iter()
+            .find(|a| a.author.as_deref() == Some("Reviewer"));
+        let results = db.search_annotations("FIXED", None, 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].reply_to, parent.map(|a| a.id.clone()));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writes_back_edits_and_deletions_of_imported_annotations() {
+        let dir = std::env::temp_dir().join(format!("pdf-write-back-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reviewed.pdf");
+        let output = dir.join("edited.pdf");
+        blank_pdf(&path);
+
+        let mut doc = Document::load(&path).unwrap();
+        let page_id = doc.get_pages()[&1];
+        let highlight = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Highlight",
+            "NM" => text_string("acro-1"),
+            "Contents" => text_string("Check this claim"),
+            "C" => vec![1.into(), 1.into(), 0.into()],
+            "Rect" => vec![72.into(), 698.into(), 300.into(), 710.into()],
+            "QuadPoints" => vec![
+                72.into(), 710.into(), 300.into(), 710.into(),
+                72.into(), 698.into(), 300.into(), 698.into(),
+            ],
+        });
+        let reply = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "IRT" => highlight,
+            "Contents" => text_string("Unnamed reply"),
+            "Rect" => vec![72.into(), 698.into(), 92.into(), 718.into()],
+        });
+        let stale = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Square",
+            "NM" => text_string("acro-2"),
+            "Rect" => vec![100.into(), 100.into(), 200.into(), 200.into()],
+        });
+        let popup = doc.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Popup",
+            "Parent" => stale,
+            "Rect" => vec![400.into(), 600.into(), 600.into(), 700.into()],
+        });
+        doc.get_dictionary_mut(stale).unwrap().set("Popup", popup);
+        doc.get_dictionary_mut(page_id).unwrap().set(
+            "Annots",
+            vec![highlight.into(), reply.into(), stale.into(), popup.into()],
+        );
+        doc.save(&path).unwrap();
+
+        let mut annotations: Vec<Annotation> = read_pdf_annotations(&path, "doc", &HashSet::new())
+            .unwrap()
+            .into_iter()
+            .map(|found| found.annotation)
+            .collect();
+        assert_eq!(annotations.len(), 3);
+        annotations[0].content = Some("Claim checked".to_string());
+        annotations[1].content = Some("Reply, reworded".to_string());
+        annotations[2].deleted_at = Some(Utc::now());
+        let known: HashSet<String> = annotations.iter().map(|a| a.id.clone()).collect();
+
+        let export = write_pdf_annotations(&path, &output, &annotations, &known).unwrap();
+        assert_eq!((export.written, export.updated, export.removed), (0, 2, 1));
+
+        let written = Document::load(&output).unwrap();
+        let annots = page_annotation_ids(&written, written.get_pages()[&1]);
+        // Edited annotations keep their object, so the reply still points at its parent
+        assert_eq!(annots, [highlight, reply]);
+        let rewritten = written.get_dictionary(reply).unwrap();
+        assert_eq!(
+            rewritten.get(b"IRT").unwrap().as_reference().unwrap(),
+            highlight
+        );
+        assert_eq!(
+            text_value(&written, rewritten, b"NM").as_deref(),
+            Some(annotations[1].id.as_str())
+        );
+
+        // Reading the file back finds the edit and skips the annotation that is now ours
+        let found = read_pdf_annotations(&output, "doc", &known).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].annotation.source_key.as_deref(), Some("acro-1"));
+        assert_eq!(
+            found[0].annotation.content.as_deref(),
+            Some("Claim checked")
+        );
+
+        // Nothing has changed since, so exporting again leaves them alone
+        let again = write_pdf_annotations(&output, &output, &annotations, &known).unwrap();
+        assert_eq!(
+            (again.written, again.replaced, again.updated, again.removed),
+            (0, 0, 0, 0)
+        );
+        let written = Document::load(&output).unwrap();
+        assert_eq!(
+            page_annotation_ids(&written, written.get_pages()[&1]),
+            [highlight, reply]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
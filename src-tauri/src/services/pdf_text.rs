@@ -0,0 +1,896 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Share of the page height at the top and bottom searched for running headers and footers
+const MARGIN_BAND: f32 = 0.1;
+/// A margin line repeated on at least this share of pages is a running header/footer
+const REPEAT_SHARE: f32 = 0.4;
+/// Width in thousandths of an em assumed for glyphs a font gives no width for
+const DEFAULT_GLYPH_WIDTH: f32 = 500.0;
+
+/// Helvetica widths for codes 32 to 126, from its AFM; Arial shares them
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+/// Times-Roman widths for codes 32 to 126, from its AFM
+const TIMES_WIDTHS: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 180, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611,
+    556, 722, 722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722,
+    722, 611, 333, 278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500,
+    278, 778, 500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+/// One line of text as laid out on the page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextLine {
+    pub text: String,
+    /// Baseline start in PDF user space (origin bottom-left)
+    pub x: f32,
+    pub y: f32,
+    pub font_size: f32,
+    /// Left edge of each character of `text` in user space, then the right
+    /// edge of the last one
+    #[serde(default)]
+    pub char_x: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageText {
+    pub page_number: u32,
+    pub width: f32,
+    pub height: f32,
+    pub lines: Vec<TextLine>,
+}
+
+/// A page left out of the reading text because it could not be read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedPage {
+    pub page_number: u32,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    pub title: String,
+    /// 1 for top-level entries
+    pub level: usize,
+    pub page_number: u32,
+}
+
+/// Options for turning pages into text meant to be read aloud
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadingTextOptions {
+    pub skip_headers_footers: bool,
+    pub read_captions: bool,
+}
+
+impl Default for ReadingTextOptions {
+    fn default() -> Self {
+        Self {
+            skip_headers_footers: true,
+            read_captions: false,
+        }
+    }
+}
+
+pub fn load_pdf_document(path: &Path) -> Result<Document, String> {
+    Document::load(path).map_err(|e| format!("Failed to open PDF {}: {}", path.display(), e))
+}
+
+/// The document outline flattened in reading order; empty when the PDF has none
+pub fn read_outline(doc: &Document) -> Vec<OutlineEntry> {
+    doc.get_toc()
+        .map(|toc| {
+            toc.toc
+                .into_iter()
+                .map(|entry| OutlineEntry {
+                    title: entry.title.trim().to_string(),
+                    level: entry.level,
+                    page_number: entry.page as u32,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Text lines of a page in content-stream order, which for most producers is
+/// reading order (column by column)
+pub fn extract_page_text(doc: &Document, page_number: u32) -> Result<PageText, String> {
+    let page_id = *doc
+        .get_pages()
+        .get(&page_number)
+        .ok_or_else(|| format!("Page {} does not exist", page_number))?;
+
+    let (width, height) = page_size(doc, page_id);
+
+    let fonts: HashMap<Vec<u8>, PageFont> = doc
+        .get_page_fonts(page_id)
+        .map_err(|e| format!("Failed to read fonts on page {}: {}", page_number, e))?
+        .into_iter()
+        .filter_map(|(name, font)| {
+            let page_font = PageFont {
+                encoding: font.get_font_encoding(doc).ok()?,
+                widths: GlyphWidths::of(doc, font),
+            };
+            Some((name, page_font))
+        })
+        .collect();
+
+    let content = doc.get_page_content(page_id);
+    let content = Content::decode(&content)
+        .map_err(|e| format!("Failed to parse page {}: {}", page_number, e))?;
+
+    let mut reader = TextReader::default();
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        let number = |index: usize| {
+            operands
+                .get(index)
+                .and_then(|o| o.as_float().ok())
+                .unwrap_or(0.0)
+        };
+
+        match operation.operator.as_str() {
+            "q" => reader.ctm_stack.push(reader.ctm),
+            "Q" => reader.ctm = reader.ctm_stack.pop().unwrap_or(IDENTITY),
+            "cm" => {
+                let m = [
+                    number(0),
+                    number(1),
+                    number(2),
+                    number(3),
+                    number(4),
+                    number(5),
+                ];
+                reader.ctm = multiply(&m, &reader.ctm);
+            }
+            "BT" => {
+                reader.text_matrix = IDENTITY;
+                reader.line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                reader.font = operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .map(|name| name.to_vec());
+                reader.font_size = number(1);
+            }
+            "TL" => reader.leading = number(0),
+            "Tc" => reader.char_spacing = number(0),
+            "Tw" => reader.word_spacing = number(0),
+            "Tz" => reader.horizontal_scale = number(0) / 100.0,
+            "Td" => reader.move_line(number(0), number(1)),
+            "TD" => {
+                reader.leading = -number(1);
+                reader.move_line(number(0), number(1));
+            }
+            "Tm" => {
+                let m = [
+                    number(0),
+                    number(1),
+                    number(2),
+                    number(3),
+                    number(4),
+                    number(5),
+                ];
+                reader.text_matrix = m;
+                reader.line_matrix = m;
+            }
+            "T*" => reader.move_line(0.0, -reader.leading),
+            "Tj" => reader.show(operands.first(), &fonts),
+            "'" => {
+                reader.move_line(0.0, -reader.leading);
+                reader.show(operands.first(), &fonts);
+            }
+            "\"" => {
+                reader.word_spacing = number(0);
+                reader.char_spacing = number(1);
+                reader.move_line(0.0, -reader.leading);
+                reader.show(operands.get(2), &fonts);
+            }
+            "TJ" => {
+                if let Some(Object::Array(items)) = operands.first() {
+                    for item in items {
+                        match item {
+                            // Large negative adjustments are word gaps
+                            Object::Integer(_) | Object::Real(_) => {
+                                let adjustment = item.as_float().unwrap_or(0.0);
+                                if adjustment < -200.0 {
+                                    reader.push_space();
+                                }
+                                reader.advance(-adjustment / 1000.0 * reader.font_size);
+                            }
+                            _ => reader.show(Some(item), &fonts),
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let lines = reader
+        .lines
+        .into_iter()
+        .map(|mut line| {
+            collapse_whitespace(&mut line);
+            line
+        })
+        .filter(|line| !line.text.is_empty())
+        .collect();
+
+    Ok(PageText {
+        page_number,
+        width,
+        height,
+        lines,
+    })
+}
+
+/// Drop running headers, footers and page numbers from a run of pages.
+///
+/// Lines near the top or bottom edge count as running text when they are
+/// page numbers or when, ignoring digits, they repeat across pages.
+pub fn strip_headers_footers(pages: &mut [PageText]) {
+    let mut repeats: HashMap<String, usize> = HashMap::new();
+    for page in pages.iter() {
+        let mut seen: Vec<String> = page
+            .lines
+            .iter()
+            .filter(|line| in_margin(page.height, line))
+            .map(|line| margin_key(&line.text))
+            .collect();
+        seen.sort();
+        seen.dedup();
+        for key in seen {
+            *repeats.entry(key).or_default() += 1;
+        }
+    }
+
+    let threshold = ((pages.len() as f32 * REPEAT_SHARE).ceil() as usize).max(2);
+    for page in pages.iter_mut() {
+        let height = page.height;
+        page.lines.retain(|line| {
+            if !in_margin(height, line) {
+                return true;
+            }
+            let key = margin_key(&line.text);
+            !(is_page_number(&key) || repeats.get(&key).copied().unwrap_or(0) >= threshold)
+        });
+    }
+}
+
+/// Remove figure and table captions: a line starting with "Figure 3", "Fig. 3"
+/// or "Table 3" and the lines that continue it
+pub fn strip_captions(page: &mut PageText) {
+    let mut kept = Vec::with_capacity(page.lines.len());
+    let mut caption: Option<TextLine> = None;
+
+    for line in std::mem::take(&mut page.lines) {
+        if let Some(previous) = &caption {
+            let gap = previous.y - line.y;
+            let continues = gap > 0.0
+                && gap < previous.font_size * 1.6
+                && (line.font_size - previous.font_size).abs() < previous.font_size * 0.1;
+            if continues {
+                caption = Some(line);
+                continue;
+            }
+            caption = None;
+        }
+
+        if is_caption_start(&line.text) {
+            caption = Some(line);
+        } else {
+            kept.push(line);
+        }
+    }
+
+    page.lines = kept;
+}
+
+/// Join the lines of a page into flowing text, undoing end-of-line hyphenation
+pub fn page_reading_text(page: &PageText) -> String {
+    let mut text = String::new();
+    for line in &page.lines {
+        if text.ends_with('-') && line.text.chars().next().is_some_and(|c| c.is_lowercase()) {
+            text.pop();
+        } else if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&line.text);
+    }
+    text
+}
+
+/// Text of a page range ready for speech, one string per page. Pages that
+/// can't be read are left out and listed with their errors.
+pub fn read_pages(
+    doc: &Document,
+    start_page: u32,
+    end_page: u32,
+    options: &ReadingTextOptions,
+) -> (Vec<(u32, String)>, Vec<SkippedPage>) {
+    let mut pages = Vec::new();
+    let mut skipped = Vec::new();
+    for page_number in start_page..=end_page {
+        match extract_page_text(doc, page_number) {
+            Ok(page) => pages.push(page),
+            Err(error) => skipped.push(SkippedPage { page_number, error }),
+        }
+    }
+
+    if options.skip_headers_footers {
+        strip_headers_footers(&mut pages);
+    }
+    if !options.read_captions {
+        pages.iter_mut().for_each(strip_captions);
+    }
+
+    let pages = pages
+        .iter()
+        .map(|page| (page.page_number, page_reading_text(page)))
+        .collect();
+    (pages, skipped)
+}
+
+const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn multiply(a: &[f32; 6], b: &[f32; 6]) -> [f32; 6] {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+struct TextReader {
+    ctm: [f32; 6],
+    ctm_stack: Vec<[f32; 6]>,
+    text_matrix: [f32; 6],
+    line_matrix: [f32; 6],
+    font: Option<Vec<u8>>,
+    font_size: f32,
+    leading: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    lines: Vec<TextLine>,
+    /// Where the previous fragment ended
+    last_end_x: f32,
+}
+
+impl Default for TextReader {
+    fn default() -> Self {
+        Self {
+            ctm: IDENTITY,
+            ctm_stack: Vec::new(),
+            text_matrix: IDENTITY,
+            line_matrix: IDENTITY,
+            font: None,
+            font_size: 0.0,
+            leading: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            lines: Vec::new(),
+            last_end_x: 0.0,
+        }
+    }
+}
+
+impl TextReader {
+    fn move_line(&mut self, tx: f32, ty: f32) {
+        self.line_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, ty], &self.line_matrix);
+        self.text_matrix = self.line_matrix;
+    }
+
+    /// Move along the line by `tx` unscaled text space units
+    fn advance(&mut self, tx: f32) {
+        let tx = tx * self.horizontal_scale;
+        self.text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, tx, 0.0], &self.text_matrix);
+    }
+
+    fn push_space(&mut self) {
+        if let Some(line) = self.lines.last_mut() {
+            if !line.text.ends_with(' ') {
+                append(line, " ", &[self.last_end_x, self.last_end_x]);
+            }
+        }
+    }
+
+    fn show(&mut self, operand: Option<&Object>, fonts: &HashMap<Vec<u8>, PageFont>) {
+        let Some(Object::String(bytes, _)) = operand else {
+            return;
+        };
+        let Some(font) = self.font.as_ref().and_then(|font| fonts.get(font)) else {
+            return;
+        };
+        let Ok(text) = Document::decode_text(&font.encoding, bytes) else {
+            return;
+        };
+
+        let matrix = multiply(&self.text_matrix, &self.ctm);
+        let (x, y) = (matrix[4], matrix[5]);
+        let font_size = (self.font_size * matrix[2].hypot(matrix[3])).abs().max(1.0);
+
+        // Advance of each byte in text space, as the font's widths give it
+        let advances: Vec<f32> = bytes
+            .iter()
+            .map(|&code| {
+                let spacing = if code == b' ' {
+                    self.char_spacing + self.word_spacing
+                } else {
+                    self.char_spacing
+                };
+                (font.widths.width(code) / 1000.0 * self.font_size + spacing)
+                    * self.horizontal_scale
+            })
+            .collect();
+        let scale = matrix[0].hypot(matrix[1]);
+        let total: f32 = advances.iter().sum();
+        let width = total * scale;
+        let count = text.chars().count();
+        let mut positions = Vec::with_capacity(count + 1);
+        if count == advances.len() {
+            let mut offset = x;
+            for advance in &advances {
+                positions.push(offset);
+                offset += advance * scale;
+            }
+            positions.push(offset);
+        } else {
+            // The encoding did not map bytes to characters one to one
+            positions.extend((0..=count).map(|i| x + width * i as f32 / count.max(1) as f32));
+        }
+
+        match self.lines.last_mut() {
+            Some(line) if (line.y - y).abs() < font_size * 0.3 => {
+                if x - self.last_end_x > font_size * 0.2 && !line.text.ends_with(' ') {
+                    append(line, " ", &[self.last_end_x, x]);
+                }
+                append(line, &text, &positions);
+            }
+            _ => self.lines.push(TextLine {
+                text,
+                x,
+                y,
+                font_size,
+                char_x: positions,
+            }),
+        }
+
+        self.last_end_x = x + width;
+        self.text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, total, 0.0], &self.text_matrix);
+    }
+}
+
+/// A font's encoding and glyph widths
+struct PageFont<'a> {
+    encoding: Encoding<'a>,
+    widths: GlyphWidths,
+}
+
+/// Advance widths of a simple font's glyphs, in thousandths of an em
+struct GlyphWidths {
+    first_char: u32,
+    widths: Vec<f32>,
+    missing: f32,
+}
+
+impl GlyphWidths {
+    /// From the font's /Widths, or for a standard font without them its
+    /// published metrics; bold and oblique faces are close enough to regular
+    fn of(doc: &Document, font: &Dictionary) -> Self {
+        let resolve = |object| doc.dereference(object).ok().map(|(_, resolved)| resolved);
+        let missing = font
+            .get(b"FontDescriptor")
+            .ok()
+            .and_then(resolve)
+            .and_then(|descriptor| descriptor.as_dict().ok())
+            .and_then(|descriptor| descriptor.get(b"MissingWidth").ok())
+            .and_then(|width| width.as_float().ok())
+            .filter(|width| *width > 0.0)
+            .unwrap_or(DEFAULT_GLYPH_WIDTH);
+
+        if let Some(widths) = font
+            .get(b"Widths")
+            .ok()
+            .and_then(resolve)
+            .and_then(|widths| widths.as_array().ok())
+        {
+            let first_char = font
+                .get(b"FirstChar")
+                .and_then(Object::as_i64)
+                .ok()
+                .and_then(|first| u32::try_from(first).ok())
+                .unwrap_or(0);
+            return Self {
+                first_char,
+                widths: widths
+                    .iter()
+                    .map(|width| {
+                        resolve(width)
+                            .and_then(|w| w.as_float().ok())
+                            .unwrap_or(missing)
+                    })
+                    .collect(),
+                missing,
+            };
+        }
+
+        let base_font = font
+            .get(b"BaseFont")
+            .and_then(Object::as_name)
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .unwrap_or_default();
+        // Subsets are named like "ABCDEF+Helvetica"
+        let base_font = base_font
+            .split_once('+')
+            .map_or(base_font.as_str(), |(_, name)| name);
+        let standard: &[u16] =
+            if base_font.starts_with("Helvetica") || base_font.starts_with("Arial") {
+                &HELVETICA_WIDTHS
+            } else if base_font.starts_with("Times") {
+                &TIMES_WIDTHS
+            } else if base_font.starts_with("Courier") {
+                &[600; 95]
+            } else {
+                &[]
+            };
+        Self {
+            first_char: 32,
+            widths: standard.iter().map(|&width| width as f32).collect(),
+            missing,
+        }
+    }
+
+    fn width(&self, code: u8) -> f32 {
+        (code as u32)
+            .checked_sub(self.first_char)
+            .and_then(|index| self.widths.get(index as usize))
+            .copied()
+            .unwrap_or(self.missing)
+    }
+}
+
+/// Add `text` to the end of `line`; `positions` has the left edge of each
+/// character and then the right edge of the last
+fn append(line: &mut TextLine, text: &str, positions: &[f32]) {
+    line.text.push_str(text);
+    line.char_x.pop();
+    line.char_x.extend_from_slice(positions);
+}
+
+/// Collapse runs of whitespace to single spaces and trim the line, keeping
+/// `char_x` in step with `text`
+fn collapse_whitespace(line: &mut TextLine) {
+    let positions = std::mem::take(&mut line.char_x);
+    let known = positions.len() == line.text.chars().count() + 1;
+    let mut text = String::new();
+    let mut char_x = Vec::new();
+    let mut end = line.x;
+    for (index, c) in line.text.chars().enumerate() {
+        if c.is_whitespace() {
+            if text.is_empty() || text.ends_with(' ') {
+                continue;
+            }
+            text.push(' ');
+        } else {
+            text.push(c);
+            if known {
+                end = positions[index + 1];
+            }
+        }
+        if known {
+            char_x.push(positions[index]);
+        }
+    }
+    if text.ends_with(' ') {
+        text.pop();
+        char_x.pop();
+    }
+    if known {
+        char_x.push(end);
+    }
+    line.text = text;
+    line.char_x = char_x;
+}
+
+fn page_size(doc: &Document, page_id: ObjectId) -> (f32, f32) {
+    let mut node = doc.get_dictionary(page_id).ok();
+    while let Some(dict) = node {
+        if let Some(size) = media_box(doc, dict) {
+            return size;
+        }
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    // US Letter
+    (612.0, 792.0)
+}
+
+fn media_box(doc: &Document, dict: &Dictionary) -> Option<(f32, f32)> {
+    let object = dict.get(b"MediaBox").ok()?;
+    let (_, object) = doc.dereference(object).ok()?;
+    let values: Vec<f32> = object
+        .as_array()
+        .ok()?
+        .iter()
+        .filter_map(|v| v.as_float().ok())
+        .collect();
+    match values[..] {
+        [x0, y0, x1, y1] => Some(((x1 - x0).abs(), (y1 - y0).abs())),
+        _ => None,
+    }
+}
+
+fn in_margin(page_height: f32, line: &TextLine) -> bool {
+    let band = page_height * MARGIN_BAND;
+    line.y > page_height - band || line.y < band
+}
+
+/// Margin text with digits collapsed, so "Chapter 2 · 14" matches "Chapter 2 · 15"
+fn margin_key(text: &str) -> String {
+    let mut key = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_digit() {
+            if !key.ends_with('#') {
+                key.push('#');
+            }
+        } else {
+            key.push(c);
+        }
+    }
+    key
+}
+
+fn is_page_number(key: &str) -> bool {
+    let stripped: String = key
+        .chars()
+        .filter(|c| !matches!(c, '-' | '–' | '—' | ' ' | '.' | '|'))
+        .collect();
+    stripped == "#"
+        || is_roman_numeral(&stripped)
+        || stripped == "page#"
+        || stripped == "page#of#"
+        || stripped == "#of#"
+        || stripped == "#/#"
+}
+
+/// Lowercase roman numerals in their usual spelling only, so words made of the
+/// same letters ("dvd", "civic") are not taken for page numbers
+fn is_roman_numeral(text: &str) -> bool {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut value = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let Some((amount, numeral)) = NUMERALS
+            .iter()
+            .find(|(_, numeral)| rest.starts_with(numeral))
+        else {
+            return false;
+        };
+        value += amount;
+        rest = &rest[numeral.len()..];
+    }
+
+    let mut spelled = String::new();
+    let mut remaining = value;
+    for (amount, numeral) in NUMERALS {
+        while remaining >= amount {
+            spelled.push_str(numeral);
+            remaining -= amount;
+        }
+    }
+    value > 0 && spelled == text
+}
+
+fn is_caption_start(text: &str) -> bool {
+    let lower = text.trim_start().to_lowercase();
+    ["figure", "fig.", "fig ", "table", "chart", "plate"]
+        .iter()
+        .any(|prefix| {
+            lower.strip_prefix(prefix).is_some_and(|rest| {
+                rest.trim_start()
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_digit())
+            })
+        })
+}
+
+/// Chapters covering `start_page..=end_page`: outline entries at the shallowest
+/// level present in the range, each running until the next one
+pub fn chapters_for_range(
+    outline: &[OutlineEntry],
+    start_page: u32,
+    end_page: u32,
+) -> Vec<(String, u32, u32)> {
+    let in_range: Vec<&OutlineEntry> = outline
+        .iter()
+        .filter(|entry| entry.page_number >= start_page && entry.page_number <= end_page)
+        .collect();
+    let Some(level) = in_range.iter().map(|entry| entry.level).min() else {
+        return vec![(
+            format!("Pages {}-{}", start_page, end_page),
+            start_page,
+            end_page,
+        )];
+    };
+
+    let mut starts: BTreeMap<u32, String> = BTreeMap::new();
+    for entry in in_range.iter().filter(|entry| entry.level == level) {
+        starts
+            .entry(entry.page_number)
+            .or_insert_with(|| entry.title.clone());
+    }
+
+    // Pages before the first chapter belong to whichever chapter they continue
+    starts.entry(start_page).or_insert_with(|| {
+        outline
+            .iter()
+            .rfind(|entry| entry.level <= level && entry.page_number < start_page)
+            .map(|entry| entry.title.clone())
+            .unwrap_or_else(|| format!("Pages {}-{}", start_page, start_page))
+    });
+
+    let starts: Vec<(u32, String)> = starts.into_iter().collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, (page, title))| {
+            let end = starts
+                .get(index + 1)
+                .map(|(next, _)| next - 1)
+                .unwrap_or(end_page);
+            (title.clone(), *page, end)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline() -> Vec<OutlineEntry> {
+        [
+            ("Preface", 1, 1),
+            ("Chapter 1", 1, 3),
+            ("Section 1.1", 2, 4),
+            ("Chapter 2", 1, 8),
+            ("Chapter 2 (again)", 1, 8),
+            ("Appendix", 1, 12),
+        ]
+        .into_iter()
+        .map(|(title, level, page_number)| OutlineEntry {
+            title: title.to_string(),
+            level,
+            page_number,
+        })
+        .collect()
+    }
+
+    fn chapter(title: &str, start: u32, end: u32) -> (String, u32, u32) {
+        (title.to_string(), start, end)
+    }
+
+    #[test]
+    fn chapters_run_until_the_next_top_level_entry() {
+        assert_eq!(
+            chapters_for_range(&outline(), 1, 10),
+            [
+                chapter("Preface", 1, 2),
+                chapter("Chapter 1", 3, 7),
+                chapter("Chapter 2", 8, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn range_starting_mid_chapter_continues_that_chapter() {
+        assert_eq!(
+            chapters_for_range(&outline(), 5, 9),
+            [chapter("Chapter 1", 5, 7), chapter("Chapter 2", 8, 9)]
+        );
+    }
+
+    #[test]
+    fn deeper_entries_are_used_when_no_top_level_entry_is_in_range() {
+        assert_eq!(
+            chapters_for_range(&outline(), 4, 6),
+            [chapter("Section 1.1", 4, 6)]
+        );
+    }
+
+    #[test]
+    fn pages_without_an_outline_form_one_chapter() {
+        assert_eq!(chapters_for_range(&[], 2, 5), [chapter("Pages 2-5", 2, 5)]);
+    }
+
+    #[test]
+    fn roman_page_numbers_must_be_real_numerals() {
+        for key in ["xiv", "- iv -", "mcmxcix", "#", "page # of #"] {
+            assert!(is_page_number(key), "{} is a page number", key);
+        }
+        for key in ["dvd", "civic", "mix it", "iiii", "vx"] {
+            assert!(!is_page_number(key), "{} is not a page number", key);
+        }
+    }
+
+    #[test]
+    fn unreadable_pages_are_skipped_and_reported() {
+        use lopdf::{dictionary, Stream};
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        // The second page's parent is missing, so its resources can't be resolved
+        let parents = [pages_id, (999, 0)];
+        let page_ids: Vec<Object> = parents
+            .into_iter()
+            .map(|parent| {
+                let content = doc.add_object(Stream::new(
+                    dictionary! {},
+                    b"BT /F1 12 Tf 72 700 Td (Readable page) Tj ET".to_vec(),
+                ));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => parent,
+                    "Contents" => content,
+                    "Resources" => dictionary! { "Font" => dictionary! { "F1" => font } },
+                })
+                .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => page_ids,
+                "Count" => 2,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+
+        let (pages, skipped) = read_pages(&doc, 1, 2, &ReadingTextOptions::default());
+        assert_eq!(pages, [(1, "Readable page".to_string())]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].page_number, 2);
+    }
+}
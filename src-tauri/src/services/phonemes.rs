@@ -0,0 +1,638 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// File name of an optional CMU Pronouncing Dictionary in the models directory.
+/// Format: `WORD  P1 P2 ...` per line (ARPAbet, stress digits allowed).
+pub const CMUDICT_FILE_NAME: &str = "cmudict.dict";
+
+/// Common words whose spelling the letter-to-sound rules get wrong
+const BUILTIN_EXCEPTIONS: &[(&str, &str)] = &[
+    ("a", "AH"),
+    ("the", "DH AH"),
+    ("of", "AH V"),
+    ("to", "T UW"),
+    ("do", "D UW"),
+    ("does", "D AH Z"),
+    ("who", "HH UW"),
+    ("what", "W AH T"),
+    ("was", "W AA Z"),
+    ("is", "IH Z"),
+    ("his", "HH IH Z"),
+    ("as", "AE Z"),
+    ("has", "HH AE Z"),
+    ("you", "Y UW"),
+    ("your", "Y AO R"),
+    ("one", "W AH N"),
+    ("two", "T UW"),
+    ("said", "S EH D"),
+    ("says", "S EH Z"),
+    ("are", "AA R"),
+    ("were", "W ER"),
+    ("have", "HH AE V"),
+    ("give", "G IH V"),
+    ("live", "L IH V"),
+    ("love", "L AH V"),
+    ("come", "K AH M"),
+    ("some", "S AH M"),
+    ("done", "D AH N"),
+    ("gone", "G AO N"),
+    ("there", "DH EH R"),
+    ("their", "DH EH R"),
+    ("where", "W EH R"),
+    ("here", "HH IH R"),
+    ("this", "DH IH S"),
+    ("that", "DH AE T"),
+    ("these", "DH IY Z"),
+    ("those", "DH OW Z"),
+    ("them", "DH EH M"),
+    ("then", "DH EH N"),
+    ("than", "DH AE N"),
+    ("they", "DH EY"),
+    ("though", "DH OW"),
+    ("through", "TH R UW"),
+    ("thought", "TH AO T"),
+    ("with", "W IH DH"),
+    ("could", "K UH D"),
+    ("would", "W UH D"),
+    ("should", "SH UH D"),
+    ("people", "P IY P AH L"),
+    ("because", "B IH K AO Z"),
+    ("friend", "F R EH N D"),
+    ("laugh", "L AE F"),
+    ("enough", "IH N AH F"),
+    ("eye", "AY"),
+    ("busy", "B IH Z IY"),
+    ("only", "OW N L IY"),
+    ("any", "EH N IY"),
+    ("many", "M EH N IY"),
+    ("again", "AH G EH N"),
+    ("women", "W IH M AH N"),
+    ("water", "W AO T ER"),
+];
+
+/// Broad articulatory class used to weigh substitutions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhonemeClass {
+    Vowel,
+    Stop,
+    Fricative,
+    Affricate,
+    Nasal,
+    Liquid,
+    Glide,
+}
+
+/// ARPAbet phoneme inventory: (symbol, class, example word, spelling hint)
+const PHONEMES: &[(&str, PhonemeClass, &str, &str)] = &[
+    ("AA", PhonemeClass::Vowel, "father", "ah"),
+    ("AE", PhonemeClass::Vowel, "cat", "a"),
+    ("AH", PhonemeClass::Vowel, "cup", "uh"),
+    ("AO", PhonemeClass::Vowel, "thought", "aw"),
+    ("AW", PhonemeClass::Vowel, "cow", "ow"),
+    ("AY", PhonemeClass::Vowel, "hide", "eye"),
+    ("EH", PhonemeClass::Vowel, "bed", "e"),
+    ("ER", PhonemeClass::Vowel, "hurt", "er"),
+    ("EY", PhonemeClass::Vowel, "ate", "ay"),
+    ("IH", PhonemeClass::Vowel, "it", "i"),
+    ("IY", PhonemeClass::Vowel, "eat", "ee"),
+    ("OW", PhonemeClass::Vowel, "oat", "oh"),
+    ("OY", PhonemeClass::Vowel, "toy", "oy"),
+    ("UH", PhonemeClass::Vowel, "hood", "oo"),
+    ("UW", PhonemeClass::Vowel, "two", "oo"),
+    ("B", PhonemeClass::Stop, "be", "b"),
+    ("D", PhonemeClass::Stop, "day", "d"),
+    ("G", PhonemeClass::Stop, "go", "g"),
+    ("K", PhonemeClass::Stop, "key", "k"),
+    ("P", PhonemeClass::Stop, "pea", "p"),
+    ("T", PhonemeClass::Stop, "tea", "t"),
+    ("DH", PhonemeClass::Fricative, "this", "th"),
+    ("F", PhonemeClass::Fricative, "fee", "f"),
+    ("HH", PhonemeClass::Fricative, "he", "h"),
+    ("S", PhonemeClass::Fricative, "see", "s"),
+    ("SH", PhonemeClass::Fricative, "she", "sh"),
+    ("TH", PhonemeClass::Fricative, "think", "th"),
+    ("V", PhonemeClass::Fricative, "vee", "v"),
+    ("Z", PhonemeClass::Fricative, "zoo", "z"),
+    ("ZH", PhonemeClass::Fricative, "measure", "zh"),
+    ("CH", PhonemeClass::Affricate, "cheese", "ch"),
+    ("JH", PhonemeClass::Affricate, "jam", "j"),
+    ("M", PhonemeClass::Nasal, "me", "m"),
+    ("N", PhonemeClass::Nasal, "no", "n"),
+    ("NG", PhonemeClass::Nasal, "sing", "ng"),
+    ("L", PhonemeClass::Liquid, "lee", "l"),
+    ("R", PhonemeClass::Liquid, "read", "r"),
+    ("W", PhonemeClass::Glide, "we", "w"),
+    ("Y", PhonemeClass::Glide, "yes", "y"),
+];
+
+/// Pairs that differ only in voicing, the most common L2 confusion
+const VOICING_PAIRS: &[(&str, &str)] = &[
+    ("P", "B"),
+    ("T", "D"),
+    ("K", "G"),
+    ("F", "V"),
+    ("S", "Z"),
+    ("TH", "DH"),
+    ("SH", "ZH"),
+    ("CH", "JH"),
+];
+
+pub fn phoneme_class(phoneme: &str) -> Option<PhonemeClass> {
+    PHONEMES
+        .iter()
+        .find(|(symbol, ..)| *symbol == phoneme)
+        .map(|(_, class, ..)| *class)
+}
+
+/// Human-readable description, e.g. `"th" (as in "think")`
+pub fn describe_phoneme(phoneme: &str) -> String {
+    match PHONEMES.iter().find(|(symbol, ..)| *symbol == phoneme) {
+        Some((_, _, example, hint)) => format!("\"{}\" (as in \"{}\")", hint, example),
+        None => format!("\"{}\"", phoneme.to_lowercase()),
+    }
+}
+
+/// Broad IPA transcription of an ARPAbet sequence, e.g. `θɪŋk` for TH IH NG K
+pub fn arpabet_to_ipa(phonemes: &[String]) -> String {
+    phonemes
+        .iter()
+        .map(|phoneme| match strip_stress(phoneme).as_str() {
+            "AA" => "ɑ",
+            "AE" => "æ",
+            "AH" => "ʌ",
+            "AO" => "ɔ",
+            "AW" => "aʊ",
+            "AY" => "aɪ",
+            "EH" => "ɛ",
+            "ER" => "ɝ",
+            "EY" => "eɪ",
+            "IH" => "ɪ",
+            "IY" => "i",
+            "OW" => "oʊ",
+            "OY" => "ɔɪ",
+            "UH" => "ʊ",
+            "UW" => "u",
+            "B" => "b",
+            "D" => "d",
+            "G" => "ɡ",
+            "K" => "k",
+            "P" => "p",
+            "T" => "t",
+            "DH" => "ð",
+            "F" => "f",
+            "HH" => "h",
+            "S" => "s",
+            "SH" => "ʃ",
+            "TH" => "θ",
+            "V" => "v",
+            "Z" => "z",
+            "ZH" => "ʒ",
+            "CH" => "tʃ",
+            "JH" => "dʒ",
+            "M" => "m",
+            "N" => "n",
+            "NG" => "ŋ",
+            "L" => "l",
+            "R" => "ɹ",
+            "W" => "w",
+            "Y" => "j",
+            _ => "",
+        })
+        .collect()
+}
+
+/// IPA symbols and their espeak-ng phoneme mnemonics, longest first
+const IPA_TO_ESPEAK: &[(&str, &str)] = &[
+    ("tʃ", "tS"),
+    ("dʒ", "dZ"),
+    ("aʊ", "aU"),
+    ("aɪ", "aI"),
+    ("eɪ", "eI"),
+    ("oʊ", "oU"),
+    ("əʊ", "oU"),
+    ("ɔɪ", "OI"),
+    ("iː", "i:"),
+    ("uː", "u:"),
+    ("ɑː", "A:"),
+    ("ɔː", "O:"),
+    ("ɜː", "3:"),
+    ("ɑ", "A:"),
+    ("æ", "a"),
+    ("ʌ", "V"),
+    ("ɔ", "O:"),
+    ("ɛ", "E"),
+    ("ɝ", "3:"),
+    ("ɜ", "3:"),
+    ("ɚ", "3"),
+    ("ə", "@"),
+    ("ɪ", "I"),
+    ("i", "i:"),
+    ("ʊ", "U"),
+    ("u", "u:"),
+    ("ð", "D"),
+    ("θ", "T"),
+    ("ʃ", "S"),
+    ("ʒ", "Z"),
+    ("ŋ", "N"),
+    ("ɹ", "r"),
+    ("ɾ", "t"),
+    ("ɡ", "g"),
+    ("ˈ", "'"),
+    ("ˌ", ","),
+    ("ː", ":"),
+    (".", ""),
+];
+
+/// Convert broad IPA to the phoneme mnemonics espeak-ng reads inside `[[ ]]`.
+/// Letters shared by both notations (b, d, k, s, ...) pass through.
+pub fn ipa_to_espeak(ipa: &str) -> String {
+    let mut espeak = String::new();
+    let mut rest = ipa;
+    while let Some(c) = rest.chars().next() {
+        match IPA_TO_ESPEAK
+            .iter()
+            .find(|(symbol, _)| rest.starts_with(symbol))
+        {
+            Some((symbol, mnemonic)) => {
+                espeak.push_str(mnemonic);
+                rest = &rest[symbol.len()..];
+            }
+            None => {
+                if c.is_ascii_alphabetic() || c.is_whitespace() {
+                    espeak.push(c);
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    espeak
+}
+
+/// Cost of hearing `b` where `a` was expected, in [0, 1]
+pub fn substitution_cost(a: &str, b: &str) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+    if VOICING_PAIRS
+        .iter()
+        .any(|(x, y)| (*x == a && *y == b) || (*x == b && *y == a))
+    {
+        return 0.5;
+    }
+    match (phoneme_class(a), phoneme_class(b)) {
+        (Some(x), Some(y)) if x == y => 0.7,
+        _ => 1.0,
+    }
+}
+
+/// Grapheme-to-phoneme conversion for English: dictionary lookup first
+/// (CMUdict when available), then letter-to-sound rules
+pub struct PhonemeDictionary {
+    entries: HashMap<String, Vec<String>>,
+}
+
+impl PhonemeDictionary {
+    /// Dictionary containing only the built-in exception list
+    pub fn builtin() -> Self {
+        let entries = BUILTIN_EXCEPTIONS
+            .iter()
+            .map(|(word, phonemes)| (word.to_string(), split_phonemes(phonemes)))
+            .collect();
+        Self { entries }
+    }
+
+    /// Built-in exceptions extended with a CMUdict-format file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read pronunciation dictionary: {}", e))?;
+
+        let mut dictionary = Self::builtin();
+        for line in content.lines() {
+            if line.starts_with(";;;") || line.trim().is_empty() {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let Some(word) = parts.next() else { continue };
+
+            // Alternate pronunciations are written `word(2)`; keep the first
+            if word.contains('(') {
+                continue;
+            }
+
+            let phonemes: Vec<String> = parts
+                .take_while(|p| !p.starts_with('#'))
+                .map(strip_stress)
+                .collect();
+            if !phonemes.is_empty() {
+                dictionary.entries.insert(word.to_lowercase(), phonemes);
+            }
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Phonemes (ARPAbet, without stress) for a single word
+    pub fn phonemes(&self, word: &str) -> Vec<String> {
+        let word: String = word
+            .chars()
+            .filter(|c| c.is_alphabetic() || *c == '\'')
+            .flat_map(char::to_lowercase)
+            .collect();
+
+        if let Some(entry) = self.entries.get(&word) {
+            return entry.clone();
+        }
+
+        // Possessives and simple plurals of known words
+        if let Some(stem) = word.strip_suffix("'s").or_else(|| word.strip_suffix('s')) {
+            if let Some(entry) = self.entries.get(stem) {
+                let mut phonemes = entry.clone();
+                phonemes.push(plural_suffix(entry.last().map(String::as_str)).to_string());
+                return phonemes;
+            }
+        }
+
+        letter_to_sound(&word)
+    }
+}
+
+impl Default for PhonemeDictionary {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn split_phonemes(phonemes: &str) -> Vec<String> {
+    phonemes.split_whitespace().map(str::to_string).collect()
+}
+
+fn strip_stress(phoneme: &str) -> String {
+    phoneme
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .to_string()
+}
+
+/// `-s` is pronounced /S/ after voiceless sounds and /Z/ otherwise
+fn plural_suffix(last: Option<&str>) -> &'static str {
+    match last {
+        Some("P" | "T" | "K" | "F" | "TH") => "S",
+        _ => "Z",
+    }
+}
+
+fn is_vowel(c: u8) -> bool {
+    matches!(c, b'a' | b'e' | b'i' | b'o' | b'u')
+}
+
+/// Ordered multi-letter spelling patterns; longer patterns are tried first
+const PATTERNS: &[(&str, &str)] = &[
+    ("tion", "SH AH N"),
+    ("sion", "ZH AH N"),
+    ("ture", "CH ER"),
+    ("ough", "AO"),
+    ("augh", "AO"),
+    ("eigh", "EY"),
+    ("tch", "CH"),
+    ("igh", "AY"),
+    ("dge", "JH"),
+    ("sh", "SH"),
+    ("ch", "CH"),
+    ("th", "TH"),
+    ("ph", "F"),
+    ("wh", "W"),
+    ("ck", "K"),
+    ("ng", "NG"),
+    ("qu", "K W"),
+    ("gh", ""),
+    ("ee", "IY"),
+    ("ea", "IY"),
+    ("oo", "UW"),
+    ("ou", "AW"),
+    ("oi", "OY"),
+    ("oy", "OY"),
+    ("ai", "EY"),
+    ("ay", "EY"),
+    ("au", "AO"),
+    ("aw", "AO"),
+    ("ie", "IY"),
+    ("ei", "EY"),
+    ("oa", "OW"),
+    ("ue", "UW"),
+    ("ew", "UW"),
+    ("er", "ER"),
+    ("ir", "ER"),
+    ("ur", "ER"),
+    ("ar", "AA R"),
+    ("or", "AO R"),
+];
+
+/// Rule-based English letter-to-sound conversion, in the spirit of the
+/// NRL/espeak rule sets but much smaller. Good enough to find the sounds a
+/// learner is likely to get wrong when a dictionary entry is missing.
+pub fn letter_to_sound(word: &str) -> Vec<String> {
+    let word: String = word
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let word = word.as_str();
+    let bytes = word.as_bytes();
+    let len = bytes.len();
+    let mut phonemes: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    // Silent initial letters
+    if word.starts_with("kn") || word.starts_with("wr") || word.starts_with("gn") {
+        i = 1;
+    }
+
+    let magic_e = len >= 3
+        && bytes[len - 1] == b'e'
+        && !is_vowel(bytes[len - 2])
+        && bytes[len - 2] != b'l'
+        && is_vowel(bytes[len - 3]);
+
+    while i < len {
+        let rest = &word[i..];
+
+        // Final consonant + "le" as in "table"
+        if rest == "le" && i > 0 && !is_vowel(bytes[i - 1]) {
+            phonemes.extend(split_phonemes("AH L"));
+            break;
+        }
+
+        // "ow" at the end of a word is usually /OW/ ("snow"), otherwise /AW/ ("town")
+        if rest.starts_with("ow") {
+            phonemes.push(if i + 2 == len { "OW" } else { "AW" }.to_string());
+            i += 2;
+            continue;
+        }
+
+        if let Some((pattern, sounds)) = PATTERNS.iter().find(|(p, _)| rest.starts_with(p)) {
+            phonemes.extend(split_phonemes(sounds));
+            i += pattern.len();
+            continue;
+        }
+
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+
+        // Doubled consonants are pronounced once
+        if !is_vowel(c) && next == Some(c) {
+            i += 1;
+            continue;
+        }
+
+        let sounds: &str = match c {
+            b'a' | b'e' | b'i' | b'o' | b'u' => {
+                if c == b'e' && i == len - 1 && len > 2 {
+                    // Silent final e
+                    ""
+                } else if magic_e && i == len - 3 {
+                    match c {
+                        b'a' => "EY",
+                        b'e' => "IY",
+                        b'i' => "AY",
+                        b'o' => "OW",
+                        _ => "UW",
+                    }
+                } else if i == len - 1 {
+                    match c {
+                        b'a' => "AH",
+                        b'e' => "IY",
+                        b'i' => "IY",
+                        b'o' => "OW",
+                        _ => "UW",
+                    }
+                } else {
+                    match c {
+                        b'a' => "AE",
+                        b'e' => "EH",
+                        b'i' => "IH",
+                        b'o' => "AA",
+                        _ => "AH",
+                    }
+                }
+            }
+            b'y' => {
+                if i == 0 {
+                    "Y"
+                } else if i == len - 1 {
+                    if len <= 3 {
+                        "AY"
+                    } else {
+                        "IY"
+                    }
+                } else {
+                    "IH"
+                }
+            }
+            b'c' => match next {
+                Some(b'e' | b'i' | b'y') => "S",
+                _ => "K",
+            },
+            b'g' => match next {
+                Some(b'e' | b'i' | b'y') => "JH",
+                _ => "G",
+            },
+            b's' => {
+                let between_vowels =
+                    i > 0 && is_vowel(bytes[i - 1]) && next.map(is_vowel).unwrap_or(false);
+                if between_vowels {
+                    "Z"
+                } else {
+                    "S"
+                }
+            }
+            b'x' => {
+                if i == 0 {
+                    "Z"
+                } else {
+                    "K S"
+                }
+            }
+            b'j' => "JH",
+            b'q' => "K",
+            b'h' => "HH",
+            b'b' => "B",
+            b'd' => "D",
+            b'f' => "F",
+            b'k' => "K",
+            b'l' => "L",
+            b'm' => "M",
+            b'n' => "N",
+            b'p' => "P",
+            b'r' => "R",
+            b't' => "T",
+            b'v' => "V",
+            b'w' => "W",
+            b'z' => "Z",
+            _ => "",
+        };
+
+        phonemes.extend(split_phonemes(sounds));
+        i += 1;
+    }
+
+    phonemes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phonemes(list: &str) -> Vec<String> {
+        split_phonemes(list)
+    }
+
+    #[test]
+    fn dictionary_words_ignore_case_and_punctuation() {
+        let dictionary = PhonemeDictionary::builtin();
+        assert_eq!(dictionary.phonemes("Thought,"), phonemes("TH AO T"));
+        assert_eq!(dictionary.phonemes("thoughts"), phonemes("TH AO T S"));
+        assert_eq!(dictionary.phonemes("friend's"), phonemes("F R EH N D Z"));
+    }
+
+    #[test]
+    fn unknown_words_fall_back_to_letter_to_sound() {
+        let dictionary = PhonemeDictionary::builtin();
+        assert_eq!(dictionary.phonemes("ship"), phonemes("SH IH P"));
+        assert_eq!(dictionary.phonemes("cake"), phonemes("K EY K"));
+        assert_eq!(dictionary.phonemes("fiction"), phonemes("F IH K SH AH N"));
+        assert_eq!(dictionary.phonemes("blorf"), phonemes("B L AO R F"));
+    }
+
+    #[test]
+    fn loads_cmudict_without_stress_or_alternates() {
+        let dir = std::env::temp_dir().join(format!("phonemes-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CMUDICT_FILE_NAME);
+        std::fs::write(
+            &path,
+            ";;; comment\ntomato T AH0 M EY1 T OW2\ntomato(2) T AH0 M AA1 T OW2\nwater W AO1 T ER0 # override\n",
+        )
+        .unwrap();
+
+        let dictionary = PhonemeDictionary::load(&path).unwrap();
+        assert_eq!(dictionary.phonemes("tomato"), phonemes("T AH M EY T OW"));
+        assert_eq!(dictionary.phonemes("water"), phonemes("W AO T ER"));
+        assert_eq!(dictionary.phonemes("the"), phonemes("DH AH"));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn near_substitutions_cost_less_than_unrelated_ones() {
+        assert_eq!(substitution_cost("T", "T"), 0.0);
+        assert_eq!(substitution_cost("T", "D"), 0.5);
+        assert_eq!(substitution_cost("D", "T"), 0.5);
+        assert_eq!(substitution_cost("T", "K"), 0.7);
+        assert_eq!(substitution_cost("T", "IY"), 1.0);
+        assert_eq!(substitution_cost("T", "?"), 1.0);
+    }
+
+    #[test]
+    fn transcribes_arpabet_to_ipa() {
+        assert_eq!(arpabet_to_ipa(&phonemes("TH IH NG K")), "θɪŋk");
+    }
+}
@@ -0,0 +1,444 @@
+use super::phonemes::{describe_phoneme, substitution_cost, PhonemeDictionary};
+use super::reading_aligner::{ReadingAligner, WordStatus};
+use super::whisper_service::{TranscriptionResult, TranscriptionWord};
+use serde::{Deserialize, Serialize};
+
+/// Words scoring below this are considered mispronounced, which a correctly
+/// recognized word does when Whisper was less than two-thirds sure of it
+pub const MISPRONOUNCED_THRESHOLD: f32 = 90.0;
+/// Share of the word score that comes from Whisper's acoustic confidence
+const ACOUSTIC_WEIGHT: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PhonemeErrorKind {
+    Correct,
+    Substituted,
+    Omitted,
+    Inserted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhonemeScore {
+    pub expected: Option<String>,
+    pub recognized: Option<String>,
+    pub kind: PhonemeErrorKind,
+    /// 0-100
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordAssessment {
+    pub word: String,
+    pub recognized: Option<String>,
+    pub expected_phonemes: Vec<String>,
+    pub recognized_phonemes: Vec<String>,
+    pub phonemes: Vec<PhonemeScore>,
+    /// 0-100
+    pub score: f32,
+    pub mispronounced: bool,
+    /// Plain-language explanations of what went wrong
+    pub issues: Vec<String>,
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PronunciationAssessment {
+    pub expected_text: String,
+    pub recognized_text: String,
+    pub language: String,
+    /// "arpabet" for English, "graphemes" where no phoneme set is available
+    pub unit: String,
+    /// 0-100
+    pub overall_score: f32,
+    pub words: Vec<WordAssessment>,
+}
+
+/// Pronunciation assessment on top of Whisper transcriptions.
+///
+/// Expected words are converted to phonemes and compared against the
+/// phonemes of what Whisper heard; the phoneme alignment pinpoints which
+/// sounds were substituted or dropped, and Whisper's token probabilities
+/// temper the score where recognition itself was unsure.
+///
+/// Whisper only reports spelling, so the "recognized phonemes" are the
+/// dictionary pronunciation of the word it wrote down, not the sounds the
+/// learner made. Errors show up only where they changed the word Whisper
+/// heard; an accent that still transcribes as the right word is caught at
+/// most through lower confidence.
+pub struct PronunciationService {
+    dictionary: PhonemeDictionary,
+}
+
+impl PronunciationService {
+    pub fn new(dictionary: PhonemeDictionary) -> Self {
+        Self { dictionary }
+    }
+
+    /// Score every word of `expected_text` against a transcription of the learner
+    pub fn assess(
+        &self,
+        expected_text: &str,
+        transcription: &TranscriptionResult,
+        language: &str,
+    ) -> PronunciationAssessment {
+        let spoken: Vec<TranscriptionWord> = transcription
+            .segments
+            .iter()
+            .flat_map(|segment| segment.words.iter().cloned())
+            .collect();
+
+        // Fall back to untimed words when word timestamps are unavailable
+        let spoken = if spoken.is_empty() {
+            transcription
+                .text
+                .split_whitespace()
+                .map(|word| TranscriptionWord {
+                    word: word.to_string(),
+                    start: 0.0,
+                    end: 0.0,
+                    probability: transcription.confidence,
+                })
+                .collect()
+        } else {
+            spoken
+        };
+
+        let mut aligner = ReadingAligner::new(expected_text);
+        let alignment = aligner.update(&spoken, true);
+
+        let words: Vec<WordAssessment> = alignment
+            .words
+            .iter()
+            .map(|aligned| {
+                let heard = spoken.iter().find(|w| {
+                    Some(w.start) == aligned.start
+                        && Some(w.word.trim()) == aligned.spoken.as_deref()
+                });
+                let acoustic = heard.map(|w| w.probability).unwrap_or(0.0);
+
+                match (aligned.status, aligned.spoken.as_deref()) {
+                    (WordStatus::Matched | WordStatus::Mismatched, Some(recognized)) => {
+                        let mut assessment =
+                            self.assess_word(&aligned.text, recognized, acoustic, language);
+                        assessment.start = aligned.start;
+                        assessment.end = aligned.end;
+                        assessment
+                    }
+                    _ => self.missing_word(&aligned.text, language),
+                }
+            })
+            .collect();
+
+        let overall_score = if words.is_empty() {
+            0.0
+        } else {
+            words.iter().map(|w| w.score).sum::<f32>() / words.len() as f32
+        };
+
+        PronunciationAssessment {
+            expected_text: expected_text.to_string(),
+            recognized_text: transcription.text.clone(),
+            language: language.to_string(),
+            unit: unit_for(language).to_string(),
+            overall_score,
+            words,
+        }
+    }
+
+    /// Compare one expected word with what was recognized in its place
+    pub fn assess_word(
+        &self,
+        expected: &str,
+        recognized: &str,
+        acoustic_confidence: f32,
+        language: &str,
+    ) -> WordAssessment {
+        let expected_units = self.units(expected, language);
+        let recognized_units = self.units(recognized, language);
+        let phonemes = align_phonemes(&expected_units, &recognized_units);
+
+        let phoneme_accuracy = if phonemes.is_empty() {
+            0.0
+        } else {
+            phonemes.iter().map(|p| p.score).sum::<f32>() / phonemes.len() as f32
+        };
+        let score = phoneme_accuracy * (1.0 - ACOUSTIC_WEIGHT)
+            + acoustic_confidence.clamp(0.0, 1.0) * 100.0 * ACOUSTIC_WEIGHT;
+
+        let issues = explain(&phonemes, language);
+
+        WordAssessment {
+            word: expected.to_string(),
+            recognized: Some(recognized.to_string()),
+            expected_phonemes: expected_units,
+            recognized_phonemes: recognized_units,
+            // Any wrong sound counts, however long the word it is diluted in
+            mispronounced: score < MISPRONOUNCED_THRESHOLD
+                || phonemes.iter().any(|p| p.kind != PhonemeErrorKind::Correct),
+            phonemes,
+            score,
+            issues,
+            start: None,
+            end: None,
+        }
+    }
+
+    fn missing_word(&self, expected: &str, language: &str) -> WordAssessment {
+        let expected_units = self.units(expected, language);
+        WordAssessment {
+            word: expected.to_string(),
+            recognized: None,
+            phonemes: expected_units
+                .iter()
+                .map(|unit| PhonemeScore {
+                    expected: Some(unit.clone()),
+                    recognized: None,
+                    kind: PhonemeErrorKind::Omitted,
+                    score: 0.0,
+                })
+                .collect(),
+            expected_phonemes: expected_units,
+            recognized_phonemes: Vec::new(),
+            score: 0.0,
+            mispronounced: true,
+            issues: vec!["The word was not heard".to_string()],
+            start: None,
+            end: None,
+        }
+    }
+
+    /// Phonemes for English, lowercase letters for languages without G2P support
+    fn units(&self, word: &str, language: &str) -> Vec<String> {
+        if unit_for(language) == "arpabet" {
+            self.dictionary.phonemes(word)
+        } else {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .map(|c| c.to_string())
+                .collect()
+        }
+    }
+}
+
+impl Default for PronunciationService {
+    fn default() -> Self {
+        Self::new(PhonemeDictionary::builtin())
+    }
+}
+
+fn unit_for(language: &str) -> &'static str {
+    if language.is_empty() || language.starts_with("en") {
+        "arpabet"
+    } else {
+        "graphemes"
+    }
+}
+
+/// Weighted edit-distance alignment between expected and recognized phonemes
+fn align_phonemes(expected: &[String], recognized: &[String]) -> Vec<PhonemeScore> {
+    let (m, n) = (expected.len(), recognized.len());
+    let mut cost = vec![vec![0.0f32; n + 1]; m + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i as f32;
+    }
+    for (j, value) in cost[0].iter_mut().enumerate() {
+        *value = j as f32;
+    }
+
+    for i in 1..=m {
+        for j in 1..=n {
+            let substitute = cost[i - 1][j - 1] + substitution_cost(&expected[i - 1], &recognized[j - 1]);
+            let omit = cost[i - 1][j] + 1.0;
+            let insert = cost[i][j - 1] + 1.0;
+            cost[i][j] = substitute.min(omit).min(insert);
+        }
+    }
+
+    let mut scores = Vec::new();
+    let (mut i, mut j) = (m, n);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 {
+            let sub = substitution_cost(&expected[i - 1], &recognized[j - 1]);
+            if (cost[i][j] - (cost[i - 1][j - 1] + sub)).abs() < 1e-4 {
+                scores.push(PhonemeScore {
+                    expected: Some(expected[i - 1].clone()),
+                    recognized: Some(recognized[j - 1].clone()),
+                    kind: if sub == 0.0 {
+                        PhonemeErrorKind::Correct
+                    } else {
+                        PhonemeErrorKind::Substituted
+                    },
+                    score: (1.0 - sub) * 100.0,
+                });
+                i -= 1;
+                j -= 1;
+                continue;
+            }
+        }
+        if i > 0 && (j == 0 || (cost[i][j] - (cost[i - 1][j] + 1.0)).abs() < 1e-4) {
+            scores.push(PhonemeScore {
+                expected: Some(expected[i - 1].clone()),
+                recognized: None,
+                kind: PhonemeErrorKind::Omitted,
+                score: 0.0,
+            });
+            i -= 1;
+        } else {
+            scores.push(PhonemeScore {
+                expected: None,
+                recognized: Some(recognized[j - 1].clone()),
+                kind: PhonemeErrorKind::Inserted,
+                score: 0.0,
+            });
+            j -= 1;
+        }
+    }
+
+    scores.reverse();
+    scores
+}
+
+fn explain(phonemes: &[PhonemeScore], language: &str) -> Vec<String> {
+    let describe = |unit: &str| {
+        if unit_for(language) == "arpabet" {
+            describe_phoneme(unit)
+        } else {
+            format!("\"{}\"", unit)
+        }
+    };
+    let last_expected = phonemes.iter().rposition(|p| p.expected.is_some());
+
+    phonemes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, p)| match (p.kind, &p.expected, &p.recognized) {
+            (PhonemeErrorKind::Substituted, Some(expected), Some(recognized)) => Some(format!(
+                "{} was pronounced as {}",
+                describe(expected),
+                describe(recognized)
+            )),
+            (PhonemeErrorKind::Omitted, Some(expected), _) => {
+                let position = if index == 0 {
+                    "initial "
+                } else if Some(index) == last_expected {
+                    "final "
+                } else {
+                    ""
+                };
+                Some(format!("The {}sound {} was dropped", position, describe(expected)))
+            }
+            (PhonemeErrorKind::Inserted, _, Some(recognized)) => {
+                Some(format!("An extra sound {} was added", describe(recognized)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_match_scores_full_marks() {
+        let service = PronunciationService::default();
+        let word = service.assess_word("thought", "thought", 1.0, "en");
+
+        assert_eq!(word.expected_phonemes, ["TH", "AO", "T"]);
+        assert_eq!(word.score, 100.0);
+        assert!(!word.mispronounced);
+        assert!(word.issues.is_empty());
+        assert!(word
+            .phonemes
+            .iter()
+            .all(|p| p.kind == PhonemeErrorKind::Correct));
+    }
+
+    #[test]
+    fn acoustic_confidence_tempers_the_score() {
+        let service = PronunciationService::default();
+        let word = service.assess_word("thought", "thought", 0.5, "en");
+        assert!((word.score - 85.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn low_confidence_marks_a_correct_word_mispronounced() {
+        let service = PronunciationService::default();
+        let unsure = service.assess_word("thought", "thought", 0.4, "en");
+        assert!(unsure.issues.is_empty());
+        assert!(unsure.mispronounced);
+
+        let sure = service.assess_word("thought", "thought", 0.9, "en");
+        assert!(!sure.mispronounced);
+    }
+
+    #[test]
+    fn one_wrong_phoneme_marks_the_word_mispronounced() {
+        let service = PronunciationService::default();
+        // One voicing slip out of five sounds keeps the score above the threshold
+        let word = service.assess_word("blorf", "blorv", 1.0, "en");
+        assert!(word.score >= MISPRONOUNCED_THRESHOLD);
+        assert!(word.mispronounced);
+
+        let unrelated = service.assess_word("bad", "bam", 1.0, "en");
+        assert!(unrelated.score < MISPRONOUNCED_THRESHOLD);
+        assert!(unrelated.mispronounced);
+    }
+
+    #[test]
+    fn near_phoneme_substitution_costs_less_than_an_unrelated_one() {
+        let service = PronunciationService::default();
+        let voicing = service.assess_word("bad", "bat", 1.0, "en");
+        let unrelated = service.assess_word("bad", "bam", 1.0, "en");
+
+        assert_eq!(voicing.phonemes[2].kind, PhonemeErrorKind::Substituted);
+        assert_eq!(voicing.phonemes[2].score, 50.0);
+        assert_eq!(unrelated.phonemes[2].score, 0.0);
+        assert!(voicing.score > unrelated.score);
+        assert_eq!(
+            voicing.issues,
+            ["\"d\" (as in \"day\") was pronounced as \"t\" (as in \"tea\")"]
+        );
+    }
+
+    #[test]
+    fn dropped_final_sound_is_explained() {
+        let service = PronunciationService::default();
+        let word = service.assess_word("cats", "cat", 1.0, "en");
+
+        assert_eq!(
+            word.phonemes.last().unwrap().kind,
+            PhonemeErrorKind::Omitted
+        );
+        assert_eq!(
+            word.issues,
+            ["The final sound \"s\" (as in \"see\") was dropped"]
+        );
+        assert_eq!(word.score, 82.5);
+        assert!(word.mispronounced);
+    }
+
+    #[test]
+    fn out_of_dictionary_words_are_scored_from_letter_to_sound() {
+        let service = PronunciationService::default();
+        let exact = service.assess_word("blorf", "blorf", 1.0, "en");
+        assert_eq!(exact.expected_phonemes, ["B", "L", "AO", "R", "F"]);
+        assert_eq!(exact.score, 100.0);
+
+        let misheard = service.assess_word("blorf", "blorv", 1.0, "en");
+        assert!(misheard.score < 100.0);
+        assert_eq!(misheard.phonemes[4].recognized.as_deref(), Some("V"));
+    }
+
+    #[test]
+    fn other_languages_compare_letters() {
+        let service = PronunciationService::default();
+        let word = service.assess_word("Straße", "strasse", 1.0, "de");
+
+        assert_eq!(word.expected_phonemes, ["s", "t", "r", "a", "ß", "e"]);
+        assert!(word.issues.iter().any(|issue| issue.contains("\"ß\"")));
+    }
+}
@@ -0,0 +1,545 @@
+use super::whisper_service::TranscriptionWord;
+use serde::{Deserialize, Serialize};
+
+/// Spoken words at or above this similarity count as correctly read
+const MATCH_THRESHOLD: f32 = 0.85;
+/// Cost of passing over a reference word that was never spoken
+const SKIP_COST: f32 = 1.0;
+/// Cost of a spoken word that has no counterpart in the reference
+const INSERT_COST: f32 = 1.0;
+/// Re-reading a word that was just read is common and cheap
+const REPEAT_COST: f32 = 0.4;
+/// How many already-read words a repetition may refer back to
+const REPEAT_LOOKBACK: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WordStatus {
+    /// Not reached yet
+    Pending,
+    /// Read correctly
+    Matched,
+    /// Read, but what was heard differs from the reference
+    Mismatched,
+    /// Passed over by the reader
+    Skipped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InsertionKind {
+    /// The reader went back and repeated a word
+    Repetition,
+    /// A word that is not in the reference text at this point
+    Extra,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceWordAlignment {
+    pub index: usize,
+    pub text: String,
+    pub status: WordStatus,
+    pub spoken: Option<String>,
+    pub similarity: f32,
+    pub start: Option<f32>,
+    pub end: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsertedWord {
+    pub text: String,
+    pub kind: InsertionKind,
+    /// Reference word the insertion follows, if any
+    pub after_index: Option<usize>,
+    pub start: f32,
+    pub end: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlignmentUpdate {
+    pub words: Vec<ReferenceWordAlignment>,
+    pub insertions: Vec<InsertedWord>,
+    /// Index of the next reference word the reader is expected to say
+    pub current_index: usize,
+    /// Most recent reference word that was actually spoken
+    pub last_spoken_index: Option<usize>,
+    pub is_final: bool,
+}
+
+struct ReferenceWord {
+    text: String,
+    normalized: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Align,
+    Skip,
+    Insert,
+    Repeat,
+}
+
+/// Tracks a reader's position in a known reference text as transcription
+/// results arrive.
+///
+/// Final hypotheses are committed and advance the reading position; partial
+/// hypotheses are aligned against the same state but only previewed, so the
+/// highlight can follow the reader mid-utterance without being locked in.
+pub struct ReadingAligner {
+    reference: Vec<ReferenceWord>,
+    alignments: Vec<ReferenceWordAlignment>,
+    insertions: Vec<InsertedWord>,
+    cursor: usize,
+    last_spoken: Option<usize>,
+}
+
+impl ReadingAligner {
+    pub fn new(reference_text: &str) -> Self {
+        let reference: Vec<ReferenceWord> = reference_text
+            .split_whitespace()
+            .filter_map(|raw| {
+                let normalized = normalize_word(raw);
+                (!normalized.is_empty()).then(|| ReferenceWord {
+                    text: raw.to_string(),
+                    normalized,
+                })
+            })
+            .collect();
+
+        let alignments = reference
+            .iter()
+            .enumerate()
+            .map(|(index, word)| ReferenceWordAlignment {
+                index,
+                text: word.text.clone(),
+                status: WordStatus::Pending,
+                spoken: None,
+                similarity: 0.0,
+                start: None,
+                end: None,
+            })
+            .collect();
+
+        Self {
+            reference,
+            alignments,
+            insertions: Vec::new(),
+            cursor: 0,
+            last_spoken: None,
+        }
+    }
+
+    /// Align a hypothesis that continues from the last committed position.
+    /// `is_final` commits the result; otherwise the returned update is a preview.
+    pub fn update(&mut self, spoken: &[TranscriptionWord], is_final: bool) -> AlignmentUpdate {
+        let spoken: Vec<&TranscriptionWord> = spoken
+            .iter()
+            .filter(|w| !normalize_word(&w.word).is_empty())
+            .collect();
+
+        if is_final {
+            self.apply(&spoken);
+            self.snapshot(true)
+        } else {
+            let mut preview = ReadingAligner {
+                reference: Vec::new(),
+                alignments: self.alignments.clone(),
+                insertions: self.insertions.clone(),
+                cursor: self.cursor,
+                last_spoken: self.last_spoken,
+            };
+            let path = self.align(&spoken);
+            preview.commit(&spoken, &path, &self.reference);
+            preview.snapshot(false)
+        }
+    }
+
+    /// Move the reading position, e.g. when the user clicks a word to start from
+    pub fn seek(&mut self, index: usize) -> AlignmentUpdate {
+        self.cursor = index.min(self.reference.len());
+        for alignment in &mut self.alignments[self.cursor..] {
+            alignment.status = WordStatus::Pending;
+            alignment.spoken = None;
+            alignment.similarity = 0.0;
+            alignment.start = None;
+            alignment.end = None;
+        }
+        // Keep what was said before the new position; an insertion ahead of
+        // the first word is before position 0 itself
+        let cursor = self.cursor;
+        self.insertions.retain(|insertion| {
+            insertion
+                .after_index
+                .map_or(cursor > 0, |index| index < cursor)
+        });
+        self.last_spoken = None;
+        self.snapshot(true)
+    }
+
+    fn apply(&mut self, spoken: &[&TranscriptionWord]) {
+        let path = self.align(spoken);
+        let reference = std::mem::take(&mut self.reference);
+        self.commit(spoken, &path, &reference);
+        self.reference = reference;
+    }
+
+    /// Semi-global alignment of the spoken words against the reference from the
+    /// cursor on: every spoken word is consumed, leading reference words can be
+    /// skipped at a cost, and reference words after the last spoken one are free
+    /// (they simply haven't been read yet).
+    fn align(&self, spoken: &[&TranscriptionWord]) -> Vec<Step> {
+        let m = spoken.len();
+        let window_end = (self.cursor + m * 2 + 20).min(self.reference.len());
+        let window = &self.reference[self.cursor..window_end];
+        let n = window.len();
+
+        if m == 0 {
+            return Vec::new();
+        }
+
+        let spoken_norm: Vec<String> = spoken.iter().map(|w| normalize_word(&w.word)).collect();
+        let recent: Vec<&str> = self.alignments[..self.cursor]
+            .iter()
+            .rev()
+            .filter(|a| a.status == WordStatus::Matched)
+            .take(REPEAT_LOOKBACK)
+            .map(|a| self.reference[a.index].normalized.as_str())
+            .collect();
+
+        let mut cost = vec![vec![f32::INFINITY; n + 1]; m + 1];
+        let mut back = vec![vec![Step::Align; n + 1]; m + 1];
+        cost[0][0] = 0.0;
+
+        for i in 0..=m {
+            for j in 0..=n {
+                let current = cost[i][j];
+                if !current.is_finite() {
+                    continue;
+                }
+
+                if i < m && j < n {
+                    let sim = similarity(&spoken_norm[i], &window[j].normalized);
+                    relax(&mut cost, &mut back, i + 1, j + 1, current + (1.0 - sim), Step::Align);
+                }
+                if j < n {
+                    relax(&mut cost, &mut back, i, j + 1, current + SKIP_COST, Step::Skip);
+                }
+                if i < m {
+                    // A repetition echoes one of the words read just before
+                    // this point, so re-reading a short phrase counts too
+                    let repeats = window[..j]
+                        .iter()
+                        .rev()
+                        .map(|word| word.normalized.as_str())
+                        .chain(recent.iter().copied())
+                        .take(REPEAT_LOOKBACK)
+                        .any(|word| word == spoken_norm[i]);
+                    let (step_cost, step) = if repeats {
+                        (REPEAT_COST, Step::Repeat)
+                    } else {
+                        (INSERT_COST, Step::Insert)
+                    };
+                    relax(&mut cost, &mut back, i + 1, j, current + step_cost, step);
+                }
+            }
+        }
+
+        // Trailing reference words are unread, not skipped: pick the cheapest
+        // end column, preferring the earliest on ties
+        let mut best_j = 0;
+        for j in 1..=n {
+            if cost[m][j] < cost[m][best_j] - f32::EPSILON {
+                best_j = j;
+            }
+        }
+
+        let mut path = Vec::new();
+        let (mut i, mut j) = (m, best_j);
+        while i > 0 || j > 0 {
+            let step = back[i][j];
+            path.push(step);
+            match step {
+                Step::Align => {
+                    i -= 1;
+                    j -= 1;
+                }
+                Step::Skip => j -= 1,
+                Step::Insert | Step::Repeat => i -= 1,
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    fn commit(&mut self, spoken: &[&TranscriptionWord], path: &[Step], reference: &[ReferenceWord]) {
+        let mut i = 0;
+        for step in path {
+            match step {
+                Step::Align => {
+                    let word = spoken[i];
+                    let index = self.cursor;
+                    let sim = similarity(&normalize_word(&word.word), &reference[index].normalized);
+                    let alignment = &mut self.alignments[index];
+                    alignment.status = if sim >= MATCH_THRESHOLD {
+                        WordStatus::Matched
+                    } else {
+                        WordStatus::Mismatched
+                    };
+                    alignment.spoken = Some(word.word.trim().to_string());
+                    alignment.similarity = sim;
+                    alignment.start = Some(word.start);
+                    alignment.end = Some(word.end);
+                    self.last_spoken = Some(index);
+                    self.cursor += 1;
+                    i += 1;
+                }
+                Step::Skip => {
+                    self.alignments[self.cursor].status = WordStatus::Skipped;
+                    self.cursor += 1;
+                }
+                Step::Insert | Step::Repeat => {
+                    let word = spoken[i];
+                    self.insertions.push(InsertedWord {
+                        text: word.word.trim().to_string(),
+                        kind: if *step == Step::Repeat {
+                            InsertionKind::Repetition
+                        } else {
+                            InsertionKind::Extra
+                        },
+                        after_index: self.cursor.checked_sub(1),
+                        start: word.start,
+                        end: word.end,
+                    });
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    fn snapshot(&self, is_final: bool) -> AlignmentUpdate {
+        AlignmentUpdate {
+            words: self.alignments.clone(),
+            insertions: self.insertions.clone(),
+            current_index: self.cursor,
+            last_spoken_index: self.last_spoken,
+            is_final,
+        }
+    }
+}
+
+fn relax(
+    cost: &mut [Vec<f32>],
+    back: &mut [Vec<Step>],
+    i: usize,
+    j: usize,
+    value: f32,
+    step: Step,
+) {
+    if value < cost[i][j] {
+        cost[i][j] = value;
+        back[i][j] = step;
+    }
+}
+
+/// Lowercase and strip everything except letters, digits and inner apostrophes
+pub fn normalize_word(word: &str) -> String {
+    let cleaned: String = word
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '\'' || *c == '’')
+        .map(|c| if c == '’' { '\'' } else { c })
+        .flat_map(char::to_lowercase)
+        .collect();
+    cleaned.trim_matches('\'').to_string()
+}
+
+/// Normalized Levenshtein similarity in [0, 1]
+pub fn similarity(a: &str, b: &str) -> f32 {
+    if a == b {
+        return 1.0;
+    }
+
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    1.0 - previous[b.len()] as f32 / longest as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words half a second apart, as Whisper returns them with leading spaces
+    fn spoken(text: &str) -> Vec<TranscriptionWord> {
+        text.split_whitespace()
+            .enumerate()
+            .map(|(index, word)| TranscriptionWord {
+                word: format!(" {}", word),
+                start: index as f32 * 0.5,
+                end: index as f32 * 0.5 + 0.4,
+                probability: 0.9,
+            })
+            .collect()
+    }
+
+    fn statuses(update: &AlignmentUpdate) -> Vec<WordStatus> {
+        update.words.iter().map(|word| word.status).collect()
+    }
+
+    fn insertions(update: &AlignmentUpdate) -> Vec<(&str, InsertionKind, Option<usize>)> {
+        update
+            .insertions
+            .iter()
+            .map(|word| (word.text.as_str(), word.kind, word.after_index))
+            .collect()
+    }
+
+    #[test]
+    fn skipped_word_is_marked_and_reading_continues() {
+        let mut aligner = ReadingAligner::new("The quick brown fox jumps.");
+        let update = aligner.update(&spoken("the quick fox jumps"), true);
+
+        use WordStatus::*;
+        assert_eq!(
+            statuses(&update),
+            vec![Matched, Matched, Skipped, Matched, Matched]
+        );
+        assert_eq!(update.current_index, 5);
+        assert_eq!(update.last_spoken_index, Some(4));
+        assert!(update.insertions.is_empty());
+        assert_eq!(update.words[3].start, Some(1.0));
+    }
+
+    #[test]
+    fn filler_word_is_an_extra_insertion() {
+        let mut aligner = ReadingAligner::new("The quick brown fox");
+        let update = aligner.update(&spoken("the um quick brown"), true);
+
+        assert_eq!(
+            insertions(&update),
+            vec![("um", InsertionKind::Extra, Some(0))]
+        );
+        assert_eq!(update.current_index, 3);
+        assert_eq!(update.words[3].status, WordStatus::Pending);
+    }
+
+    #[test]
+    fn repeated_phrase_is_a_repetition() {
+        let mut aligner = ReadingAligner::new("The quick brown fox jumps");
+        let update = aligner.update(&spoken("the quick the quick brown fox"), true);
+
+        assert_eq!(
+            insertions(&update),
+            vec![
+                ("the", InsertionKind::Repetition, Some(1)),
+                ("quick", InsertionKind::Repetition, Some(1)),
+            ]
+        );
+        assert!(update.words[..4]
+            .iter()
+            .all(|word| word.status == WordStatus::Matched));
+
+        // Going back over words committed by an earlier hypothesis
+        let update = aligner.update(&spoken("brown fox jumps"), true);
+        assert_eq!(update.insertions.len(), 4);
+        assert_eq!(update.insertions[2].kind, InsertionKind::Repetition);
+        assert_eq!(update.insertions[3].kind, InsertionKind::Repetition);
+        assert_eq!(update.words[4].status, WordStatus::Matched);
+        assert_eq!(update.current_index, 5);
+    }
+
+    #[test]
+    fn partial_hypotheses_are_previewed_without_committing() {
+        let mut aligner = ReadingAligner::new("one two three four");
+        let preview = aligner.update(&spoken("one two"), false);
+        assert!(!preview.is_final);
+        assert_eq!(preview.current_index, 2);
+
+        let update = aligner.update(&spoken("one two three"), true);
+        assert_eq!(update.current_index, 3);
+        assert!(update.insertions.is_empty());
+    }
+
+    #[test]
+    fn speech_past_the_end_of_the_text_is_inserted() {
+        let mut aligner = ReadingAligner::new("one two three");
+        let update = aligner.update(&spoken("one two three and more"), true);
+        assert_eq!(update.current_index, 3);
+        assert_eq!(
+            insertions(&update),
+            vec![
+                ("and", InsertionKind::Extra, Some(2)),
+                ("more", InsertionKind::Extra, Some(2)),
+            ]
+        );
+
+        // Once the window is empty everything spoken is an insertion
+        let update = aligner.update(&spoken("still talking"), true);
+        assert_eq!(update.current_index, 3);
+        assert_eq!(update.insertions.len(), 4);
+
+        // Seeking back forgets what was read from there on
+        let update = aligner.seek(1);
+        use WordStatus::*;
+        assert_eq!(statuses(&update), vec![Matched, Pending, Pending]);
+        assert_eq!(update.current_index, 1);
+        assert!(update.insertions.is_empty());
+    }
+
+    #[test]
+    fn seeking_back_drops_insertions_from_there_on() {
+        let mut aligner = ReadingAligner::new("The quick brown fox jumps");
+        let update = aligner.update(&spoken("um the er quick the quick brown fox"), true);
+        assert_eq!(
+            insertions(&update),
+            vec![
+                ("um", InsertionKind::Extra, None),
+                ("er", InsertionKind::Extra, Some(0)),
+                ("the", InsertionKind::Repetition, Some(1)),
+                ("quick", InsertionKind::Repetition, Some(1)),
+            ]
+        );
+
+        let update = aligner.seek(1);
+        assert_eq!(
+            insertions(&update),
+            vec![
+                ("um", InsertionKind::Extra, None),
+                ("er", InsertionKind::Extra, Some(0)),
+            ]
+        );
+        let update = aligner.seek(0);
+        assert!(update.insertions.is_empty());
+    }
+
+    #[test]
+    fn window_follows_the_reader_through_a_long_text() {
+        let text: Vec<String> = (0..200).map(|i| format!("w{}", i)).collect();
+        let mut aligner = ReadingAligner::new(&text.join(" "));
+
+        for chunk in text.chunks(10) {
+            let update = aligner.update(&spoken(&chunk.join(" ")), true);
+            assert!(update.insertions.is_empty());
+        }
+        let update = aligner.update(&[], true);
+        assert_eq!(update.current_index, 200);
+        assert!(update
+            .words
+            .iter()
+            .all(|word| word.status == WordStatus::Matched));
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A heartbeat arriving later than this after the previous one starts a new
+/// session; the gap is treated as time away, not reading
+pub const IDLE_TIMEOUT_SECONDS: i64 = 120;
+
+/// Where the reader is in a document, restored when it is reopened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingPosition {
+    pub page: i64,
+    /// Fraction of the page scrolled past, 0.0 at the top
+    pub scroll_offset: Option<f64>,
+    pub zoom: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingProgress {
+    pub document_id: String,
+    pub current_page: i64,
+    pub scroll_offset: Option<f64>,
+    pub zoom: Option<f64>,
+    pub total_time_seconds: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Sent periodically by the reader while a document is open
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingHeartbeat {
+    pub document_id: String,
+    pub page: i64,
+    /// False when the reader saw no input since the last heartbeat; the time
+    /// keeps the session alive but is not counted
+    pub active: bool,
+}
+
+/// Continuous stretch of reading one document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingSession {
+    pub id: String,
+    pub document_id: String,
+    /// Local calendar day the session started on
+    pub day: NaiveDate,
+    pub started_at: DateTime<Utc>,
+    pub last_active_at: DateTime<Utc>,
+    pub active_seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyReading {
+    pub day: NaiveDate,
+    pub seconds: i64,
+    /// Distinct pages read that day
+    pub pages: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentReadingTime {
+    pub document_id: String,
+    pub title: String,
+    pub total_seconds: i64,
+    pub current_page: i64,
+    pub total_pages: i64,
+    /// Distinct pages ever read
+    pub pages_read: i64,
+    /// `None` until enough has been read to estimate a pace
+    pub estimated_seconds_remaining: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingStats {
+    /// Reading time within the requested period
+    pub total_seconds: i64,
+    /// Days with any reading in the period, oldest first
+    pub days: Vec<DailyReading>,
+    pub average_pages_per_day: f64,
+    /// Consecutive days read, ending today or yesterday
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Documents with recorded reading time, most read first
+    pub documents: Vec<DocumentReadingTime>,
+}
+
+/// Seconds to credit for a heartbeat, or `None` if it starts a new session
+pub fn heartbeat_credit(last_active_at: DateTime<Utc>, now: DateTime<Utc>) -> Option<i64> {
+    let gap = (now - last_active_at).num_seconds();
+    (gap <= IDLE_TIMEOUT_SECONDS).then_some(gap.max(0))
+}
+
+/// Current and longest runs of consecutive days in `days`, sorted ascending.
+/// A streak not yet continued today still counts until the day is over.
+pub fn reading_streaks(days: &[NaiveDate], today: NaiveDate) -> (u32, u32) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days {
+        run = match previous {
+            Some(previous) if previous.succ_opt() == Some(day) => run + 1,
+            Some(previous) if previous == day => run,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+
+    let current = match previous {
+        Some(last) if last == today || last.succ_opt() == Some(today) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+/// Time left at the pace read so far
+pub fn estimate_seconds_remaining(
+    total_seconds: i64,
+    pages_read: i64,
+    current_page: i64,
+    total_pages: i64,
+) -> Option<i64> {
+    if pages_read == 0 || total_seconds == 0 {
+        return None;
+    }
+    let pages_left = (total_pages - current_page).max(0);
+    Some(total_seconds * pages_left / pages_read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::database::{Database, DocumentRecord, DATABASE_FILE_NAME};
+    use chrono::Duration;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    #[test]
+    fn counts_current_and_longest_streaks() {
+        let days = [day(1), day(2), day(3), day(4), day(7), day(8)];
+        assert_eq!(reading_streaks(&days, day(8)), (2, 4));
+        // Not read yet today, but yesterday keeps the streak going
+        assert_eq!(reading_streaks(&days, day(9)), (2, 4));
+        assert_eq!(reading_streaks(&days, day(10)), (0, 4));
+        assert_eq!(reading_streaks(&[], day(10)), (0, 0));
+    }
+
+    #[test]
+    fn long_gaps_start_a_new_session() {
+        let start = Utc::now();
+        assert_eq!(
+            heartbeat_credit(start, start + Duration::seconds(30)),
+            Some(30)
+        );
+        assert_eq!(
+            heartbeat_credit(start, start + Duration::seconds(IDLE_TIMEOUT_SECONDS + 1)),
+            None
+        );
+        assert_eq!(estimate_seconds_remaining(600, 10, 20, 50), Some(1800));
+        assert_eq!(estimate_seconds_remaining(0, 0, 1, 50), None);
+    }
+
+    #[test]
+    fn heartbeats_accumulate_active_time() {
+        let dir = std::env::temp_dir().join(format!("reading-progress-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::open(dir.join(DATABASE_FILE_NAME)).unwrap();
+        let start = Utc::now();
+        db.insert_document(&DocumentRecord {
+            id: "doc".to_string(),
+            file_path: "/book.pdf".to_string(),
+            file_hash: "hash".to_string(),
+            title: "Book".to_string(),
+            total_pages: 100,
+            created_at: start,
+            updated_at: start,
+            last_opened_at: start,
+        })
+        .unwrap();
+
+        let beat = |seconds: i64, page: i64, active: bool| {
+            db.record_reading_heartbeat(
+                &ReadingHeartbeat {
+                    document_id: "doc".to_string(),
+                    page,
+                    active,
+                },
+                start + Duration::seconds(seconds),
+                day(8),
+            )
+            .unwrap()
+        };
+        let first = beat(0, 1, true);
+        beat(60, 2, true);
+        assert_eq!(beat(90, 2, false).active_seconds, 60);
+        // Back after a break: a new session, the break is not counted
+        let second = beat(90 + IDLE_TIMEOUT_SECONDS + 1, 3, true);
+        assert_ne!(second.id, first.id);
+        beat(90 + IDLE_TIMEOUT_SECONDS + 31, 4, true);
+
+        db.save_reading_position(
+            "doc",
+            &ReadingPosition {
+                page: 4,
+                scroll_offset: Some(0.5),
+                zoom: Some(1.25),
+            },
+            start,
+        )
+        .unwrap();
+        let progress = db.reading_progress("doc").unwrap().unwrap();
+        assert_eq!(progress.total_time_seconds, 90);
+        assert_eq!(progress.zoom, Some(1.25));
+
+        let stats = db.reading_stats(day(2), day(8)).unwrap();
+        assert_eq!(stats.total_seconds, 90);
+        assert_eq!(stats.days.len(), 1);
+        assert_eq!(stats.days[0].pages, 4);
+        assert_eq!(stats.current_streak, 1);
+        // 90 seconds for 4 pages, 96 pages to go
+        assert_eq!(stats.documents[0].estimated_seconds_remaining, Some(2160));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
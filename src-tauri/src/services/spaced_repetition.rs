@@ -0,0 +1,225 @@
+use chrono::{DateTime, Duration, Local, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_EASE_FACTOR: f64 = 2.5;
+const MIN_EASE_FACTOR: f64 = 1.3;
+const MAX_INTERVAL_DAYS: f64 = 365.0;
+
+/// Answer buttons offered to the learner, mapped onto SM-2 quality scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewGrade {
+    /// Forgot / mispronounced; start over
+    Again,
+    /// Correct with serious difficulty
+    Hard,
+    /// Correct after some hesitation
+    Good,
+    /// Perfect recall
+    Easy,
+}
+
+impl ReviewGrade {
+    /// SM-2 quality (0-5)
+    pub fn quality(self) -> u8 {
+        match self {
+            ReviewGrade::Again => 1,
+            ReviewGrade::Hard => 3,
+            ReviewGrade::Good => 4,
+            ReviewGrade::Easy => 5,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReviewGrade::Again => "again",
+            ReviewGrade::Hard => "hard",
+            ReviewGrade::Good => "good",
+            ReviewGrade::Easy => "easy",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "again" => Some(ReviewGrade::Again),
+            "hard" => Some(ReviewGrade::Hard),
+            "good" => Some(ReviewGrade::Good),
+            "easy" => Some(ReviewGrade::Easy),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewSchedule {
+    pub ease_factor: f64,
+    pub interval_days: f64,
+    /// Consecutive successful reviews
+    pub repetitions: u32,
+    /// Times the word was forgotten after having been learned
+    pub lapses: u32,
+    pub due_at: DateTime<Utc>,
+    pub last_reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl ReviewSchedule {
+    /// Schedule for a word that has never been reviewed: due immediately
+    pub fn new_card(now: DateTime<Utc>) -> Self {
+        Self {
+            ease_factor: DEFAULT_EASE_FACTOR,
+            interval_days: 0.0,
+            repetitions: 0,
+            lapses: 0,
+            due_at: now,
+            last_reviewed_at: None,
+        }
+    }
+
+    /// Apply a review using the SM-2 algorithm and return the next schedule
+    pub fn review(&self, grade: ReviewGrade, now: DateTime<Utc>) -> Self {
+        let quality = grade.quality() as f64;
+
+        let ease_factor = (self.ease_factor
+            + (0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02)))
+            .max(MIN_EASE_FACTOR);
+
+        let (repetitions, lapses, interval_days) = if grade == ReviewGrade::Again {
+            let lapses = if self.repetitions > 0 {
+                self.lapses + 1
+            } else {
+                self.lapses
+            };
+            // Relearn within the same session
+            (0, lapses, 0.0)
+        } else {
+            let interval = match self.repetitions {
+                0 => 1.0,
+                1 => 6.0,
+                _ => self.interval_days * ease_factor,
+            };
+            let interval = match grade {
+                ReviewGrade::Hard => (interval * 0.8).max(1.0),
+                ReviewGrade::Easy => interval * 1.3,
+                _ => interval,
+            };
+            (
+                self.repetitions + 1,
+                self.lapses,
+                interval.round().min(MAX_INTERVAL_DAYS),
+            )
+        };
+
+        let due_at = if interval_days == 0.0 {
+            now + Duration::minutes(10)
+        } else {
+            now + Duration::days(interval_days as i64)
+        };
+
+        Self {
+            ease_factor,
+            interval_days,
+            repetitions,
+            lapses,
+            due_at,
+            last_reviewed_at: Some(now),
+        }
+    }
+}
+
+/// End of the current local day; everything due before it is in today's queue
+pub fn end_of_today() -> DateTime<Utc> {
+    let tomorrow = Local::now().date_naive() + Duration::days(1);
+    tomorrow
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|end| end.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc::now() + Duration::days(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn good_reviews_grow_the_interval() {
+        let first = ReviewSchedule::new_card(now()).review(ReviewGrade::Good, now());
+        assert_eq!((first.repetitions, first.interval_days), (1, 1.0));
+        assert!((first.ease_factor - 2.5).abs() < 1e-9);
+        assert_eq!(first.due_at, now() + Duration::days(1));
+        assert_eq!(first.last_reviewed_at, Some(now()));
+
+        let second = first.review(ReviewGrade::Good, now());
+        assert_eq!((second.repetitions, second.interval_days), (2, 6.0));
+
+        // From the third review on, the interval is multiplied by the ease
+        let third = second.review(ReviewGrade::Good, now());
+        assert_eq!((third.repetitions, third.interval_days), (3, 15.0));
+        assert_eq!(third.due_at, now() + Duration::days(15));
+    }
+
+    #[test]
+    fn grades_adjust_ease_and_interval() {
+        let card = ReviewSchedule::new_card(now());
+
+        let easy = card.review(ReviewGrade::Easy, now());
+        assert!((easy.ease_factor - 2.6).abs() < 1e-9);
+
+        let hard = card.review(ReviewGrade::Hard, now());
+        assert!((hard.ease_factor - 2.36).abs() < 1e-9);
+        // Hard never drops below a day
+        assert_eq!(hard.interval_days, 1.0);
+
+        let learned = ReviewSchedule {
+            repetitions: 2,
+            interval_days: 10.0,
+            ..card.clone()
+        };
+        // 10 days × 2.36 ease × 0.8 for hard, × 2.6 ease × 1.3 for easy, rounded
+        assert_eq!(learned.review(ReviewGrade::Hard, now()).interval_days, 19.0);
+        assert_eq!(learned.review(ReviewGrade::Easy, now()).interval_days, 34.0);
+    }
+
+    #[test]
+    fn again_resets_repetitions_and_counts_a_lapse() {
+        let learned = ReviewSchedule {
+            repetitions: 3,
+            interval_days: 15.0,
+            ..ReviewSchedule::new_card(now())
+        };
+        let lapsed = learned.review(ReviewGrade::Again, now());
+        assert_eq!((lapsed.repetitions, lapsed.lapses), (0, 1));
+        assert_eq!(lapsed.interval_days, 0.0);
+        assert!((lapsed.ease_factor - 1.96).abs() < 1e-9);
+        // Relearned in the same session
+        assert_eq!(lapsed.due_at, now() + Duration::minutes(10));
+
+        // Forgetting a word that was never learned is not a lapse
+        let new = ReviewSchedule::new_card(now()).review(ReviewGrade::Again, now());
+        assert_eq!(new.lapses, 0);
+    }
+
+    #[test]
+    fn ease_never_drops_below_floor_and_interval_is_capped() {
+        let mut schedule = ReviewSchedule::new_card(now());
+        for _ in 0..10 {
+            schedule = schedule.review(ReviewGrade::Again, now());
+        }
+        assert_eq!(schedule.ease_factor, MIN_EASE_FACTOR);
+
+        let long = ReviewSchedule {
+            repetitions: 5,
+            interval_days: 300.0,
+            ..ReviewSchedule::new_card(now())
+        };
+        assert_eq!(
+            long.review(ReviewGrade::Easy, now()).interval_days,
+            MAX_INTERVAL_DAYS
+        );
+    }
+}
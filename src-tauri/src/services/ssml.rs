@@ -0,0 +1,915 @@
+use super::tts_stream::split_chunks;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Pause after an SSML `<p>` or a generated paragraph, in seconds
+const PARAGRAPH_PAUSE_SECONDS: f32 = 0.6;
+/// Pause after an SSML `<s>`, in seconds
+const SENTENCE_PAUSE_SECONDS: f32 = 0.3;
+
+/// A run of text spoken with one voice and prosody, followed by an optional
+/// pause. Segments with empty text are pure silence.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeechSegment {
+    pub text: String,
+    /// Offset of `text` in the source, in UTF-16 code units
+    pub char_offset: usize,
+    /// Voice named explicitly by the markup; wins over `language`
+    pub voice_id: Option<String>,
+    /// BCP 47 language of the span when it differs from the surrounding text
+    pub language: Option<String>,
+    /// Multipliers applied on top of the session's `TtsOptions`
+    pub rate: f32,
+    pub pitch: f32,
+    pub volume: f32,
+    /// IPA pronunciation to speak instead of `text`
+    pub phonemes: Option<String>,
+    /// Silence after the segment, in seconds
+    pub pause_after: f32,
+    /// (offset in `text`, offset in the source) pairs where `text` stops
+    /// matching the source one for one, e.g. after collapsed whitespace or an
+    /// entity. Empty when `text` is a verbatim slice of the source.
+    #[serde(skip)]
+    source_map: Vec<(usize, usize)>,
+}
+
+impl SpeechSegment {
+    pub fn plain(text: &str, char_offset: usize) -> Self {
+        Self {
+            text: text.to_string(),
+            char_offset,
+            voice_id: None,
+            language: None,
+            rate: 1.0,
+            pitch: 1.0,
+            volume: 1.0,
+            phonemes: None,
+            pause_after: 0.0,
+            source_map: Vec::new(),
+        }
+    }
+
+    /// Offset in the source of a UTF-16 offset into `text`
+    pub fn source_offset(&self, text_offset: usize) -> usize {
+        let Some(index) = self
+            .source_map
+            .iter()
+            .rposition(|(text, _)| *text <= text_offset)
+        else {
+            return self.char_offset + text_offset;
+        };
+        let (text, source) = self.source_map[index];
+        // Text that stands for less of the source, like an alias, stops at
+        // the next anchor
+        let room = self
+            .source_map
+            .get(index + 1)
+            .map_or(usize::MAX, |(_, next)| next - source);
+        source + (text_offset - text).min(room)
+    }
+}
+
+/// How plain document text is turned into segments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechStructureOptions {
+    /// Main language of the text; detected from the sentences when unset
+    pub language: Option<String>,
+    /// Voice for quoted speech; quotes use the session voice when unset
+    pub quote_voice_id: Option<String>,
+    /// Seconds of silence at paragraph ends
+    pub paragraph_pause: f32,
+    /// Read sentences in another language with a voice for that language
+    pub detect_languages: bool,
+}
+
+impl Default for SpeechStructureOptions {
+    fn default() -> Self {
+        Self {
+            language: None,
+            quote_voice_id: None,
+            paragraph_pause: PARAGRAPH_PAUSE_SECONDS,
+            detect_languages: true,
+        }
+    }
+}
+
+/// Voice and prosody in effect inside an element
+#[derive(Debug, Clone)]
+struct Scope {
+    voice_id: Option<String>,
+    language: Option<String>,
+    rate: f32,
+    pitch: f32,
+    volume: f32,
+    phonemes: Option<String>,
+    /// Replacement text from `<sub alias>`
+    alias: Option<String>,
+    /// Inside `<audio>`, `<desc>` or another element whose text is not spoken
+    silent: bool,
+}
+
+/// Parse SSML into segments.
+///
+/// Supports `speak`, `p`, `s`, `break`, `emphasis`, `prosody`, `phoneme`
+/// (IPA), `lang`, `voice`, `sub` and `xml:lang` on any element. Unknown
+/// elements are read as their text content. Offsets point into `markup`.
+pub fn parse_ssml(markup: &str) -> Result<Vec<SpeechSegment>, String> {
+    let mut reader = Reader::from_str(markup);
+    reader.config_mut().trim_text(false);
+
+    let mut scopes = vec![Scope {
+        voice_id: None,
+        language: None,
+        rate: 1.0,
+        pitch: 1.0,
+        volume: 1.0,
+        phonemes: None,
+        alias: None,
+        silent: false,
+    }];
+    let mut segments: Vec<SpeechSegment> = Vec::new();
+
+    loop {
+        let position = reader.buffer_position() as usize;
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid SSML at byte {}: {}", position, e))?;
+        let scope = scopes.last().cloned().expect("root scope is never popped");
+
+        match event {
+            Event::Start(element) => {
+                let name = element_name(&element);
+                let child = enter_element(&name, &element, &scope)?;
+                if let Some(alias) = &child.alias {
+                    push_replacement(&mut segments, &child, alias, utf16_offset(markup, position));
+                }
+                scopes.push(child);
+            }
+            Event::Empty(element) => {
+                let name = element_name(&element);
+                if name == "break" {
+                    add_pause(&mut segments, break_seconds(&element)?, markup, position);
+                } else {
+                    let child = enter_element(&name, &element, &scope)?;
+                    // `<phoneme ph="..."/>` and `<sub alias="..."/>` still speak
+                    let text = child.alias.clone().or_else(|| child.phonemes.clone());
+                    if let Some(text) = text {
+                        push_replacement(
+                            &mut segments,
+                            &child,
+                            &text,
+                            utf16_offset(markup, position),
+                        );
+                    }
+                }
+            }
+            Event::End(element) => {
+                if scopes.len() > 1 {
+                    scopes.pop();
+                }
+                let pause = match element.name().as_ref() {
+                    b"p" | b"paragraph" => PARAGRAPH_PAUSE_SECONDS,
+                    b"s" | b"sentence" => SENTENCE_PAUSE_SECONDS,
+                    _ => 0.0,
+                };
+                add_pause(&mut segments, pause, markup, position);
+            }
+            Event::Text(_) => {
+                if scope.silent || scope.alias.is_some() {
+                    continue;
+                }
+                let raw = &markup[position..reader.buffer_position() as usize];
+                let (text, source_map) = normalize_text(raw, utf16_offset(markup, position), true)
+                    .map_err(|e| format!("Invalid SSML at byte {}: {}", position, e))?;
+                push_text(&mut segments, &scope, text, source_map);
+            }
+            Event::CData(data) => {
+                if scope.silent || scope.alias.is_some() {
+                    continue;
+                }
+                let text = String::from_utf8_lossy(&data.into_inner()).to_string();
+                // Skip the `<![CDATA[` prefix
+                let (text, source_map) =
+                    normalize_text(&text, utf16_offset(markup, position + 9), false)
+                        .map_err(|e| format!("Invalid SSML at byte {}: {}", position, e))?;
+                push_text(&mut segments, &scope, text, source_map);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Split plain text into segments from its structure: paragraphs end with a
+/// pause, quoted speech can use its own voice and sentences detected in
+/// another language are read with a voice for that language. Offsets point
+/// into `text`.
+pub fn structure_segments(
+    text: &str,
+    options: &SpeechStructureOptions,
+    detect_language: impl Fn(&str) -> String,
+) -> Vec<SpeechSegment> {
+    let mut segments = Vec::new();
+    for (paragraph_offset, paragraph) in paragraphs(text) {
+        for (sentence_offset, sentence) in split_chunks(paragraph) {
+            for (quote_offset, span, quoted) in quote_spans(sentence) {
+                let offset = paragraph_offset + sentence_offset + quote_offset;
+                let mut segment = SpeechSegment::plain(span, offset);
+                if quoted {
+                    segment.voice_id = options.quote_voice_id.clone();
+                }
+                segments.push(segment);
+            }
+        }
+        if let Some(last) = segments.last_mut() {
+            last.pause_after = options.paragraph_pause;
+        }
+    }
+
+    if options.detect_languages {
+        // Quotes are detected on their own, as they are often in another language
+        let languages: Vec<String> = segments
+            .iter()
+            .map(|segment| detect_language(&segment.text))
+            .collect();
+        let main_language = options.language.clone().or_else(|| {
+            most_common(
+                languages
+                    .iter()
+                    .zip(&segments)
+                    .map(|(language, segment)| (language, segment.text.chars().count())),
+            )
+        });
+
+        for (segment, language) in segments.iter_mut().zip(languages) {
+            let is_main = main_language
+                .as_deref()
+                .is_some_and(|main| primary_language(main) == primary_language(&language));
+            if !is_main {
+                // As with `xml:lang` inside `<voice>`, the language picks the voice
+                segment.language = Some(language);
+                segment.voice_id = None;
+            }
+        }
+    }
+    segments
+}
+
+/// Render segments as SSML, e.g. to show or edit what `structure_segments` produced
+pub fn segments_to_ssml(segments: &[SpeechSegment], language: Option<&str>) -> String {
+    let mut ssml = String::from("<speak version=\"1.1\"");
+    if let Some(language) = language {
+        ssml.push_str(&format!(" xml:lang=\"{}\"", escape_xml(language)));
+    }
+    ssml.push('>');
+
+    for segment in segments {
+        let mut closing = Vec::new();
+        if let Some(voice_id) = &segment.voice_id {
+            ssml.push_str(&format!("<voice name=\"{}\">", escape_xml(voice_id)));
+            closing.push("</voice>");
+        }
+        if let Some(language) = &segment.language {
+            ssml.push_str(&format!("<lang xml:lang=\"{}\">", escape_xml(language)));
+            closing.push("</lang>");
+        }
+        let prosody: Vec<String> = [
+            ("rate", segment.rate),
+            ("pitch", segment.pitch),
+            ("volume", segment.volume),
+        ]
+        .iter()
+        .filter(|(_, value)| (value - 1.0).abs() > f32::EPSILON)
+        .map(|(name, value)| format!(" {}=\"{:.0}%\"", name, value * 100.0))
+        .collect();
+        if !prosody.is_empty() {
+            ssml.push_str(&format!("<prosody{}>", prosody.concat()));
+            closing.push("</prosody>");
+        }
+        if let Some(phonemes) = &segment.phonemes {
+            ssml.push_str(&format!(
+                "<phoneme alphabet=\"ipa\" ph=\"{}\">",
+                escape_xml(phonemes)
+            ));
+            closing.push("</phoneme>");
+        }
+
+        ssml.push_str(&escape_xml(&segment.text));
+        for tag in closing.iter().rev() {
+            ssml.push_str(tag);
+        }
+        if segment.pause_after > 0.0 {
+            ssml.push_str(&format!(
+                "<break time=\"{}ms\"/>",
+                (segment.pause_after * 1000.0).round() as u32
+            ));
+        } else {
+            ssml.push(' ');
+        }
+    }
+
+    ssml.push_str("</speak>");
+    ssml
+}
+
+/// Primary subtag, lowercased: `en` for `en_US` or `en-GB`
+pub fn primary_language(language: &str) -> String {
+    language
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+fn element_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.name().as_ref()).to_string()
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, String> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| format!("Invalid SSML attribute: {}", e))?;
+        if attribute.key.as_ref() == name.as_bytes() {
+            let value = attribute
+                .unescape_value()
+                .map_err(|e| format!("Invalid SSML attribute: {}", e))?;
+            return Ok(Some(value.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+fn enter_element(name: &str, element: &BytesStart, parent: &Scope) -> Result<Scope, String> {
+    let mut scope = Scope {
+        alias: None,
+        ..parent.clone()
+    };
+
+    if let Some(language) = attribute(element, "xml:lang")? {
+        // A language switch picks a voice for that language unless one is named
+        scope.language = Some(language);
+        scope.voice_id = None;
+    }
+
+    match name {
+        "voice" => {
+            if let Some(name) = attribute(element, "name")? {
+                scope.voice_id = Some(name);
+            }
+        }
+        "emphasis" => {
+            let (rate, pitch, volume) = match attribute(element, "level")?.as_deref() {
+                Some("strong") => (0.85, 1.1, 1.3),
+                Some("reduced") => (1.1, 0.95, 0.8),
+                Some("none") => (1.0, 1.0, 1.0),
+                _ => (0.92, 1.05, 1.15),
+            };
+            scope.rate *= rate;
+            scope.pitch *= pitch;
+            scope.volume *= volume;
+        }
+        "prosody" => {
+            if let Some(rate) = attribute(element, "rate")? {
+                scope.rate *= parse_prosody(&rate, &RATE_LEVELS)?;
+            }
+            if let Some(pitch) = attribute(element, "pitch")? {
+                scope.pitch *= parse_prosody(&pitch, &PITCH_LEVELS)?;
+            }
+            if let Some(volume) = attribute(element, "volume")? {
+                scope.volume *= parse_prosody(&volume, &VOLUME_LEVELS)?;
+            }
+        }
+        "phoneme" => {
+            let alphabet = attribute(element, "alphabet")?.unwrap_or_else(|| "ipa".to_string());
+            // Other alphabets fall back to reading the element's text
+            if alphabet.eq_ignore_ascii_case("ipa") {
+                scope.phonemes = attribute(element, "ph")?;
+            }
+        }
+        "sub" => scope.alias = attribute(element, "alias")?,
+        "audio" | "desc" | "mark" | "meta" | "metadata" | "lexicon" => scope.silent = true,
+        _ => {}
+    }
+    Ok(scope)
+}
+
+const RATE_LEVELS: [(&str, f32); 6] = [
+    ("x-slow", 0.5),
+    ("slow", 0.75),
+    ("medium", 1.0),
+    ("fast", 1.25),
+    ("x-fast", 1.75),
+    ("default", 1.0),
+];
+
+const PITCH_LEVELS: [(&str, f32); 6] = [
+    ("x-low", 0.6),
+    ("low", 0.8),
+    ("medium", 1.0),
+    ("high", 1.2),
+    ("x-high", 1.4),
+    ("default", 1.0),
+];
+
+const VOLUME_LEVELS: [(&str, f32); 7] = [
+    ("silent", 0.0),
+    ("x-soft", 0.4),
+    ("soft", 0.7),
+    ("medium", 1.0),
+    ("loud", 1.3),
+    ("x-loud", 1.6),
+    ("default", 1.0),
+];
+
+/// A prosody value as a multiplier: a named level, `150%`, `+10%`, `-2st`,
+/// `+6dB` or a bare number. Absolute pitches in Hz are left unchanged.
+fn parse_prosody(value: &str, levels: &[(&str, f32)]) -> Result<f32, String> {
+    let value = value.trim();
+    if let Some((_, level)) = levels.iter().find(|(name, _)| *name == value) {
+        return Ok(*level);
+    }
+
+    let number = |text: &str| {
+        text.trim()
+            .parse::<f32>()
+            .map_err(|_| format!("Invalid prosody value: {}", value))
+    };
+    let relative = value.starts_with('+') || value.starts_with('-');
+
+    let multiplier = if let Some(percent) = value.strip_suffix('%') {
+        let percent = number(percent)?;
+        if relative {
+            1.0 + percent / 100.0
+        } else {
+            percent / 100.0
+        }
+    } else if let Some(semitones) = value.strip_suffix("st") {
+        2f32.powf(number(semitones)? / 12.0)
+    } else if let Some(decibels) = value.strip_suffix("dB") {
+        10f32.powf(number(decibels)? / 20.0)
+    } else if value.ends_with("Hz") {
+        1.0
+    } else {
+        number(value)?
+    };
+    Ok(multiplier.max(0.0))
+}
+
+fn break_seconds(element: &BytesStart) -> Result<f32, String> {
+    if let Some(time) = attribute(element, "time")? {
+        let time = time.trim();
+        let (number, scale) = match time.strip_suffix("ms") {
+            Some(milliseconds) => (milliseconds, 0.001),
+            None => (time.strip_suffix('s').unwrap_or(time), 1.0),
+        };
+        return number
+            .trim()
+            .parse::<f32>()
+            .map(|value| (value * scale).max(0.0))
+            .map_err(|_| format!("Invalid break time: {}", time));
+    }
+
+    Ok(match attribute(element, "strength")?.as_deref() {
+        Some("none") => 0.0,
+        Some("x-weak") => 0.1,
+        Some("weak") => 0.2,
+        Some("strong") => PARAGRAPH_PAUSE_SECONDS,
+        Some("x-strong") => 1.0,
+        _ => 0.35,
+    })
+}
+
+/// Collapse whitespace in `raw`, which starts `offset` UTF-16 code units into
+/// the source, and resolve its entities when `entities` is set. Returns the
+/// text with the source map for its segment.
+fn normalize_text(
+    raw: &str,
+    offset: usize,
+    entities: bool,
+) -> Result<(String, Vec<(usize, usize)>), String> {
+    let mut text = String::new();
+    let mut text_offset = 0;
+    let mut source_offset = offset;
+    let mut source_map = vec![(0, offset)];
+    let mut rest = raw;
+
+    while let Some(c) = rest.chars().next() {
+        let (consumed, produced) = if c.is_whitespace() {
+            let run = rest.len() - rest.trim_start().len();
+            // Leading and trailing whitespace is dropped, runs become one space
+            let space = !text.is_empty() && run < rest.len();
+            (&rest[..run], Cow::Borrowed(if space { " " } else { "" }))
+        } else if entities && c == '&' {
+            let end = rest
+                .find(';')
+                .ok_or_else(|| "unterminated entity".to_string())?;
+            let entity = &rest[..=end];
+            let value = quick_xml::escape::unescape(entity).map_err(|e| e.to_string())?;
+            (entity, value)
+        } else {
+            let c = &rest[..c.len_utf8()];
+            (c, Cow::Borrowed(c))
+        };
+        text.push_str(&produced);
+
+        let before = (text_offset, source_offset);
+        text_offset += produced.encode_utf16().count();
+        source_offset += consumed.encode_utf16().count();
+        if text_offset - before.0 != source_offset - before.1 {
+            source_map.push(before);
+            source_map.push((text_offset, source_offset));
+        }
+        rest = &rest[consumed.len()..];
+    }
+    Ok((text, source_map))
+}
+
+/// Text from an attribute, spoken in place of the element at `char_offset`
+fn push_replacement(
+    segments: &mut Vec<SpeechSegment>,
+    scope: &Scope,
+    text: &str,
+    char_offset: usize,
+) {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let length = text.encode_utf16().count();
+    push_text(
+        segments,
+        scope,
+        text,
+        vec![(0, char_offset), (length, char_offset)],
+    );
+}
+
+fn push_text(
+    segments: &mut Vec<SpeechSegment>,
+    scope: &Scope,
+    text: String,
+    source_map: Vec<(usize, usize)>,
+) {
+    if text.is_empty() {
+        return;
+    }
+
+    let mut segment = SpeechSegment {
+        text,
+        char_offset: 0,
+        voice_id: scope.voice_id.clone(),
+        language: scope.language.clone(),
+        rate: scope.rate,
+        pitch: scope.pitch,
+        volume: scope.volume,
+        phonemes: scope.phonemes.clone(),
+        pause_after: 0.0,
+        source_map,
+    };
+    segment.char_offset = segment.source_offset(0);
+    segments.push(segment);
+}
+
+/// Add silence after the last segment, or as a silent segment at the start
+fn add_pause(segments: &mut Vec<SpeechSegment>, seconds: f32, markup: &str, position: usize) {
+    if seconds <= 0.0 {
+        return;
+    }
+    match segments.last_mut() {
+        Some(last) => last.pause_after = last.pause_after.max(seconds),
+        None => {
+            let mut silence = SpeechSegment::plain("", utf16_offset(markup, position));
+            silence.pause_after = seconds;
+            segments.push(silence);
+        }
+    }
+}
+
+fn utf16_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset].encode_utf16().count()
+}
+
+/// Paragraphs separated by blank lines, with their UTF-16 offsets
+fn paragraphs(text: &str) -> Vec<(usize, &str)> {
+    let mut paragraphs = Vec::new();
+    let mut start = 0;
+    let mut blank_run = false;
+    let mut line_start = 0;
+
+    for (index, c) in text.char_indices() {
+        if c != '\n' {
+            continue;
+        }
+        let line = &text[line_start..index];
+        if line.trim().is_empty() && !blank_run {
+            paragraphs.push((start, line_start));
+            blank_run = true;
+        } else if !line.trim().is_empty() && blank_run {
+            start = line_start;
+            blank_run = false;
+        }
+        line_start = index + 1;
+    }
+    if blank_run && !text[line_start..].trim().is_empty() {
+        start = line_start;
+    }
+    paragraphs.push((start, text.len()));
+
+    paragraphs
+        .into_iter()
+        .filter(|(start, end)| start < end && !text[*start..*end].trim().is_empty())
+        .map(|(start, end)| (utf16_offset(text, start), &text[start..end]))
+        .collect()
+}
+
+/// Split a sentence at quotation marks into (UTF-16 offset, text, quoted)
+/// spans, keeping the marks with the quoted text
+fn quote_spans(sentence: &str) -> Vec<(usize, &str, bool)> {
+    let mut spans = Vec::new();
+    let mut span_start = 0;
+    let mut closing: Option<char> = None;
+
+    for (index, c) in sentence.char_indices() {
+        match closing {
+            None => {
+                let close = match c {
+                    '“' => '”',
+                    '„' => '“',
+                    '«' => '»',
+                    '"' => '"',
+                    _ => continue,
+                };
+                // A straight quote only opens at a word start
+                let at_word_start = sentence[..index]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|previous| previous.is_whitespace() || "([—–-".contains(previous));
+                if c == '"' && !at_word_start {
+                    continue;
+                }
+                spans.push((span_start, index, false));
+                span_start = index;
+                closing = Some(close);
+            }
+            Some(close) if c == close => {
+                let end = index + c.len_utf8();
+                spans.push((span_start, end, true));
+                span_start = end;
+                closing = None;
+            }
+            Some(_) => {}
+        }
+    }
+    spans.push((span_start, sentence.len(), closing.is_some()));
+
+    spans
+        .into_iter()
+        .filter_map(|(start, end, quoted)| {
+            let span = &sentence[start..end];
+            let trimmed = span.trim_start();
+            let start = start + span.len() - trimmed.len();
+            let trimmed = trimmed.trim_end();
+            // Punctuation left between quotes is not worth its own segment
+            trimmed
+                .chars()
+                .any(char::is_alphanumeric)
+                .then(|| (utf16_offset(sentence, start), trimmed, quoted))
+        })
+        .collect()
+}
+
+/// The value covering the most characters; ties go to the one seen first
+fn most_common<'a>(values: impl Iterator<Item = (&'a String, usize)>) -> Option<String> {
+    let mut totals: Vec<(&String, usize)> = Vec::new();
+    for (value, weight) in values {
+        match totals.iter_mut().find(|(seen, _)| *seen == value) {
+            Some((_, total)) => *total += weight,
+            None => totals.push((value, weight)),
+        }
+    }
+    totals
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, total)| *total)
+        .map(|(value, _)| value.clone())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(segments: &[SpeechSegment]) -> Vec<(&str, usize, f32)> {
+        segments
+            .iter()
+            .map(|s| (s.text.as_str(), s.char_offset, s.pause_after))
+            .collect()
+    }
+
+    #[test]
+    fn breaks_pause_after_the_previous_segment() {
+        let markup = r#"<speak>Hello<break time="500ms"/> world<break strength="strong"/></speak>"#;
+        let segments = parse_ssml(markup).unwrap();
+        assert_eq!(
+            texts(&segments),
+            vec![("Hello", 7, 0.5), ("world", 34, PARAGRAPH_PAUSE_SECONDS)]
+        );
+
+        // A break before any text becomes a silent segment
+        let segments = parse_ssml(r#"<speak><break time="1.5s"/>Hi</speak>"#).unwrap();
+        assert_eq!(texts(&segments), vec![("", 7, 1.5), ("Hi", 27, 0.0)]);
+    }
+
+    #[test]
+    fn nested_prosody_multiplies() {
+        let markup = r#"<speak><prosody rate="50%" pitch="high"><prosody rate="+20%" volume="loud">inner</prosody> outer</prosody> plain</speak>"#;
+        let segments = parse_ssml(markup).unwrap();
+        let prosody: Vec<(&str, f32, f32, f32)> = segments
+            .iter()
+            .map(|s| (s.text.as_str(), s.rate, s.pitch, s.volume))
+            .collect();
+        assert_eq!(
+            prosody,
+            vec![
+                ("inner", 0.6, 1.2, 1.3),
+                ("outer", 0.5, 1.2, 1.0),
+                ("plain", 1.0, 1.0, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn language_voice_and_phoneme_apply_to_their_span() {
+        let markup = r#"<speak xml:lang="en-US">Say <lang xml:lang="fr-FR">bonjour</lang> to <voice name="piper:de_DE-thorsten-medium">Herr <phoneme alphabet="ipa" ph="ˈmʏlɐ">Müller</phoneme></voice></speak>"#;
+        let segments = parse_ssml(markup).unwrap();
+
+        let bonjour = segments.iter().find(|s| s.text == "bonjour").unwrap();
+        assert_eq!(bonjour.language.as_deref(), Some("fr-FR"));
+        let herr = segments.iter().find(|s| s.text == "Herr").unwrap();
+        assert_eq!(
+            herr.voice_id.as_deref(),
+            Some("piper:de_DE-thorsten-medium")
+        );
+        let muller = segments.iter().find(|s| s.text == "Müller").unwrap();
+        assert_eq!(muller.phonemes.as_deref(), Some("ˈmʏlɐ"));
+        assert_eq!(muller.voice_id, herr.voice_id);
+    }
+
+    #[test]
+    fn say_as_and_other_unknown_elements_read_their_text() {
+        let markup =
+            r#"<speak>Call <say-as interpret-as="telephone">555-0100</say-as> now</speak>"#;
+        let segments = parse_ssml(markup).unwrap();
+        let number_offset = markup.find("555").unwrap();
+        assert_eq!(
+            texts(&segments),
+            vec![
+                ("Call", 7, 0.0),
+                ("555-0100", number_offset, 0.0),
+                ("now", markup.find("now").unwrap(), 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_markup_is_an_error() {
+        let error = parse_ssml("<speak><p>Hello</s></speak>").unwrap_err();
+        assert!(error.starts_with("Invalid SSML"), "{}", error);
+
+        let error = parse_ssml(r#"<speak>Hi<break time="soon"/></speak>"#).unwrap_err();
+        assert_eq!(error, "Invalid break time: soon");
+
+        let error = parse_ssml(r#"<speak><prosody rate="quick">Hi</prosody></speak>"#).unwrap_err();
+        assert_eq!(error, "Invalid prosody value: quick");
+    }
+
+    /// UTF-16 offset of `needle` in `text`
+    fn offset_of(text: &str, needle: &str) -> usize {
+        utf16_offset(text, text.find(needle).unwrap())
+    }
+
+    #[test]
+    fn offsets_survive_collapsed_whitespace_and_entities() {
+        let markup = "<speak>\n  Fish &amp;   chips\n   for tw\u{f6} &#8212; 🙂 today</speak>";
+        let segments = parse_ssml(markup).unwrap();
+        assert_eq!(segments.len(), 1);
+        let segment = &segments[0];
+        assert_eq!(segment.text, "Fish & chips for twö — 🙂 today");
+        assert_eq!(segment.char_offset, offset_of(markup, "Fish"));
+
+        let at = |word: &str| segment.source_offset(offset_of(&segment.text, word));
+        assert_eq!(at("&"), offset_of(markup, "&amp;"));
+        assert_eq!(at("chips"), offset_of(markup, "chips"));
+        assert_eq!(at("for"), offset_of(markup, "for"));
+        assert_eq!(at("—"), offset_of(markup, "&#8212;"));
+        assert_eq!(at("today"), offset_of(markup, "today"));
+        // The end of an entity is the end of its markup
+        assert_eq!(
+            segment.source_offset(offset_of(&segment.text, "&") + 1),
+            offset_of(markup, "&amp;") + 5
+        );
+    }
+
+    #[test]
+    fn aliases_point_at_their_element() {
+        let markup = r#"<speak>The <sub alias="World Wide Web">WWW</sub> grew</speak>"#;
+        let segments = parse_ssml(markup).unwrap();
+        let alias = segments
+            .iter()
+            .find(|s| s.text == "World Wide Web")
+            .unwrap();
+        let element = offset_of(markup, "<sub");
+        assert_eq!(alias.char_offset, element);
+        assert_eq!(alias.source_offset(6), element);
+        assert_eq!(
+            segments.last().unwrap().char_offset,
+            offset_of(markup, "grew")
+        );
+    }
+
+    #[test]
+    fn structure_splits_paragraphs_quotes_and_languages() {
+        let text = "Élan starts here 🙂. He said “Bonjour à tous” and left.\n\n\
+            Der Hund schläft.\nShe replied “fine”.";
+        let options = SpeechStructureOptions {
+            quote_voice_id: Some("piper:quote".to_string()),
+            ..Default::default()
+        };
+        let segments = structure_segments(text, &options, |sentence| {
+            if sentence.contains("Bonjour") {
+                "fr".to_string()
+            } else if sentence.contains("Hund") {
+                "de".to_string()
+            } else {
+                "en_US".to_string()
+            }
+        });
+
+        let summary: Vec<_> = segments
+            .iter()
+            .map(|s| {
+                (
+                    s.text.as_str(),
+                    s.char_offset,
+                    s.voice_id.as_deref(),
+                    s.language.as_deref(),
+                    s.pause_after,
+                )
+            })
+            .collect();
+        let pause = PARAGRAPH_PAUSE_SECONDS;
+        assert_eq!(
+            summary,
+            vec![
+                ("Élan starts here 🙂.", 0, None, None, 0.0),
+                ("He said", offset_of(text, "He"), None, None, 0.0),
+                (
+                    "“Bonjour à tous”",
+                    offset_of(text, "“B"),
+                    None,
+                    Some("fr"),
+                    0.0
+                ),
+                ("and left.", offset_of(text, "and"), None, None, pause),
+                (
+                    "Der Hund schläft.",
+                    offset_of(text, "Der"),
+                    None,
+                    Some("de"),
+                    0.0
+                ),
+                ("She replied", offset_of(text, "She"), None, None, 0.0),
+                (
+                    "“fine”",
+                    offset_of(text, "“f"),
+                    Some("piper:quote"),
+                    None,
+                    pause
+                ),
+            ]
+        );
+        // Segments are slices of the text, so offsets inside them map directly
+        assert_eq!(segments[3].source_offset(4), offset_of(text, "left"));
+    }
+
+    #[test]
+    fn structure_can_keep_one_language() {
+        let text = "Der Hund schläft. The dog sleeps.";
+        let options = SpeechStructureOptions {
+            language: Some("en".to_string()),
+            detect_languages: false,
+            ..Default::default()
+        };
+        let segments = structure_segments(text, &options, |_| unreachable!());
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|s| s.language.is_none()));
+        assert_eq!(segments[1].char_offset, offset_of(text, "The"));
+    }
+}
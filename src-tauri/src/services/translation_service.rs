@@ -1,3 +1,4 @@
+use super::model_manager::DownloadedModel;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -15,6 +16,8 @@ pub struct TranslationResult {
     pub source_language: String,
     pub target_language: String,
     pub confidence: Option<f32>,
+    /// File name of the catalog model that translated the text
+    pub model: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,7 +47,8 @@ pub struct DocumentTranslationResult {
 }
 
 /// Mock translation service
-/// In production, integrate with NLLB-200 model or cloud translation APIs
+/// In production, integrate with NLLB-200 model or cloud translation APIs.
+/// Callers pick the model through `ModelManager::model_for`.
 pub struct TranslationService {
     cache: HashMap<String, String>,
 }
@@ -57,8 +61,9 @@ impl TranslationService {
     }
 
     /// Translate text with mock implementation
-    pub fn translate_text(&mut self, text: &str, options: &TranslationOptions) -> TranslationResult {
-        let cache_key = format!("{}-{}-{}", 
+    pub fn translate_text(&mut self, text: &str, options: &TranslationOptions, model: &DownloadedModel) -> TranslationResult {
+        let cache_key = format!("{}-{}-{}-{}", 
+            model.file_name,
             text, 
             options.source_language.as_deref().unwrap_or("auto"), 
             options.target_language
@@ -79,17 +84,18 @@ impl TranslationService {
             source_language: options.source_language.clone().unwrap_or_else(|| "en".to_string()),
             target_language: options.target_language.clone(),
             confidence: Some(0.95),
+            model: model.file_name.clone(),
         }
     }
 
     /// Translate page text in chunks
-    pub fn translate_page(&mut self, page_text: &str, page_number: usize, options: &TranslationOptions) -> PageTranslationResult {
+    pub fn translate_page(&mut self, page_text: &str, page_number: usize, options: &TranslationOptions, model: &DownloadedModel) -> PageTranslationResult {
         let chunks = self.chunk_text(page_text, 500);
         let mut translated_chunks = Vec::new();
         let mut offset = 0;
 
         for (index, chunk) in chunks.iter().enumerate() {
-            let result = self.translate_text(chunk, options);
+            let result = self.translate_text(chunk, options, model);
             translated_chunks.push(TranslationChunk {
                 index,
                 original: chunk.clone(),
@@ -117,12 +123,13 @@ impl TranslationService {
     pub fn translate_document(
         &mut self, 
         pages: Vec<(usize, String)>, 
-        options: &TranslationOptions
+        options: &TranslationOptions,
+        model: &DownloadedModel,
     ) -> DocumentTranslationResult {
         let mut page_results = Vec::new();
 
         for (page_number, page_text) in pages {
-            let result = self.translate_page(&page_text, page_number, options);
+            let result = self.translate_page(&page_text, page_number, options, model);
             page_results.push(result);
         }
 
@@ -138,7 +145,7 @@ impl TranslationService {
     /// Detect language of text
     pub fn detect_language(&self, text: &str) -> String {
         // Mock language detection - in production, use proper language detection
-        if text.chars().any(|c| c as u32 > 0x4E00 && c as u32 < 0x9FFF) {
+        if text.chars().any(|c| c as u32 > 0x4E00 && (c as u32) < 0x9FFF) {
             "zh".to_string()
         } else if text.chars().any(|c| "àâäèéêëîïôùûüÿæœç".contains(c)) {
             "fr".to_string()
@@ -0,0 +1,801 @@
+use super::audio_decoder::{encode_wav, resample};
+use super::espeak_library::{espeak_synthesize, word_event_boundaries, WordEvent};
+use super::model_manager::ModelManager;
+use super::model_registry::ModelKind;
+use super::phonemes::{ipa_to_espeak, PhonemeDictionary};
+use super::ssml::{primary_language, SpeechSegment};
+use crate::utils::find_executable;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tauri::AppHandle;
+
+/// Piper reads a voice's settings from `<voice>.onnx.json` next to the model
+pub const PIPER_CONFIG_SUFFIX: &str = ".json";
+
+/// espeak-ng speaking rate at `rate = 1.0`, in words per minute
+const ESPEAK_DEFAULT_WPM: f32 = 175.0;
+/// Silence longer than this inside an utterance is treated as a phrase break
+const PHRASE_BREAK_SECONDS: f32 = 0.15;
+/// Samples below this amplitude count as silence when locating speech
+const SILENCE_AMPLITUDE: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TtsEngine {
+    EspeakNg,
+    Piper,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsVoice {
+    /// `engine:voice`, e.g. `piper:en_US-lessac-medium` or `espeak-ng:en-us`
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub engine: TtsEngine,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TtsOptions {
+    /// Voice to use; defaults to the first installed Piper voice, then espeak-ng
+    pub voice_id: Option<String>,
+    /// 1.0 is the voice's normal speed
+    pub rate: Option<f32>,
+    /// 1.0 is the voice's normal pitch (espeak-ng only)
+    pub pitch: Option<f32>,
+    /// 0.0-2.0, 1.0 leaves the level unchanged
+    pub volume: Option<f32>,
+}
+
+/// Timing of one spoken word, mirroring the webview's `boundary` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechBoundary {
+    pub word: String,
+    /// Offset into the spoken text in UTF-16 code units, like `SpeechSynthesisEvent.charIndex`
+    pub char_offset: usize,
+    pub char_length: usize,
+    /// Seconds from the start of the audio
+    pub start: f32,
+    pub end: f32,
+    /// Spread over the audio from the text, because the engine reported no
+    /// timing for the word
+    pub estimated: bool,
+}
+
+/// Synthesized mono audio with its word timings
+#[derive(Debug, Clone)]
+pub struct SynthesizedSpeech {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+    pub boundaries: Vec<SpeechBoundary>,
+}
+
+impl SynthesizedSpeech {
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    pub fn append_silence(&mut self, seconds: f32) {
+        let length = (seconds.max(0.0) * self.sample_rate as f32) as usize;
+        self.samples.extend(std::iter::repeat_n(0.0, length));
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SynthesisResult {
+    /// 16-bit PCM WAV
+    pub audio: Vec<u8>,
+    pub sample_rate: u32,
+    pub duration: f32,
+    pub boundaries: Vec<SpeechBoundary>,
+}
+
+impl From<SynthesizedSpeech> for SynthesisResult {
+    fn from(speech: SynthesizedSpeech) -> Self {
+        Self {
+            audio: encode_wav(&speech.samples, speech.sample_rate),
+            sample_rate: speech.sample_rate,
+            duration: speech.duration(),
+            boundaries: speech.boundaries,
+        }
+    }
+}
+
+/// Offline text-to-speech through locally installed engines.
+///
+/// Piper voices are ONNX models kept in the models directory; espeak-ng is
+/// used from the system as a fallback that covers most languages. The
+/// espeak-ng library reports when each word is spoken; Piper and the
+/// espeak-ng command line don't, so their boundaries are estimated from the
+/// text and aligned to the pauses in the synthesized audio.
+pub struct TtsService {
+    app_handle: AppHandle,
+}
+
+impl TtsService {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self { app_handle }
+    }
+
+    pub fn is_available(&self) -> bool {
+        find_espeak().is_some() || self.find_piper().is_some()
+    }
+
+    /// Installed voices, Piper first
+    pub fn list_voices(&self) -> Result<Vec<TtsVoice>, String> {
+        let mut voices = Vec::new();
+
+        if self.find_piper().is_some() {
+            for (file_name, config) in self.piper_voices()? {
+                let stem = file_name.trim_end_matches(".onnx").to_string();
+                voices.push(TtsVoice {
+                    id: format!("piper:{}", stem),
+                    name: stem.clone(),
+                    language: config.language,
+                    engine: TtsEngine::Piper,
+                });
+            }
+        }
+
+        if let Some(espeak) = find_espeak() {
+            let output = Command::new(espeak)
+                .arg("--voices")
+                .output()
+                .map_err(|e| format!("Failed to list espeak-ng voices: {}", e))?;
+
+            // Columns: Pty Language Age/Gender VoiceName File Other
+            for line in String::from_utf8_lossy(&output.stdout).lines().skip(1) {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                if columns.len() >= 4 {
+                    voices.push(TtsVoice {
+                        id: format!("espeak-ng:{}", columns[1]),
+                        name: columns[3].replace('_', " "),
+                        language: columns[1].to_string(),
+                        engine: TtsEngine::EspeakNg,
+                    });
+                }
+            }
+        }
+
+        Ok(voices)
+    }
+
+    /// Synthesize `text` in one piece
+    pub fn synthesize(
+        &self,
+        text: &str,
+        options: &TtsOptions,
+    ) -> Result<SynthesizedSpeech, String> {
+        if text.trim().is_empty() {
+            return Err("Nothing to speak".to_string());
+        }
+
+        Ok(self.render(text, None, options)?.into_speech(text))
+    }
+
+    /// Synthesize parsed or generated SSML segments back to back, with word
+    /// timings offset to each segment's position in the source
+    pub fn synthesize_segments(
+        &self,
+        segments: &[SpeechSegment],
+        options: &TtsOptions,
+    ) -> Result<SynthesizedSpeech, String> {
+        let voices = self.voices_for_segments(segments)?;
+        let mut speech: Option<SynthesizedSpeech> = None;
+        let mut leading_silence = 0.0;
+
+        for segment in segments {
+            let Some(part) = self.synthesize_span(&segment.text, segment, options, &voices)? else {
+                leading_silence += segment.pause_after;
+                continue;
+            };
+            let speech = speech.get_or_insert_with(|| SynthesizedSpeech {
+                sample_rate: part.sample_rate,
+                samples: Vec::new(),
+                boundaries: Vec::new(),
+            });
+            if speech.samples.is_empty() {
+                speech.append_silence(leading_silence);
+            }
+
+            let start = speech.duration();
+            let samples = if part.sample_rate == speech.sample_rate {
+                part.samples
+            } else {
+                resample(&part.samples, part.sample_rate, speech.sample_rate)?
+            };
+            speech.samples.extend(samples);
+            speech
+                .boundaries
+                .extend(part.boundaries.into_iter().map(|boundary| {
+                    let source = segment.source_offset(boundary.char_offset);
+                    SpeechBoundary {
+                        char_offset: source,
+                        char_length: segment
+                            .source_offset(boundary.char_offset + boundary.char_length)
+                            - source,
+                        start: boundary.start + start,
+                        end: boundary.end + start,
+                        ..boundary
+                    }
+                }));
+            speech.append_silence(segment.pause_after);
+        }
+
+        speech.ok_or_else(|| "Nothing to speak".to_string())
+    }
+
+    /// Synthesize `text` (all or part of `segment`) with the segment's voice
+    /// and prosody. Returns `None` for segments that are only a pause; the
+    /// pause itself is left to the caller.
+    pub fn synthesize_span(
+        &self,
+        text: &str,
+        segment: &SpeechSegment,
+        options: &TtsOptions,
+        voices: &[TtsVoice],
+    ) -> Result<Option<SynthesizedSpeech>, String> {
+        if text.trim().is_empty() && segment.phonemes.is_none() {
+            return Ok(None);
+        }
+
+        let scaled = |value: Option<f32>, factor: f32| {
+            ((factor - 1.0).abs() > f32::EPSILON || value.is_some())
+                .then(|| value.unwrap_or(1.0) * factor)
+        };
+        let span_options = TtsOptions {
+            voice_id: segment_voice(segment, options, voices),
+            rate: scaled(options.rate, segment.rate),
+            pitch: scaled(options.pitch, segment.pitch),
+            volume: scaled(options.volume, segment.volume),
+        };
+
+        let rendered = self.render(text, segment.phonemes.as_deref(), &span_options)?;
+        Ok(Some(rendered.into_speech(text)))
+    }
+
+    /// Installed voices when some segment needs a voice for its language
+    pub fn voices_for_segments(&self, segments: &[SpeechSegment]) -> Result<Vec<TtsVoice>, String> {
+        if segments.iter().any(|segment| segment.language.is_some()) {
+            self.list_voices()
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Run the engine for `text`, or for IPA `phonemes` when the engine
+    /// takes them
+    fn render(
+        &self,
+        text: &str,
+        phonemes: Option<&str>,
+        options: &TtsOptions,
+    ) -> Result<Rendered, String> {
+        let (engine, voice) = self.resolve_voice(options.voice_id.as_deref())?;
+        let rate = options.rate.unwrap_or(1.0).clamp(0.25, 4.0);
+
+        // espeak-ng reads its own phoneme mnemonics inside `[[ ]]`; Piper has
+        // no phoneme input, so it reads the text
+        let input = match (phonemes, engine) {
+            (Some(ipa), TtsEngine::EspeakNg) => format!("[[{}]]", ipa_to_espeak(ipa)),
+            _ => text.to_string(),
+        };
+        // Word events point into the input, which is only the text without phonemes
+        let mut words = None;
+
+        let (sample_rate, mut samples, language) = match engine {
+            TtsEngine::Piper => {
+                let (model_path, config) = self.piper_voice(&voice)?;
+                let piper = self
+                    .find_piper()
+                    .ok_or_else(|| "Piper is not installed".to_string())?;
+                let raw = run_engine(
+                    Command::new(piper)
+                        .arg("--model")
+                        .arg(&model_path)
+                        .arg("--output_raw")
+                        .arg("--length_scale")
+                        .arg(format!("{:.3}", 1.0 / rate)),
+                    &input,
+                )?;
+                (config.sample_rate, pcm16_to_f32(&raw), config.language)
+            }
+            TtsEngine::EspeakNg => {
+                let espeak =
+                    find_espeak().ok_or_else(|| "espeak-ng is not installed".to_string())?;
+                let words_per_minute = (ESPEAK_DEFAULT_WPM * rate) as u32;
+                let pitch = ((50.0 * options.pitch.unwrap_or(1.0).clamp(0.0, 2.0)) as u32).min(99);
+                match espeak_synthesize(Some(&espeak), &voice, &input, words_per_minute, pitch) {
+                    Some(speech) => {
+                        let speech = speech?;
+                        if phonemes.is_none() {
+                            words = Some(speech.words);
+                        }
+                        (speech.sample_rate, speech.samples, voice)
+                    }
+                    None => {
+                        let wav = run_engine(
+                            Command::new(espeak)
+                                .arg("--stdout")
+                                .arg("-v")
+                                .arg(&voice)
+                                .arg("-s")
+                                .arg(words_per_minute.to_string())
+                                .arg("-p")
+                                .arg(pitch.to_string()),
+                            &input,
+                        )?;
+                        let (sample_rate, samples) = parse_wav(&wav)?;
+                        (sample_rate, samples, voice)
+                    }
+                }
+            }
+        };
+
+        if let Some(volume) = options.volume {
+            let gain = volume.clamp(0.0, 2.0);
+            for sample in &mut samples {
+                *sample = (*sample * gain).clamp(-1.0, 1.0);
+            }
+        }
+
+        Ok(Rendered {
+            sample_rate,
+            samples,
+            language,
+            words,
+        })
+    }
+
+    fn resolve_voice(&self, voice_id: Option<&str>) -> Result<(TtsEngine, String), String> {
+        match voice_id {
+            Some(id) => match id.split_once(':') {
+                Some(("piper", voice)) => Ok((TtsEngine::Piper, voice.to_string())),
+                Some(("espeak-ng", voice)) => Ok((TtsEngine::EspeakNg, voice.to_string())),
+                _ => Err(format!("Unknown voice: {}", id)),
+            },
+            None => {
+                if self.find_piper().is_some() {
+                    if let Some((file_name, _)) = self.piper_voices()?.into_iter().next() {
+                        let voice = file_name.trim_end_matches(".onnx").to_string();
+                        return Ok((TtsEngine::Piper, voice));
+                    }
+                }
+                if find_espeak().is_some() {
+                    return Ok((TtsEngine::EspeakNg, "en".to_string()));
+                }
+                Err(
+                    "No text-to-speech engine found. Install espeak-ng or download a Piper voice."
+                        .to_string(),
+                )
+            }
+        }
+    }
+
+    /// Installed Piper voices that have their config alongside
+    fn piper_voices(&self) -> Result<Vec<(String, PiperConfig)>, String> {
+        let mut voices: Vec<(String, PiperConfig)> = ModelManager::new(self.app_handle.clone())
+            .installed_models(ModelKind::Voice)?
+            .into_iter()
+            .filter_map(|voice| {
+                let config = read_piper_config(Path::new(&voice.path)).ok()?;
+                Some((voice.file_name, config))
+            })
+            .collect();
+        voices.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(voices)
+    }
+
+    fn piper_voice(&self, voice: &str) -> Result<(PathBuf, PiperConfig), String> {
+        let manager = ModelManager::new(self.app_handle.clone());
+        let file_name = format!("{}.onnx", voice);
+        let model_path = manager.get_model_path(&file_name)?;
+        if !model_path.exists() {
+            return Err(format!("Piper voice not downloaded: {}", voice));
+        }
+        let config = read_piper_config(&model_path)?;
+        let _ = manager.mark_used(&file_name);
+        Ok((model_path, config))
+    }
+
+    /// Piper bundled in the models directory, else from `PATH`
+    fn find_piper(&self) -> Option<PathBuf> {
+        let binary = if cfg!(windows) { "piper.exe" } else { "piper" };
+        ModelManager::new(self.app_handle.clone())
+            .get_models_dir()
+            .ok()
+            .map(|dir| dir.join("piper").join(binary))
+            .filter(|path| path.is_file())
+            .or_else(|| find_executable("piper"))
+    }
+}
+
+struct PiperConfig {
+    sample_rate: u32,
+    language: String,
+}
+
+fn read_piper_config(model_path: &Path) -> Result<PiperConfig, String> {
+    let mut config_path = model_path.as_os_str().to_owned();
+    config_path.push(PIPER_CONFIG_SUFFIX);
+
+    let content = std::fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read Piper voice config: {}", e))?;
+    let config: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid Piper voice config: {}", e))?;
+
+    Ok(PiperConfig {
+        sample_rate: config["audio"]["sample_rate"].as_u64().unwrap_or(22_050) as u32,
+        language: config["language"]["code"]
+            .as_str()
+            .or_else(|| config["espeak"]["voice"].as_str())
+            .unwrap_or("en")
+            .to_string(),
+    })
+}
+
+/// The segment's named voice, else a voice for its language, else the session voice
+fn segment_voice(
+    segment: &SpeechSegment,
+    options: &TtsOptions,
+    voices: &[TtsVoice],
+) -> Option<String> {
+    if segment.voice_id.is_some() {
+        return segment.voice_id.clone();
+    }
+    let Some(language) = &segment.language else {
+        return options.voice_id.clone();
+    };
+
+    // Keep the session voice when it already speaks the language
+    let session_voice = options
+        .voice_id
+        .as_ref()
+        .and_then(|id| voices.iter().find(|voice| &voice.id == id));
+    if session_voice
+        .is_some_and(|voice| primary_language(&voice.language) == primary_language(language))
+    {
+        return options.voice_id.clone();
+    }
+
+    voice_for_language(voices, language)
+        .map(|voice| voice.id.clone())
+        .or_else(|| options.voice_id.clone())
+}
+
+/// Best installed voice for a language: an exact tag match, then the same
+/// primary language, Piper before espeak-ng
+pub fn voice_for_language<'a>(voices: &'a [TtsVoice], language: &str) -> Option<&'a TtsVoice> {
+    let normalize = |tag: &str| tag.replace('_', "-").to_lowercase();
+    let wanted = normalize(language);
+    voices
+        .iter()
+        .find(|voice| normalize(&voice.language) == wanted)
+        .or_else(|| {
+            voices
+                .iter()
+                .find(|voice| primary_language(&voice.language) == primary_language(language))
+        })
+}
+
+fn find_espeak() -> Option<PathBuf> {
+    find_executable("espeak-ng").or_else(|| find_executable("espeak"))
+}
+
+/// Run a TTS engine with `text` on stdin and return its stdout
+fn run_engine(command: &mut Command, text: &str) -> Result<Vec<u8>, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start speech engine: {}", e))?;
+
+    // Engines read line by line; keep the passage on one line
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    // Engines stream audio while they read, so write from another thread;
+    // otherwise a full stdout pipe blocks them and us on a long passage
+    let writer = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || stdin.write_all(format!("{}\n", line).as_bytes()))
+    });
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Speech engine failed: {}", e))?;
+    // An engine that failed early also breaks the pipe; its stderr says more
+    if !output.status.success() {
+        return Err(format!(
+            "Speech engine failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    if let Some(writer) = writer {
+        writer
+            .join()
+            .map_err(|_| "Speech engine input writer panicked".to_string())?
+            .map_err(|e| format!("Failed to send text to speech engine: {}", e))?;
+    }
+    Ok(output.stdout)
+}
+
+fn pcm16_to_f32(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0)
+        .collect()
+}
+
+/// Read 16-bit mono PCM from a WAV stream. espeak-ng writes an unknown data
+/// length when streaming to stdout, so the data chunk runs to the end.
+fn parse_wav(bytes: &[u8]) -> Result<(u32, Vec<f32>), String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("Speech engine did not return WAV audio".to_string());
+    }
+
+    let mut sample_rate = None;
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let id = &bytes[position..position + 4];
+        let size = u32::from_le_bytes([
+            bytes[position + 4],
+            bytes[position + 5],
+            bytes[position + 6],
+            bytes[position + 7],
+        ]) as usize;
+        let body = position + 8;
+
+        if id == b"fmt " && body + 8 <= bytes.len() {
+            sample_rate = Some(u32::from_le_bytes([
+                bytes[body + 4],
+                bytes[body + 5],
+                bytes[body + 6],
+                bytes[body + 7],
+            ]));
+        } else if id == b"data" {
+            let end = body.saturating_add(size).min(bytes.len());
+            let sample_rate =
+                sample_rate.ok_or_else(|| "WAV audio has no format chunk".to_string())?;
+            return Ok((sample_rate, pcm16_to_f32(&bytes[body..end])));
+        }
+
+        position = body.saturating_add(size + (size & 1));
+    }
+
+    Err("WAV audio has no data".to_string())
+}
+
+/// Engine output for one piece of text
+struct Rendered {
+    sample_rate: u32,
+    samples: Vec<f32>,
+    /// The voice's language
+    language: String,
+    /// When each word of the text was spoken, if the engine said
+    words: Option<Vec<WordEvent>>,
+}
+
+impl Rendered {
+    fn into_speech(self, text: &str) -> SynthesizedSpeech {
+        let boundaries = match &self.words {
+            Some(words) => {
+                let duration = self.samples.len() as f32 / self.sample_rate.max(1) as f32;
+                word_event_boundaries(text, words, duration)
+            }
+            None => estimate_boundaries(text, &self.samples, self.sample_rate, &self.language),
+        };
+        SynthesizedSpeech {
+            sample_rate: self.sample_rate,
+            samples: self.samples,
+            boundaries,
+        }
+    }
+}
+
+struct TextWord<'a> {
+    text: &'a str,
+    utf16_offset: usize,
+    weight: f32,
+    /// Punctuation after this word ends a phrase
+    ends_phrase: bool,
+}
+
+/// Estimate when each word of `text` is spoken in `samples`.
+///
+/// Words are weighted by their sound count and spread over the voiced audio.
+/// When the pauses in the audio line up with the phrase breaks in the text,
+/// each phrase is spread over its own stretch of speech, which keeps
+/// highlighting from drifting over long sentences.
+pub fn estimate_boundaries(
+    text: &str,
+    samples: &[f32],
+    sample_rate: u32,
+    language: &str,
+) -> Vec<SpeechBoundary> {
+    let dictionary = language
+        .to_lowercase()
+        .starts_with("en")
+        .then(PhonemeDictionary::builtin);
+
+    let mut words = Vec::new();
+    let mut utf16_offset = 0;
+    let mut word_start: Option<(usize, usize)> = None;
+    for (index, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        if c.is_whitespace() {
+            if let Some((start, offset)) = word_start.take() {
+                let raw = &text[start..index];
+                let sounds = match &dictionary {
+                    Some(dictionary) => dictionary.phonemes(raw).len(),
+                    None => raw.chars().filter(|c| c.is_alphanumeric()).count(),
+                };
+                words.push(TextWord {
+                    text: raw,
+                    utf16_offset: offset,
+                    weight: sounds.max(1) as f32,
+                    ends_phrase: raw.ends_with([',', ';', ':', '.', '!', '?']),
+                });
+            }
+        } else if word_start.is_none() {
+            word_start = Some((index, utf16_offset));
+        }
+        if index < text.len() {
+            utf16_offset += c.len_utf16();
+        }
+    }
+
+    if words.is_empty() {
+        return Vec::new();
+    }
+
+    let segments = speech_segments(samples, sample_rate);
+    let mut phrases: Vec<&[TextWord]> = Vec::new();
+    let mut phrase_start = 0;
+    for (index, word) in words.iter().enumerate() {
+        if word.ends_phrase || index + 1 == words.len() {
+            phrases.push(&words[phrase_start..=index]);
+            phrase_start = index + 1;
+        }
+    }
+
+    let spans: Vec<(&[TextWord], (f32, f32))> = if segments.len() == phrases.len() {
+        phrases.into_iter().zip(segments).collect()
+    } else {
+        let total = samples.len() as f32 / sample_rate.max(1) as f32;
+        let start = segments.first().map(|s| s.0).unwrap_or(0.0);
+        let end = segments.last().map(|s| s.1).unwrap_or(total);
+        vec![(&words[..], (start, end))]
+    };
+
+    let mut boundaries = Vec::with_capacity(words.len());
+    for (phrase, (start, end)) in spans {
+        let total_weight: f32 = phrase.iter().map(|w| w.weight).sum();
+        let mut time = start;
+        for word in phrase {
+            let length = (end - start) * word.weight / total_weight;
+            boundaries.push(SpeechBoundary {
+                word: word.text.to_string(),
+                char_offset: word.utf16_offset,
+                char_length: word.text.encode_utf16().count(),
+                start: time,
+                end: time + length,
+                estimated: true,
+            });
+            time += length;
+        }
+    }
+    boundaries
+}
+
+/// Stretches of sound separated by pauses of at least `PHRASE_BREAK_SECONDS`
+fn speech_segments(samples: &[f32], sample_rate: u32) -> Vec<(f32, f32)> {
+    let rate = sample_rate.max(1) as f32;
+    let window = (rate * 0.01) as usize;
+    let min_gap = (PHRASE_BREAK_SECONDS * rate) as usize;
+
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    for (index, chunk) in samples.chunks(window.max(1)).enumerate() {
+        let peak = chunk.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        if peak < SILENCE_AMPLITUDE {
+            continue;
+        }
+        let start = index * window.max(1);
+        let end = start + chunk.len();
+        match segments.last_mut() {
+            Some(last) if start - last.1 < min_gap => last.1 = end,
+            _ => segments.push((start, end)),
+        }
+    }
+
+    segments
+        .into_iter()
+        .map(|(start, end)| (start as f32 / rate, end as f32 / rate))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    /// Alternating stretches of tone and silence, in seconds
+    fn audio(stretches: &[(bool, f32)]) -> Vec<f32> {
+        let mut samples = Vec::new();
+        for &(voiced, seconds) in stretches {
+            let count = (seconds * RATE as f32) as usize;
+            samples.extend((0..count).map(|i| {
+                if voiced {
+                    (i as f32 / RATE as f32 * 200.0 * std::f32::consts::TAU).sin() * 0.5
+                } else {
+                    0.0
+                }
+            }));
+        }
+        samples
+    }
+
+    #[test]
+    fn boundaries_cover_words_in_order_with_utf16_offsets() {
+        let text = "Café  au lait";
+        let samples = audio(&[(false, 0.2), (true, 1.2), (false, 0.2)]);
+        let boundaries = estimate_boundaries(text, &samples, RATE, "fr");
+
+        let words: Vec<(&str, usize, usize)> = boundaries
+            .iter()
+            .map(|b| (b.word.as_str(), b.char_offset, b.char_length))
+            .collect();
+        assert_eq!(words, vec![("Café", 0, 4), ("au", 6, 2), ("lait", 9, 4)]);
+
+        // Spread over the voiced audio only, without gaps between words
+        assert!((boundaries[0].start - 0.2).abs() < 0.02);
+        assert!((boundaries[2].end - 1.4).abs() < 0.02);
+        for pair in boundaries.windows(2) {
+            assert!((pair[0].end - pair[1].start).abs() < 1e-4);
+        }
+        // Without a dictionary, longer words take longer
+        let length = |b: &SpeechBoundary| b.end - b.start;
+        assert!(length(&boundaries[0]) > length(&boundaries[1]));
+    }
+
+    #[test]
+    fn phrases_follow_pauses_in_the_audio() {
+        let text = "Hello there, general Kenobi.";
+        let samples = audio(&[(true, 0.5), (false, 0.4), (true, 1.0)]);
+        let boundaries = estimate_boundaries(text, &samples, RATE, "en-US");
+
+        assert_eq!(boundaries.len(), 4);
+        // "there," ends the first phrase at the pause, "general" starts after it
+        assert!((boundaries[1].end - 0.5).abs() < 0.02);
+        assert!((boundaries[2].start - 0.9).abs() < 0.02);
+        assert!((boundaries[3].end - 1.9).abs() < 0.02);
+    }
+
+    #[test]
+    fn pauses_that_do_not_match_phrases_spread_words_over_all_speech() {
+        let text = "one two three four";
+        let samples = audio(&[(true, 0.5), (false, 0.4), (true, 0.5)]);
+        let boundaries = estimate_boundaries(text, &samples, RATE, "en");
+
+        assert_eq!(boundaries.len(), 4);
+        assert!(boundaries[0].start.abs() < 0.02);
+        assert!((boundaries[3].end - 1.4).abs() < 0.02);
+    }
+
+    #[test]
+    fn blank_text_has_no_boundaries() {
+        assert!(estimate_boundaries("  \n ", &audio(&[(true, 0.5)]), RATE, "en").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn engine_output_larger_than_a_pipe_does_not_block() {
+        // `cat` writes while it reads, like Piper and espeak-ng
+        let text = "word ".repeat(100_000);
+        let output = run_engine(&mut Command::new("cat"), &text).unwrap();
+        assert_eq!(output.len(), text.len());
+    }
+}
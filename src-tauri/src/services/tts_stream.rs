@@ -0,0 +1,289 @@
+use super::audio_decoder::encode_wav;
+use super::ssml::SpeechSegment;
+use super::tts_service::{SpeechBoundary, SynthesizedSpeech, TtsOptions, TtsService};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tauri::{AppHandle, Emitter};
+
+/// Event carrying each synthesized sentence as it becomes ready
+pub const TTS_CHUNK_EVENT: &str = "tts-chunk";
+/// Event emitted once all chunks were sent or the session was stopped
+pub const TTS_FINISHED_EVENT: &str = "tts-finished";
+/// Event emitted when synthesis fails
+pub const TTS_ERROR_EVENT: &str = "tts-error";
+
+/// Sentences are split further when longer than this many bytes
+const MAX_CHUNK_CHARS: usize = 400;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsChunkEvent {
+    pub session_id: String,
+    pub chunk_index: usize,
+    pub text: String,
+    /// Offset of the chunk in the full text, in UTF-16 code units
+    pub char_offset: usize,
+    /// 16-bit PCM WAV
+    pub audio: Vec<u8>,
+    pub sample_rate: u32,
+    /// Seconds from the start of the session at which this chunk plays
+    pub start: f32,
+    pub duration: f32,
+    /// Word timings relative to the chunk, with offsets into the full text
+    pub boundaries: Vec<SpeechBoundary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsFinishedEvent {
+    pub session_id: String,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsErrorEvent {
+    pub session_id: String,
+    pub message: String,
+}
+
+/// Handle to a speech session synthesizing on its own worker thread
+pub struct TtsStream {
+    cancelled: Arc<AtomicBool>,
+    worker: JoinHandle<()>,
+}
+
+impl TtsStream {
+    /// Speak SSML or structured segments, each with its own voice and prosody.
+    /// Each segment is synthesized sentence by sentence and every chunk is
+    /// emitted as soon as it is ready, so playback can start before the whole
+    /// passage is done. Chunk offsets point into the text the segments were
+    /// made from.
+    pub fn start_segments(
+        app_handle: AppHandle,
+        session_id: String,
+        segments: Vec<SpeechSegment>,
+        options: TtsOptions,
+    ) -> Result<Self, String> {
+        let service = TtsService::new(app_handle.clone());
+        if !service.is_available() {
+            return Err(
+                "No text-to-speech engine found. Install espeak-ng or download a Piper voice."
+                    .to_string(),
+            );
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = cancelled.clone();
+        let worker = std::thread::Builder::new()
+            .name("tts-stream".to_string())
+            .spawn(move || {
+                let emit_error = |message: String| {
+                    let _ = app_handle.emit(
+                        TTS_ERROR_EVENT,
+                        TtsErrorEvent {
+                            session_id: session_id.clone(),
+                            message,
+                        },
+                    );
+                };
+                let voices = match service.voices_for_segments(&segments) {
+                    Ok(voices) => voices,
+                    Err(message) => {
+                        emit_error(message);
+                        Vec::new()
+                    }
+                };
+
+                let mut start = 0.0;
+                let mut chunk_index = 0;
+                // Pauses before the first words are played ahead of the next chunk
+                let mut pending_silence = 0.0;
+
+                'segments: for segment in &segments {
+                    // Phoneme overrides are spoken as one piece
+                    let chunks = if segment.phonemes.is_some() {
+                        vec![(0, segment.text.as_str())]
+                    } else {
+                        split_chunks(&segment.text)
+                    };
+                    let last_chunk = chunks.len().saturating_sub(1);
+
+                    for (index, (char_offset, chunk)) in chunks.into_iter().enumerate() {
+                        if worker_cancelled.load(Ordering::Relaxed) {
+                            break 'segments;
+                        }
+
+                        let speech =
+                            match service.synthesize_span(chunk, segment, &options, &voices) {
+                                Ok(Some(speech)) => speech,
+                                Ok(None) => continue,
+                                Err(message) => {
+                                    emit_error(message);
+                                    break 'segments;
+                                }
+                            };
+
+                        let mut padded = SynthesizedSpeech {
+                            samples: Vec::new(),
+                            boundaries: Vec::new(),
+                            ..speech
+                        };
+                        padded.append_silence(pending_silence);
+                        let lead = padded.duration();
+                        pending_silence = 0.0;
+                        padded.samples.extend(speech.samples);
+                        if index == last_chunk {
+                            padded.append_silence(segment.pause_after);
+                        }
+
+                        let duration = padded.duration();
+                        let boundaries = speech
+                            .boundaries
+                            .into_iter()
+                            .map(|boundary| {
+                                let start = boundary.char_offset + char_offset;
+                                let source = segment.source_offset(start);
+                                SpeechBoundary {
+                                    char_offset: source,
+                                    char_length: segment
+                                        .source_offset(start + boundary.char_length)
+                                        - source,
+                                    start: boundary.start + lead,
+                                    end: boundary.end + lead,
+                                    ..boundary
+                                }
+                            })
+                            .collect();
+                        let char_offset = segment.source_offset(char_offset);
+
+                        let _ = app_handle.emit(
+                            TTS_CHUNK_EVENT,
+                            TtsChunkEvent {
+                                session_id: session_id.clone(),
+                                chunk_index,
+                                text: chunk.to_string(),
+                                char_offset,
+                                audio: encode_wav(&padded.samples, padded.sample_rate),
+                                sample_rate: padded.sample_rate,
+                                start,
+                                duration,
+                                boundaries,
+                            },
+                        );
+                        start += duration;
+                        chunk_index += 1;
+                    }
+
+                    if segment.text.trim().is_empty() {
+                        pending_silence += segment.pause_after;
+                    }
+                }
+
+                let _ = app_handle.emit(
+                    TTS_FINISHED_EVENT,
+                    TtsFinishedEvent {
+                        session_id,
+                        cancelled: worker_cancelled.load(Ordering::Relaxed),
+                    },
+                );
+            })
+            .map_err(|e| format!("Failed to start speech worker: {}", e))?;
+
+        Ok(Self { cancelled, worker })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Stop after the chunk currently being synthesized
+    pub fn stop(self) -> Result<(), String> {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.worker
+            .join()
+            .map_err(|_| "Speech worker panicked".to_string())
+    }
+}
+
+/// Split text into sentences (and long sentences at clause breaks), returning
+/// each chunk with its offset in UTF-16 code units
+pub fn split_chunks(text: &str) -> Vec<(usize, &str)> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0;
+    let mut last_clause_break = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let next_is_space = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+        let end = index + c.len_utf8();
+
+        if matches!(c, ',' | ';' | ':') && next_is_space {
+            last_clause_break = Some(end);
+        }
+
+        let sentence_end = matches!(c, '.' | '!' | '?' | '\n') && next_is_space;
+        if sentence_end {
+            chunks.push((chunk_start, end));
+            chunk_start = end;
+            last_clause_break = None;
+        } else if end - chunk_start > MAX_CHUNK_CHARS {
+            let split = last_clause_break.unwrap_or(end);
+            chunks.push((chunk_start, split));
+            chunk_start = split;
+            last_clause_break = None;
+        }
+    }
+    chunks.push((chunk_start, text.len()));
+
+    chunks
+        .into_iter()
+        .filter_map(|(start, end)| {
+            let chunk = &text[start..end];
+            let trimmed = chunk.trim_start();
+            let start = start + chunk.len() - trimmed.len();
+            let trimmed = trimmed.trim_end();
+            (!trimmed.is_empty()).then(|| (text[..start].encode_utf16().count(), trimmed))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_sentences_with_utf16_offsets() {
+        let text = "Grüße! Wie geht's?  Gut.\nDanke";
+        assert_eq!(
+            split_chunks(text),
+            vec![
+                (0, "Grüße!"),
+                (7, "Wie geht's?"),
+                (20, "Gut."),
+                (25, "Danke")
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_decimal_numbers_together() {
+        assert_eq!(
+            split_chunks("It costs 3.50 today."),
+            vec![(0, "It costs 3.50 today.")]
+        );
+    }
+
+    #[test]
+    fn splits_long_sentences_at_the_last_clause_break() {
+        let first = format!("{},", "a".repeat(300));
+        let text = format!("{} {} end.", first, "b".repeat(200));
+        let chunks = split_chunks(&text);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], (0, first.as_str()));
+        assert_eq!(chunks[1].0, first.len() + 1);
+        assert!(chunks
+            .iter()
+            .all(|(_, chunk)| chunk.len() <= MAX_CHUNK_CHARS));
+    }
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+
+/// Energy-based voice activity detection tuning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VadConfig {
+    /// Analysis frame length in milliseconds
+    pub frame_ms: u32,
+    /// How far above the tracked noise floor a frame must be to count as speech
+    pub threshold_db: f32,
+    /// Frames quieter than this are never speech, regardless of the noise floor
+    pub min_speech_dbfs: f32,
+    /// Consecutive speech needed before an utterance starts
+    pub speech_start_ms: u32,
+    /// Trailing silence that ends an utterance
+    pub silence_end_ms: u32,
+    /// Audio kept from before the detected start so word onsets aren't clipped
+    pub pre_roll_ms: u32,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            frame_ms: 30,
+            threshold_db: 9.0,
+            min_speech_dbfs: -50.0,
+            speech_start_ms: 90,
+            silence_end_ms: 600,
+            pre_roll_ms: 250,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VadEvent {
+    /// Speech started; carries the absolute sample index of the utterance start
+    /// (including pre-roll)
+    SpeechStart(usize),
+    /// Speech ended; carries the absolute sample index just after the last voiced frame
+    SpeechEnd(usize),
+}
+
+/// Streaming voice activity detector operating on mono PCM
+pub struct VoiceActivityDetector {
+    config: VadConfig,
+    frame_len: usize,
+    sample_rate: u32,
+    pending: Vec<f32>,
+    /// Absolute index of the first sample in `pending`
+    position: usize,
+    noise_floor_db: f32,
+    in_speech: bool,
+    voiced_run: usize,
+    silent_run: usize,
+    last_voiced_end: usize,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(config: VadConfig, sample_rate: u32) -> Self {
+        let frame_len = ((sample_rate as u64 * config.frame_ms as u64) / 1000).max(1) as usize;
+        Self {
+            config,
+            frame_len,
+            sample_rate,
+            pending: Vec::new(),
+            position: 0,
+            noise_floor_db: -60.0,
+            in_speech: false,
+            voiced_run: 0,
+            silent_run: 0,
+            last_voiced_end: 0,
+        }
+    }
+
+    /// Feed samples and return any speech boundary events they produced
+    pub fn process(&mut self, samples: &[f32]) -> Vec<VadEvent> {
+        self.pending.extend_from_slice(samples);
+        let mut events = Vec::new();
+
+        let start_frames = self.ms_to_frames(self.config.speech_start_ms).max(1);
+        let end_frames = self.ms_to_frames(self.config.silence_end_ms).max(1);
+        let pre_roll = self.ms_to_samples(self.config.pre_roll_ms);
+
+        let mut offset = 0;
+        while self.pending.len() - offset >= self.frame_len {
+            let frame = &self.pending[offset..offset + self.frame_len];
+            let frame_start = self.position + offset;
+            let frame_end = frame_start + self.frame_len;
+            let energy_db = frame_dbfs(frame);
+
+            let voiced = energy_db > self.config.min_speech_dbfs
+                && energy_db > self.noise_floor_db + self.config.threshold_db;
+
+            if !voiced {
+                // Track the noise floor quickly downwards and slowly upwards so
+                // steady background noise is learned but speech isn't
+                let rate = if energy_db < self.noise_floor_db { 0.3 } else { 0.02 };
+                self.noise_floor_db += (energy_db - self.noise_floor_db) * rate;
+            }
+
+            if voiced {
+                self.voiced_run += 1;
+                self.silent_run = 0;
+                self.last_voiced_end = frame_end;
+            } else {
+                self.silent_run += 1;
+                self.voiced_run = 0;
+            }
+
+            if !self.in_speech && self.voiced_run >= start_frames {
+                self.in_speech = true;
+                let onset = frame_end - self.voiced_run * self.frame_len;
+                events.push(VadEvent::SpeechStart(onset.saturating_sub(pre_roll)));
+            } else if self.in_speech && self.silent_run >= end_frames {
+                self.in_speech = false;
+                events.push(VadEvent::SpeechEnd(self.last_voiced_end));
+            }
+
+            offset += self.frame_len;
+        }
+
+        self.pending.drain(..offset);
+        self.position += offset;
+        events
+    }
+
+    fn ms_to_samples(&self, ms: u32) -> usize {
+        (self.sample_rate as u64 * ms as u64 / 1000) as usize
+    }
+
+    fn ms_to_frames(&self, ms: u32) -> usize {
+        self.ms_to_samples(ms) / self.frame_len
+    }
+}
+
+/// RMS level of a frame in dB relative to full scale
+fn frame_dbfs(frame: &[f32]) -> f32 {
+    let mean_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len().max(1) as f32;
+    10.0 * (mean_square + 1e-10).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    /// Silence, then a tone, then silence, each `ms` long
+    fn utterance(silence_ms: usize, speech_ms: usize) -> Vec<f32> {
+        let samples = |ms: usize| ms * RATE as usize / 1000;
+        let mut audio = vec![0.0; samples(silence_ms)];
+        audio.extend(
+            (0..samples(speech_ms))
+                .map(|i| (i as f32 / RATE as f32 * 220.0 * std::f32::consts::TAU).sin() * 0.3),
+        );
+        audio.extend(vec![0.0; samples(silence_ms)]);
+        audio
+    }
+
+    #[test]
+    fn reports_speech_start_with_pre_roll_and_end_after_trailing_silence() {
+        let mut vad = VoiceActivityDetector::new(VadConfig::default(), RATE);
+        // 960 ms is a whole number of 30 ms frames
+        let events = vad.process(&utterance(960, 960));
+
+        // Tone from sample 15 360 to 30 720; 250 ms pre-roll is 4 000 samples
+        assert_eq!(
+            events,
+            vec![VadEvent::SpeechStart(11_360), VadEvent::SpeechEnd(30_720)]
+        );
+    }
+
+    #[test]
+    fn ignores_blips_shorter_than_speech_start() {
+        let mut vad = VoiceActivityDetector::new(VadConfig::default(), RATE);
+        assert!(vad.process(&utterance(960, 60)).is_empty());
+    }
+
+    #[test]
+    fn waits_for_silence_end_before_ending_speech() {
+        let mut vad = VoiceActivityDetector::new(VadConfig::default(), RATE);
+        let mut audio = utterance(960, 960);
+        // Only 300 ms of the trailing silence, short of the 600 ms needed
+        audio.truncate(30_720 + 4_800);
+        assert_eq!(vad.process(&audio), vec![VadEvent::SpeechStart(11_360)]);
+
+        assert_eq!(
+            vad.process(&vec![0.0; 4_800]),
+            vec![VadEvent::SpeechEnd(30_720)]
+        );
+    }
+
+    #[test]
+    fn chunk_size_does_not_change_events() {
+        let audio = utterance(960, 960);
+        let mut whole = VoiceActivityDetector::new(VadConfig::default(), RATE);
+        let expected = whole.process(&audio);
+
+        let mut chunked = VoiceActivityDetector::new(VadConfig::default(), RATE);
+        let events: Vec<VadEvent> = audio
+            .chunks(333)
+            .flat_map(|chunk| chunked.process(chunk))
+            .collect();
+        assert_eq!(events, expected);
+    }
+}
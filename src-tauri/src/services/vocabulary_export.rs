@@ -0,0 +1,743 @@
+use super::database::{Database, ImportedPracticeWord, PracticeWordDetails, VocabularyEntry};
+use crate::utils::escape_html;
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+
+/// Note type id used for every export, so re-importing a deck into Anki
+/// updates the existing notes instead of creating a second note type
+const ANKI_MODEL_ID: i64 = 1_716_000_000_000;
+const ANKI_MODEL_NAME: &str = "PDF Reader Vocabulary";
+const ANKI_FIELDS: [&str; 7] = ["Word", "Translation", "IPA", "Context", "Page", "Document", "Audio"];
+
+const CSV_HEADERS: [&str; 10] = [
+    "word",
+    "language",
+    "translation",
+    "ipa",
+    "context",
+    "page_number",
+    "document_id",
+    "document_title",
+    "attempts",
+    "audio_path",
+];
+
+/// Defaults applied to imported rows that leave a column empty
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VocabularyImportDefaults {
+    pub document_id: Option<String>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VocabularyImportSummary {
+    pub imported: usize,
+    /// Rows whose word was already being practiced
+    pub duplicates: usize,
+    pub skipped: usize,
+    /// Why rows were skipped, with their 1-based line numbers
+    pub errors: Vec<String>,
+}
+
+/// Write practice words as CSV with a header row
+pub fn export_vocabulary_csv(path: &Path, entries: &[VocabularyEntry]) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(path)
+        .map_err(|e| format!("Failed to create CSV file: {}", e))?;
+
+    writer
+        .write_record(CSV_HEADERS)
+        .map_err(|e| format!("Failed to write CSV: {}", e))?;
+
+    for entry in entries {
+        let details = &entry.details;
+        writer
+            .write_record([
+                entry.word.as_str(),
+                entry.language.as_str(),
+                details.translation.as_deref().unwrap_or(""),
+                details.ipa.as_deref().unwrap_or(""),
+                details.context.as_deref().unwrap_or(""),
+                &entry.page_number.to_string(),
+                entry.document_id.as_str(),
+                entry.document_title.as_deref().unwrap_or(""),
+                &entry.attempts.to_string(),
+                details.audio_path.as_deref().unwrap_or(""),
+            ])
+            .map_err(|e| format!("Failed to write CSV: {}", e))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+/// Import practice words from a CSV file with a header row.
+///
+/// Only `word` is required; headers are matched case-insensitively and
+/// missing columns fall back to `defaults`. Words the document already has in
+/// that language are not duplicated, and rows that can't be used are reported
+/// in the summary rather than failing the import.
+pub fn import_vocabulary_csv(
+    db: &Database,
+    path: &Path,
+    defaults: &VocabularyImportDefaults,
+) -> Result<VocabularyImportSummary, String> {
+    let (words, mut summary) = read_vocabulary_csv(path, defaults)?;
+    let mut known_documents = HashMap::new();
+
+    for imported in &words {
+        let exists = match known_documents.get(&imported.document_id) {
+            Some(&exists) => exists,
+            None => {
+                let exists = db
+                    .document_exists(&imported.document_id)
+                    .map_err(|e| format!("Failed to import vocabulary: {}", e))?;
+                known_documents.insert(imported.document_id.clone(), exists);
+                exists
+            }
+        };
+
+        if !exists {
+            summary.skipped += 1;
+            summary.errors.push(format!(
+                "\"{}\": unknown document {}",
+                imported.word, imported.document_id
+            ));
+            continue;
+        }
+
+        if db
+            .import_practice_word(imported)
+            .map_err(|e| format!("Failed to import vocabulary: {}", e))?
+        {
+            summary.imported += 1;
+        } else {
+            summary.duplicates += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+fn read_vocabulary_csv(
+    path: &Path,
+    defaults: &VocabularyImportDefaults,
+) -> Result<(Vec<ImportedPracticeWord>, VocabularyImportSummary), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open CSV file: {}", e))?;
+
+    let headers: HashMap<String, usize> = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {}", e))?
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (name.to_lowercase(), index))
+        .collect();
+
+    if !headers.contains_key("word") {
+        return Err("CSV file has no \"word\" column".to_string());
+    }
+
+    let mut words = Vec::new();
+    let mut summary = VocabularyImportSummary::default();
+
+    for (index, record) in reader.records().enumerate() {
+        // Header is line 1
+        let line = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                summary.skipped += 1;
+                summary.errors.push(format!("Line {}: {}", line, e));
+                continue;
+            }
+        };
+
+        let field = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|&i| record.get(i))
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        let Some(word) = field("word") else {
+            summary.skipped += 1;
+            summary.errors.push(format!("Line {}: missing word", line));
+            continue;
+        };
+
+        let Some(document_id) = field("document_id").or_else(|| defaults.document_id.clone())
+        else {
+            summary.skipped += 1;
+            summary
+                .errors
+                .push(format!("Line {}: no document for \"{}\"", line, word));
+            continue;
+        };
+
+        words.push(ImportedPracticeWord {
+            document_id,
+            word,
+            language: field("language")
+                .or_else(|| defaults.language.clone())
+                .unwrap_or_else(|| "en".to_string()),
+            page_number: field("page_number")
+                .and_then(|page| page.parse().ok())
+                .unwrap_or(1),
+            details: PracticeWordDetails {
+                context: field("context"),
+                translation: field("translation"),
+                ipa: field("ipa"),
+                // Recordings only survive the round trip on the same machine
+                audio_path: field("audio_path").filter(|audio| Path::new(audio).exists()),
+            },
+        });
+    }
+
+    Ok((words, summary))
+}
+
+/// Write practice words as an Anki package (`.apkg`).
+///
+/// The package holds a legacy `collection.anki2` SQLite collection with one
+/// note per word and the recordings as media. Words that have been reviewed
+/// keep their interval and ease; the rest arrive as new cards.
+pub fn export_vocabulary_apkg(
+    path: &Path,
+    deck_name: &str,
+    entries: &[VocabularyEntry],
+) -> Result<(), String> {
+    let collection_path =
+        std::env::temp_dir().join(format!("anki-export-{}.anki2", uuid::Uuid::new_v4()));
+
+    let result = write_anki_collection(&collection_path, deck_name, entries)
+        .and_then(|media| write_apkg(path, &collection_path, &media));
+
+    let _ = std::fs::remove_file(&collection_path);
+    result
+}
+
+/// Create the collection database and return the media files it references
+fn write_anki_collection(
+    path: &Path,
+    deck_name: &str,
+    entries: &[VocabularyEntry],
+) -> Result<Vec<(String, String)>, String> {
+    let conn = Connection::open(path).map_err(|e| format!("Failed to create Anki collection: {}", e))?;
+    conn.execute_batch(ANKI_SCHEMA)
+        .map_err(|e| format!("Failed to create Anki collection: {}", e))?;
+
+    let now = Utc::now();
+    let now_ms = now.timestamp_millis();
+    let now_secs = now.timestamp();
+    let collection_created = day_start(now);
+    let deck_id = now_ms;
+
+    conn.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            collection_created,
+            now_ms,
+            anki_conf(deck_id).to_string(),
+            anki_models(deck_id, now_secs).to_string(),
+            anki_decks(deck_id, deck_name, now_secs).to_string(),
+            anki_deck_config().to_string(),
+        ],
+    )
+    .map_err(|e| format!("Failed to write Anki collection: {}", e))?;
+
+    let mut media = Vec::new();
+
+    for (position, entry) in entries.iter().enumerate() {
+        let note_id = now_ms + position as i64;
+        let details = &entry.details;
+
+        let audio_field = match details.audio_path.as_deref() {
+            Some(audio) if Path::new(audio).exists() => {
+                let name = format!("pdfreader-{}.wav", entry.id);
+                media.push((name.clone(), audio.to_string()));
+                format!("[sound:{}]", name)
+            }
+            _ => String::new(),
+        };
+
+        let context = details
+            .context
+            .as_deref()
+            .map(|context| highlight_word(context, &entry.word))
+            .unwrap_or_default();
+
+        let fields = [
+            escape_html(&entry.word),
+            escape_html(details.translation.as_deref().unwrap_or("")),
+            escape_html(details.ipa.as_deref().unwrap_or("")),
+            context,
+            entry.page_number.to_string(),
+            escape_html(entry.document_title.as_deref().unwrap_or("")),
+            audio_field,
+        ];
+
+        conn.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![
+                note_id,
+                entry.id,
+                ANKI_MODEL_ID,
+                now_secs,
+                format!(" {} ", entry.language),
+                fields.join("\u{1f}"),
+                entry.word,
+                field_checksum(&entry.word),
+            ],
+        )
+        .map_err(|e| format!("Failed to write Anki note: {}", e))?;
+
+        // type/queue 2 = review card due on a day counted from collection creation;
+        // 0 = new card ordered by position
+        let (card_type, due, interval, factor, reps, lapses) = match &entry.schedule {
+            Some(schedule) if schedule.repetitions > 0 => (
+                2,
+                (schedule.due_at.timestamp() - collection_created).div_euclid(86_400),
+                schedule.interval_days.round() as i64,
+                (schedule.ease_factor * 1000.0).round() as i64,
+                schedule.repetitions as i64,
+                schedule.lapses as i64,
+            ),
+            _ => (0, position as i64, 0, 0, 0, 0),
+        };
+
+        conn.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, ?4, ?4, ?5, ?6, ?7, ?8, ?9, 0, 0, 0, 0, '')",
+            params![note_id, deck_id, now_secs, card_type, due, interval, factor, reps, lapses],
+        )
+        .map_err(|e| format!("Failed to write Anki card: {}", e))?;
+    }
+
+    Ok(media)
+}
+
+fn write_apkg(path: &Path, collection: &Path, media: &[(String, String)]) -> Result<(), String> {
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create Anki package: {}", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    let collection_bytes = std::fs::read(collection)
+        .map_err(|e| format!("Failed to read Anki collection: {}", e))?;
+    zip.start_file("collection.anki2", options)
+        .and_then(|_| zip.write_all(&collection_bytes).map_err(Into::into))
+        .map_err(|e| format!("Failed to write Anki package: {}", e))?;
+
+    // Media are stored under their index and named by the `media` manifest
+    let mut manifest = serde_json::Map::new();
+    for (index, (name, source)) in media.iter().enumerate() {
+        let bytes = std::fs::read(source)
+            .map_err(|e| format!("Failed to read recording {}: {}", source, e))?;
+        zip.start_file(index.to_string(), options)
+            .and_then(|_| zip.write_all(&bytes).map_err(Into::into))
+            .map_err(|e| format!("Failed to write Anki package: {}", e))?;
+        manifest.insert(index.to_string(), json!(name));
+    }
+
+    zip.start_file("media", options)
+        .and_then(|_| {
+            zip.write_all(serde_json::Value::Object(manifest).to_string().as_bytes())
+                .map_err(Into::into)
+        })
+        .map_err(|e| format!("Failed to write Anki package: {}", e))?;
+
+    zip.finish()
+        .map_err(|e| format!("Failed to write Anki package: {}", e))?;
+    Ok(())
+}
+
+const ANKI_SCHEMA: &str = r#"
+    CREATE TABLE col (
+        id integer primary key, crt integer not null, mod integer not null,
+        scm integer not null, ver integer not null, dty integer not null,
+        usn integer not null, ls integer not null, conf text not null,
+        models text not null, decks text not null, dconf text not null,
+        tags text not null
+    );
+    CREATE TABLE notes (
+        id integer primary key, guid text not null, mid integer not null,
+        mod integer not null, usn integer not null, tags text not null,
+        flds text not null, sfld integer not null, csum integer not null,
+        flags integer not null, data text not null
+    );
+    CREATE TABLE cards (
+        id integer primary key, nid integer not null, did integer not null,
+        ord integer not null, mod integer not null, usn integer not null,
+        type integer not null, queue integer not null, due integer not null,
+        ivl integer not null, factor integer not null, reps integer not null,
+        lapses integer not null, left integer not null, odue integer not null,
+        odid integer not null, flags integer not null, data text not null
+    );
+    CREATE TABLE revlog (
+        id integer primary key, cid integer not null, usn integer not null,
+        ease integer not null, ivl integer not null, lastIvl integer not null,
+        factor integer not null, time integer not null, type integer not null
+    );
+    CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+    CREATE INDEX ix_notes_usn on notes (usn);
+    CREATE INDEX ix_cards_usn on cards (usn);
+    CREATE INDEX ix_revlog_usn on revlog (usn);
+    CREATE INDEX ix_cards_nid on cards (nid);
+    CREATE INDEX ix_cards_sched on cards (did, queue, due);
+    CREATE INDEX ix_revlog_cid on revlog (cid);
+    CREATE INDEX ix_notes_csum on notes (csum);
+"#;
+
+const ANKI_CSS: &str = ".card { font-family: arial; font-size: 22px; text-align: center; }
+.word { font-size: 36px; }
+.ipa { color: #666; }
+.context { font-size: 18px; margin-top: 12px; }
+.source { font-size: 14px; color: #999; margin-top: 12px; }";
+
+fn anki_conf(deck_id: i64) -> serde_json::Value {
+    json!({
+        "activeDecks": [deck_id],
+        "curDeck": deck_id,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": ANKI_MODEL_ID.to_string(),
+        "nextPos": 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true
+    })
+}
+
+fn anki_models(deck_id: i64, now_secs: i64) -> serde_json::Value {
+    let fields: Vec<serde_json::Value> = ANKI_FIELDS
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": []
+            })
+        })
+        .collect();
+
+    json!({
+        ANKI_MODEL_ID.to_string(): {
+            "id": ANKI_MODEL_ID,
+            "name": ANKI_MODEL_NAME,
+            "type": 0,
+            "mod": now_secs,
+            "usn": -1,
+            "sortf": 0,
+            "did": deck_id,
+            "tmpls": [{
+                "name": "Pronounce",
+                "ord": 0,
+                "qfmt": "<div class=\"word\">{{Word}}</div>{{#Context}}<div class=\"context\">{{Context}}</div>{{/Context}}",
+                "afmt": "{{FrontSide}}<hr id=\"answer\"><div class=\"ipa\">{{IPA}}</div><div>{{Translation}}</div>{{Audio}}<div class=\"source\">{{Document}} p. {{Page}}</div>",
+                "did": null,
+                "bqfmt": "",
+                "bafmt": ""
+            }],
+            "flds": fields,
+            "css": ANKI_CSS,
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "tags": [],
+            "vers": [],
+            "req": [[0, "any", [0]]]
+        }
+    })
+}
+
+fn anki_decks(deck_id: i64, deck_name: &str, now_secs: i64) -> serde_json::Value {
+    let deck = |id: i64, name: &str| {
+        json!({
+            "id": id,
+            "name": name,
+            "desc": "",
+            "mod": now_secs,
+            "usn": -1,
+            "collapsed": false,
+            "browserCollapsed": false,
+            "newToday": [0, 0],
+            "revToday": [0, 0],
+            "lrnToday": [0, 0],
+            "timeToday": [0, 0],
+            "dyn": 0,
+            "conf": 1,
+            "extendNew": 10,
+            "extendRev": 50
+        })
+    };
+
+    json!({
+        "1": deck(1, "Default"),
+        deck_id.to_string(): deck(deck_id, deck_name),
+    })
+}
+
+fn anki_deck_config() -> serde_json::Value {
+    json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": 0,
+            "usn": 0,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": {
+                "delays": [1, 10],
+                "ints": [1, 4, 7],
+                "initialFactor": 2500,
+                "order": 1,
+                "perDay": 20,
+                "bury": true
+            },
+            "rev": {
+                "perDay": 200,
+                "ease4": 1.3,
+                "ivlFct": 1,
+                "maxIvl": 36500,
+                "hardFactor": 1.2,
+                "bury": true
+            },
+            "lapse": {
+                "delays": [10],
+                "mult": 0,
+                "minInt": 1,
+                "leechFails": 8,
+                "leechAction": 0
+            }
+        }
+    })
+}
+
+/// Anki's duplicate check: first 8 hex digits of the SHA-1 of the sort field
+fn field_checksum(field: &str) -> i64 {
+    let digest = Sha1::digest(field.as_bytes());
+    u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as i64
+}
+
+/// Local midnight of `time`, in seconds, as Anki counts review days from it
+fn day_start(time: DateTime<Utc>) -> i64 {
+    time.with_timezone(&Local)
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map(|midnight| midnight.timestamp())
+        .unwrap_or_else(|| time.timestamp())
+}
+
+/// Escape the context sentence and bold occurrences of the practiced word
+fn highlight_word(context: &str, word: &str) -> String {
+    context
+        .split(' ')
+        .map(|token| {
+            if super::reading_aligner::normalize_word(token) == word.to_lowercase() {
+                format!("<b>{}</b>", escape_html(token))
+            } else {
+                escape_html(token)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::spaced_repetition::ReviewSchedule;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vocabulary-export-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(id: &str, word: &str, context: &str) -> VocabularyEntry {
+        VocabularyEntry {
+            id: id.to_string(),
+            document_id: "doc-1".to_string(),
+            document_title: Some("Notes, \"draft\"".to_string()),
+            word: word.to_string(),
+            language: "en".to_string(),
+            page_number: 7,
+            attempts: 3,
+            details: PracticeWordDetails {
+                context: Some(context.to_string()),
+                translation: Some("Ding, Sache".to_string()),
+                ipa: Some("θɪŋ".to_string()),
+                audio_path: None,
+            },
+            schedule: None,
+        }
+    }
+
+    #[test]
+    fn csv_round_trips_quoted_commas_and_newlines() {
+        let dir = temp_dir();
+        let path = dir.join("vocabulary.csv");
+        let entries = [
+            entry(
+                "w1",
+                "thing",
+                "One thing, then \"another\"\non the next line",
+            ),
+            entry("w2", "other", "plain"),
+        ];
+
+        export_vocabulary_csv(&path, &entries).unwrap();
+        let (words, summary) =
+            read_vocabulary_csv(&path, &VocabularyImportDefaults::default()).unwrap();
+
+        assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+        assert_eq!(words.len(), 2);
+        let first = &words[0];
+        assert_eq!(first.word, "thing");
+        assert_eq!(first.document_id, "doc-1");
+        assert_eq!(first.language, "en");
+        assert_eq!(first.page_number, 7);
+        assert_eq!(
+            first.details.context.as_deref(),
+            Some("One thing, then \"another\"\non the next line")
+        );
+        assert_eq!(first.details.translation.as_deref(), Some("Ding, Sache"));
+        assert_eq!(first.details.ipa.as_deref(), Some("θɪŋ"));
+        assert_eq!(words[1].word, "other");
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn csv_import_applies_defaults_and_reports_bad_rows() {
+        let dir = temp_dir();
+        let path = dir.join("vocabulary.csv");
+        std::fs::write(&path, "Word,Translation\nhouse,Haus\n,leer\n").unwrap();
+
+        let defaults = VocabularyImportDefaults {
+            document_id: Some("doc-2".to_string()),
+            language: Some("de".to_string()),
+        };
+        let (words, summary) = read_vocabulary_csv(&path, &defaults).unwrap();
+
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].document_id, "doc-2");
+        assert_eq!(words[0].language, "de");
+        assert_eq!(words[0].page_number, 1);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.errors, ["Line 3: missing word"]);
+
+        std::fs::write(&path, "translation\nHaus\n").unwrap();
+        assert!(read_vocabulary_csv(&path, &defaults).is_err());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn apkg_contains_collection_with_notes_cards_and_media() {
+        let dir = temp_dir();
+        let audio = dir.join("recording.wav");
+        std::fs::write(&audio, b"RIFF").unwrap();
+
+        let mut reviewed = entry("w1", "thing", "One thing <here>");
+        reviewed.details.audio_path = Some(audio.to_string_lossy().into_owned());
+        reviewed.schedule = Some(ReviewSchedule {
+            ease_factor: 2.5,
+            interval_days: 3.0,
+            repetitions: 2,
+            lapses: 1,
+            due_at: Utc::now() + chrono::Duration::days(3),
+            last_reviewed_at: Some(Utc::now()),
+        });
+        let entries = [reviewed, entry("w2", "other", "plain")];
+
+        let package = dir.join("deck.apkg");
+        export_vocabulary_apkg(&package, "Reading", &entries).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&package).unwrap()).unwrap();
+        let mut media = String::new();
+        archive
+            .by_name("media")
+            .unwrap()
+            .read_to_string(&mut media)
+            .unwrap();
+        assert_eq!(media, r#"{"0":"pdfreader-w1.wav"}"#);
+
+        let mut collection = Vec::new();
+        archive
+            .by_name("collection.anki2")
+            .unwrap()
+            .read_to_end(&mut collection)
+            .unwrap();
+        let collection_path = dir.join("collection.anki2");
+        std::fs::write(&collection_path, collection).unwrap();
+        let conn = Connection::open(&collection_path).unwrap();
+
+        let notes: Vec<(String, i64, String)> = conn
+            .prepare("SELECT guid, mid, flds FROM notes ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].0, "w1");
+        assert_eq!(notes[0].1, ANKI_MODEL_ID);
+        let fields: Vec<&str> = notes[0].2.split('\u{1f}').collect();
+        assert_eq!(
+            fields,
+            [
+                "thing",
+                "Ding, Sache",
+                "θɪŋ",
+                "One <b>thing</b> &lt;here&gt;",
+                "7",
+                "Notes, &quot;draft&quot;",
+                "[sound:pdfreader-w1.wav]",
+            ]
+        );
+
+        let cards: Vec<(i64, i64, i64, i64, i64)> = conn
+            .prepare("SELECT type, ivl, factor, reps, lapses FROM cards ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(cards, [(2, 3, 2500, 2, 1), (0, 0, 0, 0, 0)]);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -1,5 +1,8 @@
+use super::audio_decoder::{decode_audio_bytes, decode_audio_file, SUPPORTED_AUDIO_FORMATS};
+use super::model_manager::ModelManager;
+use super::model_registry::ModelKind;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +19,17 @@ pub struct TranscriptionSegment {
     pub end: f32,
     pub text: String,
     pub confidence: f32,
+    #[serde(default)]
+    pub words: Vec<TranscriptionWord>,
+}
+
+/// A single recognized word with its timing in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionWord {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
+    pub probability: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,8 +41,18 @@ pub struct WhisperConfig {
     pub single_segment: bool,
 }
 
+/// Transcription options sent from the frontend.
+/// The model is referenced by its `ModelManager` file name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionOptions {
+    pub model_file_name: String,
+    pub language: Option<String>,
+    pub translate: Option<bool>,
+    pub no_context: Option<bool>,
+    pub single_segment: Option<bool>,
+}
+
 pub struct WhisperService {
-    #[allow(dead_code)]
     app_handle: AppHandle,
 }
 
@@ -37,135 +61,495 @@ impl WhisperService {
         Self { app_handle }
     }
 
+    /// Build a `WhisperConfig` for a model managed by `ModelManager`
+    pub fn config_from_options(&self, options: &TranscriptionOptions) -> Result<WhisperConfig, String> {
+        let manager = ModelManager::new(self.app_handle.clone());
+
+        if !manager.is_model_downloaded(&options.model_file_name)? {
+            return Err(format!(
+                "Model {} is not downloaded",
+                options.model_file_name
+            ));
+        }
+
+        // Usage tracking is best effort; a read-only models directory is fine
+        let _ = manager.mark_used(&options.model_file_name);
+
+        Ok(WhisperConfig {
+            model_path: manager.get_model_path(&options.model_file_name)?,
+            language: options.language.clone().filter(|lang| lang != "auto"),
+            translate: options.translate.unwrap_or(false),
+            no_context: options.no_context.unwrap_or(true),
+            single_segment: options.single_segment.unwrap_or(false),
+        })
+    }
+
     /// Transcribe audio file to text
-    /// 
-    /// Note: This is a placeholder implementation.
-    /// Full Whisper integration requires whisper-rs crate and C++ dependencies:
-    /// 
-    /// Dependencies needed:
-    /// - whisper-rs = "0.11"
-    /// - Or whisper-cpp bindings
-    /// 
-    /// Build requirements:
-    /// - CMake
-    /// - C++ compiler (MSVC on Windows, GCC/Clang on Unix)
-    /// - Metal framework (macOS)
-    /// - CUDA toolkit (optional, for GPU acceleration)
     ///
-    /// For production deployment, consider using:
-    /// 1. Pre-built whisper-rs binaries
-    /// 2. Cloud-based transcription API (OpenAI, Azure)
-    /// 3. On-device model with whisper.cpp
+    /// Requires the `whisper` cargo feature, which builds whisper.cpp via whisper-rs
+    /// (CMake and a C++ compiler are needed at build time).
     pub fn transcribe_audio(
         &self,
-        _audio_path: PathBuf,
-        _config: WhisperConfig,
+        audio_path: PathBuf,
+        config: WhisperConfig,
     ) -> Result<TranscriptionResult, String> {
-        // Placeholder implementation
-        // Real implementation would:
-        // 1. Load the Whisper model from config.model_path
-        // 2. Read and decode audio file
-        // 3. Run inference
-        // 4. Parse and return results
+        let audio_data = self.load_audio(&audio_path)?;
+        self.transcribe_samples(&audio_data, &config)
+    }
 
-        Err("Whisper integration not yet implemented. Requires whisper-rs dependency and C++ build tools.".to_string())
+    /// Transcribe an encoded audio buffer (e.g. a recording from the frontend)
+    pub fn transcribe_audio_data(
+        &self,
+        audio_data: Vec<u8>,
+        format: Option<&str>,
+        config: WhisperConfig,
+    ) -> Result<TranscriptionResult, String> {
+        let samples = decode_audio_bytes(audio_data, format)?;
+        self.transcribe_samples(&samples, &config)
+    }
+
+    /// Transcribe mono f32 PCM sampled at 16 kHz
+    pub fn transcribe_samples(
+        &self,
+        samples: &[f32],
+        config: &WhisperConfig,
+    ) -> Result<TranscriptionResult, String> {
+        if samples.is_empty() {
+            return Err("No audio samples to transcribe".to_string());
+        }
+
+        engine::transcribe(samples, config)
     }
 
     /// Check if Whisper is available and properly configured
     pub fn is_available(&self) -> bool {
-        // Check if whisper-rs is compiled and model exists
-        false
+        if !Self::is_compiled() {
+            return false;
+        }
+
+        ModelManager::new(self.app_handle.clone())
+            .installed_models(ModelKind::Speech)
+            .is_ok_and(|models| !models.is_empty())
+    }
+
+    /// Whether whisper.cpp support was compiled into this build
+    pub fn is_compiled() -> bool {
+        cfg!(feature = "whisper")
+    }
+
+    /// Decode an audio file to mono f32 PCM at 16 kHz, ready for inference
+    pub fn load_audio(&self, audio_path: &Path) -> Result<Vec<f32>, String> {
+        decode_audio_file(audio_path)
     }
 
     /// Get supported audio formats
     pub fn get_supported_formats() -> Vec<String> {
-        vec![
-            "wav".to_string(),
-            "mp3".to_string(),
-            "ogg".to_string(),
-            "flac".to_string(),
-            "m4a".to_string(),
-        ]
+        SUPPORTED_AUDIO_FORMATS
+            .iter()
+            .map(|format| format.to_string())
+            .collect()
     }
 }
 
-// Placeholder for future whisper-rs integration:
-/*
-use whisper_rs::{WhisperContext, FullParams, SamplingStrategy};
+/// A decoded text token with its timing (centiseconds) and probability
+#[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+struct TokenPiece {
+    bytes: Vec<u8>,
+    t0: i64,
+    t1: i64,
+    t_dtw: i64,
+    probability: f32,
+}
 
-impl WhisperService {
-    pub fn transcribe_audio(
-        &self,
-        audio_path: PathBuf,
-        config: WhisperConfig,
-    ) -> Result<TranscriptionResult, String> {
-        // Load model
-        let ctx = WhisperContext::new(&config.model_path.to_string_lossy())
+/// Merge BPE tokens into words. A token starting with a space opens a new word;
+/// everything else (word pieces, punctuation) is appended to the current one.
+/// Bytes are joined before decoding so multi-byte characters split across
+/// tokens survive intact.
+#[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+fn group_words(pieces: &[TokenPiece], segment_end: f32) -> Vec<TranscriptionWord> {
+    struct Pending {
+        bytes: Vec<u8>,
+        start: i64,
+        end: i64,
+        prob_sum: f32,
+        count: usize,
+    }
+
+    let mut pending: Vec<Pending> = Vec::new();
+
+    for piece in pieces {
+        let starts_word = piece.bytes.first() == Some(&b' ') || pending.is_empty();
+        // DTW timestamps are more precise than the decoder's token timestamps
+        let start = if piece.t_dtw >= 0 {
+            piece.t_dtw
+        } else {
+            piece.t0
+        };
+
+        if starts_word {
+            pending.push(Pending {
+                bytes: Vec::new(),
+                start,
+                end: piece.t1,
+                prob_sum: 0.0,
+                count: 0,
+            });
+        }
+
+        let word = pending.last_mut().expect("word was just pushed");
+        word.bytes.extend_from_slice(&piece.bytes);
+        word.end = word.end.max(piece.t1);
+        word.prob_sum += piece.probability;
+        word.count += 1;
+    }
+
+    let mut words: Vec<TranscriptionWord> = pending
+        .into_iter()
+        .filter_map(|w| {
+            let text = String::from_utf8_lossy(&w.bytes).trim().to_string();
+            if text.is_empty() {
+                return None;
+            }
+            Some(TranscriptionWord {
+                word: text,
+                start: w.start as f32 / 100.0,
+                end: w.end as f32 / 100.0,
+                probability: w.prob_sum / w.count as f32,
+            })
+        })
+        .collect();
+
+    // Keep words monotonic and non-overlapping
+    for i in 0..words.len() {
+        let next_start = words.get(i + 1).map(|w| w.start).unwrap_or(segment_end);
+        let word = &mut words[i];
+        word.end = word.end.min(next_start.max(word.start));
+        if word.end < word.start {
+            word.end = word.start;
+        }
+    }
+
+    words
+}
+
+#[cfg(feature = "whisper")]
+mod engine {
+    use super::{
+        group_words, TokenPiece, TranscriptionResult, TranscriptionSegment, WhisperConfig,
+    };
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use whisper_rs::{
+        DtwMode, DtwModelPreset, DtwParameters, FullParams, SamplingStrategy, WhisperContext,
+        WhisperContextParameters, WhisperState,
+    };
+
+    /// The most recently loaded model, kept around so that repeated
+    /// transcriptions don't reload a multi-hundred-megabyte file each time
+    static CONTEXT_CACHE: Mutex<Option<(PathBuf, Arc<WhisperContext>)>> = Mutex::new(None);
+
+    pub(super) fn load_context(model_path: &Path) -> Result<Arc<WhisperContext>, String> {
+        let mut cache = CONTEXT_CACHE.lock().map_err(|e| e.to_string())?;
+
+        if let Some((path, ctx)) = cache.as_ref() {
+            if path == model_path {
+                return Ok(ctx.clone());
+            }
+        }
+
+        let mut ctx_params = WhisperContextParameters::default();
+        if let Some(model_preset) = dtw_preset(model_path) {
+            ctx_params.dtw_parameters(DtwParameters {
+                mode: DtwMode::ModelPreset { model_preset },
+                ..Default::default()
+            });
+        }
+
+        let ctx = WhisperContext::new_with_params(&model_path.to_string_lossy(), ctx_params)
             .map_err(|e| format!("Failed to load model: {}", e))?;
 
-        // Create parameters
+        let ctx = Arc::new(ctx);
+        *cache = Some((model_path.to_path_buf(), ctx.clone()));
+        Ok(ctx)
+    }
+
+    /// Alignment-head preset for DTW word timestamps, derived from the ggml file name.
+    /// Quantized variants (e.g. `ggml-base.en-q5_1.bin`) share the preset of their base model.
+    fn dtw_preset(model_path: &Path) -> Option<DtwModelPreset> {
+        let name = model_path.file_stem()?.to_str()?.strip_prefix("ggml-")?;
+        let base = name.split("-q").next().unwrap_or(name);
+
+        Some(match base {
+            "tiny.en" => DtwModelPreset::TinyEn,
+            "tiny" => DtwModelPreset::Tiny,
+            "base.en" => DtwModelPreset::BaseEn,
+            "base" => DtwModelPreset::Base,
+            "small.en" => DtwModelPreset::SmallEn,
+            "small" => DtwModelPreset::Small,
+            "medium.en" => DtwModelPreset::MediumEn,
+            "medium" => DtwModelPreset::Medium,
+            "large-v1" => DtwModelPreset::LargeV1,
+            "large-v2" => DtwModelPreset::LargeV2,
+            "large-v3" | "large" => DtwModelPreset::LargeV3,
+            "large-v3-turbo" => DtwModelPreset::LargeV3Turbo,
+            _ => return None,
+        })
+    }
+
+    pub(super) fn full_params<'a>(config: &'a WhisperConfig) -> FullParams<'a, 'a> {
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-        
-        if let Some(lang) = config.language {
-            params.set_language(Some(&lang));
-        }
+
+        let threads = std::thread::available_parallelism()
+            .map(|n| n.get().min(8))
+            .unwrap_or(4);
+        params.set_n_threads(threads as i32);
+
+        // "auto" asks whisper.cpp to detect the spoken language
+        params.set_language(Some(config.language.as_deref().unwrap_or("auto")));
         params.set_translate(config.translate);
         params.set_no_context(config.no_context);
         params.set_single_segment(config.single_segment);
+        params.set_token_timestamps(true);
 
-        // Load and decode audio
-        let audio_data = load_audio_file(&audio_path)?;
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
 
-        // Run inference
-        let mut state = ctx.create_state()
+        params
+    }
+
+    pub(super) fn transcribe(
+        samples: &[f32],
+        config: &WhisperConfig,
+    ) -> Result<TranscriptionResult, String> {
+        let ctx = load_context(&config.model_path)?;
+
+        let mut state = ctx
+            .create_state()
             .map_err(|e| format!("Failed to create state: {}", e))?;
-        
-        state.full(params, &audio_data)
+
+        state
+            .full(full_params(config), samples)
             .map_err(|e| format!("Failed to run inference: {}", e))?;
 
-        // Extract results
-        let num_segments = state.full_n_segments()
+        collect_result(&ctx, &state, config)
+    }
+
+    pub(super) fn collect_result(
+        ctx: &WhisperContext,
+        state: &WhisperState,
+        config: &WhisperConfig,
+    ) -> Result<TranscriptionResult, String> {
+        let num_segments = state
+            .full_n_segments()
             .map_err(|e| format!("Failed to get segments: {}", e))?;
 
+        let token_eot = ctx.token_eot();
         let mut segments = Vec::new();
         let mut full_text = String::new();
+        let mut prob_sum = 0.0f32;
+        let mut prob_count = 0usize;
 
         for i in 0..num_segments {
-            let segment_text = state.full_get_segment_text(i)
+            let segment_text = state
+                .full_get_segment_text_lossy(i)
                 .map_err(|e| format!("Failed to get segment text: {}", e))?;
-            
-            let start = state.full_get_segment_t0(i)
-                .map_err(|e| format!("Failed to get segment start: {}", e))? as f32 / 100.0;
-            
-            let end = state.full_get_segment_t1(i)
-                .map_err(|e| format!("Failed to get segment end: {}", e))? as f32 / 100.0;
+
+            // Timestamps are reported in centiseconds
+            let start = state
+                .full_get_segment_t0(i)
+                .map_err(|e| format!("Failed to get segment start: {}", e))? as f32
+                / 100.0;
+            let end = state
+                .full_get_segment_t1(i)
+                .map_err(|e| format!("Failed to get segment end: {}", e))? as f32
+                / 100.0;
+
+            let num_tokens = state
+                .full_n_tokens(i)
+                .map_err(|e| format!("Failed to get token count: {}", e))?;
+
+            let mut segment_sum = 0.0f32;
+            let mut segment_count = 0usize;
+            let mut pieces = Vec::new();
+            for t in 0..num_tokens {
+                let data = state
+                    .full_get_token_data(i, t)
+                    .map_err(|e| format!("Failed to get token data: {}", e))?;
+
+                // Special tokens (timestamps, language, end-of-text) carry no text
+                if data.id >= token_eot {
+                    continue;
+                }
+
+                segment_sum += data.p;
+                segment_count += 1;
+
+                pieces.push(TokenPiece {
+                    bytes: state
+                        .full_get_token_bytes(i, t)
+                        .map_err(|e| format!("Failed to get token text: {}", e))?,
+                    t0: data.t0,
+                    t1: data.t1,
+                    t_dtw: data.t_dtw,
+                    probability: data.p,
+                });
+            }
+
+            prob_sum += segment_sum;
+            prob_count += segment_count;
+
+            let confidence = if segment_count > 0 {
+                segment_sum / segment_count as f32
+            } else {
+                0.0
+            };
+
+            let text = segment_text.trim().to_string();
+            if !full_text.is_empty() && !text.is_empty() {
+                full_text.push(' ');
+            }
+            full_text.push_str(&text);
 
             segments.push(TranscriptionSegment {
                 start,
                 end,
-                text: segment_text.clone(),
-                confidence: 0.9, // whisper-rs doesn't provide confidence directly
+                text,
+                confidence,
+                words: group_words(&pieces, end),
             });
-
-            full_text.push_str(&segment_text);
-            full_text.push(' ');
         }
 
+        let language = match config.language.clone() {
+            Some(lang) => lang,
+            None => state
+                .full_lang_id_from_state()
+                .ok()
+                .and_then(whisper_rs::get_lang_str)
+                .unwrap_or("en")
+                .to_string(),
+        };
+
         Ok(TranscriptionResult {
-            text: full_text.trim().to_string(),
-            language: config.language.unwrap_or_else(|| "en".to_string()),
-            confidence: 0.9,
+            text: full_text,
+            language,
+            confidence: if prob_count > 0 {
+                prob_sum / prob_count as f32
+            } else {
+                0.0
+            },
             segments,
         })
     }
 }
 
-fn load_audio_file(path: &PathBuf) -> Result<Vec<f32>, String> {
-    // Load audio file and convert to f32 samples at 16kHz
-    // This would use libraries like:
-    // - hound for WAV
-    // - minimp3 for MP3
-    // - lewton for OGG
-    todo!("Audio loading not implemented")
+#[cfg(not(feature = "whisper"))]
+mod engine {
+    use super::{TranscriptionResult, WhisperConfig};
+
+    pub(super) fn transcribe(
+        _samples: &[f32],
+        _config: &WhisperConfig,
+    ) -> Result<TranscriptionResult, String> {
+        Err("Whisper support is not compiled into this build. Rebuild with `--features whisper` (requires CMake and a C++ compiler).".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(text: &str, t0: i64, t1: i64) -> TokenPiece {
+        TokenPiece {
+            bytes: text.as_bytes().to_vec(),
+            t0,
+            t1,
+            t_dtw: -1,
+            probability: 0.9,
+        }
+    }
+
+    fn texts(words: &[TranscriptionWord]) -> Vec<&str> {
+        words.iter().map(|w| w.word.as_str()).collect()
+    }
+
+    #[test]
+    fn leading_space_starts_a_new_word() {
+        let pieces = [
+            piece(" Hel", 0, 20),
+            piece("lo", 20, 40),
+            piece(" world", 50, 90),
+        ];
+        let words = group_words(&pieces, 1.0);
+
+        assert_eq!(texts(&words), ["Hello", "world"]);
+        assert_eq!((words[0].start, words[0].end), (0.0, 0.4));
+        assert_eq!((words[1].start, words[1].end), (0.5, 0.9));
+    }
+
+    #[test]
+    fn punctuation_joins_the_previous_word() {
+        let pieces = [
+            piece("Well", 0, 30),
+            piece(",", 30, 32),
+            piece(" yes", 40, 60),
+            piece(".", 60, 62),
+        ];
+        let words = group_words(&pieces, 1.0);
+
+        assert_eq!(texts(&words), ["Well,", "yes."]);
+        assert_eq!(words[1].end, 0.62);
+    }
+
+    #[test]
+    fn multibyte_characters_split_across_tokens_survive() {
+        let bytes = " café".as_bytes();
+        let split = bytes.len() - 1;
+        let pieces = [
+            TokenPiece {
+                bytes: bytes[..split].to_vec(),
+                ..piece("", 0, 20)
+            },
+            TokenPiece {
+                bytes: bytes[split..].to_vec(),
+                ..piece("", 20, 40)
+            },
+        ];
+
+        assert_eq!(texts(&group_words(&pieces, 1.0)), ["café"]);
+    }
+
+    #[test]
+    fn averages_probability_and_prefers_dtw_start() {
+        let pieces = [
+            TokenPiece {
+                t_dtw: 12,
+                probability: 0.6,
+                ..piece(" to", 10, 30)
+            },
+            TokenPiece {
+                probability: 1.0,
+                ..piece("day", 30, 50)
+            },
+        ];
+        let words = group_words(&pieces, 1.0);
+
+        assert_eq!(words[0].start, 0.12);
+        assert!((words[0].probability - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn overlapping_words_are_clipped_to_the_next_start() {
+        let pieces = [
+            piece(" one", 0, 70),
+            piece(" two", 50, 90),
+            piece(" ", 90, 95),
+        ];
+        let words = group_words(&pieces, 0.8);
+
+        assert_eq!(texts(&words), ["one", "two"]);
+        assert_eq!(words[0].end, 0.5);
+        assert_eq!(words[1].end, 0.8);
+    }
 }
-*/
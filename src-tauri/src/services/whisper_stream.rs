@@ -0,0 +1,491 @@
+use super::audio_decoder::{StreamResampler, WHISPER_SAMPLE_RATE};
+use super::vad::{VadConfig, VadEvent, VoiceActivityDetector};
+use super::whisper_service::{
+    TranscriptionOptions, TranscriptionResult, TranscriptionWord, WhisperConfig, WhisperService,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Event carrying partial and final hypotheses for a streaming session
+pub const TRANSCRIPTION_STREAM_EVENT: &str = "transcription-stream";
+/// Event emitted when a streaming session hits an error it cannot recover from
+pub const TRANSCRIPTION_STREAM_ERROR_EVENT: &str = "transcription-stream-error";
+
+/// Whisper works on at most 30 s of audio; longer utterances are cut here
+const DEFAULT_MAX_UTTERANCE_SECONDS: f32 = 25.0;
+const DEFAULT_PARTIAL_INTERVAL_MS: u64 = 500;
+/// Partial hypotheses decode at most this much of the end of an utterance
+const DEFAULT_PARTIAL_WINDOW_SECONDS: f32 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamingOptions {
+    pub transcription: TranscriptionOptions,
+    /// Sample rate of the chunks the frontend will push
+    pub sample_rate: u32,
+    pub partial_interval_ms: Option<u64>,
+    pub max_utterance_seconds: Option<f32>,
+    pub partial_window_seconds: Option<f32>,
+    pub vad: Option<VadConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionStreamEvent {
+    pub session_id: String,
+    pub utterance_id: u32,
+    pub is_final: bool,
+    pub text: String,
+    /// Seconds since the stream started. A partial covers at most the
+    /// partial window at the end of the utterance, so it may start later.
+    pub start: f32,
+    pub end: f32,
+    pub confidence: f32,
+    pub words: Vec<TranscriptionWord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionStreamError {
+    pub session_id: String,
+    pub message: String,
+}
+
+enum StreamMessage {
+    Audio(Vec<f32>),
+    Stop,
+}
+
+/// Handle to a live transcription session running on its own worker thread
+pub struct TranscriptionStream {
+    sender: Sender<StreamMessage>,
+    worker: JoinHandle<()>,
+}
+
+impl TranscriptionStream {
+    pub fn start(
+        app_handle: AppHandle,
+        session_id: String,
+        options: StreamingOptions,
+    ) -> Result<Self, String> {
+        if !WhisperService::is_compiled() {
+            return Err("Whisper support is not compiled into this build".to_string());
+        }
+        if options.sample_rate == 0 {
+            return Err("Sample rate must be greater than zero".to_string());
+        }
+
+        let service = WhisperService::new(app_handle.clone());
+        let config = service.config_from_options(&options.transcription)?;
+        let resampler = StreamResampler::new(options.sample_rate, WHISPER_SAMPLE_RATE)?;
+
+        let worker = StreamWorker {
+            app_handle,
+            session_id,
+            service,
+            final_config: config.clone(),
+            partial_config: WhisperConfig {
+                single_segment: true,
+                no_context: true,
+                ..config
+            },
+            resampler,
+            segmenter: Segmenter::new(&options),
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let worker = std::thread::Builder::new()
+            .name("whisper-stream".to_string())
+            .spawn(move || worker.run(receiver))
+            .map_err(|e| format!("Failed to start transcription worker: {}", e))?;
+
+        Ok(Self { sender, worker })
+    }
+
+    /// Queue a chunk of mono PCM samples at the session's sample rate
+    pub fn push_audio(&self, samples: Vec<f32>) -> Result<(), String> {
+        self.sender
+            .send(StreamMessage::Audio(samples))
+            .map_err(|_| "Transcription stream has stopped".to_string())
+    }
+
+    /// Finish the session, emitting a final hypothesis for any speech in progress
+    pub fn stop(self) -> Result<(), String> {
+        // The worker may already have exited after an error
+        let _ = self.sender.send(StreamMessage::Stop);
+        self.worker
+            .join()
+            .map_err(|_| "Transcription worker panicked".to_string())
+    }
+}
+
+struct StreamWorker {
+    app_handle: AppHandle,
+    session_id: String,
+    service: WhisperService,
+    final_config: WhisperConfig,
+    partial_config: WhisperConfig,
+    resampler: StreamResampler,
+    segmenter: Segmenter,
+}
+
+impl StreamWorker {
+    fn run(mut self, receiver: Receiver<StreamMessage>) {
+        loop {
+            let message = match receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => StreamMessage::Stop,
+            };
+
+            // Drain everything that queued up while we were busy so that
+            // partial results track the newest audio instead of lagging behind
+            let mut chunk = Vec::new();
+            let mut stop = false;
+            for message in std::iter::once(message).chain(receiver.try_iter()) {
+                match message {
+                    StreamMessage::Audio(samples) => chunk.extend(samples),
+                    StreamMessage::Stop => stop = true,
+                }
+            }
+
+            let result = self.handle_audio(&chunk, stop);
+            if let Err(message) = result {
+                self.emit_error(message);
+                return;
+            }
+            if stop {
+                return;
+            }
+        }
+    }
+
+    fn handle_audio(&mut self, chunk: &[f32], stop: bool) -> Result<(), String> {
+        let mut samples = self.resampler.push(chunk)?;
+        if stop {
+            samples.extend(self.resampler.flush()?);
+        }
+
+        for request in self.segmenter.push(&samples, stop) {
+            let config = if request.is_final {
+                &self.final_config
+            } else {
+                &self.partial_config
+            };
+            let result = self.service.transcribe_samples(&request.audio, config)?;
+            self.emit(&request, result);
+        }
+        Ok(())
+    }
+
+    fn emit(&self, request: &DecodeRequest, result: TranscriptionResult) {
+        let offset = request.start as f32 / WHISPER_SAMPLE_RATE as f32;
+
+        let words = result
+            .segments
+            .iter()
+            .flat_map(|segment| segment.words.iter().cloned())
+            .map(|word| TranscriptionWord {
+                start: word.start + offset,
+                end: word.end + offset,
+                ..word
+            })
+            .collect();
+
+        let event = TranscriptionStreamEvent {
+            session_id: self.session_id.clone(),
+            utterance_id: request.utterance_id,
+            is_final: request.is_final,
+            text: result.text,
+            start: offset,
+            end: request.end as f32 / WHISPER_SAMPLE_RATE as f32,
+            confidence: result.confidence,
+            words,
+        };
+
+        let _ = self.app_handle.emit(TRANSCRIPTION_STREAM_EVENT, event);
+    }
+
+    fn emit_error(&self, message: String) {
+        let _ = self.app_handle.emit(
+            TRANSCRIPTION_STREAM_ERROR_EVENT,
+            TranscriptionStreamError {
+                session_id: self.session_id.clone(),
+                message,
+            },
+        );
+    }
+}
+
+/// Audio the decoder should run on, placed by absolute 16 kHz sample
+struct DecodeRequest {
+    utterance_id: u32,
+    start: usize,
+    end: usize,
+    is_final: bool,
+    audio: Vec<f32>,
+}
+
+/// Cuts 16 kHz audio into utterances and decides when each is decoded
+struct Segmenter {
+    vad: VoiceActivityDetector,
+    /// 16 kHz audio, starting at absolute sample `buffer_start`
+    buffer: Vec<f32>,
+    buffer_start: usize,
+    /// Absolute start sample of the utterance in progress
+    utterance: Option<usize>,
+    utterance_id: u32,
+    last_partial_end: usize,
+    partial_interval: usize,
+    partial_window: usize,
+    max_utterance: usize,
+    /// Audio kept between utterances, enough for the VAD to reach back to
+    idle_history: usize,
+}
+
+impl Segmenter {
+    fn new(options: &StreamingOptions) -> Self {
+        let vad = options.vad.clone().unwrap_or_default();
+        // A start is reported once speech has lasted `speech_start_ms`, up to a
+        // frame late, and reaches back `pre_roll_ms` before the speech
+        let idle_history = samples_for_ms(u64::from(
+            vad.pre_roll_ms + vad.speech_start_ms + vad.frame_ms,
+        ));
+        let seconds = |seconds: f32| (seconds * WHISPER_SAMPLE_RATE as f32) as usize;
+
+        Self {
+            vad: VoiceActivityDetector::new(vad, WHISPER_SAMPLE_RATE),
+            buffer: Vec::new(),
+            buffer_start: 0,
+            utterance: None,
+            utterance_id: 0,
+            last_partial_end: 0,
+            partial_interval: samples_for_ms(
+                options
+                    .partial_interval_ms
+                    .unwrap_or(DEFAULT_PARTIAL_INTERVAL_MS),
+            ),
+            partial_window: seconds(
+                options
+                    .partial_window_seconds
+                    .unwrap_or(DEFAULT_PARTIAL_WINDOW_SECONDS)
+                    .clamp(1.0, 29.0),
+            ),
+            max_utterance: seconds(
+                options
+                    .max_utterance_seconds
+                    .unwrap_or(DEFAULT_MAX_UTTERANCE_SECONDS)
+                    .clamp(1.0, 29.0),
+            ),
+            idle_history,
+        }
+    }
+
+    /// Take the next 16 kHz samples; `stop` finishes any speech in progress
+    fn push(&mut self, samples: &[f32], stop: bool) -> Vec<DecodeRequest> {
+        let mut requests = Vec::new();
+        self.buffer.extend_from_slice(samples);
+        for event in self.vad.process(samples) {
+            match event {
+                VadEvent::SpeechStart(start) => self.begin_utterance(start),
+                VadEvent::SpeechEnd(end) => requests.extend(self.finish_utterance(end)),
+            }
+        }
+
+        let now = self.buffer_end();
+        if let Some(start) = self.utterance {
+            if stop {
+                requests.extend(self.finish_utterance(now));
+            } else if now - start >= self.max_utterance {
+                requests.extend(self.finish_utterance(now));
+                self.begin_utterance(now);
+            } else if now - self.last_partial_end >= self.partial_interval {
+                let from = start.max(now.saturating_sub(self.partial_window));
+                self.last_partial_end = now;
+                requests.push(self.request(from, now, false));
+            }
+        } else {
+            self.trim_buffer();
+        }
+
+        requests
+    }
+
+    fn begin_utterance(&mut self, start: usize) {
+        let start = start.max(self.buffer_start);
+        self.utterance = Some(start);
+        self.utterance_id += 1;
+        self.last_partial_end = start;
+    }
+
+    fn finish_utterance(&mut self, end: usize) -> Option<DecodeRequest> {
+        let start = self.utterance.take()?;
+        // Very short blips (clicks, breaths) aren't worth a decoder pass
+        let request = (end - start >= samples_for_ms(200)).then(|| self.request(start, end, true));
+        self.trim_buffer();
+        request
+    }
+
+    fn request(&self, start: usize, end: usize, is_final: bool) -> DecodeRequest {
+        let from = start
+            .saturating_sub(self.buffer_start)
+            .min(self.buffer.len());
+        let to = end.saturating_sub(self.buffer_start).min(self.buffer.len());
+        DecodeRequest {
+            utterance_id: self.utterance_id,
+            start,
+            end,
+            is_final,
+            audio: self.buffer[from..to.max(from)].to_vec(),
+        }
+    }
+
+    fn buffer_end(&self) -> usize {
+        self.buffer_start + self.buffer.len()
+    }
+
+    /// Drop audio that can no longer become part of an utterance
+    fn trim_buffer(&mut self) {
+        let keep_from = match self.utterance {
+            Some(start) => start,
+            None => self.buffer_end().saturating_sub(self.idle_history),
+        };
+
+        let excess = keep_from
+            .saturating_sub(self.buffer_start)
+            .min(self.buffer.len());
+        self.buffer.drain(..excess);
+        self.buffer_start += excess;
+    }
+}
+
+fn samples_for_ms(ms: u64) -> usize {
+    (WHISPER_SAMPLE_RATE as u64 * ms / 1000) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> StreamingOptions {
+        StreamingOptions {
+            transcription: TranscriptionOptions {
+                model_file_name: "ggml-base.bin".to_string(),
+                language: None,
+                translate: None,
+                no_context: None,
+                single_segment: None,
+            },
+            sample_rate: WHISPER_SAMPLE_RATE,
+            partial_interval_ms: None,
+            max_utterance_seconds: None,
+            partial_window_seconds: None,
+            vad: None,
+        }
+    }
+
+    fn silence(ms: usize) -> Vec<f32> {
+        vec![0.0; samples_for_ms(ms as u64)]
+    }
+
+    fn tone(ms: usize) -> Vec<f32> {
+        (0..samples_for_ms(ms as u64))
+            .map(|i| {
+                (i as f32 / WHISPER_SAMPLE_RATE as f32 * 220.0 * std::f32::consts::TAU).sin() * 0.3
+            })
+            .collect()
+    }
+
+    /// Feed `audio` in 100 ms chunks, then stop
+    fn segment(segmenter: &mut Segmenter, audio: &[f32]) -> Vec<DecodeRequest> {
+        let mut requests: Vec<DecodeRequest> = audio
+            .chunks(samples_for_ms(100))
+            .flat_map(|chunk| segmenter.push(chunk, false))
+            .collect();
+        requests.extend(segmenter.push(&[], true));
+        requests
+    }
+
+    #[test]
+    fn finalizes_each_utterance_with_its_pre_roll() {
+        let mut segmenter = Segmenter::new(&options());
+        let audio = [
+            silence(960),
+            tone(960),
+            silence(960),
+            tone(960),
+            silence(960),
+        ]
+        .concat();
+        let requests = segment(&mut segmenter, &audio);
+
+        let finals: Vec<(u32, usize, usize)> = requests
+            .iter()
+            .filter(|request| request.is_final)
+            .map(|request| (request.utterance_id, request.start, request.end))
+            .collect();
+        // Tones start at samples 15 360 and 46 080; the pre-roll is 4 000 samples
+        assert_eq!(finals, [(1, 11_360, 30_720), (2, 42_080, 61_440)]);
+        for request in &requests {
+            assert_eq!(request.audio.len(), request.end - request.start);
+        }
+        // The first final holds the tone itself, not audio trimmed from before it
+        let first = requests.iter().find(|request| request.is_final).unwrap();
+        assert_eq!(&first.audio[4_000..4_100], &audio[15_360..15_460]);
+
+        // Partials follow each utterance at the partial interval
+        assert!(requests
+            .iter()
+            .any(|request| !request.is_final && request.utterance_id == 1));
+    }
+
+    #[test]
+    fn keeps_a_pre_roll_longer_than_a_second() {
+        let mut segmenter = Segmenter::new(&StreamingOptions {
+            vad: Some(VadConfig {
+                pre_roll_ms: 1_500,
+                ..VadConfig::default()
+            }),
+            ..options()
+        });
+        let audio = [silence(2_400), tone(960), silence(960)].concat();
+        let requests = segment(&mut segmenter, &audio);
+
+        let last = requests.last().unwrap();
+        assert!(last.is_final);
+        // The tone starts at sample 38 400, and 1.5 s before it is 24 000 samples
+        assert_eq!(last.start, 14_400);
+        assert_eq!(last.audio.len(), last.end - last.start);
+    }
+
+    #[test]
+    fn stopping_finalizes_speech_in_progress() {
+        let mut segmenter = Segmenter::new(&options());
+        let audio = [silence(960), tone(960)].concat();
+        let requests = segment(&mut segmenter, &audio);
+
+        let last = requests.last().unwrap();
+        assert!(last.is_final);
+        assert_eq!((last.start, last.end), (11_360, audio.len()));
+    }
+
+    #[test]
+    fn long_utterances_are_cut_and_partials_stay_in_their_window() {
+        let mut segmenter = Segmenter::new(&StreamingOptions {
+            max_utterance_seconds: Some(3.0),
+            partial_window_seconds: Some(1.0),
+            ..options()
+        });
+        let requests = segment(&mut segmenter, &[silence(960), tone(8_000)].concat());
+
+        let finals = requests.iter().filter(|request| request.is_final);
+        assert!(finals.clone().count() >= 3);
+        assert!(finals
+            .clone()
+            .all(|request| request.end - request.start <= 3 * 16_000 + 1_600));
+        assert!(requests
+            .iter()
+            .filter(|request| !request.is_final)
+            .all(|request| request.audio.len() <= 16_000));
+    }
+}
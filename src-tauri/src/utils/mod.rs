@@ -1,3 +1,7 @@
 pub mod hash;
+pub mod html;
+pub mod process;
 
 pub use hash::*;
+pub use html::*;
+pub use process::*;
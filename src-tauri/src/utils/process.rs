@@ -0,0 +1,15 @@
+use std::path::PathBuf;
+
+/// Locate an executable on `PATH`, adding `.exe` on Windows
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let binary = if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    };
+
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(&binary))
+        .find(|candidate| candidate.is_file())
+}
@@ -0,0 +1,178 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** PDF user space: points from the bottom-left corner of the page */
+export interface Point {
+  x: number;
+  y: number;
+}
+
+export interface Rect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+/** One line of marked-up text; corners follow the PDF QuadPoints order */
+export interface Quad {
+  top_left: Point;
+  top_right: Point;
+  bottom_left: Point;
+  bottom_right: Point;
+}
+
+export type AnnotationGeometry =
+  | { type: 'highlight'; quads: Quad[] }
+  | { type: 'underline'; quads: Quad[] }
+  | { type: 'strikeout'; quads: Quad[] }
+  | { type: 'note'; anchor: Point }
+  | { type: 'ink'; strokes: Point[][]; line_width: number }
+  | { type: 'rectangle'; rect: Rect; line_width: number }
+  | { type: 'free_text'; rect: Rect; font_size: number };
+
+export interface Annotation {
+  id: string;
+  document_id: string;
+  page_number: number;
+  geometry: AnnotationGeometry;
+  content: string | null;
+  /** `#rrggbb` or a toolbar colour name such as "yellow" */
+  color: string;
+  created_at: string;
+  updated_at: string;
+  /** Set while the annotation is in the trash */
+  deleted_at: string | null;
+  /** Who wrote it, for annotations imported from the PDF */
+  author: string | null;
+  /** Id of the annotation this one replies to */
+  reply_to: string | null;
+  /** Set on annotations imported from the PDF; these are not written back on export */
+  source_key: string | null;
+}
+
+export interface NewAnnotation {
+  document_id: string;
+  page_number: number;
+  geometry: AnnotationGeometry;
+  content?: string | null;
+  color: string;
+  reply_to?: string | null;
+}
+
+/** Omitted fields are left alone; `content: null` clears the content */
+export interface AnnotationUpdate {
+  page_number?: number;
+  geometry?: AnnotationGeometry;
+  content?: string | null;
+  color?: string;
+}
+
+export async function createAnnotation(annotation: NewAnnotation): Promise<Annotation> {
+  return await invoke('create_annotation', { annotation });
+}
+
+export async function updateAnnotation(
+  annotationId: string,
+  update: AnnotationUpdate
+): Promise<Annotation> {
+  return await invoke('update_annotation', { annotationId, update });
+}
+
+/** Moves the annotation to the trash; `undoAnnotationChange` restores it */
+export async function deleteAnnotation(annotationId: string): Promise<boolean> {
+  return await invoke('delete_annotation', { annotationId });
+}
+
+export async function getAnnotations(
+  documentId: string,
+  pageNumber?: number,
+  includeDeleted = false
+): Promise<Annotation[]> {
+  return await invoke('get_annotations', { documentId, pageNumber, includeDeleted });
+}
+
+/** Annotations whose text or author contains `query`; all documents unless one is given */
+export async function searchAnnotations(
+  query: string,
+  documentId?: string,
+  limit?: number
+): Promise<Annotation[]> {
+  return await invoke('search_annotations', { query, documentId, limit });
+}
+
+/** Reverts the latest change; resolves to `null` when there is nothing to undo */
+export async function undoAnnotationChange(documentId: string): Promise<Annotation | null> {
+  return await invoke('undo_annotation_change', { documentId });
+}
+
+export async function purgeDeletedAnnotations(documentId: string): Promise<number> {
+  return await invoke('purge_deleted_annotations', { documentId });
+}
+
+export interface PdfAnnotationExport {
+  output_path: string;
+  written: number;
+  /** Annotations from an earlier export that were replaced or removed */
+  replaced: number;
+  /** Annotations read from the PDF that were edited here and rewritten */
+  updated: number;
+  /** Annotations read from the PDF that were deleted here */
+  removed: number;
+}
+
+/**
+ * Write the document's annotations into the PDF so other readers show them.
+ * Without `outputPath` the document's own file is updated in place.
+ */
+export async function exportAnnotationsToPdf(
+  documentId: string,
+  outputPath?: string
+): Promise<PdfAnnotationExport> {
+  return await invoke('export_annotations_to_pdf', { documentId, outputPath });
+}
+
+export type AnnotationFileFormat = 'xfdf' | 'fdf';
+
+/**
+ * Write the document's annotations to an XFDF or FDF file for Acrobat, Foxit and
+ * other readers; the PDF is left unchanged. Resolves to the number written.
+ */
+export async function exportAnnotationsToFile(
+  documentId: string,
+  path: string,
+  format?: AnnotationFileFormat
+): Promise<number> {
+  return await invoke('export_annotations_to_file', { documentId, path, format });
+}
+
+export interface AnnotationImportSummary {
+  imported: number;
+  /** Annotations we had that the file has a newer version of */
+  updated: number;
+  /** Annotations we had already, at least as recent as the file's */
+  duplicates: number;
+  skipped: number;
+  /** Why annotations were skipped */
+  errors: string[];
+}
+
+export async function importAnnotationsFromFile(
+  documentId: string,
+  path: string
+): Promise<AnnotationImportSummary> {
+  return await invoke('import_annotations_from_file', { documentId, path });
+}
+
+export type NotesFormat = 'markdown' | 'html';
+
+/**
+ * The document's highlights and notes grouped by chapter: Markdown with Obsidian
+ * page links, or HTML. Also written to `outputPath` when given.
+ */
+export async function exportAnnotationNotes(
+  documentId: string,
+  format: NotesFormat,
+  outputPath?: string
+): Promise<string> {
+  return await invoke('export_annotation_notes', { documentId, format, outputPath });
+}
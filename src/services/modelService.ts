@@ -1,4 +1,7 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+export type ModelKind = 'speech' | 'translation' | 'ocr' | 'voice' | 'embedding' | 'dictionary';
 
 export interface ModelInfo {
   name: string;
@@ -7,19 +10,116 @@ export interface ModelInfo {
   file_name: string;
   description: string;
   language: string;
+  kind: ModelKind;
+  task: string;
+  /** BCP 47 tags; empty for multilingual models */
+  languages: string[];
+  quantization: string | null;
+  license: string | null;
+  min_ram_mb: number | null;
+  /** File names of registry models installed alongside */
+  dependencies: string[];
+  /** Older versions this model replaces; garbage collection removes them */
+  supersedes: string[];
+  sha256?: string | null;
+  size_bytes?: number | null;
+}
+
+export type VerificationState = 'verified' | 'unverified' | 'modified' | 'corrupt';
+
+export interface DownloadedModel {
+  file_name: string;
+  size: number;
+  verification: VerificationState;
+  kind: ModelKind | null;
+  path: string;
+  /** Found in a search directory rather than the models directory */
+  read_only: boolean;
+}
+
+export type ImportMode = 'copy' | 'move' | 'symlink';
+
+export interface ModelUsage {
+  file_name: string;
+  size: number;
+  last_used: string | null;
+}
+
+export interface StorageUsage {
+  used_bytes: number;
+  quota_mb: number | null;
+  available_bytes: number | null;
+  partial_bytes: number;
+  quarantine_bytes: number;
+  models: ModelUsage[];
+}
+
+export interface GcOptions {
+  unused_days?: number | null;
+  remove_superseded?: boolean;
+  stale_partial_days?: number | null;
+  empty_quarantine?: boolean;
+  dry_run?: boolean;
+}
+
+export interface RemovedFile {
+  file_name: string;
+  bytes: number;
+  reason: 'superseded' | 'unused' | 'stale_partial' | 'quarantined';
+}
+
+export interface GcReport {
+  removed: RemovedFile[];
+  freed_bytes: number;
+  dry_run: boolean;
+}
+
+export interface ModelRegistry {
+  schema_version: number;
+  revision: number;
+  refresh_url: string | null;
+  models: ModelInfo[];
+}
+
+export interface ModelVerification {
+  file_name: string;
+  state: VerificationState;
+  size: number;
+  expected_size: number | null;
+  sha256: string | null;
+  expected_sha256: string | null;
+  quarantined_path: string | null;
 }
 
 export interface DownloadProgress {
+  file_name: string;
   downloaded: number;
   total: number;
   percentage: number;
-  status: 'pending' | 'downloading' | 'completed' | 'failed' | 'paused';
+  status:
+    | 'pending'
+    | 'downloading'
+    | 'verifying'
+    | 'completed'
+    | 'failed'
+    | 'paused'
+    | 'cancelled';
+  error: string | null;
 }
 
 export async function getAvailableModels(): Promise<ModelInfo[]> {
   return await invoke('get_available_models');
 }
 
+export async function getModelRegistry(kind?: ModelKind): Promise<ModelRegistry> {
+  return await invoke('get_model_registry', { kind });
+}
+
+/** Fetch a newer registry manifest from `url`, or the registry's refresh URL */
+export async function refreshModelRegistry(url?: string): Promise<ModelRegistry> {
+  return await invoke('refresh_model_registry', { url });
+}
+
 export async function getModelsDir(): Promise<string> {
   return await invoke('get_models_dir');
 }
@@ -28,7 +128,7 @@ export async function isModelDownloaded(fileName: string): Promise<boolean> {
   return await invoke('is_model_downloaded', { fileName });
 }
 
-export async function listDownloadedModels(): Promise<string[]> {
+export async function listDownloadedModels(): Promise<DownloadedModel[]> {
   return await invoke('list_downloaded_models');
 }
 
@@ -45,69 +145,142 @@ export async function getModelSize(fileName: string): Promise<number> {
 }
 
 /**
- * Download a model with progress tracking
- * This is a placeholder - actual implementation will use Rust HTTP client
+ * Register a model from a local file. `fileName` renames it, e.g. to its registry name;
+ * a Piper voice needs its `.onnx.json` config next to it.
+ */
+export async function importModel(
+  sourcePath: string,
+  mode: ImportMode,
+  fileName?: string
+): Promise<DownloadedModel> {
+  return await invoke('import_model', { sourcePath, mode, fileName });
+}
+
+export async function getModelSearchDirs(): Promise<string[]> {
+  return await invoke('get_model_search_dirs');
+}
+
+/** Replace the read-only directories searched for models, e.g. a shared mount */
+export async function setModelSearchDirs(dirs: string[]): Promise<string[]> {
+  return await invoke('set_model_search_dirs', { dirs });
+}
+
+/** Hash a downloaded model; a file that fails is moved to quarantine */
+export async function verifyModel(fileName: string): Promise<ModelVerification> {
+  return await invoke('verify_model', { fileName });
+}
+
+/**
+ * Download a model in the backend, resuming any partial download.
+ * Resolves once the file is in place; rejects if it fails, is paused or is cancelled.
  */
 export async function downloadModel(
   modelInfo: ModelInfo,
   onProgress: (progress: DownloadProgress) => void
 ): Promise<void> {
-  // For now, use browser fetch API
-  // In production, this should be implemented in Rust for better control
-  const response = await fetch(modelInfo.download_url);
-  
-  if (!response.ok) {
-    throw new Error(`Failed to download: ${response.statusText}`);
-  }
+  let unlisten: (() => void) | undefined;
+
+  try {
+    await new Promise<void>((resolve, reject) => {
+      void listen<DownloadProgress>('model-download-progress', (event) => {
+        const progress = event.payload;
+        if (progress.file_name !== modelInfo.file_name) return;
 
-  const reader = response.body?.getReader();
-  
-  if (!reader) {
-    throw new Error('Failed to get response reader');
+        onProgress(progress);
+        if (progress.status === 'completed') {
+          resolve();
+        } else if (progress.status === 'failed') {
+          reject(new Error(progress.error ?? 'Download failed'));
+        } else if (progress.status === 'paused' || progress.status === 'cancelled') {
+          reject(new Error(`Download ${progress.status}`));
+        }
+      })
+        .then((stop) => {
+          unlisten = stop;
+          return invoke('download_model', { fileName: modelInfo.file_name });
+        })
+        .catch(reject);
+    });
+  } finally {
+    unlisten?.();
   }
-  
-  const contentLength = Number(response.headers.get('content-length') ?? '0');
+}
 
-  let downloaded = 0;
-  const chunks: Uint8Array[] = [];
+export async function pauseModelDownload(fileName: string): Promise<void> {
+  await invoke('pause_model_download', { fileName });
+}
 
-  onProgress({
-    downloaded: 0,
-    total: contentLength,
-    percentage: 0,
-    status: 'downloading',
-  });
+export async function resumeModelDownload(fileName: string): Promise<void> {
+  await invoke('resume_model_download', { fileName });
+}
 
-  // eslint-disable-next-line @typescript-eslint/no-unnecessary-condition
-  while (true) {
-    const { done, value } = await reader.read();
+export async function cancelModelDownload(fileName: string): Promise<void> {
+  await invoke('cancel_model_download', { fileName });
+}
 
-    if (done) break;
+export async function getModelDownloadProgress(fileName: string): Promise<DownloadProgress | null> {
+  return await invoke('get_model_download_progress', { fileName });
+}
 
-    chunks.push(value);
-    downloaded += value.length;
+export async function getModelStorageUsage(): Promise<StorageUsage> {
+  return await invoke('get_model_storage_usage');
+}
 
-    onProgress({
-      downloaded,
-      total: contentLength,
-      percentage: (downloaded / contentLength) * 100,
-      status: 'downloading',
-    });
-  }
+/** Cap the models directory; `null` removes the limit */
+export async function setModelQuota(quotaMb: number | null): Promise<void> {
+  await invoke('set_model_quota', { quotaMb });
+}
+
+/** Remove superseded or unused models and stale download leftovers */
+export async function collectModelGarbage(options?: GcOptions): Promise<GcReport> {
+  return await invoke('collect_model_garbage', { options });
+}
+
+export interface HardwareProfile {
+  cpu_brand: string;
+  arch: string;
+  logical_cores: number;
+  physical_cores: number | null;
+  /** Detected vector extensions, e.g. 'avx2', 'avx512f', 'neon' */
+  simd: string[];
+  total_ram_mb: number;
+  available_ram_mb: number;
+}
+
+export interface ModelAssessment {
+  file_name: string;
+  name: string;
+  fits_memory: boolean;
+  /** Estimated processing time per second of audio */
+  estimated_rtf: number | null;
+  installed: boolean;
+  warning: string | null;
+}
+
+export interface TaskRecommendation {
+  kind: ModelKind;
+  recommended: string | null;
+  candidates: ModelAssessment[];
+}
+
+export interface HardwareRecommendation {
+  hardware: HardwareProfile;
+  tasks: TaskRecommendation[];
+}
+
+export interface RecommendationOptions {
+  /** Slowest acceptable processing time per second of audio; defaults to 1 */
+  max_real_time_factor?: number;
+  language?: string | null;
+}
+
+export async function getHardwareProfile(): Promise<HardwareProfile> {
+  return await invoke('get_hardware_profile');
+}
 
-  // Combine chunks
-  const blob = new Blob(chunks);
-  const arrayBuffer = await blob.arrayBuffer();
-  const uint8Array = new Uint8Array(arrayBuffer);
-
-  // Save to file system via Rust
-  // TODO: Implement save_model command in Rust
-  console.log('Model downloaded, size:', uint8Array.length);
-
-  onProgress({
-    downloaded: contentLength,
-    total: contentLength,
-    percentage: 100,
-    status: 'completed',
-  });
+/** Suggest a model per task that this machine can run */
+export async function recommendModels(
+  options?: RecommendationOptions
+): Promise<HardwareRecommendation> {
+  return await invoke('recommend_models', { options });
 }
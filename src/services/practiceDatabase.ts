@@ -8,6 +8,7 @@
  * - User progress metrics
  */
 
+import { invoke } from '@tauri-apps/api/core';
 import Database from '@tauri-apps/plugin-sql';
 
 let db: Database | null = null;
@@ -45,73 +46,22 @@ export interface DifficultyStats {
 
 /**
  * Initialize database connection
+ *
+ * Connects to the app database opened by the backend, which also creates and
+ * migrates these tables.
  */
 export async function initDatabase(): Promise<void> {
   if (db) return;
 
   try {
-    db = await Database.load('sqlite:practice.db');
-    await createTables();
+    const path = await invoke<string>('get_database_path');
+    db = await Database.load(`sqlite:${path}`);
   } catch (error) {
     console.error('Failed to initialize practice database:', error);
     throw error;
   }
 }
 
-/**
- * Create database tables
- */
-async function createTables(): Promise<void> {
-  if (!db) throw new Error('Database not initialized');
-
-  // Practice sessions table
-  await db.execute(`
-    CREATE TABLE IF NOT EXISTS practice_sessions (
-      id INTEGER PRIMARY KEY AUTOINCREMENT,
-      document_hash TEXT NOT NULL,
-      page_number INTEGER NOT NULL,
-      text TEXT NOT NULL,
-      accuracy REAL NOT NULL,
-      duration INTEGER NOT NULL,
-      timestamp INTEGER NOT NULL,
-      UNIQUE(document_hash, page_number, timestamp)
-    )
-  `);
-
-  // Word practice history table
-  await db.execute(`
-    CREATE TABLE IF NOT EXISTS word_practice (
-      id INTEGER PRIMARY KEY AUTOINCREMENT,
-      word TEXT NOT NULL,
-      document_hash TEXT NOT NULL,
-      page_number INTEGER NOT NULL,
-      attempts INTEGER NOT NULL DEFAULT 0,
-      success_count INTEGER NOT NULL DEFAULT 0,
-      last_accuracy REAL NOT NULL,
-      average_accuracy REAL NOT NULL,
-      marked INTEGER NOT NULL DEFAULT 0,
-      last_practiced INTEGER NOT NULL,
-      UNIQUE(word, document_hash, page_number)
-    )
-  `);
-
-  // Create indexes for faster queries
-  await db.execute(`
-    CREATE INDEX IF NOT EXISTS idx_sessions_document 
-    ON practice_sessions(document_hash, page_number)
-  `);
-
-  await db.execute(`
-    CREATE INDEX IF NOT EXISTS idx_word_practice 
-    ON word_practice(word, document_hash)
-  `);
-
-  await db.execute(`
-    CREATE INDEX IF NOT EXISTS idx_marked_words 
-    ON word_practice(marked, last_practiced)
-  `);
-}
-
 /**
  * Save a practice session
  */
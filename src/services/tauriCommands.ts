@@ -20,6 +20,7 @@ export interface TranslationResult {
   sourceLanguage: string;
   targetLanguage: string;
   confidence?: number;
+  model: string;
 }
 
 export interface PageTranslationResult {
@@ -71,6 +72,7 @@ export interface OCRResult {
   confidence: number;
   language: string;
   pageNumber: number;
+  model: string;
 }
 
 export interface DocumentOCRResult {
@@ -83,6 +85,44 @@ export interface DocumentOCRResult {
   totalScannedPages: number;
 }
 
+// Reading progress types
+export interface ReadingProgress {
+  document_id: string;
+  current_page: number;
+  scroll_offset: number | null;
+  zoom: number | null;
+  total_time_seconds: number;
+  updated_at: string;
+}
+
+export interface ReadingSession {
+  id: string;
+  document_id: string;
+  day: string;
+  started_at: string;
+  last_active_at: string;
+  active_seconds: number;
+}
+
+export interface DocumentReadingTime {
+  document_id: string;
+  title: string;
+  total_seconds: number;
+  current_page: number;
+  total_pages: number;
+  pages_read: number;
+  estimated_seconds_remaining: number | null;
+}
+
+export interface ReadingStats {
+  total_seconds: number;
+  days: Array<{ day: string; seconds: number; pages: number }>;
+  average_pages_per_day: number;
+  current_streak: number;
+  longest_streak: number;
+  documents: DocumentReadingTime[];
+}
+
 export async function invokeCommand<T>(
   command: string,
   args?: Record<string, unknown>
@@ -99,13 +139,34 @@ export const tauriCommands = {
     return await invokeCommand<PdfMetadata>('get_pdf_info', { path });
   },
 
-  saveReadingProgress: async (documentId: string, page: number) => {
+  saveReadingProgress: async (
+    documentId: string,
+    page: number,
+    scrollOffset?: number,
+    zoom?: number
+  ) => {
     await invokeCommand<undefined>('save_reading_progress', {
       documentId,
       page,
+      scrollOffset,
+      zoom,
     });
   },
 
+  getReadingProgress: async (documentId: string) => {
+    return await invokeCommand<ReadingProgress | null>('get_reading_progress', { documentId });
+  },
+
+  recordReadingHeartbeat: async (documentId: string, page: number, active: boolean) => {
+    return await invokeCommand<ReadingSession>('record_reading_heartbeat', {
+      heartbeat: { document_id: documentId, page, active },
+    });
+  },
+
+  getReadingStats: async (days?: number) => {
+    return await invokeCommand<ReadingStats>('get_reading_stats', { days });
+  },
+
   openFile: async () => {
     return await invokeCommand<string | null>('open_file_dialog');
   },